@@ -0,0 +1,88 @@
+#[macro_use]
+extern crate criterion;
+extern crate rand;
+extern crate space3;
+
+use criterion::{Criterion, black_box};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use std::rc::Rc;
+
+use space3::block::{BlockRenderData, ObscuresFaces, Chunk, Neighbours, World, CHUNK_SIZE};
+use space3::gl_util::{Vertex, PackedVertex};
+
+fn render_data() -> Vec<BlockRenderData> {
+	vec![
+		BlockRenderData {
+			obscures: ObscuresFaces::NONE,
+			color: [0.0f32; 3],
+			should_render: false,
+			liquid: false,
+			tint: None,
+			trim: None,
+		},
+		BlockRenderData {
+			obscures: ObscuresFaces::ALL,
+			color: [0.3, 0.4, 0.2],
+			should_render: true,
+			liquid: false,
+			tint: None,
+			trim: None,
+		},
+	]
+}
+
+fn random_chunk() -> Chunk {
+	let mut rng = StdRng::seed_from_u64(12);
+	let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+	for plane in blocks.iter_mut() {
+		for row in plane.iter_mut() {
+			for block in row.iter_mut() {
+				*block = rng.gen_range(0, 2);
+			}
+		}
+	}
+	Chunk::new(blocks)
+}
+
+fn terrain_chunk() -> Chunk {
+	let world = World::new();
+	let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+	world.generate_blocks(0, 0, 0, &mut blocks);
+	Chunk::new(blocks)
+}
+
+// Every solid block is surrounded by air, so nothing can be merged and every face is visible
+fn checkerboard_chunk() -> Chunk {
+	let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+	for x in 0..CHUNK_SIZE {
+		for y in 0..CHUNK_SIZE {
+			for z in 0..CHUNK_SIZE {
+				blocks[x][y][z] = (x + y + z) % 2;
+			}
+		}
+	}
+	Chunk::new(blocks)
+}
+
+fn bench_mesh(c: &mut Criterion, name: &str, chunk: Chunk) {
+	let data = render_data();
+	let shared = Rc::new(chunk);
+
+	let chunk = shared.clone();
+	c.bench_function(&format!("{} standard", name), move |b| b.iter(|| black_box(chunk.build_mesh_data::<Vertex>(&data, Neighbours::none()))));
+
+	let data = render_data();
+	let chunk = shared;
+	c.bench_function(&format!("{} packed", name), move |b| b.iter(|| black_box(chunk.build_mesh_data::<PackedVertex>(&data, Neighbours::none()))));
+}
+
+fn meshing(c: &mut Criterion) {
+	bench_mesh(c, "build_mesh_data random", random_chunk());
+	bench_mesh(c, "build_mesh_data terrain", terrain_chunk());
+	bench_mesh(c, "build_mesh_data checkerboard", checkerboard_chunk());
+}
+
+criterion_group!(benches, meshing);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+#[macro_use]
+extern crate criterion;
+extern crate space3;
+
+use criterion::{Criterion, black_box};
+
+use space3::block::{World, CHUNK_SIZE};
+use space3::net::{compress_chunk, decompress_chunk};
+
+// Near the surface, where there's the most variety to encode
+fn terrain_blocks() -> [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE] {
+	let world = World::new();
+	let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+	world.generate_blocks(0, 0, -3, &mut blocks);
+	blocks
+}
+
+fn network(c: &mut Criterion) {
+	let blocks = terrain_blocks();
+	c.bench_function("compress_chunk terrain", move |b| b.iter(|| black_box(compress_chunk(&blocks).unwrap())));
+
+	let data = compress_chunk(&terrain_blocks()).unwrap();
+	c.bench_function("decompress_chunk terrain", move |b| b.iter(|| {
+		let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+		decompress_chunk(&data, &mut blocks).unwrap();
+		black_box(blocks)
+	}));
+}
+
+criterion_group!(benches, network);
+criterion_main!(benches);
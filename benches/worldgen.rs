@@ -0,0 +1,23 @@
+#[macro_use]
+extern crate criterion;
+extern crate space3;
+
+use criterion::{Criterion, black_box};
+
+use space3::block::{World, CHUNK_SIZE};
+
+fn gen_chunk(c: &mut Criterion) {
+	let world = World::new();
+	let mut blocks = Box::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+	let mut x = 0;
+
+	// Walk along a row of chunks so the benchmark doesn't just re-sample the same noise
+	c.bench_function("gen_chunk", move |b| b.iter(|| {
+		x += 1;
+		world.generate_blocks(x, 0, 0, &mut blocks);
+		black_box(&blocks);
+	}));
+}
+
+criterion_group!(benches, gen_chunk);
+criterion_main!(benches);
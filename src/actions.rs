@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use glium::glutin::{ModifiersState, MouseButton, VirtualKeyCode};
+
+use save::{self, SaveError};
+
+pub const KEYBINDINGS_FILE: &'static str = "keybindings.cfg";
+const MODE_PREFIX: &'static str = "mode.";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+	Forward,
+	Back,
+	Left,
+	Right,
+	Up,
+	Down,
+	Jump,
+	Sprint,
+	Crouch,
+	FlyFast,
+	Break,
+	Place,
+	PickBlock,
+	Undo,
+	Redo,
+	Save,
+}
+
+pub const ACTIONS: [Action; 16] = [
+	Action::Forward,
+	Action::Back,
+	Action::Left,
+	Action::Right,
+	Action::Up,
+	Action::Down,
+	Action::Jump,
+	Action::Sprint,
+	Action::Crouch,
+	Action::FlyFast,
+	Action::Break,
+	Action::Place,
+	Action::PickBlock,
+	Action::Undo,
+	Action::Redo,
+	Action::Save,
+];
+
+// Actions that may share a chord because they're never needed at the same time
+const SHAREABLE: &'static [(Action, Action)] = &[
+	(Action::Crouch, Action::FlyFast),
+];
+
+impl Action {
+	// As written in the keybindings file
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Action::Forward => "forward",
+			Action::Back => "back",
+			Action::Left => "left",
+			Action::Right => "right",
+			Action::Up => "up",
+			Action::Down => "down",
+			Action::Jump => "jump",
+			Action::Sprint => "sprint",
+			Action::Crouch => "crouch",
+			Action::FlyFast => "fly_fast",
+			Action::Break => "break",
+			Action::Place => "place",
+			Action::PickBlock => "pick_block",
+			Action::Undo => "undo",
+			Action::Redo => "redo",
+			Action::Save => "save",
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Input {
+	Key(VirtualKeyCode),
+	Mouse(MouseButton),
+}
+
+// A key or mouse button, along with the modifiers that have to be down for it to count
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+	pub input: Input,
+	pub ctrl: bool,
+	pub shift: bool,
+	pub alt: bool,
+}
+
+impl Chord {
+	pub fn key(key: VirtualKeyCode) -> Chord {
+		Chord { input: Input::Key(key), ctrl: false, shift: false, alt: false }
+	}
+
+	pub fn mouse(button: MouseButton) -> Chord {
+		Chord { input: Input::Mouse(button), ctrl: false, shift: false, alt: false }
+	}
+
+	// The input with whichever modifiers are down, leaving out the one the input is itself, so
+	// binding Ctrl doesn't bind Ctrl+Ctrl
+	pub fn pressed(input: Input, modifiers: ModifiersState) -> Chord {
+		use glium::glutin::VirtualKeyCode as K;
+		let is = |keys: &[VirtualKeyCode]| match input {
+			Input::Key(key) => keys.contains(&key),
+			Input::Mouse(_) => false,
+		};
+		Chord {
+			input: input,
+			ctrl: modifiers.ctrl && !is(&[K::LControl, K::RControl]),
+			shift: modifiers.shift && !is(&[K::LShift, K::RShift]),
+			alt: modifiers.alt && !is(&[K::LAlt, K::RAlt]),
+		}
+	}
+
+	pub fn with_ctrl(self) -> Chord {
+		Chord { ctrl: true, ..self }
+	}
+
+	pub fn with_shift(self) -> Chord {
+		Chord { shift: true, ..self }
+	}
+
+	fn modifier_count(&self) -> usize {
+		[self.ctrl, self.shift, self.alt].iter().filter(|&&down| down).count()
+	}
+
+	// Whether the input with these modifiers down sets the chord off. Extra modifiers don't
+	// stop it, so Ctrl sprinting doesn't stop W walking forward.
+	fn matches(&self, input: Input, modifiers: ModifiersState) -> bool {
+		self.input == input && (!self.ctrl || modifiers.ctrl) && (!self.shift || modifiers.shift) && (!self.alt || modifiers.alt)
+	}
+}
+
+// Written like "Ctrl+Shift+Z" or "Shift+MouseRight"
+impl fmt::Display for Chord {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.ctrl { write!(f, "Ctrl+")?; }
+		if self.shift { write!(f, "Shift+")?; }
+		if self.alt { write!(f, "Alt+")?; }
+		match self.input {
+			Input::Key(key) => write!(f, "{}", key_name(key)),
+			Input::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{}", button),
+			Input::Mouse(button) => write!(f, "Mouse{:?}", button),
+		}
+	}
+}
+
+fn chord_from_name(name: &str) -> Option<Chord> {
+	let mut parts: Vec<&str> = name.split('+').map(|part| part.trim()).collect();
+	let input = match parts.pop()? {
+		"MouseLeft" => Input::Mouse(MouseButton::Left),
+		"MouseRight" => Input::Mouse(MouseButton::Right),
+		"MouseMiddle" => Input::Mouse(MouseButton::Middle),
+		other if other.starts_with("Mouse") => Input::Mouse(MouseButton::Other(other["Mouse".len()..].parse().ok()?)),
+		other => Input::Key(key_from_name(other)?),
+	};
+	let mut chord = Chord { input: input, ctrl: false, shift: false, alt: false };
+	for part in parts {
+		match part {
+			"Ctrl" => chord.ctrl = true,
+			"Shift" => chord.shift = true,
+			"Alt" => chord.alt = true,
+			_ => return None,
+		}
+	}
+	Some(chord)
+}
+
+// Which chords trigger each action. Actions are held while their keys are, unless they're set
+// to toggle, when each press turns them on or off. When several chords on the same input
+// match, only the ones with the most modifiers count, so Ctrl+S saves without walking back.
+pub struct ActionMap {
+	bindings: HashMap<Action, Vec<Chord>>,
+	toggled: HashSet<Action>,
+	// The toggled actions that are on
+	on: HashSet<Action>,
+}
+
+impl Default for ActionMap {
+	fn default() -> ActionMap {
+		use glium::glutin::VirtualKeyCode as K;
+		let mut map = ActionMap { bindings: HashMap::new(), toggled: HashSet::new(), on: HashSet::new() };
+		map.bind(Action::Forward, &[Chord::key(K::W)]);
+		map.bind(Action::Back, &[Chord::key(K::S)]);
+		map.bind(Action::Left, &[Chord::key(K::A)]);
+		map.bind(Action::Right, &[Chord::key(K::D)]);
+		map.bind(Action::Up, &[Chord::key(K::E)]);
+		map.bind(Action::Down, &[Chord::key(K::Q)]);
+		map.bind(Action::Jump, &[Chord::key(K::Space)]);
+		map.bind(Action::Sprint, &[Chord::key(K::LControl), Chord::key(K::RControl)]);
+		map.bind(Action::Crouch, &[Chord::key(K::LShift), Chord::key(K::RShift)]);
+		map.bind(Action::FlyFast, &[Chord::key(K::LShift), Chord::key(K::RShift)]);
+		map.bind(Action::Break, &[Chord::mouse(MouseButton::Left)]);
+		map.bind(Action::Place, &[Chord::mouse(MouseButton::Right)]);
+		map.bind(Action::PickBlock, &[Chord::mouse(MouseButton::Middle)]);
+		map.bind(Action::Undo, &[Chord::key(K::Z).with_ctrl()]);
+		map.bind(Action::Redo, &[Chord::key(K::Y).with_ctrl(), Chord::key(K::Z).with_ctrl().with_shift()]);
+		map.bind(Action::Save, &[Chord::key(K::S).with_ctrl()]);
+		map
+	}
+}
+
+impl ActionMap {
+	pub fn bind(&mut self, action: Action, chords: &[Chord]) {
+		self.bindings.insert(action, chords.to_vec());
+	}
+
+	pub fn chords(&self, action: Action) -> &[Chord] {
+		self.bindings.get(&action).map(|chords| chords.as_slice()).unwrap_or(&[])
+	}
+
+	// Whether pressing the input with these modifiers down sets the action off
+	pub fn triggers(&self, action: Action, input: Input, modifiers: ModifiersState) -> bool {
+		let matching = |chords: &[Chord]| chords.iter()
+			.filter(|chord| chord.matches(input, modifiers))
+			.map(|chord| chord.modifier_count())
+			.max();
+		match matching(self.chords(action)) {
+			Some(count) => self.bindings.values().filter_map(|chords| matching(chords)).all(|other| other <= count),
+			None => false,
+		}
+	}
+
+	// Whether the action's bound to the input at all, whatever the modifiers, for letting go of it
+	pub fn uses(&self, action: Action, input: Input) -> bool {
+		self.chords(action).iter().any(|chord| chord.input == input)
+	}
+
+	// Only keys are tracked as held; mouse buttons are handled as they're pressed and released
+	pub fn held(&self, action: Action, keys_down: &HashSet<VirtualKeyCode>) -> bool {
+		if self.toggles(action) {
+			return self.on.contains(&action);
+		}
+		let modifiers = modifiers_held(keys_down);
+		keys_down.iter().any(|&key| self.triggers(action, Input::Key(key), modifiers))
+	}
+
+	pub fn toggles(&self, action: Action) -> bool {
+		self.toggled.contains(&action)
+	}
+
+	pub fn set_toggles(&mut self, action: Action, toggles: bool) {
+		if toggles {
+			self.toggled.insert(action);
+		} else {
+			self.toggled.remove(&action);
+			self.on.remove(&action);
+		}
+	}
+
+	// Flips the toggled actions the input triggers, for a fresh press rather than a repeat
+	pub fn press(&mut self, input: Input, modifiers: ModifiersState) {
+		for action in ACTIONS.iter().filter(|&&action| self.toggles(action) && self.triggers(action, input, modifiers)) {
+			if !self.on.remove(action) {
+				self.on.insert(*action);
+			}
+		}
+	}
+
+	// The other actions bound to any of the same chords as this one, besides those meant to
+	// share them
+	pub fn conflicts(&self, action: Action) -> Vec<Action> {
+		ACTIONS.iter().cloned()
+			.filter(|&other| other != action)
+			.filter(|&other| !SHAREABLE.iter().any(|&pair| pair == (action, other) || pair == (other, action)))
+			.filter(|&other| self.chords(other).iter().any(|chord| self.chords(action).contains(chord)))
+			.collect()
+	}
+
+	// Actions missing from the file keep their default keys, so files from before an action was
+	// added still load
+	pub fn load(path: &Path) -> Result<ActionMap, SaveError> {
+		let mut contents = String::new();
+		File::open(path)?.read_to_string(&mut contents)?;
+		let fields = save::parse_fields(&contents)?;
+
+		let mut map = ActionMap::default();
+		for action in ACTIONS.iter() {
+			if let Some(raw) = fields.get(action.name()) {
+				let chords = raw.split(',')
+					.filter(|name| !name.trim().is_empty())
+					.map(|name| chord_from_name(name.trim()).ok_or(SaveError::Malformed(format!("{}={}", action.name(), raw))))
+					.collect::<Result<Vec<_>, _>>()?;
+				map.bind(*action, &chords);
+			}
+			match fields.get(&format!("{}{}", MODE_PREFIX, action.name())).map(|mode| mode.as_str()) {
+				Some("toggle") => map.set_toggles(*action, true),
+				Some("hold") | None => (),
+				Some(mode) => return Err(SaveError::Malformed(format!("{}{}={}", MODE_PREFIX, action.name(), mode))),
+			}
+		}
+		Ok(map)
+	}
+
+	// Writes out the defaults when there is no keybindings file yet, so there's one to edit
+	pub fn load_or_create(path: &Path) -> Result<ActionMap, SaveError> {
+		if path.is_file() {
+			return ActionMap::load(path);
+		}
+		let map = ActionMap::default();
+		map.save(path)?;
+		Ok(map)
+	}
+
+	pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+		let tmp = path.with_extension("tmp");
+		{
+			let mut file = File::create(&tmp)?;
+			for action in ACTIONS.iter() {
+				let names: Vec<String> = self.chords(*action).iter().map(|chord| chord.to_string()).collect();
+				write!(file, "{}={}\n", action.name(), names.join(","))?;
+				write!(file, "{}{}={}\n", MODE_PREFIX, action.name(), if self.toggles(*action) { "toggle" } else { "hold" })?;
+			}
+		}
+		fs::rename(&tmp, path)?;
+		Ok(())
+	}
+}
+
+fn modifiers_held(keys_down: &HashSet<VirtualKeyCode>) -> ModifiersState {
+	use glium::glutin::VirtualKeyCode as K;
+	let any = |keys: &[VirtualKeyCode]| keys.iter().any(|key| keys_down.contains(key));
+	ModifiersState {
+		ctrl: any(&[K::LControl, K::RControl]),
+		shift: any(&[K::LShift, K::RShift]),
+		alt: any(&[K::LAlt, K::RAlt]),
+		logo: any(&[K::LWin, K::RWin]),
+	}
+}
+
+// Keys are named as glutin names them, like "W", "LShift" or "F3"
+pub fn key_name(key: VirtualKeyCode) -> String {
+	format!("{:?}", key)
+}
+
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+	use glium::glutin::VirtualKeyCode as K;
+	const KEYS: &'static [VirtualKeyCode] = &[
+		K::Key1, K::Key2, K::Key3, K::Key4, K::Key5, K::Key6, K::Key7, K::Key8, K::Key9, K::Key0,
+		K::A, K::B, K::C, K::D, K::E, K::F, K::G, K::H, K::I, K::J, K::K, K::L, K::M,
+		K::N, K::O, K::P, K::Q, K::R, K::S, K::T, K::U, K::V, K::W, K::X, K::Y, K::Z,
+		K::F1, K::F2, K::F3, K::F4, K::F5, K::F6, K::F7, K::F8, K::F9, K::F10, K::F11, K::F12,
+		K::Insert, K::Home, K::Delete, K::End, K::PageDown, K::PageUp,
+		K::Left, K::Up, K::Right, K::Down, K::Back, K::Return, K::Space, K::Tab,
+		K::Numpad0, K::Numpad1, K::Numpad2, K::Numpad3, K::Numpad4, K::Numpad5, K::Numpad6, K::Numpad7, K::Numpad8, K::Numpad9,
+		K::Apostrophe, K::Backslash, K::Comma, K::Equals, K::Grave, K::LBracket, K::Minus, K::Period, K::RBracket, K::Semicolon, K::Slash,
+		K::LAlt, K::LControl, K::LShift, K::LWin, K::RAlt, K::RControl, K::RShift, K::RWin,
+	];
+	KEYS.iter().cloned().find(|&key| key_name(key) == name)
+}
@@ -0,0 +1,169 @@
+use std::cell::Cell;
+use std::f32::consts::PI;
+
+use rand;
+use rodio::{self, Device, Sink};
+use rodio::buffer::SamplesBuffer;
+use rodio::source::ChannelVolume;
+use na::{Point3, Vector3};
+
+const SAMPLE_RATE: u32 = 44100;
+
+// Sounds are at full volume within this many blocks, and fall off inversely with distance past it
+const REFERENCE_DISTANCE: f32 = 2.0;
+// Past this they aren't played at all
+const MAX_DISTANCE: f32 = 48.0;
+// How far towards one ear a sound directly to the side goes, so that one ear never hears nothing
+const MAX_PAN: f32 = 0.8;
+
+// Everything the game can play. They're made up on startup rather than loaded from files.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sound {
+	BlockBreak,
+	SandBreak,
+	BlockPlace,
+	Splash,
+	Step,
+	SandStep,
+	ItemPickup,
+}
+
+const SOUNDS: [Sound; 7] = [
+	Sound::BlockBreak,
+	Sound::SandBreak,
+	Sound::BlockPlace,
+	Sound::Splash,
+	Sound::Step,
+	Sound::SandStep,
+	Sound::ItemPickup,
+];
+
+// What a block type sounds like, with None for silence
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlockSounds {
+	pub broken: Option<Sound>,
+	pub placed: Option<Sound>,
+	// Walking on top of it
+	pub step: Option<Sound>,
+}
+
+impl BlockSounds {
+	pub fn silent() -> BlockSounds {
+		BlockSounds { broken: None, placed: None, step: None }
+	}
+}
+
+// Plays sounds relative to a listener, normally the camera. Without an output device, as on a
+// headless machine, everything still works but nothing is heard.
+pub struct AudioService {
+	device: Option<Device>,
+	// Mono samples, indexed like SOUNDS
+	samples: Vec<Vec<f32>>,
+	listener: Cell<Point3<f32>>,
+	// Unit vector towards the listener's right ear
+	right: Cell<Vector3<f32>>,
+	master_volume: Cell<f32>,
+	effects_volume: Cell<f32>,
+}
+
+impl AudioService {
+	pub fn new() -> AudioService {
+		let device = rodio::default_output_device();
+		if device.is_none() {
+			println!("No audio output device, sound is off");
+		}
+		AudioService {
+			device: device,
+			samples: SOUNDS.iter().map(|&sound| synthesize(sound)).collect(),
+			listener: Cell::new(Point3::origin()),
+			right: Cell::new(Vector3::x()),
+			master_volume: Cell::new(1.0),
+			effects_volume: Cell::new(1.0),
+		}
+	}
+
+	pub fn set_listener(&self, position: Point3<f32>, direction: Vector3<f32>, up: Vector3<f32>) {
+		self.listener.set(position);
+		self.right.set(direction.cross(&up).normalize());
+	}
+
+	// Both from 0 to 1; sounds play at their product
+	pub fn set_volume(&self, master: f32, effects: f32) {
+		self.master_volume.set(master.max(0.0).min(1.0));
+		self.effects_volume.set(effects.max(0.0).min(1.0));
+	}
+
+	pub fn play_at(&self, position: [f32; 3], sound: Sound) {
+		let device = match self.device {
+			Some(ref device) => device,
+			None => return,
+		};
+
+		let offset = Point3::new(position[0], position[1], position[2]) - self.listener.get();
+		let distance = offset.norm();
+		if distance > MAX_DISTANCE {
+			return;
+		}
+		let gain = attenuation(distance) * self.master_volume.get() * self.effects_volume.get();
+		if gain <= 0.0 {
+			return;
+		}
+
+		// From -1 for hard left to 1 for hard right, with equal power across the two ears
+		let pan = if distance > 0.0 { offset.dot(&self.right.get()) / distance * MAX_PAN } else { 0.0 };
+		let angle = (pan + 1.0) * PI / 4.0;
+
+		let samples = SamplesBuffer::new(1, SAMPLE_RATE, self.samples[sound as usize].clone());
+		let sink = Sink::new(device);
+		sink.set_volume(gain);
+		sink.append(ChannelVolume::new(samples, vec![angle.cos(), angle.sin()]));
+		// Plays on to the end by itself
+		sink.detach();
+	}
+}
+
+// 1 within the reference distance, then inversely proportional to distance
+fn attenuation(distance: f32) -> f32 {
+	REFERENCE_DISTANCE / distance.max(REFERENCE_DISTANCE)
+}
+
+fn synthesize(sound: Sound) -> Vec<f32> {
+	match sound {
+		// A short crunch
+		Sound::BlockBreak => noise(0.15, 30.0, 0.3, 1.0),
+		// Hiss, trickling away for longer
+		Sound::SandBreak => noise(0.3, 12.0, 0.7, 0.6),
+		// A low knock
+		Sound::BlockPlace => envelope(0.1, 40.0, |t| (2.0 * PI * 150.0 * t).sin()),
+		// Dull noise, wobbling like it's sloshing about
+		Sound::Splash => {
+			let mut splash = noise(0.4, 8.0, 0.08, 1.0);
+			for (i, sample) in splash.iter_mut().enumerate() {
+				let t = i as f32 / SAMPLE_RATE as f32;
+				*sample *= 0.6 + 0.4 * (2.0 * PI * 25.0 * t).sin();
+			}
+			splash
+		},
+		Sound::Step => noise(0.08, 50.0, 0.2, 0.4),
+		Sound::SandStep => noise(0.1, 40.0, 0.6, 0.3),
+		// A rising blip
+		Sound::ItemPickup => envelope(0.1, 20.0, |t| (2.0 * PI * (600.0 + 2000.0 * t) * t).sin() * 0.5),
+	}
+}
+
+// White noise put through a low pass, where `brightness` from 0 to 1 keeps that much of the highs
+fn noise(length: f32, decay: f32, brightness: f32, volume: f32) -> Vec<f32> {
+	let mut low = 0.0;
+	envelope(length, decay, |_| {
+		low += (rand::random::<f32>() * 2.0 - 1.0 - low) * brightness;
+		low * volume
+	})
+}
+
+// `length` seconds of `wave`, given the time in seconds, fading exponentially at `decay` per second
+fn envelope<F: FnMut(f32) -> f32>(length: f32, decay: f32, mut wave: F) -> Vec<f32> {
+	(0..(length * SAMPLE_RATE as f32) as usize)
+		.map(|i| i as f32 / SAMPLE_RATE as f32)
+		.map(|t| wave(t) * (-decay * t).exp())
+		.collect()
+}
@@ -0,0 +1,63 @@
+use block::CHUNK_SIZE;
+
+// The climate of a column of the world, which the generator picks. Blocks drawn with a tint,
+// like grass and water, are colored by the biome they're in, so one block looks different
+// across the world.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Biome {
+	Plains,
+	Forest,
+	Desert,
+	Tundra,
+	Swamp,
+}
+
+// In the order of Biome::index
+pub const BIOMES: [Biome; 5] = [Biome::Plains, Biome::Forest, Biome::Desert, Biome::Tundra, Biome::Swamp];
+
+// The biome of each column of a chunk, by x then y
+pub type ChunkBiomes = [[Biome; CHUNK_SIZE]; CHUNK_SIZE];
+
+// Which of a biome's colors a tinted face takes
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Tint {
+	Grass,
+	Water,
+}
+
+impl Biome {
+	pub fn index(&self) -> usize {
+		match *self {
+			Biome::Plains => 0,
+			Biome::Forest => 1,
+			Biome::Desert => 2,
+			Biome::Tundra => 3,
+			Biome::Swamp => 4,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Biome::Plains => "plains",
+			Biome::Forest => "forest",
+			Biome::Desert => "desert",
+			Biome::Tundra => "tundra",
+			Biome::Swamp => "swamp",
+		}
+	}
+
+	// Multiplied into the color of faces with the tint. Plains leave colors as they are.
+	pub fn tint_color(&self, tint: Tint) -> [f32; 3] {
+		match (*self, tint) {
+			(Biome::Plains, _) => [1.0, 1.0, 1.0],
+			(Biome::Forest, Tint::Grass) => [0.75, 1.0, 0.7],
+			(Biome::Forest, Tint::Water) => [0.9, 1.0, 0.95],
+			(Biome::Desert, Tint::Grass) => [1.7, 1.35, 0.9],
+			(Biome::Desert, Tint::Water) => [1.0, 1.3, 1.2],
+			(Biome::Tundra, Tint::Grass) => [1.3, 1.4, 1.6],
+			(Biome::Tundra, Tint::Water) => [1.2, 1.2, 1.3],
+			(Biome::Swamp, Tint::Grass) => [0.9, 0.85, 0.6],
+			(Biome::Swamp, Tint::Water) => [0.9, 1.1, 0.6],
+		}
+	}
+}
@@ -1,60 +1,903 @@
-use noise::{Fbm, Seedable, MultiFractal, NoiseModule};
 use std::collections::HashMap;
-use std::cell::RefCell;
+use std::cell::{Cell, Ref, RefCell};
 use std::rc::{Rc, Weak};
+use std::sync::Arc;
 use std::ops::Deref;
+use std::mem::size_of;
 
 pub struct World {
-	generator: Fbm<f32>,
-	chunks: RefCell<HashMap<[i64; 3], Weak<RefCell<Chunk>>>>,
+	generator: Arc<WorldGenerator>,
+	chunks: ChunkMap<Weak<RefCell<Chunk>>>,
+	cache: RefCell<ChunkCache>,
+	generation: RefCell<GenerationPool<Option<GeneratedChunk>>>,
+	journal: RefCell<EditJournal>,
+	updates: RefCell<UpdateQueue>,
+	subscribers: RefCell<Subscribers>,
+	metadata: WorldMetadata,
+	save_dir: Option<PathBuf>,
+	weather: Cell<Weather>,
+	// Spent generating chunks since the last `take_generation_time`
+	generation_time: Cell<Duration>,
+	// For worlds that aren't saved, once something's asked where it is
+	found_spawn: Cell<Option<[i64; 3]>>,
 }
 
 use rand;
-use noise;
 use rand::distributions::Standard;
+use std::path::{Path, PathBuf};
+use save::{self, WorldMetadata, SaveError};
+use chunk_cache::{ChunkCache, DEFAULT_MEMORY_BUDGET};
+use chunk_map::ChunkMap;
+use generation_queue::{GenerationPool, Requester};
+use worldgen::{self, WorldGenerator, WorldPreset, TerrainGenerator, TerrainSettings, VoidGenerator};
+use biome::{Biome, BIOMES, ChunkBiomes, Tint};
+use orientation::{Orientation, Orienting};
+use random::WorldRandom;
+use schematic::Schematic;
+use journal::{EditJournal, BlockChange, DEFAULT_JOURNAL_BUDGET};
+use block_update::{BlockUpdate, UpdateQueue, Subscribers, SubscriptionId, MAX_UPDATES_PER_TICK};
+use audio::{BlockSounds, Sound};
+use weather::Weather;
+use game_rules::GameRules;
+use time::{Duration, PreciseTime};
+
 impl World {
 	pub fn new() -> World {
-		let seed = 12;
+		let generator = TerrainGenerator::new(12, TerrainSettings::default());
+		World::with_generator(WorldMetadata::new(12, TerrainGenerator::NAME), Box::new(generator), None)
+	}
+
+	// Like `new`, never saved, but generated from `preset`
+	pub fn from_preset(seed: u32, preset: &WorldPreset) -> World {
+		let generator = preset.generator(seed);
+		let mut metadata = WorldMetadata::new(seed, generator.name());
+		metadata.generator_options = generator.options();
+		World::with_generator(metadata, generator, None)
+	}
+
+	pub fn open_or_create(dir: &Path, seed: u32) -> Result<World, SaveError> {
+		World::open_or_create_preset(dir, seed, &WorldPreset::Normal(TerrainSettings::default()))
+	}
+
+	pub fn open_or_create_preset(dir: &Path, seed: u32, preset: &WorldPreset) -> Result<World, SaveError> {
+		World::open_or_create_with(dir, seed, preset.generator(seed))
+	}
+
+	// `generator` is only used if the world doesn't exist yet; existing saves reopen with
+	// the generator recorded in their metadata
+	pub fn open_or_create_with(dir: &Path, seed: u32, generator: Box<WorldGenerator>) -> Result<World, SaveError> {
+		if WorldMetadata::exists(dir) {
+			let metadata = WorldMetadata::load(dir)?;
+			let generator = worldgen::from_metadata(&metadata)?;
+			let mut world = World::with_generator(metadata, generator, Some(dir.to_path_buf()));
+			// Saved before there were spawn points
+			if world.metadata.spawn.is_none() {
+				world.metadata.spawn = Some(world.initial_spawn());
+				world.metadata.save(dir)?;
+			}
+			return Ok(world);
+		}
+
+		let mut metadata = WorldMetadata::new(seed, generator.name());
+		metadata.generator_options = generator.options();
+		let mut world = World::with_generator(metadata, generator, Some(dir.to_path_buf()));
+		world.metadata.spawn = Some(world.initial_spawn());
+		world.metadata.save(dir)?;
+		Ok(world)
+	}
+
+	fn with_generator(metadata: WorldMetadata, generator: Box<WorldGenerator>, save_dir: Option<PathBuf>) -> World {
+		let generator: Arc<WorldGenerator> = Arc::from(generator);
+		let (shared, saves) = (generator.clone(), save_dir.clone());
+		let generation = GenerationPool::new(move |pos: [i64; 3]| {
+			// Saved chunks are loaded by the world, on its own thread
+			if saves.as_ref().map(|dir| save::chunk_path(dir, pos).is_file()).unwrap_or(false) {
+				return None;
+			}
+			Some(GeneratedChunk::new(&*shared, pos))
+		});
 		World {
-			generator: Fbm::new().set_seed(seed)
-			                     .set_octaves(6)
-			                     .set_lacunarity(2.0),
+			generator: generator,
+			chunks: ChunkMap::new(),
+			cache: RefCell::new(ChunkCache::new(DEFAULT_MEMORY_BUDGET)),
+			generation: RefCell::new(generation),
+			journal: RefCell::new(EditJournal::new(DEFAULT_JOURNAL_BUDGET)),
+			updates: RefCell::new(UpdateQueue::new()),
+			subscribers: RefCell::new(Subscribers::new()),
+			metadata: metadata,
+			save_dir: save_dir,
+			weather: Cell::new(Weather::Clear),
+			generation_time: Cell::new(Duration::zero()),
+			found_spawn: Cell::new(None),
+		}
+	}
+
+	pub fn metadata(&self) -> &WorldMetadata {
+		&self.metadata
+	}
+
+	// Where decoration and gameplay should draw their randomness from
+	pub fn random(&self) -> WorldRandom {
+		WorldRandom::new(self.metadata.seed)
+	}
+
+	pub fn weather(&self) -> Weather {
+		self.weather.get()
+	}
+
+	pub fn set_weather(&self, weather: Weather) {
+		self.weather.set(weather);
+	}
+
+	// Chunks the index still has entries for, and how many of those are still alive
+	pub fn chunk_counts(&self) -> (usize, usize) {
+		let mut live = 0;
+		self.chunks.for_each(|_, chunk| if chunk.upgrade().is_some() { live += 1 });
+		(self.chunks.len(), live)
+	}
+
+	pub fn cache_usage(&self) -> (usize, usize) {
+		let cache = self.cache.borrow();
+		(cache.len(), cache.memory_usage())
+	}
+
+	pub fn take_generation_time(&self) -> Duration {
+		self.generation_time.replace(Duration::zero())
+	}
+
+	// Saved worlds have theirs found when they're made; others the first time they're asked
+	pub fn spawn(&self) -> [i64; 3] {
+		if let Some(spawn) = self.metadata.spawn.or(self.found_spawn.get()) {
+			return spawn;
+		}
+		let spawn = self.initial_spawn();
+		self.found_spawn.set(Some(spawn));
+		spawn
+	}
+
+	// Void worlds, mirrors of other worlds among them, have nothing to stand on to look for
+	fn initial_spawn(&self) -> [i64; 3] {
+		if self.generator.name() == VoidGenerator::NAME {
+			[0, 0, 0]
+		} else {
+			self.find_spawn()
+		}
+	}
+
+	// The top of the highest column near the origin that can be stood on, or the origin itself
+	// if there's no ground in reach
+	pub fn find_spawn(&self) -> [i64; 3] {
+		let mut best: Option<[i64; 3]> = None;
+		for x in -SPAWN_SEARCH_RADIUS..SPAWN_SEARCH_RADIUS + 1 {
+			for y in -SPAWN_SEARCH_RADIUS..SPAWN_SEARCH_RADIUS + 1 {
+				let z = match self.standing_height(x, y) {
+					Some(z) => z,
+					None => continue,
+				};
+
+				let closer = |best: [i64; 3]| x * x + y * y < best[0] * best[0] + best[1] * best[1];
+				if best.map(|best| z > best[2] || z == best[2] && closer(best)).unwrap_or(true) {
+					best = Some([x, y, z]);
+				}
+			}
+		}
+		best.unwrap_or([0, 0, 0])
+	}
+
+	// The highest block in the column with room to stand above it. Water can't be stood on.
+	fn standing_height(&self, x: i64, y: i64) -> Option<i64> {
+		let mut clearance = 0;
+		for z in (SPAWN_SEARCH_BOTTOM..SPAWN_SEARCH_TOP).rev() {
+			match self.get_block([x, y, z]) {
+				AIR => clearance += 1,
+				WATER => clearance = 0,
+				_ if clearance >= SPAWN_CLEARANCE => return Some(z + 1),
+				_ => clearance = 0,
+			}
+		}
+		None
+	}
+
+	pub fn border(&self) -> Option<i64> {
+		self.metadata.border
+	}
 
-			chunks: RefCell::new(HashMap::new()),
+	// Chunks already generated keep whatever's beyond a new border; only generation stops there
+	pub fn set_border(&mut self, border: Option<i64>) -> Result<(), SaveError> {
+		self.metadata.border = border;
+		self.save_metadata()
+	}
+
+	// Seconds of world time passed, which the day follows
+	pub fn time(&self) -> f64 {
+		self.metadata.time
+	}
+
+	// Kept with the metadata the next time the world is saved
+	pub fn set_time(&mut self, time: f64) {
+		self.metadata.time = time.max(0.0);
+	}
+
+	pub fn rules(&self) -> GameRules {
+		self.metadata.rules
+	}
+
+	pub fn set_rules(&mut self, rules: GameRules) -> Result<(), SaveError> {
+		self.metadata.rules = rules;
+		self.save_metadata()
+	}
+
+	fn save_metadata(&self) -> Result<(), SaveError> {
+		match self.save_dir {
+			Some(ref dir) => self.metadata.save(dir),
+			None => Ok(()),
+		}
+	}
+
+	// Whether the column at (x, y) is inside the border, if there is one
+	pub fn within_border(&self, x: i64, y: i64) -> bool {
+		match self.metadata.border {
+			Some(border) => x >= -border && x < border && y >= -border && y < border,
+			None => true,
 		}
 	}
 
+	pub fn save_dir(&self) -> Option<&Path> {
+		self.save_dir.as_ref().map(|p| p.as_path())
+	}
+
+	pub fn set_memory_budget(&self, bytes: usize) {
+		self.cache.borrow_mut().set_budget(bytes);
+	}
+
 	pub fn get_chunk(&self, x: i64, y: i64, z: i64) -> Rc<RefCell<Chunk>> {
-		let opt = self.chunks.borrow().get(&[x, y, z]).and_then(Weak::upgrade);
-		opt.unwrap_or_else(|| self.gen_chunk(x, y, z))
+		self.insert_chunk([x, y, z], || {
+			// Whatever a worker's making of it would be out of date by the time it's done
+			self.generation.borrow().forget([x, y, z]);
+			self.load_chunk(x, y, z).unwrap_or_else(|| self.gen_chunk(x, y, z))
+		})
 	}
 
-	fn gen_chunk(&self, x: i64, y: i64, z: i64) -> Rc<RefCell<Chunk>> {
-		let rc = Rc::new(RefCell::new(Chunk::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE])));
+	fn insert_chunk<F>(&self, pos: [i64; 3], make: F) -> Rc<RefCell<Chunk>> where F: FnOnce() -> Rc<RefCell<Chunk>> {
+		let chunk = self.chunks.get_or_insert_with(pos, Weak::upgrade, || {
+			let chunk = make();
+			(Rc::downgrade(&chunk), chunk)
+		});
+		self.cache.borrow_mut().touch(pos, &chunk);
+		chunk
+	}
+
+	// Without loading or generating it
+	pub fn loaded_chunk(&self, pos: [i64; 3]) -> Option<Rc<RefCell<Chunk>>> {
+		self.chunks.get(pos, Weak::upgrade)
+	}
+
+	// Streamed chunks are generated on `count` threads of their own from then on, rather than by
+	// generate_queued itself
+	pub fn start_generation_workers(&self, count: usize) {
+		self.generation.borrow_mut().start_workers(count);
+	}
+
+	// For asking for chunks to be generated in the background of streaming
+	pub fn generation_requester(&self) -> Requester {
+		self.generation.borrow().requester()
+	}
+
+	// Queues the chunks in `wanted` that aren't loaded yet, as (priority, chunk) pairs, in place of
+	// whatever `requester` asked for before. See generation_queue::chunk_priority.
+	pub fn request_chunks(&self, requester: Requester, wanted: &[(f32, [i64; 3])]) {
+		let missing: Vec<(f32, [i64; 3])> = wanted.iter().cloned().filter(|&(_, pos)| self.loaded_chunk(pos).is_none()).collect();
+		self.generation.borrow().request(requester, &missing);
+	}
+
+	// Loads or generates up to `limit` of the queued chunks, lowest priority first, returning how
+	// many. With workers started, it's up to `limit` of those they've finished instead, and only
+	// saved chunks are loaded here. They stay in the cache for whoever asked for them to pick up.
+	pub fn generate_queued(&self, limit: usize) -> usize {
+		let generation = self.generation.borrow();
+		let mut made = 0;
+		while made < limit {
+			let next = if generation.has_workers() {
+				generation.try_take()
+			} else {
+				generation.pop().map(|pos| (pos, None))
+			};
+			let (pos, generated) = match next {
+				Some(next) => next,
+				None => break,
+			};
+			// Asked for more than once, or loaded some other way since
+			if self.loaded_chunk(pos).is_some() {
+				continue;
+			}
+			self.insert_chunk(pos, || self.load_chunk(pos[0], pos[1], pos[2]).unwrap_or_else(|| match generated {
+				Some(generated) => self.finish_chunk(pos, generated),
+				None => self.gen_chunk(pos[0], pos[1], pos[2]),
+			}));
+			made += 1;
+		}
+		made
+	}
 
+	// Drops whatever `requester` still has queued, for when they stop streaming
+	pub fn cancel_chunks(&self, requester: Requester) {
+		self.generation.borrow().cancel(requester);
+	}
+
+	pub fn queued_chunks(&self) -> usize {
+		self.generation.borrow().len()
+	}
+
+	// Queued chunks that went stale before they were generated, ever
+	pub fn dropped_chunks(&self) -> u64 {
+		self.generation.borrow().dropped()
+	}
+
+	// For chunks that arrive whole, as from a server. Like generation, this isn't an edit, so it
+	// is neither journaled nor dispatched as block updates.
+	pub fn replace_chunk(&self, pos: [i64; 3], blocks: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
+		let rc = self.get_chunk(pos[0], pos[1], pos[2]);
 		{
 			let mut chunk = rc.borrow_mut();
+			chunk.blocks = blocks;
+			chunk.states = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+		}
+		rc.borrow().invalidate_mesh();
+		self.invalidate_neighbours(pos);
+	}
+
+	// Along with replace_chunk, for blocks sent with their states
+	pub fn set_states(&self, pos: [i64; 3], states: ChunkStates) {
+		let rc = self.get_chunk(pos[0], pos[1], pos[2]);
+		rc.borrow_mut().states = states;
+		rc.borrow().invalidate_mesh();
+		self.invalidate_neighbours(pos);
+	}
+
+	// For biomes from elsewhere, as from a server whose generator picked them. Otherwise they're
+	// the generator's, even for chunks that were loaded rather than generated.
+	pub fn set_biomes(&self, pos: [i64; 3], biomes: ChunkBiomes) {
+		self.get_chunk(pos[0], pos[1], pos[2]).borrow_mut().set_biomes(biomes);
+	}
+
+	pub fn biome(&self, x: i64, y: i64) -> Biome {
+		self.generator.biome(x, y)
+	}
+
+	fn column_biomes(&self, x: i64, y: i64) -> ChunkBiomes {
+		column_biomes(&*self.generator, x, y)
+	}
+
+	// Rewrites every block in the inclusive box [min, max] for which `edit` returns a new id, a
+	// chunk at a time. Each touched chunk, and its loaded neighbours, are remeshed once.
+	// Returns the number of blocks changed.
+	fn edit_blocks<F>(&self, min: [i64; 3], max: [i64; 3], mut edit: F) -> usize where F: FnMut([i64; 3], usize) -> Option<usize> {
+		self.begin_action();
+		let mut total = 0;
+		let (min_chunk, _) = chunk_coords(min);
+		let (max_chunk, _) = chunk_coords(max);
+		for cx in min_chunk[0]..max_chunk[0] + 1 {
+			for cy in min_chunk[1]..max_chunk[1] + 1 {
+				for cz in min_chunk[2]..max_chunk[2] + 1 {
+					let base = [cx * CHUNK_SIZE as i64, cy * CHUNK_SIZE as i64, cz * CHUNK_SIZE as i64];
+					let lo = [min[0].max(base[0]), min[1].max(base[1]), min[2].max(base[2])];
+					let hi = [
+						max[0].min(base[0] + CHUNK_SIZE as i64 - 1),
+						max[1].min(base[1] + CHUNK_SIZE as i64 - 1),
+						max[2].min(base[2] + CHUNK_SIZE as i64 - 1),
+					];
+
+					let rc = self.get_chunk(cx, cy, cz);
+					let mut changed = 0;
+					{
+						let mut chunk = rc.borrow_mut();
+						for x in lo[0]..hi[0] + 1 {
+							for y in lo[1]..hi[1] + 1 {
+								for z in lo[2]..hi[2] + 1 {
+									let (lx, ly, lz) = ((x - base[0]) as usize, (y - base[1]) as usize, (z - base[2]) as usize);
+									let old = chunk.blocks[lx][ly][lz];
+									match edit([x, y, z], old) {
+										Some(block) if block != old => {
+											self.journal.borrow_mut().record(BlockChange { pos: [x, y, z], old: old, new: block });
+											self.notify([x, y, z], old, block);
+											chunk.blocks[lx][ly][lz] = block;
+											chunk.states[lx][ly][lz] = 0;
+											changed += 1;
+										},
+										_ => (),
+									}
+								}
+							}
+						}
+					}
+
+					if changed > 0 {
+						let chunk = rc.borrow();
+						chunk.dirty.set(true);
+						chunk.invalidate_mesh();
+						self.invalidate_neighbours([cx, cy, cz]);
+						total += changed;
+					}
+				}
+			}
+		}
+		self.end_action();
+		total
+	}
+
+	// Steps through the blocks along a ray until `hit` accepts one, returning it along with the
+	// block the ray passed through just before
+	pub fn raycast<F>(&self, origin: [f32; 3], direction: [f32; 3], max_distance: f32, hit: F) -> Option<([i64; 3], [i64; 3])>
+			where F: Fn(usize) -> bool {
+		let length = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+		if length == 0.0 {
+			return None;
+		}
+
+		let mut cell = [origin[0].floor() as i64, origin[1].floor() as i64, origin[2].floor() as i64];
+		let mut previous = cell;
+		let mut step = [0; 3];
+		// Distance along the ray to the next boundary on each axis, and between boundaries
+		let mut next = [::std::f32::INFINITY; 3];
+		let mut delta = [::std::f32::INFINITY; 3];
+		for axis in 0..3 {
+			let d = direction[axis] / length;
+			if d > 0.0 {
+				step[axis] = 1;
+				next[axis] = ((cell[axis] + 1) as f32 - origin[axis]) / d;
+				delta[axis] = 1.0 / d;
+			} else if d < 0.0 {
+				step[axis] = -1;
+				next[axis] = (cell[axis] as f32 - origin[axis]) / d;
+				delta[axis] = -1.0 / d;
+			}
+		}
+
+		loop {
+			if hit(self.get_block(cell)) {
+				return Some((cell, previous));
+			}
+
+			let axis = (0..3).min_by(|&a, &b| next[a].partial_cmp(&next[b]).unwrap()).unwrap();
+			if next[axis] > max_distance {
+				return None;
+			}
+			previous = cell;
+			cell[axis] += step[axis];
+			next[axis] += delta[axis];
+		}
+	}
+
+	pub fn get_block(&self, pos: [i64; 3]) -> usize {
+		let (chunk, local) = chunk_coords(pos);
+		self.get_chunk(chunk[0], chunk[1], chunk[2]).borrow().blocks[local[0]][local[1]][local[2]]
+	}
+
+	pub fn get_state(&self, pos: [i64; 3]) -> u8 {
+		let (chunk, local) = chunk_coords(pos);
+		self.get_chunk(chunk[0], chunk[1], chunk[2]).borrow().state(local[0], local[1], local[2])
+	}
+
+	// A block's state goes back to 0 whenever the block is replaced, so set it after the block.
+	// States aren't journaled or dispatched as block updates; undoing the removal of a turned
+	// block brings it back unturned.
+	pub fn set_state(&self, pos: [i64; 3], state: u8) {
+		let (chunk_pos, local) = chunk_coords(pos);
+		let rc = self.get_chunk(chunk_pos[0], chunk_pos[1], chunk_pos[2]);
+		if rc.borrow().state(local[0], local[1], local[2]) == state {
+			return;
+		}
+		rc.borrow_mut().set_state(local[0], local[1], local[2], state);
+		self.invalidate_neighbours(chunk_pos);
+	}
+
+	pub fn get_light(&self, pos: [i64; 3]) -> Light {
+		let (chunk, local) = chunk_coords(pos);
+		self.get_chunk(chunk[0], chunk[1], chunk[2]).borrow().light(local[0], local[1], local[2])
+	}
+
+	// Only chunks whose light actually changes are remeshed, along with neighbours with face
+	// corners lit by the changed cell, which across an edge or corner of the chunk may be diagonal
+	pub fn set_light(&self, pos: [i64; 3], light: Light) {
+		let (chunk_pos, local) = chunk_coords(pos);
+		let rc = self.get_chunk(chunk_pos[0], chunk_pos[1], chunk_pos[2]);
+		if rc.borrow().light(local[0], local[1], local[2]) == light {
+			return;
+		}
+		rc.borrow_mut().set_light(local[0], local[1], local[2], light);
+
+		let steps = |axis: usize| -> &'static [i64] {
+			if local[axis] == 0 {
+				&[0, -1]
+			} else if local[axis] == CHUNK_SIZE - 1 {
+				&[0, 1]
+			} else {
+				&[0]
+			}
+		};
+		for &x in steps(0) {
+			for &y in steps(1) {
+				for &z in steps(2) {
+					if [x, y, z] == [0, 0, 0] {
+						continue;
+					}
+					if let Some(chunk) = self.loaded_chunk([chunk_pos[0] + x, chunk_pos[1] + y, chunk_pos[2] + z]) {
+						chunk.borrow().invalidate_mesh();
+					}
+				}
+			}
+		}
+	}
+
+	pub fn set_block(&self, pos: [i64; 3], block: usize) {
+		self.begin_action();
+		self.apply_changes(&[(pos, block)], true);
+		self.end_action();
+	}
+
+	// For changes made by simulations rather than the player, which shouldn't be undoable
+	pub fn set_block_unjournaled(&self, pos: [i64; 3], block: usize) {
+		self.apply_changes(&[(pos, block)], false);
+	}
+
+	// Each chunk touched is remeshed once, however many of its blocks change
+	pub fn set_blocks_unjournaled(&self, changes: &[([i64; 3], usize)]) {
+		self.apply_changes(changes, false);
+	}
+
+	// Edits between these are undone and redone together
+	pub fn begin_action(&self) {
+		self.journal.borrow_mut().begin();
+	}
+
+	pub fn end_action(&self) {
+		self.journal.borrow_mut().end();
+	}
+
+	pub fn undo(&self) -> bool {
+		let action = self.journal.borrow_mut().take_undo();
+		match action {
+			Some(action) => {
+				let changes: Vec<([i64; 3], usize)> = action.iter().rev().map(|change| (change.pos, change.old)).collect();
+				self.apply_changes(&changes, false);
+				self.journal.borrow_mut().push_redo(action);
+				true
+			},
+			None => false,
+		}
+	}
+
+	pub fn redo(&self) -> bool {
+		let action = self.journal.borrow_mut().take_redo();
+		match action {
+			Some(action) => {
+				let changes: Vec<([i64; 3], usize)> = action.iter().map(|change| (change.pos, change.new)).collect();
+				self.apply_changes(&changes, false);
+				self.journal.borrow_mut().push_undo(action);
+				true
+			},
+			None => false,
+		}
+	}
+
+	// `subscriber` is called for every block update dispatched by `update_blocks`
+	pub fn subscribe<F>(&self, subscriber: F) -> SubscriptionId where F: FnMut(&World, &BlockUpdate) + 'static {
+		self.subscribers.borrow_mut().add(Box::new(subscriber))
+	}
+
+	pub fn unsubscribe(&self, id: SubscriptionId) {
+		self.subscribers.borrow_mut().remove(id);
+	}
+
+	pub fn pending_updates(&self) -> usize {
+		self.updates.borrow().len()
+	}
+
+	// Dispatches the updates queued so far, up to MAX_UPDATES_PER_TICK. Updates caused by
+	// subscribers reacting to these wait for the next call.
+	pub fn update_blocks(&self) {
+		let batch = self.updates.borrow_mut().take(MAX_UPDATES_PER_TICK);
+		if batch.is_empty() {
+			return;
+		}
+
+		let mut subscribers = self.subscribers.borrow_mut().take();
+		for update in &batch {
+			for &mut (_, ref mut subscriber) in subscribers.iter_mut() {
+				subscriber(self, update);
+			}
+		}
+		self.subscribers.borrow_mut().restore(subscribers);
+	}
 
-			for index_x in 0..CHUNK_SIZE {
-				for index_y in 0..CHUNK_SIZE {
-					for index_z in 0..CHUNK_SIZE {
-						let (block_x, block_y, block_z) = (CHUNK_SIZE as i64 * x + index_x as i64, CHUNK_SIZE as i64 * y + index_y as i64, CHUNK_SIZE as i64 * z + index_z as i64);
+	// Nothing is queued while no one is listening
+	fn notify(&self, pos: [i64; 3], old: usize, new: usize) {
+		if !self.subscribers.borrow().is_empty() {
+			self.updates.borrow_mut().block_changed(pos, old, new);
+		}
+	}
 
-						let mut density = -block_z as f32 / 128.0;
-						let noise = self.generator.get([block_x as f32 / 128.0, block_y as f32 / 128.0, block_z as f32 / 128.0]) * 64.0;
-						density += (noise);
+	// Sets scattered blocks, grouping them by chunk so each is remeshed once
+	fn apply_changes(&self, changes: &[([i64; 3], usize)], record: bool) {
+		let mut by_chunk: HashMap<[i64; 3], Vec<([usize; 3], [i64; 3], usize)>> = HashMap::new();
+		for &(pos, block) in changes {
+			let (chunk, local) = chunk_coords(pos);
+			by_chunk.entry(chunk).or_insert_with(Vec::new).push((local, pos, block));
+		}
 
-						if density > 0.0 {
-							chunk.blocks[index_x][index_y][index_z] = 1;
+		for (chunk_pos, blocks) in by_chunk {
+			let rc = self.get_chunk(chunk_pos[0], chunk_pos[1], chunk_pos[2]);
+			let mut changed = false;
+			{
+				let mut chunk = rc.borrow_mut();
+				for (local, pos, block) in blocks {
+					let old = replace(&mut chunk.blocks[local[0]][local[1]][local[2]], block);
+					if old != block {
+						chunk.states[local[0]][local[1]][local[2]] = 0;
+						if record {
+							self.journal.borrow_mut().record(BlockChange { pos: pos, old: old, new: block });
 						}
+						self.notify(pos, old, block);
+						changed = true;
 					}
 				}
 			}
 
-			self.chunks.borrow_mut().insert([x, y, z], Rc::downgrade(&rc));
+			if changed {
+				let chunk = rc.borrow();
+				chunk.dirty.set(true);
+				chunk.invalidate_mesh();
+				self.invalidate_neighbours(chunk_pos);
+			}
+		}
+	}
+
+	// Neighbouring meshes depend on this chunk's boundary faces
+	fn invalidate_neighbours(&self, pos: [i64; 3]) {
+		for offset in NEIGHBOUR_OFFSETS.iter() {
+			if let Some(chunk) = self.loaded_chunk([pos[0] + offset[0], pos[1] + offset[1], pos[2] + offset[2]]) {
+				chunk.borrow().invalidate_mesh();
+			}
+		}
+	}
+
+	pub fn place_structure(&self, origin: [i64; 3], schematic: &Schematic) {
+		let size = schematic.size();
+		if size.iter().any(|&s| s == 0) {
+			return;
 		}
+
+		let max = [origin[0] + size[0] as i64 - 1, origin[1] + size[1] as i64 - 1, origin[2] + size[2] as i64 - 1];
+		self.edit_blocks(origin, max, |pos, _| {
+			schematic.get((pos[0] - origin[0]) as usize, (pos[1] - origin[1]) as usize, (pos[2] - origin[2]) as usize)
+		});
+	}
+
+	// The bulk edits take the corners of an inclusive box in either order and return the number
+	// of blocks changed
+	pub fn fill_region(&self, a: [i64; 3], b: [i64; 3], block: usize) -> usize {
+		let (min, max) = World::box_corners(a, b);
+		self.edit_blocks(min, max, |_, _| Some(block))
+	}
+
+	pub fn replace_region(&self, a: [i64; 3], b: [i64; 3], from: usize, to: usize) -> usize {
+		let (min, max) = World::box_corners(a, b);
+		self.edit_blocks(min, max, |_, block| if block == from { Some(to) } else { None })
+	}
+
+	pub fn clear_region(&self, a: [i64; 3], b: [i64; 3]) -> usize {
+		self.fill_region(a, b, AIR)
+	}
+
+	fn box_corners(a: [i64; 3], b: [i64; 3]) -> ([i64; 3], [i64; 3]) {
+		(
+			[a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+			[a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+		)
+	}
+
+	// Copies the inclusive box [min, max] out of the world, air included
+	pub fn capture_structure(&self, min: [i64; 3], max: [i64; 3]) -> Schematic {
+		let size = [(max[0] - min[0] + 1) as usize, (max[1] - min[1] + 1) as usize, (max[2] - min[2] + 1) as usize];
+		let mut schematic = Schematic::new(size);
+		self.edit_blocks(min, max, |pos, block| {
+			schematic.set((pos[0] - min[0]) as usize, (pos[1] - min[1]) as usize, (pos[2] - min[2]) as usize, Some(block));
+			None
+		});
+		schematic
+	}
+
+	// How many of each block there are in the inclusive box [min, max], indexed by block
+	pub fn count_blocks(&self, min: [i64; 3], max: [i64; 3], block_types: usize) -> Vec<usize> {
+		let mut counts = vec![0; block_types];
+		self.edit_blocks(min, max, |_, block| {
+			if let Some(count) = counts.get_mut(block) {
+				*count += 1;
+			}
+			None
+		});
+		counts
+	}
+
+	// The faces in the inclusive box [min, max] the mesher draws for opaque blocks, so those not
+	// hidden by their neighbour, whether or not the neighbour is in the box. Liquid surfaces
+	// aren't counted.
+	pub fn exposed_faces(&self, min: [i64; 3], max: [i64; 3], block_render_data: &[BlockRenderData]) -> usize {
+		use block::NormalDirection as ND;
+		const DIRECTIONS: [NormalDirection; 6] = [ND::Front, ND::Up, ND::Right, ND::Back, ND::Down, ND::Left];
+		let mut faces = 0;
+		for x in min[0]..max[0] + 1 {
+			for y in min[1]..max[1] + 1 {
+				for z in min[2]..max[2] + 1 {
+					let render_data = &block_render_data[self.get_block([x, y, z])];
+					if !render_data.should_render || render_data.liquid {
+						continue;
+					}
+					for dir in DIRECTIONS.iter() {
+						let offset = dir.to_vec_arr();
+						let pos = [x + offset[0] as i64, y + offset[1] as i64, z + offset[2] as i64];
+						let towards = Orientation::from_state(self.get_state(pos)).to_model(&-dir);
+						if !block_render_data[self.get_block(pos)].obscures(&towards) {
+							faces += 1;
+						}
+					}
+				}
+			}
+		}
+		faces
+	}
+
+	// Prunes dead entries from the chunk index and evicts cached chunks over the memory budget
+	pub fn maintain(&self) {
+		self.chunks.retain(|_, chunk| chunk.upgrade().is_some());
+
+		let save_dir = self.save_dir.as_ref();
+		self.cache.borrow_mut().evict(|pos, chunk| {
+			if let Some(dir) = save_dir {
+				World::persist_chunk(dir, pos, chunk);
+			}
+		});
+	}
+
+	pub fn save(&self) {
+		let dir = match self.save_dir {
+			Some(ref dir) => dir,
+			None => return,
+		};
+
+		self.chunks.for_each(|pos, chunk| {
+			if let Some(chunk) = chunk.upgrade() {
+				World::persist_chunk(dir, pos, &chunk.borrow());
+			}
+		});
+		// For the time
+		if let Err(err) = self.metadata.save(dir) {
+			eprintln!("Failed to save the world metadata: {:?}", err);
+		}
+	}
+
+	fn persist_chunk(dir: &Path, pos: [i64; 3], chunk: &Chunk) {
+		if !chunk.is_dirty() {
+			return;
+		}
+
+		match save::save_chunk(dir, pos, &chunk.blocks, &chunk.states) {
+			Ok(()) => chunk.dirty.set(false),
+			Err(err) => eprintln!("Failed to save chunk {:?}: {:?}", pos, err),
+		}
+	}
+
+	fn load_chunk(&self, x: i64, y: i64, z: i64) -> Option<Rc<RefCell<Chunk>>> {
+		let dir = self.save_dir.as_ref()?;
+		let rc = Rc::new(RefCell::new(Chunk::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE])));
+
+		let loaded = {
+			let mut chunk = rc.borrow_mut();
+			let chunk = &mut *chunk;
+			save::load_chunk(dir, [x, y, z], BLOCK_TYPES, &mut chunk.blocks, &mut chunk.states)
+		};
+		match loaded {
+			Ok(true) => {
+				rc.borrow_mut().biomes = self.column_biomes(x, y);
+				Some(rc)
+			},
+			Ok(false) => None,
+			// Moved out of the way of the chunk generated in its place, so it isn't overwritten when
+			// that's saved and is still there for whoever wants to look into it
+			Err(err) => {
+				match save::set_aside_chunk(dir, [x, y, z]) {
+					Ok(aside) => eprintln!("Failed to load chunk {:?}, moved it to {} and regenerating: {:?}", [x, y, z], aside.display(), err),
+					Err(move_err) => eprintln!("Failed to load chunk {:?}, regenerating without moving it aside ({:?}): {:?}", [x, y, z], move_err, err),
+				}
+				None
+			},
+		}
+	}
+
+	fn gen_chunk(&self, x: i64, y: i64, z: i64) -> Rc<RefCell<Chunk>> {
+		let rc = Rc::new(RefCell::new(Chunk::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE])));
+		profile_scope!("chunk gen");
+		let start = PreciseTime::now();
+		// Nothing's generated beyond the border, so there's nothing out there to stream
+		let base = [x * CHUNK_SIZE as i64, y * CHUNK_SIZE as i64];
+		let last = [base[0] + CHUNK_SIZE as i64 - 1, base[1] + CHUNK_SIZE as i64 - 1];
+		let inside = [(base[0], base[1]), (base[0], last[1]), (last[0], base[1]), (last[0], last[1])].iter()
+			.filter(|&&(x, y)| self.within_border(x, y))
+			.count();
+		if inside > 0 {
+			self.generate_blocks(x, y, z, &mut rc.borrow_mut().blocks);
+		}
+		if inside < 4 {
+			self.clear_beyond_border(x, y, &mut rc.borrow_mut().blocks);
+		}
+		rc.borrow_mut().biomes = self.column_biomes(x, y);
+		self.generation_time.set(self.generation_time.get() + start.to(PreciseTime::now()));
+		rc
+	}
+
+	// Workers generate chunks whole, and the border is applied here, as it can move meanwhile
+	fn finish_chunk(&self, pos: [i64; 3], generated: GeneratedChunk) -> Rc<RefCell<Chunk>> {
+		let rc = Rc::new(RefCell::new(Chunk::new(*generated.blocks)));
+		{
+			let mut chunk = rc.borrow_mut();
+			self.clear_beyond_border(pos[0], pos[1], &mut chunk.blocks);
+			chunk.biomes = generated.biomes;
+		}
+		self.generation_time.set(self.generation_time.get() + generated.took);
 		rc
 	}
+
+	fn clear_beyond_border(&self, x: i64, y: i64, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
+		let base = [x * CHUNK_SIZE as i64, y * CHUNK_SIZE as i64];
+		for (i, plane) in blocks.iter_mut().enumerate() {
+			for (j, column) in plane.iter_mut().enumerate() {
+				if !self.within_border(base[0] + i as i64, base[1] + j as i64) {
+					*column = [AIR; CHUNK_SIZE];
+				}
+			}
+		}
+	}
+
+	pub fn generate_blocks(&self, x: i64, y: i64, z: i64, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
+		self.generator.generate(x, y, z, blocks);
+	}
+}
+
+// A chunk's blocks and biomes as a generation worker makes them, off the world's thread
+struct GeneratedChunk {
+	blocks: Box<[[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>,
+	biomes: ChunkBiomes,
+	took: Duration,
+}
+
+impl GeneratedChunk {
+	fn new(generator: &WorldGenerator, pos: [i64; 3]) -> GeneratedChunk {
+		let start = PreciseTime::now();
+		let mut blocks = Box::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+		generator.generate(pos[0], pos[1], pos[2], &mut blocks);
+		GeneratedChunk {
+			blocks: blocks,
+			biomes: column_biomes(generator, pos[0], pos[1]),
+			took: start.to(PreciseTime::now()),
+		}
+	}
+}
+
+fn column_biomes(generator: &WorldGenerator, x: i64, y: i64) -> ChunkBiomes {
+	let mut biomes = [[Biome::Plains; CHUNK_SIZE]; CHUNK_SIZE];
+	let base = [x * CHUNK_SIZE as i64, y * CHUNK_SIZE as i64];
+	for (i, row) in biomes.iter_mut().enumerate() {
+		for (j, biome) in row.iter_mut().enumerate() {
+			*biome = generator.biome(base[0] + i as i64, base[1] + j as i64);
+		}
+	}
+	biomes
+}
+
+impl Drop for World {
+	fn drop(&mut self) {
+		self.save();
+	}
 }
 
 use glium::Display;
@@ -63,13 +906,30 @@ pub trait Region {
 }
 
 use ndarray::{Array, Ix3};
+// What a point is inside of, as far as how it looks from there
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Medium {
+	Air,
+	// With the liquid's block type
+	Liquid(usize),
+}
+
 pub struct CuboidRegion {
 	start_pos: [i64; 3],
 	chunks: Array<Rc<RefCell<Chunk>>, Ix3>,
 }
 
 use engine::DrawService;
-use ndarray::Axis;
+use error::SpaceError;
+use na::Point3;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+const MAX_MESH_UPLOADS_PER_FRAME: usize = 8;
+// Squared, so out of view chunks mesh as if twice as far away
+const OUT_OF_VIEW_PENALTY: f32 = 4.0;
+
 impl CuboidRegion {
 	pub fn new(
 		world: &World,
@@ -95,40 +955,380 @@ impl CuboidRegion {
 		}
 	}
 
-	pub fn draw(&self, block_render_data: &[BlockRenderData], draw_service: &mut DrawService, view: Matrix4<f32>) {
-		let mut x = self.start_pos[0];
-		for slice_x in self.chunks.axis_iter(Axis(0)) {
-			let mut y = self.start_pos[1];
-			for slice_y in slice_x.axis_iter(Axis(0)) {
-				let mut z = self.start_pos[2];
-				for chunk in slice_y.iter() {
-					let (vertices, indices) = chunk.borrow().build_mesh(block_render_data, [Option::None; 6], draw_service.facade()).unwrap();
+	// In chunk coordinates, from the first chunk to one past the last
+	pub fn chunk_bounds(&self) -> ([i64; 3], [i64; 3]) {
+		let (x, y, z) = self.chunks.dim();
+		let start = self.start_pos;
+		(start, [start[0] + x as i64, start[1] + y as i64, start[2] + z as i64])
+	}
+
+	// None outside the region
+	pub fn get_block(&self, pos: [i64; 3]) -> Option<usize> {
+		let (chunk, local) = chunk_coords(pos);
+		let index = [chunk[0] - self.start_pos[0], chunk[1] - self.start_pos[1], chunk[2] - self.start_pos[2]];
+		if index.iter().any(|&i| i < 0) {
+			return None;
+		}
+		self.chunks.get((index[0] as usize, index[1] as usize, index[2] as usize))
+			.map(|chunk| chunk.borrow().blocks[local[0]][local[1]][local[2]])
+	}
+
+	// A liquid with nothing of itself above only fills its block up to the surface it's drawn with
+	pub fn medium_at(&self, block_render_data: &[BlockRenderData], point: &Point3<f32>) -> Medium {
+		let pos = [point.x.floor() as i64, point.y.floor() as i64, point.z.floor() as i64];
+		let liquid = |pos| self.get_block(pos).filter(|&block| block_render_data.get(block).map(|data| data.liquid).unwrap_or(false));
+		let block = match liquid(pos) {
+			Some(block) => block,
+			None => return Medium::Air,
+		};
+
+		let above = [pos[0], pos[1], pos[2] + 1];
+		if liquid(above) == Some(block) || point.z - pos[2] as f32 <= 1.0 - LIQUID_SURFACE_DROP {
+			Medium::Liquid(block)
+		} else {
+			Medium::Air
+		}
+	}
+
+	pub fn mesh_usage(&self) -> (usize, usize) {
+		self.chunks.iter().map(|chunk| chunk.borrow().mesh_usage()).fold((0, 0), |total, usage| (total.0 + usage.0, total.1 + usage.1))
+	}
+
+	// Chunks further than `render_distance` chunks from the eye aren't drawn
+	pub fn draw(&self, block_render_data: &[BlockRenderData], draw_service: &mut DrawService, view: Matrix4<f32>, eye: &Point3<f32>, render_distance: i64)
+			-> Result<(), SpaceError> {
+		let fills = self.chunks.map(|chunk| chunk.borrow().fill(block_render_data));
+		let max_distance = (render_distance * CHUNK_SIZE as i64) as f32;
+
+		let mut visible = Vec::new();
+		for ((i, j, k), chunk) in self.chunks.indexed_iter() {
+			match fills[(i, j, k)] {
+				ChunkFill::Empty => continue,
+				// Neighbours outside the region are unknown, so only fully enclosed solid chunks are skipped
+				ChunkFill::Solid if CuboidRegion::is_enclosed(&fills, i, j, k) => continue,
+				_ => (),
+			}
+
+			let pos = [self.start_pos[0] + i as i64, self.start_pos[1] + j as i64, self.start_pos[2] + k as i64];
+			if CuboidRegion::distance_squared(pos, eye) > max_distance * max_distance {
+				continue;
+			}
+			visible.push((pos, chunk));
+		}
+
+		// Remeshing is amortized across frames, closest chunks first and those in view before
+		// those behind the camera; stale meshes keep drawing meanwhile
+		let frustum = draw_service.frustum(&view);
+		let pending: Vec<_> = visible.iter()
+			.filter(|&&(_, chunk)| !chunk.borrow().mesh_valid.get())
+			.map(|&(pos, chunk)| (CuboidRegion::mesh_priority(pos, eye, &frustum), pos, chunk))
+			.collect();
+		self.remesh(block_render_data, draw_service, pending)?;
+
+		// Hidden chunks are still remeshed above, so they're ready when they come into sight
+		let unoccluded = self.unoccluded(block_render_data, eye, max_distance);
+		let chunks: Vec<([i64; 3], Ref<Chunk>)> = visible.into_iter()
+			.filter(|&(pos, _)| unoccluded.as_ref().map(|unoccluded| unoccluded(pos)).unwrap_or(true))
+			.map(|(pos, chunk)| (pos, chunk.borrow()))
+			.collect();
+		let meshes: Vec<([i64; 3], Ref<Option<ChunkMesh>>)> = chunks.iter().map(|&(pos, ref chunk)| (pos, chunk.mesh.borrow())).collect();
+		let draws: Vec<(&ChunkMesh, [f32; 3])> = meshes.iter()
+			.filter_map(|&(pos, ref mesh)| mesh.as_ref().map(|mesh| (mesh, CuboidRegion::chunk_offset(pos))))
+			.collect();
+
+		// Translucent liquids only blend correctly drawn back to front
+		let liquid_meshes: Vec<([i64; 3], Ref<Option<Mesh>>)> = chunks.iter().map(|&(pos, ref chunk)| (pos, chunk.liquid_mesh.borrow())).collect();
+		let mut liquid_draws: Vec<(f32, &Mesh, [f32; 3])> = liquid_meshes.iter()
+			.filter_map(|&(pos, ref mesh)| mesh.as_ref().map(|mesh| (CuboidRegion::distance_squared(pos, eye), mesh, CuboidRegion::chunk_offset(pos))))
+			.collect();
+		liquid_draws.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+		let liquid_draws: Vec<(&Mesh, [f32; 3])> = liquid_draws.into_iter().map(|(_, mesh, offset)| (mesh, offset)).collect();
+
+		draw_service.set_palette(block_render_data)?;
+		draw_service.draw_chunks(&view, &draws)?;
+		draw_service.draw_liquid_chunks(&view, &liquid_draws)
+	}
+
+	// Seen from straight above, as the map shows it: every chunk meshed is drawn, with nothing
+	// too far away and nothing hidden behind anything else, `center` being the middle of the map
+	pub fn draw_map(&self, block_render_data: &[BlockRenderData], draw_service: &mut DrawService, view: Matrix4<f32>, center: &Point3<f32>)
+			-> Result<(), SpaceError> {
+		let frustum = draw_service.frustum(&view);
+		let radius = CHUNK_SIZE as f32 * 3.0f32.sqrt() / 2.0;
+		let visible: Vec<([i64; 3], &Rc<RefCell<Chunk>>)> = self.chunks.indexed_iter()
+			.filter(|&(_, chunk)| chunk.borrow().fill(block_render_data) != ChunkFill::Empty)
+			.map(|((i, j, k), chunk)| ([self.start_pos[0] + i as i64, self.start_pos[1] + j as i64, self.start_pos[2] + k as i64], chunk))
+			.filter(|&(pos, _)| frustum.intersects_sphere(&CuboidRegion::chunk_center(pos), radius))
+			.collect();
+
+		// Nothing's behind the camera here, so the middle of the map comes first
+		let pending: Vec<_> = visible.iter()
+			.filter(|&&(_, chunk)| !chunk.borrow().mesh_valid.get())
+			.map(|&(pos, chunk)| (CuboidRegion::distance_squared(pos, center), pos, chunk))
+			.collect();
+		self.remesh(block_render_data, draw_service, pending)?;
+
+		let chunks: Vec<([i64; 3], Ref<Chunk>)> = visible.into_iter().map(|(pos, chunk)| (pos, chunk.borrow())).collect();
+		let meshes: Vec<([i64; 3], Ref<Option<ChunkMesh>>)> = chunks.iter().map(|&(pos, ref chunk)| (pos, chunk.mesh.borrow())).collect();
+		let draws: Vec<(&ChunkMesh, [f32; 3])> = meshes.iter()
+			.filter_map(|&(pos, ref mesh)| mesh.as_ref().map(|mesh| (mesh, CuboidRegion::chunk_offset(pos))))
+			.collect();
+
+		// Looking down, back to front is bottom to top
+		let liquid_meshes: Vec<([i64; 3], Ref<Option<Mesh>>)> = chunks.iter().map(|&(pos, ref chunk)| (pos, chunk.liquid_mesh.borrow())).collect();
+		let mut liquid_draws: Vec<(i64, &Mesh, [f32; 3])> = liquid_meshes.iter()
+			.filter_map(|&(pos, ref mesh)| mesh.as_ref().map(|mesh| (pos[2], mesh, CuboidRegion::chunk_offset(pos))))
+			.collect();
+		liquid_draws.sort_by_key(|draw| draw.0);
+		let liquid_draws: Vec<(&Mesh, [f32; 3])> = liquid_draws.into_iter().map(|(_, mesh, offset)| (mesh, offset)).collect();
+
+		draw_service.set_palette(block_render_data)?;
+		draw_service.draw_chunks(&view, &draws)?;
+		draw_service.draw_liquid_chunks(&view, &liquid_draws)
+	}
+
+	// Remeshes as many of the `pending` chunks as a frame allows, lowest priority first
+	fn remesh(&self, block_render_data: &[BlockRenderData], draw_service: &mut DrawService, mut pending: Vec<(f32, [i64; 3], &Rc<RefCell<Chunk>>)>)
+			-> Result<(), SpaceError> {
+		pending.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+		for &(_, pos, chunk) in pending.iter().take(MAX_MESH_UPLOADS_PER_FRAME) {
+			let nearby = self.nearby(pos);
+			chunk.borrow().update_mesh(block_render_data, Neighbours::of(&nearby), draw_service)?;
+		}
+		Ok(())
+	}
+
+	// Whether a chunk may be seen from the eye, going by visibility::visible_chunks. None with the
+	// eye outside the region, where there's nowhere to start from.
+	fn unoccluded(&self, block_render_data: &[BlockRenderData], eye: &Point3<f32>, max_distance: f32) -> Option<impl Fn([i64; 3]) -> bool> {
+		let size = CHUNK_SIZE as f32;
+		let eye_chunk = [(eye.x / size).floor() as i64, (eye.y / size).floor() as i64, (eye.z / size).floor() as i64];
+		let (width, depth, height) = self.chunks.dim();
+		let dims = [width, depth, height];
+		let start = [eye_chunk[0] - self.start_pos[0], eye_chunk[1] - self.start_pos[1], eye_chunk[2] - self.start_pos[2]];
+		if (0..3).any(|axis| start[axis] < 0 || start[axis] >= dims[axis] as i64) {
+			return None;
+		}
+
+		let start_pos = self.start_pos;
+		let world_pos = move |index: [usize; 3]| [start_pos[0] + index[0] as i64, start_pos[1] + index[1] as i64, start_pos[2] + index[2] as i64];
+		let visible = visibility::visible_chunks(
+			dims,
+			[start[0] as usize, start[1] as usize, start[2] as usize],
+			|index| self.chunks[(index[0], index[1], index[2])].borrow().connectivity(block_render_data),
+			|index| CuboidRegion::distance_squared(world_pos(index), eye) <= max_distance * max_distance,
+		);
+		Some(move |pos: [i64; 3]| {
+			let index = [(pos[0] - start_pos[0]) as usize, (pos[1] - start_pos[1]) as usize, (pos[2] - start_pos[2]) as usize];
+			visible[(index[0] * dims[1] + index[1]) * dims[2] + index[2]]
+		})
+	}
 
-					draw_service.draw_buffer(
-						&(view * Matrix4::new(1.0, 0.0, 0.0, (x * CHUNK_SIZE as i64) as f32,
-						                      0.0, 1.0, 0.0, (y * CHUNK_SIZE as i64) as f32,
-						                      0.0, 0.0, 1.0, (z * CHUNK_SIZE as i64) as f32,
-						                      0.0, 0.0, 0.0, 1.0)),
-						&*vertices,
-						&*indices
-					);
+	// The chunks around the one at `pos` that are in the region, by offset, for Neighbours::of
+	fn nearby(&self, pos: [i64; 3]) -> Vec<([isize; 3], Ref<Chunk>)> {
+		let (i, j, k) = ((pos[0] - self.start_pos[0]) as usize, (pos[1] - self.start_pos[1]) as usize, (pos[2] - self.start_pos[2]) as usize);
+		let mut nearby = Vec::new();
+		for x in -1..2isize {
+			for y in -1..2isize {
+				for z in -1..2isize {
+					if [x, y, z] == [0, 0, 0] {
+						continue;
+					}
+					let index = (i.wrapping_add(x as usize), j.wrapping_add(y as usize), k.wrapping_add(z as usize));
+					if let Some(chunk) = self.chunks.get(index) {
+						nearby.push(([x, y, z], chunk.borrow()));
+					}
+				}
+			}
+		}
+		nearby
+	}
 
-					z += 1;
+	// Meshes the whole region on the CPU and writes it to `path` as Wavefront OBJ in world
+	// coordinates, with vertex colors and a material per color in a .mtl file alongside.
+	pub fn export_obj(&self, path: &Path, block_render_data: &[BlockRenderData]) -> io::Result<()> {
+		let mtl_path = path.with_extension("mtl");
+		let mut obj = BufWriter::new(File::create(path)?);
+		writeln!(obj, "mtllib {}", mtl_path.file_name().unwrap().to_string_lossy())?;
+
+		// Faces are grouped by material, each face being its vertex indices and normal index
+		let mut materials: Vec<([f32; 3], bool)> = Vec::new();
+		let mut faces: Vec<Vec<([usize; 4], usize)>> = Vec::new();
+		let (mut vertex_count, mut normal_count) = (0, 0);
+
+		for ((i, j, k), chunk) in self.chunks.indexed_iter() {
+			let pos = [self.start_pos[0] + i as i64, self.start_pos[1] + j as i64, self.start_pos[2] + k as i64];
+			let offset = CuboidRegion::chunk_offset(pos);
+			let nearby = self.nearby(pos);
+			let neighbours = Neighbours::of(&nearby);
+
+			let chunk = chunk.borrow();
+			let (opaque, _) = chunk.build_mesh_data::<Vertex>(block_render_data, neighbours);
+			let (liquid, _) = chunk.build_liquid_mesh_data(block_render_data, neighbours);
+			let quads = opaque.iter().map(|v| (v.position, v.normal, v.color, false))
+				.chain(liquid.iter().map(|v| (v.position, v.normal, v.color, true)))
+				.collect::<Vec<_>>();
+
+			// Both meshers emit four vertices per quad
+			for quad in quads.chunks(4) {
+				for &(position, _, color, _) in quad {
+					writeln!(obj, "v {} {} {} {} {} {}",
+						position[0] + offset[0], position[1] + offset[1], position[2] + offset[2],
+						color[0], color[1], color[2])?;
 				}
-				y += 1;
+				let (_, normal, color, liquid) = quad[0];
+				writeln!(obj, "vn {} {} {}", normal[0], normal[1], normal[2])?;
+
+				let material = match materials.iter().position(|&m| m == (color, liquid)) {
+					Some(index) => index,
+					None => {
+						materials.push((color, liquid));
+						faces.push(Vec::new());
+						materials.len() - 1
+					},
+				};
+				// OBJ indices are 1-based; the quad's triangles are (0, 1, 2) and (3, 2, 1)
+				let v = vertex_count + 1;
+				faces[material].push(([v, v + 1, v + 3, v + 2], normal_count + 1));
+				vertex_count += 4;
+				normal_count += 1;
+			}
+		}
+
+		for (index, material_faces) in faces.iter().enumerate() {
+			writeln!(obj, "usemtl color_{}", index)?;
+			for &(v, n) in material_faces {
+				writeln!(obj, "f {}//{} {}//{} {}//{} {}//{}", v[0], n, v[1], n, v[2], n, v[3], n)?;
+			}
+		}
+		obj.flush()?;
+
+		let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+		for (index, &(color, liquid)) in materials.iter().enumerate() {
+			writeln!(mtl, "newmtl color_{}", index)?;
+			writeln!(mtl, "Kd {} {} {}", color[0], color[1], color[2])?;
+			if liquid {
+				writeln!(mtl, "d 0.6")?;
 			}
-			x += 1;
 		}
+		mtl.flush()
+	}
+
+	fn chunk_offset(pos: [i64; 3]) -> [f32; 3] {
+		[
+			(pos[0] * CHUNK_SIZE as i64) as f32,
+			(pos[1] * CHUNK_SIZE as i64) as f32,
+			(pos[2] * CHUNK_SIZE as i64) as f32,
+		]
+	}
+
+	fn chunk_center(pos: [i64; 3]) -> Point3<f32> {
+		let half = CHUNK_SIZE as f32 / 2.0;
+		Point3::new(
+			(pos[0] * CHUNK_SIZE as i64) as f32 + half,
+			(pos[1] * CHUNK_SIZE as i64) as f32 + half,
+			(pos[2] * CHUNK_SIZE as i64) as f32 + half,
+		)
+	}
+
+	fn distance_squared(pos: [i64; 3], eye: &Point3<f32>) -> f32 {
+		(CuboidRegion::chunk_center(pos) - eye).norm_squared()
+	}
+
+	// Lower comes first. Chunks out of view count as further away than they are.
+	fn mesh_priority(pos: [i64; 3], eye: &Point3<f32>, frustum: &Frustum) -> f32 {
+		let radius = CHUNK_SIZE as f32 * 3.0f32.sqrt() / 2.0;
+		let distance_squared = CuboidRegion::distance_squared(pos, eye);
+		if frustum.intersects_sphere(&CuboidRegion::chunk_center(pos), radius) {
+			distance_squared
+		} else {
+			distance_squared * OUT_OF_VIEW_PENALTY
+		}
+	}
+
+	fn is_enclosed(fills: &Array<ChunkFill, Ix3>, i: usize, j: usize, k: usize) -> bool {
+		let neighbours = [
+			(i.wrapping_sub(1), j, k), (i + 1, j, k),
+			(i, j.wrapping_sub(1), k), (i, j + 1, k),
+			(i, j, k.wrapping_sub(1)), (i, j, k + 1),
+		];
+		neighbours.iter().all(|&index| fills.get(index) == Some(&ChunkFill::Solid))
 	}
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChunkFill {
+	Empty,
+	Solid,
+	Mixed,
+}
+
+// A byte of state per block, read by the block as it likes; turned blocks keep their Orientation
+// here. It's 0 for a block just placed.
+pub type ChunkStates = [[[u8; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+
 // FIXME: Encapsulation
 pub struct Chunk {
 	pub blocks: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
-	mesh: RefCell<Option<(Rc<VertexBuffer<Vertex>>, Rc<IndexBuffer<u16>>)>>
+	states: ChunkStates,
+	biomes: ChunkBiomes,
+	light: [[[Light; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+	mesh: RefCell<Option<ChunkMesh>>,
+	liquid_mesh: RefCell<Option<Mesh>>,
+	mesh_valid: Cell<bool>,
+	fill: Cell<Option<ChunkFill>>,
+	connectivity: Cell<Option<FaceConnectivity>>,
+	dirty: Cell<bool>,
 }
 
-#[derive(Debug, Copy, Clone)]
+// The chunks around one being meshed, which the faces and light along its edges depend on.
+// Those sharing only an edge or a corner with it matter for light alone. None where missing.
+#[derive(Copy, Clone)]
+pub struct Neighbours<'a> {
+	// By offset, x slowest, each axis running -1 to 1; the middle one is never set
+	chunks: [Option<&'a Chunk>; 27],
+}
+
+impl<'a> Neighbours<'a> {
+	pub fn none() -> Neighbours<'a> {
+		Neighbours { chunks: [None; 27] }
+	}
+
+	pub fn of(nearby: &'a [([isize; 3], Ref<Chunk>)]) -> Neighbours<'a> {
+		let mut neighbours = Neighbours::none();
+		for &(offset, ref chunk) in nearby {
+			neighbours.set(offset, Some(&**chunk));
+		}
+		neighbours
+	}
+
+	fn index(offset: [isize; 3]) -> usize {
+		((offset[0] + 1) * 9 + (offset[1] + 1) * 3 + offset[2] + 1) as usize
+	}
+
+	pub fn set(&mut self, offset: [isize; 3], chunk: Option<&'a Chunk>) {
+		assert!(offset != [0, 0, 0] && offset.iter().all(|&step| step >= -1 && step <= 1), "not a neighbour: {:?}", offset);
+		self.chunks[Neighbours::index(offset)] = chunk;
+	}
+
+	pub fn get(&self, offset: [isize; 3]) -> Option<&'a Chunk> {
+		self.chunks[Neighbours::index(offset)]
+	}
+
+	// The six sharing a face, where the mesher looks for the one in direction `-dir` at
+	// dir.to_index()
+	fn adjacent(&self) -> [Option<&'a Chunk>; 6] {
+		use block::NormalDirection as ND;
+		let mut adjacent = [None; 6];
+		for dir in [ND::Front, ND::Up, ND::Right, ND::Back, ND::Down, ND::Left].iter() {
+			let offset = (-dir).to_vec_arr();
+			adjacent[dir.to_index()] = self.get([offset[0] as isize, offset[1] as isize, offset[2] as isize]);
+		}
+		adjacent
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum NormalDirection {
 	Up,
 	Down,
@@ -141,7 +1341,7 @@ pub enum NormalDirection {
 use na::Vector3;
 impl NormalDirection {
 	#[inline]
-	fn to_vec_arr(&self) -> [f32; 3] {
+	pub fn to_vec_arr(&self) -> [f32; 3] {
 		use block::NormalDirection as ND;
 		match self {
 			&ND::Front => [ 0.0,  1.0,  0.0],
@@ -166,8 +1366,23 @@ impl NormalDirection {
 		}
 	}
 
+	// Corners of the unit cube face on this side, ordered so (0, 1, 2) and (3, 2, 1) wind
+	// counterclockwise seen from outside
+	#[inline]
+	pub fn face_corners(&self) -> [[f32; 3]; 4] {
+		use block::NormalDirection as ND;
+		match self {
+			&ND::Front => [[0.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0]],
+			&ND::Up    => [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [0.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+			&ND::Right => [[1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0]],
+			&ND::Back  => [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 1.0]],
+			&ND::Down  => [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+			&ND::Left  => [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [0.0, 1.0, 1.0]],
+		}
+	}
+
 	#[inline]
-	fn to_index(&self) -> usize {
+	pub fn to_index(&self) -> usize {
 		use block::NormalDirection as ND;
 		match self {
 			&ND::Front => 0,
@@ -219,81 +1434,569 @@ impl <'a> Neg for &'a NormalDirection {
 	}
 }
 
+// Vertex layouts the greedy mesher can emit
+pub trait ChunkVertex: Copy {
+	// `color_index` is where `color` is in the palette
+	fn new(position: [f32; 3], normal: &NormalDirection, color_index: usize, color: [f32; 3], light: Light) -> Self;
+}
+
+// There's no light attribute, so the light is multiplied into the color instead
+impl ChunkVertex for Vertex {
+	#[inline]
+	fn new(position: [f32; 3], normal: &NormalDirection, _color_index: usize, color: [f32; 3], light: Light) -> Vertex {
+		let tint = light.color();
+		Vertex {
+			position: position,
+			normal: normal.to_vec_arr(),
+			color: [color[0] * tint[0], color[1] * tint[1], color[2] * tint[2]],
+		}
+	}
+}
+
+// Chunk-local positions are at most CHUNK_SIZE, the normal is packed as its direction index and
+// the color is looked up from the block palette in the shader.
+impl ChunkVertex for PackedVertex {
+	#[inline]
+	fn new(position: [f32; 3], normal: &NormalDirection, color_index: usize, _color: [f32; 3], light: Light) -> PackedVertex {
+		PackedVertex {
+			packed_position: [position[0] as u8, position[1] as u8, position[2] as u8, normal.to_index() as u8],
+			color_index: color_index as u16,
+			light: light.packed(),
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BlockProperties {
+	// Unique, as the block is listed and searched for
+	pub name: &'static str,
+	// Entities and falling blocks can't pass through solid blocks
+	pub solid: bool,
+	// Drops when there is nothing solid beneath it
+	pub falls: bool,
+	// Stops light passing through
+	pub opaque: bool,
+	// The red, green and blue block light it gives off, each up to MAX_LIGHT
+	pub emission: [u8; 3],
+	pub sounds: BlockSounds,
+	// How it's turned when placed
+	pub orienting: Orienting,
+	// Seconds the break button has to be held on it to break it, or 0 to break it at once
+	pub hardness: f32,
+}
+
+bitflags! {
+	// The faces of a block that hide whatever is on the other side of them
+	pub struct ObscuresFaces: u8 {
+		const FRONT = 0b000001;
+		const UP    = 0b000010;
+		const RIGHT = 0b000100;
+		const BACK  = 0b001000;
+		const DOWN  = 0b010000;
+		const LEFT  = 0b100000;
+	}
+}
+
+impl ObscuresFaces {
+	pub const NONE: ObscuresFaces = ObscuresFaces::empty();
+	pub const ALL: ObscuresFaces = ObscuresFaces::all();
+}
+
+impl From<NormalDirection> for ObscuresFaces {
+	fn from(dir: NormalDirection) -> ObscuresFaces {
+		use block::NormalDirection as ND;
+		match dir {
+			ND::Front => ObscuresFaces::FRONT,
+			ND::Up    => ObscuresFaces::UP,
+			ND::Right => ObscuresFaces::RIGHT,
+			ND::Back  => ObscuresFaces::BACK,
+			ND::Down  => ObscuresFaces::DOWN,
+			ND::Left  => ObscuresFaces::LEFT,
+		}
+	}
+}
+
 pub struct BlockRenderData {
-	pub obscures: u8,
+	pub obscures: ObscuresFaces,
 	pub color: [f32; 3],
 	pub should_render: bool,
+	// Liquids are meshed separately and drawn translucent after everything else
+	pub liquid: bool,
+	// Faces colored by their biome, and which color of it they take
+	pub tint: Option<(Tint, ObscuresFaces)>,
+	// Faces in a color of their own, like the ends of a log. Faces here and in `obscures` and
+	// `tint` are of the block as modelled, before it's turned.
+	pub trim: Option<([f32; 3], ObscuresFaces)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidRenderData {
+	// Blocks that aren't drawn can't hide anything
+	HiddenObscures(ObscuresFaces),
+	// Liquids are drawn translucent, so everything behind them has to be drawn too
+	LiquidObscures(ObscuresFaces),
+	HiddenLiquid,
+	ColorOutOfRange([f32; 3]),
+}
+
+// Starts out as an opaque white block that hides everything behind it
+pub struct BlockRenderDataBuilder {
+	// Unless set, all faces for opaque blocks and none for anything else
+	obscures: Option<ObscuresFaces>,
+	color: [f32; 3],
+	should_render: bool,
+	liquid: bool,
+	tint: Option<(Tint, ObscuresFaces)>,
+	trim: Option<([f32; 3], ObscuresFaces)>,
+}
+
+impl BlockRenderDataBuilder {
+	pub fn obscures(mut self, obscures: ObscuresFaces) -> BlockRenderDataBuilder {
+		self.obscures = Some(obscures);
+		self
+	}
+
+	pub fn color(mut self, color: [f32; 3]) -> BlockRenderDataBuilder {
+		self.color = color;
+		self
+	}
+
+	pub fn should_render(mut self, should_render: bool) -> BlockRenderDataBuilder {
+		self.should_render = should_render;
+		self
+	}
+
+	pub fn liquid(mut self, liquid: bool) -> BlockRenderDataBuilder {
+		self.liquid = liquid;
+		self
+	}
+
+	pub fn tint(mut self, tint: Tint, faces: ObscuresFaces) -> BlockRenderDataBuilder {
+		self.tint = Some((tint, faces));
+		self
+	}
+
+	pub fn trim(mut self, color: [f32; 3], faces: ObscuresFaces) -> BlockRenderDataBuilder {
+		self.trim = Some((color, faces));
+		self
+	}
+
+	pub fn build(self) -> Result<BlockRenderData, InvalidRenderData> {
+		let opaque = self.should_render && !self.liquid;
+		let obscures = self.obscures.unwrap_or(if opaque { ObscuresFaces::ALL } else { ObscuresFaces::NONE });
+		if !self.should_render && !obscures.is_empty() {
+			return Err(InvalidRenderData::HiddenObscures(obscures));
+		}
+		if self.liquid && !obscures.is_empty() {
+			return Err(InvalidRenderData::LiquidObscures(obscures));
+		}
+		if self.liquid && !self.should_render {
+			return Err(InvalidRenderData::HiddenLiquid);
+		}
+		for &color in Some(self.color).iter().chain(self.trim.map(|(color, _)| color).iter()) {
+			if color.iter().any(|&c| !(c >= 0.0 && c <= 1.0)) {
+				return Err(InvalidRenderData::ColorOutOfRange(color));
+			}
+		}
+
+		Ok(BlockRenderData {
+			obscures: obscures,
+			color: self.color,
+			should_render: self.should_render,
+			liquid: self.liquid,
+			tint: self.tint,
+			trim: self.trim,
+		})
+	}
 }
 
 impl BlockRenderData {
+	pub fn builder() -> BlockRenderDataBuilder {
+		BlockRenderDataBuilder {
+			obscures: None,
+			color: [1.0; 3],
+			should_render: true,
+			liquid: false,
+			tint: None,
+			trim: None,
+		}
+	}
+
 	fn obscures(&self, dir: &NormalDirection) -> bool {
-		use block::NormalDirection as ND;
-		let bit = match dir {
-			&ND::Front => 0b000001,
-			&ND::Up    => 0b000010,
-			&ND::Right => 0b000100,
-			&ND::Back  => 0b001000,
-			&ND::Down  => 0b010000,
-			&ND::Left  => 0b100000,
-		};
-		self.obscures & bit != 0
+		self.obscures.contains(ObscuresFaces::from(*dir))
 	}
-}
 
-use glium::vertex::BufferCreationError as VertexBufferCreationError;
-use glium::index::BufferCreationError as IndexBufferCreationError;
+	// How the face of the model facing `dir` is colored in `biome`
+	fn face_paint(&self, dir: &NormalDirection, biome: Biome) -> Paint {
+		let face = ObscuresFaces::from(*dir);
+		match (self.trim, self.tint) {
+			(Some((_, faces)), _) if faces.contains(face) => Paint::Trim,
+			(_, Some((tint, faces))) if faces.contains(face) => Paint::Tinted(tint, biome),
+			_ => Paint::Plain,
+		}
+	}
 
-#[derive(Debug, Copy, Clone)]
-pub enum MeshCreationError {
-	VertexBufferCreationFailed(VertexBufferCreationError),
-	IndexBufferCreationFailed(IndexBufferCreationError),
+	fn paint_color(&self, paint: Paint) -> [f32; 3] {
+		match paint {
+			Paint::Plain => self.color,
+			Paint::Trim => self.trim.map(|(color, _)| color).unwrap_or(self.color),
+			Paint::Tinted(tint, biome) => {
+				let factor = biome.tint_color(tint);
+				[
+					(self.color[0] * factor[0]).min(1.0),
+					(self.color[1] * factor[1]).min(1.0),
+					(self.color[2] * factor[2]).min(1.0),
+				]
+			},
+		}
+	}
 }
 
-impl From<VertexBufferCreationError> for MeshCreationError {
-	fn from(err: VertexBufferCreationError) -> MeshCreationError {
-		MeshCreationError::VertexBufferCreationFailed(err)
-	}
+// Which of a block's colors a face takes
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Paint {
+	Plain,
+	Trim,
+	Tinted(Tint, Biome),
 }
 
-impl From<IndexBufferCreationError> for MeshCreationError {
-	fn from(err: IndexBufferCreationError) -> MeshCreationError {
-		MeshCreationError::IndexBufferCreationFailed(err)
+// The colors packed vertices look up by index, in rows as wide as there are blocks: the blocks'
+// own colors, their trim, then a row for each biome of them as tinted there
+pub fn palette(block_render_data: &[BlockRenderData]) -> Vec<[f32; 3]> {
+	let mut colors: Vec<[f32; 3]> = block_render_data.iter().map(|data| data.color).collect();
+	colors.extend(block_render_data.iter().map(|data| data.paint_color(Paint::Trim)));
+	for biome in BIOMES.iter() {
+		colors.extend(block_render_data.iter().map(|data| match data.tint {
+			Some((tint, _)) => data.paint_color(Paint::Tinted(tint, *biome)),
+			None => data.color,
+		}));
 	}
+	colors
+}
+
+fn palette_index(block: usize, paint: Paint, block_count: usize) -> usize {
+	let row = match paint {
+		Paint::Plain => 0,
+		Paint::Trim => 1,
+		Paint::Tinted(_, biome) => biome.index() + 2,
+	};
+	row * block_count + block
 }
 
 pub const CHUNK_SIZE: usize = 32;
 
-use glium::{VertexBuffer, IndexBuffer};
-use glium::index::PrimitiveType;
-use glium::backend::Facade;
+// Splits a world block position into its chunk position and the position within that chunk
+pub fn chunk_coords(pos: [i64; 3]) -> ([i64; 3], [usize; 3]) {
+	let size = CHUNK_SIZE as i64;
+	(
+		[pos[0].div_euclid(size), pos[1].div_euclid(size), pos[2].div_euclid(size)],
+		[pos[0].rem_euclid(size) as usize, pos[1].rem_euclid(size) as usize, pos[2].rem_euclid(size) as usize],
+	)
+}
+
+pub const NEIGHBOUR_OFFSETS: [[i64; 3]; 6] = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+
+const SPAWN_SEARCH_RADIUS: i64 = 8;
+const SPAWN_SEARCH_TOP: i64 = 256;
+const SPAWN_SEARCH_BOTTOM: i64 = -256;
+// Air blocks needed above a spawn, enough for the player to stand in
+const SPAWN_CLEARANCE: usize = 2;
+
+pub const AIR: usize = 0;
+pub const GROUND: usize = 1;
+pub const WATER: usize = 2;
+pub const SAND: usize = 3;
+pub const SNOW: usize = 4;
+pub const LOG: usize = 5;
+// Ids from here on aren't blocks
+pub const BLOCK_TYPES: usize = LOG + 1;
+
+// How AIR through LOG behave, in that order
+pub fn block_properties() -> Vec<BlockProperties> {
+	vec![
+		BlockProperties { name: "air", solid: false, falls: false, opaque: false, emission: [0; 3], sounds: BlockSounds::silent(), orienting: Orienting::Fixed, hardness: 0.0 },
+		BlockProperties {
+			name: "ground",
+			solid: true,
+			falls: false,
+			opaque: true,
+			emission: [0; 3],
+			sounds: BlockSounds { broken: Some(Sound::BlockBreak), placed: Some(Sound::BlockPlace), step: Some(Sound::Step) },
+			orienting: Orienting::Fixed,
+			hardness: 0.5,
+		},
+		BlockProperties {
+			name: "water",
+			solid: false,
+			falls: false,
+			opaque: false,
+			emission: [0; 3],
+			sounds: BlockSounds { broken: Some(Sound::Splash), placed: Some(Sound::Splash), step: None },
+			orienting: Orienting::Fixed,
+			hardness: 0.0,
+		},
+		BlockProperties {
+			name: "sand",
+			solid: true,
+			falls: true,
+			opaque: true,
+			emission: [0; 3],
+			sounds: BlockSounds { broken: Some(Sound::SandBreak), placed: Some(Sound::SandBreak), step: Some(Sound::SandStep) },
+			orienting: Orienting::Fixed,
+			hardness: 0.3,
+		},
+		BlockProperties {
+			name: "snow",
+			solid: true,
+			falls: false,
+			opaque: true,
+			emission: [0; 3],
+			sounds: BlockSounds { broken: Some(Sound::SandBreak), placed: Some(Sound::SandBreak), step: Some(Sound::SandStep) },
+			orienting: Orienting::Fixed,
+			hardness: 0.15,
+		},
+		BlockProperties {
+			name: "log",
+			solid: true,
+			falls: false,
+			opaque: true,
+			emission: [0; 3],
+			sounds: BlockSounds { broken: Some(Sound::BlockBreak), placed: Some(Sound::BlockPlace), step: Some(Sound::Step) },
+			orienting: Orienting::Axis,
+			hardness: 1.2,
+		},
+	]
+}
+
+// How AIR through LOG are drawn, in that order
+pub fn block_render_types() -> Result<Vec<BlockRenderData>, InvalidRenderData> {
+	Ok(vec![
+		BlockRenderData::builder().should_render(false).color([0.0; 3]).build()?,
+		// Grass on top
+		BlockRenderData::builder().color([0.3, 0.4, 0.2]).tint(Tint::Grass, ObscuresFaces::UP).build()?,
+		BlockRenderData::builder().color([0.1, 0.3, 0.6]).liquid(true).tint(Tint::Water, ObscuresFaces::ALL).build()?,
+		BlockRenderData::builder().color([0.8, 0.75, 0.5]).build()?,
+		BlockRenderData::builder().color([0.95, 0.95, 0.97]).build()?,
+		// Bark, with rings at the ends
+		BlockRenderData::builder().color([0.4, 0.28, 0.15]).trim([0.7, 0.55, 0.35], ObscuresFaces::FRONT | ObscuresFaces::BACK).build()?,
+	])
+}
+
+// How far the top of a liquid sits below the top of its block
+const LIQUID_SURFACE_DROP: f32 = 0.125;
+
+// For comparing corners of a face, all channels together
+fn brightness(light: Light) -> u32 {
+	CHANNELS.iter().map(|&channel| light.get(channel) as u32).sum()
+}
 
 use na::{Matrix3, Matrix4};
 
-use gl_util::Vertex;
+use std::mem::replace;
+use gl_util::{Vertex, PackedVertex, LiquidVertex, VertexFormat, Mesh, MeshCreationError, Frustum};
+use chunk_batch::ChunkMesh;
+use light::{Light, CHANNELS};
+use visibility::{self, FaceConnectivity};
 impl Chunk {
 	pub fn new(blocks: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> Chunk {
 		Chunk {
 			blocks: blocks,
+			states: [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+			biomes: [[Biome::Plains; CHUNK_SIZE]; CHUNK_SIZE],
+			light: [[[Light::SKY; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
 			mesh: RefCell::new(Option::None),
+			liquid_mesh: RefCell::new(Option::None),
+			mesh_valid: Cell::new(false),
+			fill: Cell::new(Option::None),
+			connectivity: Cell::new(Option::None),
+			dirty: Cell::new(false),
 		}
 	}
 
-	pub fn build_mesh<F: Facade>(&self, block_render_data: &[BlockRenderData], adj_chunks: [Option<&Chunk>; 6], facade: &F)
-			-> Result<(Rc<VertexBuffer<Vertex>>, Rc<IndexBuffer<u16>>), MeshCreationError> {
-		use block::NormalDirection as ND;
+	pub fn is_dirty(&self) -> bool {
+		self.dirty.get()
+	}
+
+	// In bytes: the chunk itself, its light among it, and its meshes on the GPU
+	pub fn memory_usage(&self) -> usize {
+		size_of::<Chunk>() + self.mesh_usage().1
+	}
+
+	// How many meshes the chunk holds on the GPU, and their size in bytes
+	pub fn mesh_usage(&self) -> (usize, usize) {
+		let mut usage = (0, 0);
+		if let Some(ref mesh) = *self.mesh.borrow() {
+			usage.0 += 1;
+			usage.1 += mesh.byte_size();
+		}
+		if let Some(ref mesh) = *self.liquid_mesh.borrow() {
+			usage.0 += 1;
+			usage.1 += mesh.byte_size();
+		}
+		usage
+	}
+
+	pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: usize) {
+		if self.blocks[x][y][z] == block {
+			return;
+		}
+		self.blocks[x][y][z] = block;
+		self.states[x][y][z] = 0;
+		self.dirty.set(true);
+		self.invalidate_mesh();
+	}
+
+	pub fn state(&self, x: usize, y: usize, z: usize) -> u8 {
+		self.states[x][y][z]
+	}
+
+	pub fn states(&self) -> &ChunkStates {
+		&self.states
+	}
+
+	pub fn set_state(&mut self, x: usize, y: usize, z: usize, state: u8) {
+		if self.states[x][y][z] == state {
+			return;
+		}
+		self.states[x][y][z] = state;
+		self.dirty.set(true);
+		self.invalidate_mesh();
+	}
+
+	fn orientation(&self, x: usize, y: usize, z: usize) -> Orientation {
+		Orientation::from_state(self.states[x][y][z])
+	}
+
+	pub fn biomes(&self) -> &ChunkBiomes {
+		&self.biomes
+	}
+
+	// Biomes aren't saved with the blocks, as the generator can always pick them again
+	pub fn set_biomes(&mut self, biomes: ChunkBiomes) {
+		if self.biomes == biomes {
+			return;
+		}
+		self.biomes = biomes;
+		self.invalidate_mesh();
+	}
+
+	pub fn light(&self, x: usize, y: usize, z: usize) -> Light {
+		self.light[x][y][z]
+	}
+
+	// Light isn't saved, so this doesn't make the chunk dirty
+	pub fn set_light(&mut self, x: usize, y: usize, z: usize, light: Light) {
+		if self.light[x][y][z] == light {
+			return;
+		}
+		self.light[x][y][z] = light;
+		self.invalidate_mesh();
+	}
+
+	// The stale mesh is kept until the rebuild so its buffers can be handed back to the pool
+	pub fn invalidate_mesh(&self) {
+		self.mesh_valid.set(false);
+		self.fill.set(None);
+		self.connectivity.set(None);
+	}
+
+	pub fn fill(&self, block_render_data: &[BlockRenderData]) -> ChunkFill {
+		if let Some(fill) = self.fill.get() {
+			return fill;
+		}
+
+		let (mut empty, mut solid) = (true, true);
+		for block in self.blocks.iter().flat_map(|plane| plane.iter()).flat_map(|row| row.iter()) {
+			let render_data = &block_render_data[*block];
+			empty &= !render_data.should_render;
+			solid &= render_data.should_render && render_data.obscures == ObscuresFaces::ALL;
+			if !empty && !solid {
+				break;
+			}
+		}
+
+		let fill = if empty {
+			ChunkFill::Empty
+		} else if solid {
+			ChunkFill::Solid
+		} else {
+			ChunkFill::Mixed
+		};
+		self.fill.set(Some(fill));
+		fill
+	}
+
+	// Which of the chunk's faces can be seen through it from which, for occlusion culling
+	pub fn connectivity(&self, block_render_data: &[BlockRenderData]) -> FaceConnectivity {
+		if let Some(connectivity) = self.connectivity.get() {
+			return connectivity;
+		}
+
+		let connectivity = match self.fill(block_render_data) {
+			ChunkFill::Empty => FaceConnectivity::ALL,
+			ChunkFill::Solid => FaceConnectivity::NONE,
+			ChunkFill::Mixed => FaceConnectivity::of(self, block_render_data),
+		};
+		self.connectivity.set(Some(connectivity));
+		connectivity
+	}
+
+	pub fn update_mesh(&self, block_render_data: &[BlockRenderData], neighbours: Neighbours, draw_service: &mut DrawService)
+			-> Result<(), MeshCreationError> {
+		if self.mesh_valid.get() {
+			return Ok(());
+		}
 
-		if let &Some((ref v, ref i)) = &*self.mesh.borrow() {
-			return Ok((v.clone(), i.clone()));
+		let mesh = match draw_service.chunk_vertex_format() {
+			VertexFormat::Standard => {
+				let (vertices, indices) = self.build_mesh_data::<Vertex>(block_render_data, neighbours);
+				draw_service.upload_chunk_mesh(&vertices, &indices)?
+			},
+			VertexFormat::Packed => {
+				let (vertices, indices) = self.build_mesh_data::<PackedVertex>(block_render_data, neighbours);
+				draw_service.upload_chunk_mesh(&vertices, &indices)?
+			},
+		};
+		// The old mesh's space in the arena is freed as it's dropped
+		*self.mesh.borrow_mut() = Some(mesh);
+
+		let (vertices, indices) = self.build_liquid_mesh_data(block_render_data, neighbours);
+		let liquid_mesh = if indices.is_empty() {
+			None
+		} else {
+			Some(draw_service.upload_mesh(&vertices, &indices)?)
+		};
+		if let Some(old) = replace(&mut *self.liquid_mesh.borrow_mut(), liquid_mesh) {
+			draw_service.recycle_mesh(old);
 		}
+		self.mesh_valid.set(true);
+		Ok(())
+	}
+
+	pub fn build_mesh_data<V: ChunkVertex>(&self, block_render_data: &[BlockRenderData], neighbours: Neighbours) -> (Vec<V>, Vec<u16>) {
+		use block::NormalDirection as ND;
+		profile_scope!("meshing");
+		let adj_chunks = neighbours.adjacent();
 
-		let mut data: Vec<Vertex> = Vec::new();
+		let mut data: Vec<V> = Vec::new();
 		let mut indices: Vec<u16> = Vec::new();
 
 		let mut quad_start = 0;
 		for up_dir in [ND::Up, ND::Down, ND::Left, ND::Right, ND::Front, ND::Back].into_iter() {
-			let up_vec3 = up_dir.to_vec_arr();
 			for w in 0..CHUNK_SIZE {
-				let mut slice: [[Option<[f32; 3]>; CHUNK_SIZE]; CHUNK_SIZE] = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+				// Faces only merge if they're the same block, painted alike, with the same light at each corner
+				let mut slice: [[Option<(usize, Paint, [Light; 4])>; CHUNK_SIZE]; CHUNK_SIZE] = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+				// The directions u and v run in, for finding the cells around each corner
+				let (u_axis, v_axis) = match up_dir {
+					&ND::Up    => ([1, 0, 0], [0, 1, 0]),
+					&ND::Down  => ([0, 1, 0], [1, 0, 0]),
+
+					&ND::Left  => ([0, 0, 1], [0, 1, 0]),
+					&ND::Right => ([0, 1, 0], [0, 0, 1]),
+
+					&ND::Front => ([0, 0, 1], [1, 0, 0]),
+					&ND::Back  => ([1, 0, 0], [0, 0, 1]),
+				};
 
 				for u in 0..CHUNK_SIZE {
 					for v in 0..CHUNK_SIZE {
@@ -319,29 +2022,36 @@ impl Chunk {
 							&ND::Back  => (0, -1isize as usize, 0),
 						};
 
-						if !block_render_data[self.blocks[*x][*y][*z]].should_render {
+						let render_data = &block_render_data[self.blocks[*x][*y][*z]];
+						if !render_data.should_render || render_data.liquid {
 							slice[u][v] = None;
 							continue;
 						}
 
 						let (query_x, query_y, query_z) = (x.wrapping_add(x_offset), y.wrapping_add(y_offset), z.wrapping_add(z_offset));
 
-						slice[u][v] = if query_x >= CHUNK_SIZE || query_y >= CHUNK_SIZE || query_z >= CHUNK_SIZE {
+						// The neighbour may be turned too, so ask whether the face of its model that's
+						// towards us obscures
+						let visible = if query_x >= CHUNK_SIZE || query_y >= CHUNK_SIZE || query_z >= CHUNK_SIZE {
 							if let Some(chunk) = adj_chunks[(-up_dir).to_index()] {
-								if !block_render_data[chunk.blocks[query_x % CHUNK_SIZE][query_y % CHUNK_SIZE][query_z % CHUNK_SIZE]].obscures(&-up_dir) {
-									Some(block_render_data[self.blocks[*x][*y][*z]].color)
-								} else {
-									None
-								}
+								let (query_x, query_y, query_z) = (query_x % CHUNK_SIZE, query_y % CHUNK_SIZE, query_z % CHUNK_SIZE);
+								let towards = chunk.orientation(query_x, query_y, query_z).to_model(&-up_dir);
+								!block_render_data[chunk.blocks[query_x][query_y][query_z]].obscures(&towards)
 							} else {
-								Some(block_render_data[self.blocks[*x][*y][*z]].color)
+								true
 							}
 						} else {
-							if !block_render_data[self.blocks[query_x][query_y][query_z]].obscures(&-up_dir) {
-								Some(block_render_data[self.blocks[*x][*y][*z]].color)
-							} else {
-								None
-							}
+							let towards = self.orientation(query_x, query_y, query_z).to_model(&-up_dir);
+							!block_render_data[self.blocks[query_x][query_y][query_z]].obscures(&towards)
+						};
+
+						slice[u][v] = if visible {
+							let facing = [query_x as isize, query_y as isize, query_z as isize];
+							let face = self.orientation(*x, *y, *z).to_model(up_dir);
+							let paint = render_data.face_paint(&face, self.biomes[*x][*y]);
+							Some((self.blocks[*x][*y][*z], paint, self.corner_lights(facing, u_axis, v_axis, neighbours)))
+						} else {
+							None
 						};
 					}
 				}
@@ -351,16 +2061,16 @@ impl Chunk {
 					while u < CHUNK_SIZE {
 						match slice[u][v] {
 							None => { u += 1; },
-							Some(color) => {
+							Some(face) => {
 								let mut width: usize = 1;
-								while u + width < CHUNK_SIZE && slice[u + width][v] == Some(color) {
+								while u + width < CHUNK_SIZE && slice[u + width][v] == Some(face) {
 									width += 1;
 								}
 
 								let mut height: usize = CHUNK_SIZE - v;
 								'outer: for h in 1..(CHUNK_SIZE - v) {
 									for k in 0..width {
-										if slice[u + k][v + h] != Some(color) {
+										if slice[u + k][v + h] != Some(face) {
 											height = h;
 											break 'outer;
 										}
@@ -384,10 +2094,14 @@ impl Chunk {
 									&ND::Back  => 0,
 								};
 
+								// Indexed (u, v), (u + 1, v), (u, v + 1), (u + 1, v + 1)
+								let (block, paint, corners) = face;
+								let color = block_render_data[block].paint_color(paint);
+								let color_index = palette_index(block, paint, block_render_data.len());
 								let (u_float, v_float, w_float, u_width_float, v_height_float) = (u as f32, v as f32, (w + w_offset) as f32, (u + width) as f32, (v + height) as f32);
 
-								data.push(Vertex {
-									position: match up_dir {
+								data.push(V::new(
+									match up_dir {
 										&ND::Up    => [u_float, v_height_float, w_float],
 										&ND::Down  => [v_height_float, u_float, w_float],
 
@@ -397,12 +2111,11 @@ impl Chunk {
 										&ND::Front => [v_height_float, w_float, u_float],
 										&ND::Back  => [u_float, w_float, v_height_float],
 									},
-									normal: up_vec3,
-									color: color,
-								});
+									up_dir, color_index, color, corners[2]
+								));
 
-								data.push(Vertex {
-									position: match up_dir {
+								data.push(V::new(
+									match up_dir {
 										&ND::Up    => [u_float, v_float, w_float],
 										&ND::Down  => [v_float, u_float, w_float],
 
@@ -412,12 +2125,11 @@ impl Chunk {
 										&ND::Front => [v_float, w_float, u_float],
 										&ND::Back  => [u_float, w_float, v_float],
 									},
-									normal: up_vec3,
-									color: color,
-								});
+									up_dir, color_index, color, corners[0]
+								));
 
-								data.push(Vertex {
-									position: match up_dir {
+								data.push(V::new(
+									match up_dir {
 										&ND::Up    => [u_width_float, v_height_float, w_float],
 										&ND::Down  => [v_height_float, u_width_float, w_float],
 
@@ -427,12 +2139,11 @@ impl Chunk {
 										&ND::Front => [v_height_float, w_float, u_width_float],
 										&ND::Back  => [u_width_float, w_float, v_height_float],
 									},
-									normal: up_vec3,
-									color: color,
-								});
+									up_dir, color_index, color, corners[3]
+								));
 
-								data.push(Vertex {
-									position: match up_dir {
+								data.push(V::new(
+									match up_dir {
 										&ND::Up    => [u_width_float, v_float, w_float],
 										&ND::Down  => [v_float, u_width_float, w_float],
 
@@ -442,16 +2153,19 @@ impl Chunk {
 										&ND::Front => [v_float, w_float, u_width_float],
 										&ND::Back  => [u_width_float, w_float, v_float],
 									},
-									normal: up_vec3,
-									color: color,
-								});
-
-								indices.push(quad_start + 0);
-								indices.push(quad_start + 1);
-								indices.push(quad_start + 2);
-								indices.push(quad_start + 3);
-								indices.push(quad_start + 2);
-								indices.push(quad_start + 1);
+									up_dir, color_index, color, corners[1]
+								));
+
+								// Light is interpolated across each triangle, so the diagonal they
+								// share decides which way it spreads. Along the brighter pair of
+								// corners, one dark corner stays in its own triangle rather than
+								// streaking across the quad, and the shading looks the same which
+								// way round the quad is.
+								if brightness(corners[1]) + brightness(corners[2]) > brightness(corners[0]) + brightness(corners[3]) {
+									indices.extend_from_slice(&[quad_start, quad_start + 1, quad_start + 3, quad_start, quad_start + 3, quad_start + 2]);
+								} else {
+									indices.extend_from_slice(&[quad_start, quad_start + 1, quad_start + 2, quad_start + 3, quad_start + 2, quad_start + 1]);
+								}
 
 								quad_start += 4;
 								u += width;
@@ -465,20 +2179,139 @@ impl Chunk {
 			}
 		}
 
-		let res = VertexBuffer::new(facade, &data)
-			.map(|v| Rc::new(v))
-			.map_err(|e| MeshCreationError::from(e))
-			.and_then(|v| IndexBuffer::new(facade, PrimitiveType::TrianglesList, &indices)
-				.map(|i| (v, Rc::new(i)))
-				.map_err(|e| MeshCreationError::from(e))
-			);
+		(data, indices)
+	}
 
-		if let Ok(ref mesh) = res {
-			let mut cache = self.mesh.borrow_mut();
-			*cache = Some(mesh.clone());
-		};
+	// Liquids are meshed a face at a time so every surface vertex can be lowered and animated.
+	// Faces between blocks of the same liquid are skipped, as are faces towards unloaded
+	// neighbours other than the surface.
+	pub fn build_liquid_mesh_data(&self, block_render_data: &[BlockRenderData], neighbours: Neighbours) -> (Vec<LiquidVertex>, Vec<u16>) {
+		use block::NormalDirection as ND;
+		profile_scope!("meshing");
+		let adj_chunks = neighbours.adjacent();
 
-		res
+		let mut data: Vec<LiquidVertex> = Vec::new();
+		let mut indices: Vec<u16> = Vec::new();
+
+		for x in 0..CHUNK_SIZE {
+			for y in 0..CHUNK_SIZE {
+				for z in 0..CHUNK_SIZE {
+					let block = self.blocks[x][y][z];
+					let render_data = &block_render_data[block];
+					if !render_data.should_render || !render_data.liquid {
+						continue;
+					}
+
+					let surface = self.neighbour(x, y, z, &ND::Up, adj_chunks).map(|(other, _)| other) != Some(block);
+
+					for dir in [ND::Up, ND::Down, ND::Left, ND::Right, ND::Front, ND::Back].iter() {
+						let visible = match self.neighbour(x, y, z, dir, adj_chunks) {
+							Some((other, orientation)) => other != block && !block_render_data[other].obscures(&orientation.to_model(&-dir)),
+							None => match dir {
+								&ND::Up => true,
+								_ => false,
+							},
+						};
+						if !visible {
+							continue;
+						}
+
+						let start = data.len() as u16;
+						let face = self.orientation(x, y, z).to_model(dir);
+						let color = render_data.paint_color(render_data.face_paint(&face, self.biomes[x][y]));
+						for corner in dir.face_corners().iter() {
+							let lowered = surface && corner[2] == 1.0;
+							data.push(LiquidVertex {
+								position: [
+									x as f32 + corner[0],
+									y as f32 + corner[1],
+									z as f32 + corner[2] - if lowered { LIQUID_SURFACE_DROP } else { 0.0 },
+								],
+								normal: dir.to_vec_arr(),
+								color: color,
+								wave: if lowered { 1.0 } else { 0.0 },
+							});
+						}
+						indices.extend_from_slice(&[start, start + 1, start + 2, start + 3, start + 2, start + 1]);
+					}
+				}
+			}
+		}
+
+		(data, indices)
+	}
 
+	// Each corner of a face is lit by the average of the four cells around it on the side the
+	// face looks onto, `facing` being the one directly in front of it. Until light is propagated
+	// everything meshes in full sky light unless the lighting feature is on.
+	fn corner_lights(&self, facing: [isize; 3], u_axis: [isize; 3], v_axis: [isize; 3], neighbours: Neighbours) -> [Light; 4] {
+		if !cfg!(feature = "lighting") {
+			return [Light::SKY; 4];
+		}
+
+		let mut corners = [Light::SKY; 4];
+		for (i, corner) in corners.iter_mut().enumerate() {
+			let u_step = if i & 1 == 0 { -1 } else { 1 };
+			let v_step = if i & 2 == 0 { -1 } else { 1 };
+			let mut sum = [0u32; 4];
+			let mut count = 0;
+			for &(du, dv) in [(0, 0), (u_step, 0), (0, v_step), (u_step, v_step)].iter() {
+				let pos = [
+					facing[0] + u_axis[0] * du + v_axis[0] * dv,
+					facing[1] + u_axis[1] * du + v_axis[1] * dv,
+					facing[2] + u_axis[2] * du + v_axis[2] * dv,
+				];
+				if let Some(light) = self.light_at(pos, neighbours) {
+					for (total, &channel) in sum.iter_mut().zip(CHANNELS.iter()) {
+						*total += light.get(channel) as u32;
+					}
+					count += 1;
+				}
+			}
+
+			// The cell in front of the face is always known unless its chunk is missing
+			if count > 0 {
+				let mut light = Light::DARK;
+				for (&total, &channel) in sum.iter().zip(CHANNELS.iter()) {
+					light = light.with(channel, ((total + count / 2) / count) as u8);
+				}
+				*corner = light;
+			}
+		}
+		corners
+	}
+
+	// Light at a chunk-local position, which may be just outside the chunk, across a face, edge
+	// or corner. Corners on a chunk's edge are lit by cells in up to four chunks, and both sides
+	// of the edge must see all of them or they'll light it differently. None in a neighbour we
+	// don't have.
+	fn light_at(&self, pos: [isize; 3], neighbours: Neighbours) -> Option<Light> {
+		let size = CHUNK_SIZE as isize;
+		let offset = [pos[0].div_euclid(size), pos[1].div_euclid(size), pos[2].div_euclid(size)];
+		let local = [pos[0].rem_euclid(size) as usize, pos[1].rem_euclid(size) as usize, pos[2].rem_euclid(size) as usize];
+		let chunk = if offset == [0, 0, 0] {
+			self
+		} else {
+			neighbours.get(offset)?
+		};
+		Some(chunk.light[local[0]][local[1]][local[2]])
+	}
+
+	// The block next to (x, y, z) in `dir` and how it's turned, or None if it's in a neighbouring
+	// chunk we don't have
+	fn neighbour(&self, x: usize, y: usize, z: usize, dir: &NormalDirection, adj_chunks: [Option<&Chunk>; 6]) -> Option<(usize, Orientation)> {
+		let offset = dir.to_vec_arr();
+		let (query_x, query_y, query_z) = (
+			x.wrapping_add(offset[0] as isize as usize),
+			y.wrapping_add(offset[1] as isize as usize),
+			z.wrapping_add(offset[2] as isize as usize),
+		);
+
+		if query_x >= CHUNK_SIZE || query_y >= CHUNK_SIZE || query_z >= CHUNK_SIZE {
+			let (query_x, query_y, query_z) = (query_x % CHUNK_SIZE, query_y % CHUNK_SIZE, query_z % CHUNK_SIZE);
+			adj_chunks[(-dir).to_index()].map(|chunk| (chunk.blocks[query_x][query_y][query_z], chunk.orientation(query_x, query_y, query_z)))
+		} else {
+			Some((self.blocks[query_x][query_y][query_z], self.orientation(query_x, query_y, query_z)))
+		}
 	}
 }
@@ -0,0 +1,113 @@
+use gui::GuiService;
+use block::{BlockProperties, AIR, GROUND, WATER, SAND, SNOW, LOG};
+use text_input::{TextEdit, TextInput};
+
+pub const HOTBAR_SLOTS: usize = 5;
+
+// The blocks at hand, picked between with the number keys. The one selected is placed with the
+// right mouse button.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotbar {
+	slots: [usize; HOTBAR_SLOTS],
+	selected: usize,
+}
+
+impl Default for Hotbar {
+	fn default() -> Hotbar {
+		Hotbar { slots: [GROUND, SAND, SNOW, WATER, LOG], selected: 0 }
+	}
+}
+
+impl Hotbar {
+	pub fn held(&self) -> usize {
+		self.slots[self.selected]
+	}
+
+	pub fn slots(&self) -> &[usize] {
+		&self.slots
+	}
+
+	pub fn selected(&self) -> usize {
+		self.selected
+	}
+
+	pub fn select(&mut self, slot: usize) {
+		if slot < HOTBAR_SLOTS {
+			self.selected = slot;
+		}
+	}
+
+	// Selects the block if it's already at hand, and otherwise puts it in the selected slot
+	pub fn pick(&mut self, block: usize) {
+		match self.slots.iter().position(|&slot| slot == block) {
+			Some(slot) => self.selected = slot,
+			None => self.slots[self.selected] = block,
+		}
+	}
+}
+
+// Every block there is, by name, to put in the hotbar. Opened with I; typing narrows the list
+// down to the blocks whose names contain what's typed.
+pub struct BlockPicker {
+	open: bool,
+	search: TextInput,
+}
+
+impl BlockPicker {
+	pub fn new() -> BlockPicker {
+		BlockPicker { open: false, search: TextInput::new() }
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.open
+	}
+
+	pub fn open(&mut self) {
+		self.open = true;
+		self.search.clear();
+	}
+
+	pub fn close(&mut self) {
+		self.open = false;
+	}
+
+	// Enter picks the first block found
+	pub fn edit(&mut self, edit: &TextEdit, blocks: &[BlockProperties], hotbar: &mut Hotbar) {
+		if let Some(search) = self.search.apply(edit) {
+			if let Some(block) = matching(blocks, &search).next() {
+				hotbar.pick(block);
+			}
+			self.open = false;
+		}
+	}
+
+	pub fn build(&mut self, gui: &GuiService, blocks: &[BlockProperties], hotbar: &mut Hotbar) {
+		let search = self.search.text();
+		let shown = self.search.with_cursor('|');
+		gui.window("Blocks", [40.0, 40.0], 360.0, |ui| {
+			let slots: Vec<String> = hotbar.slots().iter().enumerate()
+				.map(|(i, &block)| if i == hotbar.selected() {
+					format!("[{}]", blocks[block].name)
+				} else {
+					blocks[block].name.to_string()
+				})
+				.collect();
+			ui.label(&slots.join(" "));
+			ui.label(&format!("Search: {}", shown));
+			for block in matching(blocks, &search) {
+				if ui.button(blocks[block].name) {
+					hotbar.pick(block);
+				}
+			}
+		});
+	}
+}
+
+// The blocks whose names contain the search, ignoring case. Air can't be placed, so it's never
+// among them.
+fn matching<'a>(blocks: &'a [BlockProperties], search: &str) -> impl Iterator<Item = usize> + 'a {
+	let search = search.to_lowercase();
+	blocks.iter().enumerate()
+		.filter(move |&(block, props)| block != AIR && props.name.contains(&search[..]))
+		.map(|(block, _)| block)
+}
@@ -0,0 +1,127 @@
+use std::collections::{HashSet, VecDeque};
+
+use block::{World, NEIGHBOUR_OFFSETS};
+
+// Updates beyond this are left queued for the next tick, so a chain reaction can't stall a frame
+pub const MAX_UPDATES_PER_TICK: usize = 4096;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum UpdateKind {
+	// The block at the position itself was replaced
+	Changed { old: usize, new: usize },
+	// A block next to the position changed
+	Neighbour { from: [i64; 3] },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BlockUpdate {
+	pub pos: [i64; 3],
+	pub kind: UpdateKind,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(usize);
+
+pub type Subscriber = Box<FnMut(&World, &BlockUpdate)>;
+
+// A FIFO of pending block updates. An update already waiting in the queue isn't queued twice.
+pub struct UpdateQueue {
+	queue: VecDeque<BlockUpdate>,
+	pending: HashSet<BlockUpdate>,
+}
+
+impl UpdateQueue {
+	pub fn new() -> UpdateQueue {
+		UpdateQueue {
+			queue: VecDeque::new(),
+			pending: HashSet::new(),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.queue.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.queue.is_empty()
+	}
+
+	pub fn push(&mut self, update: BlockUpdate) {
+		if self.pending.insert(update) {
+			self.queue.push_back(update);
+		}
+	}
+
+	// Queues the change itself and a neighbour update for each of the six adjacent blocks
+	pub fn block_changed(&mut self, pos: [i64; 3], old: usize, new: usize) {
+		self.push(BlockUpdate { pos: pos, kind: UpdateKind::Changed { old: old, new: new } });
+		for offset in NEIGHBOUR_OFFSETS.iter() {
+			let neighbour = [pos[0] + offset[0], pos[1] + offset[1], pos[2] + offset[2]];
+			self.push(BlockUpdate { pos: neighbour, kind: UpdateKind::Neighbour { from: pos } });
+		}
+	}
+
+	// Removes up to `limit` updates from the front of the queue
+	pub fn take(&mut self, limit: usize) -> Vec<BlockUpdate> {
+		let count = limit.min(self.queue.len());
+		let batch: Vec<BlockUpdate> = self.queue.drain(..count).collect();
+		for update in &batch {
+			self.pending.remove(update);
+		}
+		batch
+	}
+}
+
+// Subscribers are held by id so they can unsubscribe. The world takes the whole list out while
+// dispatching, so subscribing or unsubscribing from inside a callback still works.
+pub struct Subscribers {
+	next_id: usize,
+	list: Vec<(SubscriptionId, Subscriber)>,
+	removed: Vec<SubscriptionId>,
+	dispatching: bool,
+}
+
+impl Subscribers {
+	pub fn new() -> Subscribers {
+		Subscribers {
+			next_id: 0,
+			list: Vec::new(),
+			removed: Vec::new(),
+			dispatching: false,
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		!self.dispatching && self.list.is_empty()
+	}
+
+	pub fn add(&mut self, subscriber: Subscriber) -> SubscriptionId {
+		let id = SubscriptionId(self.next_id);
+		self.next_id += 1;
+		self.list.push((id, subscriber));
+		id
+	}
+
+	pub fn remove(&mut self, id: SubscriptionId) {
+		let before = self.list.len();
+		self.list.retain(|&(other, _)| other != id);
+		if self.list.len() == before {
+			// It may be out being dispatched to
+			self.removed.push(id);
+		}
+	}
+
+	pub fn take(&mut self) -> Vec<(SubscriptionId, Subscriber)> {
+		self.dispatching = true;
+		::std::mem::replace(&mut self.list, Vec::new())
+	}
+
+	// Puts back a list taken for dispatch, ahead of anything subscribed in the meantime
+	pub fn restore(&mut self, mut list: Vec<(SubscriptionId, Subscriber)>) {
+		let removed = ::std::mem::replace(&mut self.removed, Vec::new());
+		list.retain(|&(id, _)| !removed.contains(&id));
+		list.extend(self.list.drain(..));
+		self.list = list;
+		self.dispatching = false;
+	}
+}
@@ -0,0 +1,20 @@
+#version 140
+
+in vec3 v_position;
+in vec3 v_world;
+in vec3 v_color;
+
+out vec4 color;
+
+// Where the wall has faded out completely, in blocks from the camera
+uniform float fade_distance;
+
+const float opacity = 0.5;
+// Diagonal stripes, this many blocks apart
+const float stripe_spacing = 2.0;
+
+void main() {
+	float fade = clamp(1.0 - length(v_position) / fade_distance, 0.0, 1.0);
+	float stripe = fract((v_world.x + v_world.y + v_world.z) / stripe_spacing) < 0.5 ? 1.0 : 0.4;
+	color = vec4(v_color, opacity * fade * stripe);
+}
@@ -0,0 +1,20 @@
+#version 140
+in vec3 position;
+in vec3 normal;
+in vec3 color;
+
+out vec3 v_position;
+out vec3 v_world;
+out vec3 v_color;
+
+uniform mat4 perspective;
+uniform mat4 model_view;
+
+void main() {
+	vec4 view_position = model_view * vec4(position, 1.0);
+
+	v_position = vec3(view_position) / view_position.w;
+	v_world = position;
+	v_color = color;
+	gl_Position = perspective * view_position;
+}
@@ -0,0 +1,126 @@
+use na::{Point3, Vector3};
+
+use gl_util::SimpleCamera;
+use debug_draw::DebugLines;
+
+// Steps each segment is measured in, for working out how far along the path a point is
+const SEGMENT_SAMPLES: usize = 32;
+
+// A camera flight through recorded waypoints, along a Catmull-Rom spline so it passes through
+// each one without stopping. Positions and view directions are splined alike; up stays z.
+// Distances are in blocks along the curve, so playing it at a fixed speed looks steady however
+// unevenly the waypoints were placed.
+pub struct CameraPath {
+	waypoints: Vec<(Point3<f32>, Vector3<f32>)>,
+	// The length of the curve up to each sample, SEGMENT_SAMPLES per segment and one more at the end
+	lengths: Vec<f32>,
+}
+
+impl CameraPath {
+	pub fn new() -> CameraPath {
+		CameraPath { waypoints: Vec::new(), lengths: Vec::new() }
+	}
+
+	pub fn len(&self) -> usize {
+		self.waypoints.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.waypoints.is_empty()
+	}
+
+	pub fn push(&mut self, camera: &SimpleCamera<f32>) {
+		self.waypoints.push((camera.position, camera.direction));
+		self.measure();
+	}
+
+	pub fn clear(&mut self) {
+		self.waypoints.clear();
+		self.lengths.clear();
+	}
+
+	// In blocks; 0 with fewer than two waypoints
+	pub fn length(&self) -> f32 {
+		self.lengths.last().cloned().unwrap_or(0.0)
+	}
+
+	fn measure(&mut self) {
+		self.lengths.clear();
+		if self.waypoints.len() < 2 {
+			return;
+		}
+		let segments = self.waypoints.len() - 1;
+		let mut length = 0.0;
+		let mut last = self.point(0, 0.0).0;
+		self.lengths.push(0.0);
+		for segment in 0..segments {
+			for step in 1..SEGMENT_SAMPLES + 1 {
+				let position = self.point(segment, step as f32 / SEGMENT_SAMPLES as f32).0;
+				length += (position - last).norm();
+				self.lengths.push(length);
+				last = position;
+			}
+		}
+	}
+
+	// On `segment`, from waypoint `segment` at 0 to the next at 1. The ends are repeated so the
+	// curve reaches the first and last waypoints.
+	fn point(&self, segment: usize, t: f32) -> (Point3<f32>, Vector3<f32>) {
+		let last = self.waypoints.len() - 1;
+		let p0 = self.waypoints[segment.saturating_sub(1)];
+		let p1 = self.waypoints[segment];
+		let p2 = self.waypoints[(segment + 1).min(last)];
+		let p3 = self.waypoints[(segment + 2).min(last)];
+		let position = catmull_rom(p0.0.coords, p1.0.coords, p2.0.coords, p3.0.coords, t);
+		let direction = catmull_rom(p0.1, p1.1, p2.1, p3.1, t);
+		(Point3::from(position), direction)
+	}
+
+	// The camera `distance` blocks along the path, or None past its end
+	pub fn at(&self, distance: f32) -> Option<SimpleCamera<f32>> {
+		if self.waypoints.len() < 2 || distance < 0.0 || distance > self.length() {
+			return None;
+		}
+		// The first sample at or beyond `distance`, and how far past the one before it falls
+		let index = match self.lengths.binary_search_by(|length| length.partial_cmp(&distance).unwrap()) {
+			Ok(index) | Err(index) => index.max(1).min(self.lengths.len() - 1),
+		};
+		let (from, to) = (self.lengths[index - 1], self.lengths[index]);
+		let between = if to > from { (distance - from) / (to - from) } else { 0.0 };
+		let sample = (index - 1) as f32 + between;
+		let segment = ((sample / SEGMENT_SAMPLES as f32) as usize).min(self.waypoints.len() - 2);
+		let t = sample / SEGMENT_SAMPLES as f32 - segment as f32;
+
+		let (position, direction) = self.point(segment, t);
+		// Directions pointing opposite ways either side of a waypoint can pass through zero
+		let direction = direction.try_normalize(1.0e-6).unwrap_or(self.waypoints[segment].1);
+		Some(SimpleCamera { position: position, direction: direction, up: Vector3::z() })
+	}
+
+	// The curve, with a mark at each waypoint pointing the way the camera looks there
+	pub fn draw(&self, lines: &mut DebugLines) {
+		for &(position, direction) in &self.waypoints {
+			let end = position + direction;
+			lines.line([position.x, position.y, position.z], [end.x, end.y, end.z], [1.0, 0.5, 0.1]);
+		}
+		if self.waypoints.len() < 2 {
+			return;
+		}
+		let mut last = self.waypoints[0].0;
+		for segment in 0..self.waypoints.len() - 1 {
+			for step in 1..SEGMENT_SAMPLES + 1 {
+				let position = self.point(segment, step as f32 / SEGMENT_SAMPLES as f32).0;
+				lines.line([last.x, last.y, last.z], [position.x, position.y, position.z], [1.0, 0.8, 0.2]);
+				last = position;
+			}
+		}
+	}
+}
+
+fn catmull_rom(p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>, p3: Vector3<f32>, t: f32) -> Vector3<f32> {
+	let (t2, t3) = (t * t, t * t * t);
+	(p1 * 2.0
+		+ (p2 - p0) * t
+		+ (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+		+ (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
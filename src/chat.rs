@@ -0,0 +1,73 @@
+use std::collections::VecDeque;
+
+use time::{Duration, PreciseTime};
+
+use overlay::{Overlay, GLYPH_HEIGHT};
+
+// Only this many of the latest lines are shown
+const MAX_LINES: usize = 10;
+// Font pixels are drawn this many screen pixels across
+const SCALE: f32 = 2.0;
+const LINE_HEIGHT: f32 = (GLYPH_HEIGHT + 2) as f32 * SCALE;
+const MARGIN: f32 = 8.0;
+const WIDTH: f32 = 640.0;
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.4];
+const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// How long a line stays up, the last part of which it spends fading out
+fn visible_time() -> Duration { Duration::seconds(10) }
+fn fade_time() -> Duration { Duration::seconds(2) }
+
+// The latest chat and command output, drawn in the bottom left. Lines fade away after a while,
+// but all of them come back while typing.
+pub struct Chat {
+	lines: VecDeque<(String, PreciseTime)>,
+}
+
+impl Chat {
+	pub fn new() -> Chat {
+		Chat { lines: VecDeque::new() }
+	}
+
+	pub fn push<S: Into<String>>(&mut self, line: S) {
+		if self.lines.len() == MAX_LINES {
+			self.lines.pop_front();
+		}
+		self.lines.push_back((line.into(), PreciseTime::now()));
+	}
+
+	// `input` is the line being typed, if any
+	pub fn draw(&self, overlay: &mut Overlay, screen_height: f32, input: Option<&str>) {
+		let now = PreciseTime::now();
+		let mut y = screen_height - MARGIN - LINE_HEIGHT;
+
+		if let Some(input) = input {
+			overlay.rect(MARGIN, y, WIDTH, LINE_HEIGHT, BACKGROUND);
+			overlay.text(MARGIN + SCALE, y + SCALE, SCALE, &format!("> {}", input), TEXT);
+			y -= LINE_HEIGHT;
+		}
+
+		for &(ref line, received) in self.lines.iter().rev() {
+			let alpha = if input.is_some() { 1.0 } else { fade(received.to(now)) };
+			if alpha <= 0.0 {
+				// Older lines have faded further still
+				break;
+			}
+			overlay.rect(MARGIN, y, WIDTH, LINE_HEIGHT, [BACKGROUND[0], BACKGROUND[1], BACKGROUND[2], BACKGROUND[3] * alpha]);
+			overlay.text(MARGIN + SCALE, y + SCALE, SCALE, line, [TEXT[0], TEXT[1], TEXT[2], TEXT[3] * alpha]);
+			y -= LINE_HEIGHT;
+		}
+	}
+}
+
+// 1 while fresh, falling to 0 over the fade time at the end of the visible time
+fn fade(age: Duration) -> f32 {
+	let remaining = visible_time() - age;
+	if remaining <= Duration::zero() {
+		0.0
+	} else if remaining >= fade_time() {
+		1.0
+	} else {
+		remaining.num_milliseconds() as f32 / fade_time().num_milliseconds() as f32
+	}
+}
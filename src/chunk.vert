@@ -0,0 +1,23 @@
+#version 150
+in vec3 position;
+in vec3 normal;
+in vec3 color;
+in vec3 chunk_offset;
+
+out vec3 v_normal;
+out vec3 v_position;
+out vec3 v_color;
+
+uniform mat4 perspective;
+uniform mat4 view;
+
+void main() {
+	vec4 world_position = view * vec4(position + chunk_offset, 1.0);
+
+	v_position = vec3(world_position) / world_position.w;
+	v_color = color;
+	gl_Position = perspective * world_position;
+
+	// The view matrix is a rigid transform, so its rotation part transforms normals directly
+	v_normal = mat3(view) * normal;
+}
@@ -0,0 +1,278 @@
+use std::cell::RefCell;
+use std::mem::size_of;
+use std::ops::Range;
+use std::rc::Rc;
+
+use glium::{VertexBuffer, IndexBuffer};
+use glium::index::PrimitiveType;
+use glium::backend::Facade;
+
+use gl_util::{Vertex, PackedVertex, VertexFormat, MeshCreationError};
+
+// Vertices and indices in each page's buffers. Indices are u16s counted from a mesh's first
+// vertex, so no mesh has more vertices than a u16 reaches, and a page holds a few that big.
+pub const PAGE_VERTICES: usize = 1 << 18;
+pub const PAGE_INDICES: usize = 3 << 17;
+
+// The free parts of a buffer, kept sorted with no two touching. Space is handed out first fit.
+pub struct RangeAllocator {
+	free: Vec<Range<usize>>,
+}
+
+impl RangeAllocator {
+	pub fn new(len: usize) -> RangeAllocator {
+		RangeAllocator { free: vec![0..len] }
+	}
+
+	pub fn alloc(&mut self, len: usize) -> Option<usize> {
+		if len == 0 {
+			return Some(0);
+		}
+		let i = self.free.iter().position(|range| range.end - range.start >= len)?;
+		let start = self.free[i].start;
+		self.free[i].start += len;
+		if self.free[i].start == self.free[i].end {
+			self.free.remove(i);
+		}
+		Some(start)
+	}
+
+	pub fn free(&mut self, start: usize, len: usize) {
+		if len == 0 {
+			return;
+		}
+		let end = start + len;
+		let i = self.free.iter().position(|range| range.start >= end).unwrap_or(self.free.len());
+		let joins_previous = i > 0 && self.free[i - 1].end == start;
+		let joins_next = i < self.free.len() && self.free[i].start == end;
+		match (joins_previous, joins_next) {
+			(true, true) => {
+				self.free[i - 1].end = self.free[i].end;
+				self.free.remove(i);
+			},
+			(true, false) => self.free[i - 1].end = end,
+			(false, true) => self.free[i].start = start,
+			(false, false) => self.free.insert(i, start..end),
+		}
+	}
+
+	pub fn free_len(&self) -> usize {
+		self.free.iter().map(|range| range.end - range.start).sum()
+	}
+}
+
+struct PageSpace {
+	vertices: RangeAllocator,
+	indices: RangeAllocator,
+}
+
+// Where meshes of one vertex format go in its pages. The bookkeeping is shared with the meshes
+// placed, so each gives its space back when it's dropped, wherever that happens.
+pub struct ArenaSpace {
+	format: VertexFormat,
+	pages: Rc<RefCell<Vec<PageSpace>>>,
+}
+
+impl ArenaSpace {
+	pub fn new(format: VertexFormat) -> ArenaSpace {
+		ArenaSpace {
+			format: format,
+			pages: Rc::new(RefCell::new(Vec::new())),
+		}
+	}
+
+	pub fn page_count(&self) -> usize {
+		self.pages.borrow().len()
+	}
+
+	// A page is added when none has room. None if the mesh wouldn't fit in an empty page.
+	pub fn place(&self, vertex_count: usize, index_count: usize) -> Option<ChunkMesh> {
+		if vertex_count > PAGE_VERTICES || index_count > PAGE_INDICES {
+			return None;
+		}
+		// Without indices there's nothing to draw, so no space is taken
+		if index_count == 0 {
+			return Some(self.mesh(0, 0, 0, 0, 0));
+		}
+
+		let mut pages = self.pages.borrow_mut();
+		for (page, space) in pages.iter_mut().enumerate() {
+			if let Some(first_vertex) = space.vertices.alloc(vertex_count) {
+				match space.indices.alloc(index_count) {
+					Some(first_index) => return Some(self.mesh(page, first_vertex, vertex_count, first_index, index_count)),
+					None => space.vertices.free(first_vertex, vertex_count),
+				}
+			}
+		}
+
+		let mut space = PageSpace {
+			vertices: RangeAllocator::new(PAGE_VERTICES),
+			indices: RangeAllocator::new(PAGE_INDICES),
+		};
+		let first_vertex = space.vertices.alloc(vertex_count).unwrap();
+		let first_index = space.indices.alloc(index_count).unwrap();
+		pages.push(space);
+		Some(self.mesh(pages.len() - 1, first_vertex, vertex_count, first_index, index_count))
+	}
+
+	fn mesh(&self, page: usize, first_vertex: usize, vertex_count: usize, first_index: usize, index_count: usize) -> ChunkMesh {
+		ChunkMesh {
+			format: self.format,
+			page: page,
+			first_vertex: first_vertex,
+			vertex_count: vertex_count,
+			first_index: first_index,
+			index_count: index_count,
+			space: self.pages.clone(),
+		}
+	}
+}
+
+// A chunk's opaque mesh: ranges of one of its format's pages
+pub struct ChunkMesh {
+	pub format: VertexFormat,
+	pub page: usize,
+	pub first_vertex: usize,
+	pub vertex_count: usize,
+	pub first_index: usize,
+	pub index_count: usize,
+	space: Rc<RefCell<Vec<PageSpace>>>,
+}
+
+impl ChunkMesh {
+	// Of the page it takes up
+	pub fn byte_size(&self) -> usize {
+		let vertex_size = match self.format {
+			VertexFormat::Standard => size_of::<Vertex>(),
+			VertexFormat::Packed => size_of::<PackedVertex>(),
+		};
+		self.vertex_count * vertex_size + self.index_count * size_of::<u16>()
+	}
+}
+
+impl Drop for ChunkMesh {
+	fn drop(&mut self) {
+		if self.index_count == 0 {
+			return;
+		}
+		let mut pages = self.space.borrow_mut();
+		let space = &mut pages[self.page];
+		space.vertices.free(self.first_vertex, self.vertex_count);
+		space.indices.free(self.first_index, self.index_count);
+	}
+}
+
+// One mesh's part of a multi-draw: its indices, counted from its first vertex, drawn with the
+// offset at `instance` in the instance buffer
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DrawCommand {
+	pub first_index: usize,
+	pub index_count: usize,
+	pub base_vertex: usize,
+	pub instance: usize,
+}
+
+// Meshes sharing a page, and so drawn together by one call
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Batch {
+	pub format: VertexFormat,
+	pub page: usize,
+	pub commands: Vec<DrawCommand>,
+}
+
+// Instances are numbered by place in `chunks`, as their offsets are laid out in the instance
+// buffer. Empty meshes are left out; batches come in the order of their first mesh.
+pub fn plan_batches(chunks: &[(&ChunkMesh, [f32; 3])]) -> Vec<Batch> {
+	let mut batches: Vec<Batch> = Vec::new();
+	for (instance, &(mesh, _)) in chunks.iter().enumerate() {
+		if mesh.index_count == 0 {
+			continue;
+		}
+
+		let command = DrawCommand {
+			first_index: mesh.first_index,
+			index_count: mesh.index_count,
+			base_vertex: mesh.first_vertex,
+			instance: instance,
+		};
+		let found = batches.iter().position(|batch| batch.format == mesh.format && batch.page == mesh.page);
+		match found {
+			Some(i) => batches[i].commands.push(command),
+			None => batches.push(Batch { format: mesh.format, page: mesh.page, commands: vec![command] }),
+		}
+	}
+	batches
+}
+
+pub struct Page<V: Copy> {
+	pub vertices: VertexBuffer<V>,
+	pub indices: IndexBuffer<u16>,
+}
+
+pub trait ArenaVertex: ::glium::Vertex {
+	fn pages(arena: &mut ChunkArena) -> (&ArenaSpace, &mut Vec<Page<Self>>);
+}
+
+impl ArenaVertex for Vertex {
+	fn pages(arena: &mut ChunkArena) -> (&ArenaSpace, &mut Vec<Page<Vertex>>) { (&arena.standard_space, &mut arena.standard_pages) }
+}
+
+impl ArenaVertex for PackedVertex {
+	fn pages(arena: &mut ChunkArena) -> (&ArenaSpace, &mut Vec<Page<PackedVertex>>) { (&arena.packed_space, &mut arena.packed_pages) }
+}
+
+// Every chunk's opaque mesh, in a few large buffers per vertex format rather than buffers of
+// its own, so a page's worth of chunks is drawn by a single call. Pages are never given back;
+// the space in them is reused as chunks are remeshed and unloaded.
+pub struct ChunkArena {
+	standard_space: ArenaSpace,
+	standard_pages: Vec<Page<Vertex>>,
+	packed_space: ArenaSpace,
+	packed_pages: Vec<Page<PackedVertex>>,
+}
+
+impl ChunkArena {
+	pub fn new() -> ChunkArena {
+		ChunkArena {
+			standard_space: ArenaSpace::new(VertexFormat::Standard),
+			standard_pages: Vec::new(),
+			packed_space: ArenaSpace::new(VertexFormat::Packed),
+			packed_pages: Vec::new(),
+		}
+	}
+
+	pub fn upload<F: Facade, V: ArenaVertex>(&mut self, facade: &F, vertices: &[V], indices: &[u16]) -> Result<ChunkMesh, MeshCreationError> {
+		let (space, pages) = V::pages(self);
+		let mesh = space.place(vertices.len(), indices.len()).ok_or(MeshCreationError::TooLarge)?;
+		if mesh.index_count == 0 {
+			return Ok(mesh);
+		}
+
+		while pages.len() <= mesh.page {
+			pages.push(Page {
+				vertices: VertexBuffer::empty_dynamic(facade, PAGE_VERTICES)?,
+				indices: IndexBuffer::empty_dynamic(facade, PrimitiveType::TrianglesList, PAGE_INDICES)?,
+			});
+		}
+		let page = &pages[mesh.page];
+		page.vertices.slice(mesh.first_vertex..mesh.first_vertex + mesh.vertex_count).unwrap().write(vertices);
+		page.indices.slice(mesh.first_index..mesh.first_index + mesh.index_count).unwrap().write(indices);
+		Ok(mesh)
+	}
+
+	pub fn standard_page(&self, page: usize) -> &Page<Vertex> {
+		&self.standard_pages[page]
+	}
+
+	pub fn packed_page(&self, page: usize) -> &Page<PackedVertex> {
+		&self.packed_pages[page]
+	}
+
+	// Pages, and their size in bytes whether or not all of it is in use
+	pub fn usage(&self) -> (usize, usize) {
+		let indices = PAGE_INDICES * size_of::<u16>();
+		let standard = self.standard_pages.len() * (PAGE_VERTICES * size_of::<Vertex>() + indices);
+		let packed = self.packed_pages.len() * (PAGE_VERTICES * size_of::<PackedVertex>() + indices);
+		(self.standard_pages.len() + self.packed_pages.len(), standard + packed)
+	}
+}
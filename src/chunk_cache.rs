@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, HashMap};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::mem::size_of;
+
+use block::Chunk;
+
+pub const DEFAULT_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+// Keeps recently used chunks alive after everything else has let go of them, up to a memory
+// budget. Chunks still referenced elsewhere (e.g. by a region) are pinned and never evicted.
+pub struct ChunkCache {
+	budget: usize,
+	clock: u64,
+	entries: HashMap<[i64; 3], (Rc<RefCell<Chunk>>, u64)>,
+	recency: BTreeMap<u64, [i64; 3]>,
+}
+
+impl ChunkCache {
+	pub fn new(budget: usize) -> ChunkCache {
+		ChunkCache {
+			budget: budget,
+			clock: 0,
+			entries: HashMap::new(),
+			recency: BTreeMap::new(),
+		}
+	}
+
+	pub fn budget(&self) -> usize { self.budget }
+	pub fn set_budget(&mut self, budget: usize) { self.budget = budget }
+
+	pub fn len(&self) -> usize { self.entries.len() }
+
+	// Meshes included. A chunk that's being changed right now is counted at its bare size.
+	pub fn memory_usage(&self) -> usize {
+		self.entries.values().map(|&(ref chunk, _)| ChunkCache::chunk_usage(chunk)).sum()
+	}
+
+	fn chunk_usage(chunk: &Rc<RefCell<Chunk>>) -> usize {
+		chunk.try_borrow().map(|chunk| chunk.memory_usage()).unwrap_or(size_of::<Chunk>())
+	}
+
+	pub fn touch(&mut self, pos: [i64; 3], chunk: &Rc<RefCell<Chunk>>) {
+		self.clock += 1;
+		if let Some((_, last_used)) = self.entries.insert(pos, (chunk.clone(), self.clock)) {
+			self.recency.remove(&last_used);
+		}
+		self.recency.insert(self.clock, pos);
+	}
+
+	// Drops least recently used, unpinned chunks until the cache fits its budget, handing each
+	// to `persist` first so dirty ones can be written out.
+	pub fn evict<F>(&mut self, mut persist: F) where F: FnMut([i64; 3], &Chunk) {
+		let mut usage = self.memory_usage();
+		if usage <= self.budget {
+			return;
+		}
+
+		let candidates: Vec<(u64, [i64; 3])> = self.recency.iter().map(|(&t, &p)| (t, p)).collect();
+		for (last_used, pos) in candidates {
+			if usage <= self.budget {
+				break;
+			}
+
+			let pinned = Rc::strong_count(&self.entries[&pos].0) > 1;
+			if pinned {
+				continue;
+			}
+
+			let (chunk, _) = self.entries.remove(&pos).unwrap();
+			self.recency.remove(&last_used);
+			usage -= ChunkCache::chunk_usage(&chunk);
+			persist(pos, &chunk.borrow());
+		}
+	}
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+const SHARD_COUNT: usize = 16;
+
+// Chunk positions mapped to values, split over independently locked shards so lookups of
+// different chunks rarely wait on each other once generation is threaded. Callbacks to `get`,
+// `retain` and `for_each` run under a shard's lock, so they mustn't use the map themselves;
+// `make` in `get_or_insert_with` runs without one, so generation can look at other chunks.
+pub struct ChunkMap<V> {
+	shards: Vec<Mutex<HashMap<[i64; 3], V>>>,
+}
+
+impl<V> ChunkMap<V> {
+	pub fn new() -> ChunkMap<V> {
+		ChunkMap {
+			shards: (0..SHARD_COUNT).map(|_| Mutex::new(HashMap::new())).collect(),
+		}
+	}
+
+	// Neighbouring chunks land in different shards. Positions are spread with a multiply rather
+	// than the map's own hasher, as they're not chosen by anyone hostile.
+	fn shard(&self, pos: [i64; 3]) -> MutexGuard<HashMap<[i64; 3], V>> {
+		let mixed = (pos[0] as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+			^ (pos[1] as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+			^ (pos[2] as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+		let shard = &self.shards[(mixed >> 32) as usize % SHARD_COUNT];
+		// A panic elsewhere while this was locked left the map itself whole
+		shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+
+	pub fn get<T, F>(&self, pos: [i64; 3], find: F) -> Option<T> where F: FnOnce(&V) -> Option<T> {
+		self.shard(pos).get(&pos).and_then(find)
+	}
+
+	pub fn insert(&self, pos: [i64; 3], value: V) -> Option<V> {
+		self.shard(pos).insert(pos, value)
+	}
+
+	pub fn remove(&self, pos: [i64; 3]) -> Option<V> {
+		self.shard(pos).remove(&pos)
+	}
+
+	// Looks `pos` up with `find`, and if there's nothing there it accepts, stores what `make`
+	// returns instead. Whatever's there is looked at again under the lock before storing, so if
+	// another caller got there while `make` ran, theirs is kept and returned and this one's is
+	// dropped; every caller gets the same value.
+	pub fn get_or_insert_with<T, F, G>(&self, pos: [i64; 3], find: F, make: G) -> T
+			where F: Fn(&V) -> Option<T>, G: FnOnce() -> (V, T) {
+		if let Some(found) = self.get(pos, &find) {
+			return found;
+		}
+		let (value, made) = make();
+		let mut shard = self.shard(pos);
+		if let Some(found) = shard.get(&pos).and_then(&find) {
+			return found;
+		}
+		shard.insert(pos, value);
+		made
+	}
+
+	pub fn retain<F>(&self, mut keep: F) where F: FnMut([i64; 3], &V) -> bool {
+		for shard in &self.shards {
+			shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).retain(|&pos, value| keep(pos, value));
+		}
+	}
+
+	pub fn for_each<F>(&self, mut f: F) where F: FnMut([i64; 3], &V) {
+		for shard in &self.shards {
+			for (&pos, value) in shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).iter() {
+				f(pos, value);
+			}
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.shards.iter().map(|shard| shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).len()).sum()
+	}
+}
@@ -0,0 +1,50 @@
+#version 150
+in uvec4 packed_position;
+in uint color_index;
+// A nibble each of sky, red, green and blue light, sky highest
+in uint light;
+in vec3 chunk_offset;
+
+out vec3 v_normal;
+out vec3 v_position;
+out vec3 v_color;
+
+uniform mat4 perspective;
+uniform mat4 view;
+uniform sampler2D palette;
+
+// Set once a frame from an Environment; see EnvironmentBlock
+layout(std140) uniform environment {
+	vec4 sun_direction;
+	vec4 sun_color;
+	vec4 ambient_color;
+	vec4 fog;
+	// Scales the light from the sky with the time of day
+	float sky_light;
+};
+
+// Indexed the same way as NormalDirection::to_index
+const vec3 normals[6] = vec3[6](
+	vec3( 0.0,  1.0,  0.0),
+	vec3( 0.0,  0.0,  1.0),
+	vec3( 1.0,  0.0,  0.0),
+	vec3( 0.0, -1.0,  0.0),
+	vec3( 0.0,  0.0, -1.0),
+	vec3(-1.0,  0.0,  0.0)
+);
+
+void main() {
+	vec3 position = vec3(packed_position.xyz);
+	vec4 world_position = view * vec4(position + chunk_offset, 1.0);
+
+	v_position = vec3(world_position) / world_position.w;
+	float sky = float(light >> 12u);
+	vec3 block = vec3(uvec3(light >> 8u, light >> 4u, light) & 15u);
+	// A row per biome, each as wide as there are blocks
+	int width = textureSize(palette, 0).x;
+	ivec2 texel = ivec2(int(color_index) % width, int(color_index) / width);
+	v_color = texelFetch(palette, texel, 0).rgb * max(vec3(sky * sky_light), block) / 15.0;
+	gl_Position = perspective * world_position;
+
+	v_normal = mat3(view) * normals[packed_position.w];
+}
@@ -0,0 +1,1780 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{Sender, Receiver, TryRecvError};
+
+use glium::Rect;
+use glium::glutin::{VirtualKeyCode, Event, ModifiersState, WindowEvent};
+use time::{Duration, PreciseTime};
+use na::{Point3, Vector3, Matrix4, Rotation3};
+
+use engine::{GameServices, DrawService, UpdateResult, Projection};
+use gl_util::{Camera, SimpleCamera, SmoothedCamera, MotionEffects, Vertex};
+use block::{World, BlockProperties, BlockRenderData, CuboidRegion, Medium, AIR, CHUNK_SIZE};
+use entity::{Aabb, BoxModel};
+use console::{self, Console};
+use settings::{Settings, SETTINGS_FILE};
+use actions::{Action, ActionMap, ACTIONS, Chord, Input, KEYBINDINGS_FILE};
+use debug_draw::DebugLines;
+use camera_path::CameraPath;
+use overlay::Overlay;
+use chat::Chat;
+use profile::{self, TRACE_FILE};
+use settings_menu::{SettingsMenu, MenuChoice};
+use block_picker::{BlockPicker, Hotbar};
+use text_input::TextEdit;
+use selection::Selection;
+use prefabs::PrefabBrowser;
+use weather::{Weather, Precipitation};
+use game_rules::GameRules;
+use stats::Stats;
+use post::PostEffects;
+use environment::{self, Environment, DAY_LENGTH, SUNRISE, NOON, SUNSET, MIDNIGHT};
+use net::{NetClient, NetEvent, DEFAULT_PORT};
+use error::SpaceError;
+use server::{ClientMessage, ServerMessage, PlayerInput, Snapshot, WorldStats, EYE_HEIGHT};
+use mirror;
+
+// Per second, like CAMERA_SMOOTHING
+const FOV_SMOOTHING: f32 = 10.0;
+// Per second; see SmoothedCamera
+const CAMERA_SMOOTHING: f32 = 20.0;
+// Fog under a liquid, much thicker than any weather's
+const UNDERWATER_FOG_DENSITY: f32 = 0.08;
+// How much of a liquid's color is left in the fog under it
+const UNDERWATER_FOG_BRIGHTNESS: f32 = 0.5;
+// Velocity arrows in the physics overlay show this many seconds of motion
+const DEBUG_VELOCITY_SCALE: f32 = 0.25;
+
+// The view down on the camera from above, as a fraction of the window's height, and its distance
+// from the window's corner in pixels
+const OVERHEAD_VIEW_SIZE: f32 = 0.3;
+const OVERHEAD_VIEW_MARGIN: u32 = 8;
+// Blocks above the camera
+const OVERHEAD_VIEW_HEIGHT: f32 = 48.0;
+const OVERHEAD_VIEW_FOV: f32 = ::std::f32::consts::FRAC_PI_2;
+
+// Blocks between the lines drawn across the faces of the camera's chunk
+const CHUNK_GRID_SPACING: usize = 4;
+
+// What F6 draws, going round in this order
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum ChunkBorders {
+	Off,
+	// The camera's chunk, gridded, and the ones around it
+	Chunks,
+	// Also the edges of the loaded region
+	ChunksAndRegion,
+}
+
+impl ChunkBorders {
+	fn next(&self) -> ChunkBorders {
+		match *self {
+			ChunkBorders::Off => ChunkBorders::Chunks,
+			ChunkBorders::Chunks => ChunkBorders::ChunksAndRegion,
+			ChunkBorders::ChunksAndRegion => ChunkBorders::Off,
+		}
+	}
+}
+
+// Blocks per second along a camera path, unless told otherwise
+const PATH_SPEED: f32 = 8.0;
+// Recorded frames are this far apart in time, however long they take to draw
+const CAPTURE_FRAME_RATE: f32 = 30.0;
+const FRAMES_DIR: &'static str = "frames";
+
+// A flight along the camera path in progress
+struct PathPlayback {
+	// Blocks along the path of the next frame
+	distance: f32,
+	speed: f32,
+	// Where frames are saved, if they are
+	frames_dir: Option<PathBuf>,
+	frames_shown: usize,
+}
+
+// The map's height in blocks when opened, and how far it can zoom either way
+const MAP_HEIGHT: f32 = 128.0;
+const MAP_MIN_HEIGHT: f32 = 16.0;
+const MAP_MAX_HEIGHT: f32 = 1024.0;
+// Map heights panned across per second
+const MAP_PAN_SPEED: f32 = 0.75;
+// How much a notch of the mouse wheel, or a second of holding up or down, zooms by
+const MAP_ZOOM_STEP: f32 = 1.25;
+const MAP_ZOOM_SPEED: f32 = 2.0;
+// Length of the line marking the player on the map, as a fraction of its height
+const MAP_MARKER_SIZE: f32 = 0.05;
+
+// The border shows within this many blocks of the camera, fading out with distance
+const BORDER_WALL_DISTANCE: f32 = 24.0;
+const BORDER_COLOR: [f32; 3] = [0.3, 0.6, 1.0];
+// Corners can be picked from further away than blocks can be reached
+const SELECTION_DISTANCE: f32 = 64.0;
+const SELECTION_COLOR: [f32; 3] = [1.0, 0.8, 0.2];
+
+// The loaded chunks from straight above, in place of the camera's view
+struct MapView {
+	// The point on the ground in the middle of the window
+	center: [f32; 2],
+	// Blocks from the bottom of the window to the top
+	height: f32,
+}
+
+impl MapView {
+	fn zoom(&mut self, factor: f32) {
+		self.height = (self.height / factor).max(MAP_MIN_HEIGHT).min(MAP_MAX_HEIGHT);
+	}
+}
+
+fn double_tap_interval() -> Duration { Duration::milliseconds(300) }
+
+// Input, camera and drawing. Everything in the world is the server's; the client asks it for
+// changes and draws the snapshots it sends back, and its copy of the world as the server keeps it.
+pub struct Client {
+	block_properties: Rc<Vec<BlockProperties>>,
+	block_render_types: Rc<Vec<BlockRenderData>>,
+	world: World,
+	// Of `world`'s chunks
+	region: CuboidRegion,
+	console: Console,
+	chat: Chat,
+	menu: SettingsMenu,
+	settings: Settings,
+	// As loaded, for telling which changes wait for a restart
+	started_with: Settings,
+	actions: ActionMap,
+	keys_down: HashSet<VirtualKeyCode>,
+	last_space_press: Option<PreciseTime>,
+	last_forward_press: Option<PreciseTime>,
+	walking: bool,
+	sprinting: bool,
+	fov: f32,
+	camera: SmoothedCamera<f32>,
+	motion: MotionEffects<f32>,
+	debug_physics: bool,
+	show_profiler: bool,
+	chunk_borders: ChunkBorders,
+	overhead_view: bool,
+	map: Option<MapView>,
+	hotbar: Hotbar,
+	picker: BlockPicker,
+	selection: Selection,
+	prefab_browser: PrefabBrowser,
+	// Clicks pick the selection's corners rather than breaking and placing
+	selecting: bool,
+	// While the left mouse button is held
+	breaking: bool,
+	camera_path: CameraPath,
+	playback: Option<PathPlayback>,
+	precipitation: Precipitation,
+	post_effects: PostEffects,
+	// The track last announced
+	now_playing: Option<String>,
+	snapshot: Option<Snapshot>,
+	world_stats: WorldStats,
+	// The player's, for the stats page
+	stats: Stats,
+	// Until the server goes away
+	connected: bool,
+	// While connected to someone else's server, whose world replaces the local server's in
+	// `world`. Edits and chat go to it instead, and the player flies, as walking is simulated by
+	// the local server in its own world.
+	remote: Option<NetClient>,
+	inbox: Receiver<ServerMessage>,
+	outbox: Sender<ClientMessage>,
+}
+
+impl Client {
+	pub fn new(
+		world: World,
+		region: CuboidRegion,
+		block_properties: Rc<Vec<BlockProperties>>,
+		block_render_types: Rc<Vec<BlockRenderData>>,
+		inbox: Receiver<ServerMessage>,
+		outbox: Sender<ClientMessage>
+	) -> Client {
+		let settings = Settings::load_or_create(::std::path::Path::new(SETTINGS_FILE)).unwrap_or_else(|err| {
+			println!("Failed to load settings, using defaults: {:?}", err);
+			Settings::default()
+		});
+		let mut motion = MotionEffects::new(settings.walk_speed, settings.sprint_speed);
+		motion.bobbing = settings.camera_motion;
+		motion.widening = settings.camera_motion;
+		let client = Client {
+			block_properties: block_properties,
+			block_render_types: block_render_types,
+			world: world,
+			region: region,
+			console: Console::new(),
+			chat: Chat::new(),
+			menu: SettingsMenu::new(),
+			fov: settings.fov.to_radians(),
+			started_with: settings.clone(),
+			settings: settings,
+			actions: ActionMap::load_or_create(::std::path::Path::new(KEYBINDINGS_FILE)).unwrap_or_else(|err| {
+				println!("Failed to load keybindings, using defaults: {:?}", err);
+				ActionMap::default()
+			}),
+			keys_down: HashSet::new(),
+			last_space_press: None,
+			last_forward_press: None,
+			walking: false,
+			sprinting: false,
+			camera: SmoothedCamera::new(SimpleCamera {
+				position:   Point3::new( 0.0,   0.0,  50.0),
+				direction: Vector3::new(-0.5,  -0.5,  -4.0).normalize(),
+				up:        Vector3::new( 0.0,   0.0,   1.0),
+			}, CAMERA_SMOOTHING),
+			motion: motion,
+			debug_physics: false,
+			show_profiler: false,
+			chunk_borders: ChunkBorders::Off,
+			overhead_view: false,
+			map: None,
+			hotbar: Hotbar::default(),
+			picker: BlockPicker::new(),
+			selection: Selection::new(),
+			prefab_browser: PrefabBrowser::new(),
+			selecting: false,
+			breaking: false,
+			camera_path: CameraPath::new(),
+			playback: None,
+			precipitation: Precipitation::new(),
+			post_effects: PostEffects::default(),
+			now_playing: None,
+			snapshot: None,
+			world_stats: WorldStats::default(),
+			stats: Stats::default(),
+			connected: true,
+			remote: None,
+			inbox: inbox,
+			outbox: outbox,
+		};
+		client.send(ClientMessage::SetRenderDistance(client.settings.render_distance));
+		client.send(ClientMessage::SetReach(client.settings.reach));
+		client.watch();
+		client
+	}
+
+	// Has the local server send its chunks in the region, all of them again if it already had
+	fn watch(&self) {
+		let (min, max) = self.region.chunk_bounds();
+		self.send(ClientMessage::Watch { min: min, max: [max[0] - 1, max[1] - 1, max[2] - 1] });
+	}
+
+	pub fn region(&self) -> &CuboidRegion {
+		&self.region
+	}
+
+	// As of the server's last tick
+	pub fn world_stats(&self) -> WorldStats {
+		self.world_stats
+	}
+
+	pub fn connected(&self) -> bool {
+		self.connected
+	}
+
+	// Has the server save and stop
+	pub fn quit(&self) {
+		self.send(ClientMessage::Quit);
+	}
+
+	fn send(&self, message: ClientMessage) {
+		// Without a server there's no one to ask
+		let _ = self.outbox.send(message);
+	}
+
+	fn eye(&self) -> [f32; 3] {
+		let origin = self.camera.target.forward_ray().origin;
+		[origin.x, origin.y, origin.z]
+	}
+
+	fn look(&self) -> [f32; 3] {
+		let direction = self.camera.target.forward_ray().direction;
+		[direction.x, direction.y, direction.z]
+	}
+
+	// Clear until the server says otherwise
+	fn weather(&self) -> Weather {
+		self.snapshot.as_ref().map(|snapshot| snapshot.weather).unwrap_or(Weather::Clear)
+	}
+
+	fn border(&self) -> Option<i64> {
+		self.snapshot.as_ref().and_then(|snapshot| snapshot.border)
+	}
+
+	// Noon until the server says otherwise
+	fn time(&self) -> f64 {
+		self.snapshot.as_ref().map(|snapshot| snapshot.time).unwrap_or(DAY_LENGTH * NOON as f64)
+	}
+
+	fn rules(&self) -> GameRules {
+		self.snapshot.as_ref().map(|snapshot| snapshot.rules).unwrap_or_default()
+	}
+
+	fn crouching(&self) -> bool {
+		self.snapshot.as_ref().and_then(|snapshot| snapshot.player.as_ref()).map(|player| player.crouching).unwrap_or(false)
+	}
+
+	// Handles this frame's input and sends the server what the player is doing
+	pub fn update(&mut self, services: &GameServices, time_elapsed: &Duration) -> UpdateResult {
+		// Typed in text mode, into whichever of the console and the picker is open
+		for edit in services.input_service.text_edits() {
+			if self.console.is_open() {
+				if let Some(line) = self.console.edit(edit) {
+					self.submit(services, &line);
+				}
+			} else if self.picker.is_open() {
+				self.picker.edit(edit, &self.block_properties, &mut self.hotbar);
+			}
+		}
+
+		for ev in services.input_service.events() {
+			use glium::glutin::ElementState;
+			use glium::glutin::dpi::LogicalPosition;
+			match ev {
+				&Event::WindowEvent {
+					event: WindowEvent::CloseRequested,
+					..
+				} => return UpdateResult::Quit,   // the window has been closed by the user
+
+				&Event::WindowEvent {
+					event: WindowEvent::KeyboardInput {
+						input: input,
+						..
+					},
+					..
+				} => {
+					let ::glium::glutin::KeyboardInput {
+						virtual_keycode: opt_key,
+						state: state,
+						modifiers: modifiers,
+						..
+					} = input;
+					// Keys can be bound to the same actions as mouse buttons. Held keys repeat their
+					// presses, which mustn't count again.
+					if let Some(key) = opt_key {
+						match state {
+							ElementState::Pressed if !self.keys_down.contains(&key) && !self.console.is_open() && !self.cursor_free() => {
+								self.press(services, Input::Key(key), modifiers);
+							},
+							ElementState::Released => self.release(Input::Key(key)),
+							ElementState::Pressed => (),
+						}
+					}
+					match opt_key {
+						None => (),
+						// Typing goes to the console instead, as text edits
+						Some(VirtualKeyCode::Escape) if self.console.is_open() => self.console.close(),
+						Some(_) if self.console.is_open() => (),
+						Some(key) if self.menu.is_open() => {
+							if state == ElementState::Pressed {
+								self.menu_key(services, key, modifiers);
+							}
+						},
+						// Typing goes to the search instead
+						Some(VirtualKeyCode::Escape) if self.picker.is_open() => self.picker.close(),
+						Some(_) if self.picker.is_open() => (),
+						Some(VirtualKeyCode::Escape) if self.prefab_browser.is_open() => self.prefab_browser.close(),
+						Some(key) => match key {
+							VirtualKeyCode::Escape => {
+								if state == ElementState::Pressed {
+									self.keys_down.clear();
+									self.menu.open();
+								}
+							},
+							key if self.actions.triggers(Action::Undo, Input::Key(key), modifiers) => {
+								if state == ElementState::Pressed { self.send(ClientMessage::Undo); }
+							},
+							key if self.actions.triggers(Action::Redo, Input::Key(key), modifiers) => {
+								if state == ElementState::Pressed { self.send(ClientMessage::Redo); }
+							},
+							key if self.actions.triggers(Action::Save, Input::Key(key), modifiers) && state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								self.send(ClientMessage::Save);
+							},
+							// Held keys repeat their presses, which mustn't count as taps
+							VirtualKeyCode::F if state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								self.toggle_walking();
+							},
+							VirtualKeyCode::F3 if state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								self.debug_physics = !self.debug_physics;
+								self.send(ClientMessage::SetPhysicsDebug(self.debug_physics));
+							},
+							VirtualKeyCode::F4 if state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								self.show_profiler = !self.show_profiler;
+							},
+							VirtualKeyCode::F6 if state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								self.chunk_borders = self.chunk_borders.next();
+							},
+							VirtualKeyCode::F7 if state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								self.overhead_view = !self.overhead_view;
+							},
+							VirtualKeyCode::M if state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								self.toggle_map();
+							},
+							VirtualKeyCode::V if state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								self.toggle_selecting();
+							},
+							VirtualKeyCode::Key1 | VirtualKeyCode::Key2 | VirtualKeyCode::Key3 | VirtualKeyCode::Key4 | VirtualKeyCode::Key5
+									if state == ElementState::Pressed => {
+								self.hotbar.select(key as usize - VirtualKeyCode::Key1 as usize);
+							},
+							key if self.actions.triggers(Action::Forward, Input::Key(key), modifiers) && state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								let now = PreciseTime::now();
+								match self.last_forward_press {
+									Some(last) if last.to(now) <= double_tap_interval() => self.sprinting = true,
+									_ => (),
+								}
+								self.last_forward_press = Some(now);
+							},
+							VirtualKeyCode::Space if state == ElementState::Pressed && !self.keys_down.contains(&key) => {
+								self.keys_down.insert(key);
+								let now = PreciseTime::now();
+								match self.last_space_press {
+									Some(last) if last.to(now) <= double_tap_interval() => {
+										self.last_space_press = None;
+										self.toggle_walking();
+									},
+									_ => self.last_space_press = Some(now),
+								}
+							},
+							code => match state {
+								ElementState::Pressed => { self.keys_down.insert(code); },
+								ElementState::Released => { self.keys_down.remove(&code); },
+							},
+						}
+					}
+				},
+
+				&Event::WindowEvent {
+					event: WindowEvent::ReceivedCharacter(c),
+					..
+				} => {
+					if self.menu.is_open() {
+						continue;
+					}
+					// Characters only get here when typed outside text mode, or in the frame it
+					// was asked for
+					let typed = TextEdit::Insert(c.to_string());
+					if self.picker.is_open() {
+						self.picker.edit(&typed, &self.block_properties, &mut self.hotbar);
+						continue;
+					}
+					if !self.console.is_open() {
+						// T opens chat, and a slash opens it with a command started, and I opens
+						// the block picker
+						match c {
+							'i' | 'I' => {
+								self.keys_down.clear();
+								self.breaking = false;
+								self.picker.open();
+							},
+							'/' | 't' | 'T' => {
+								self.keys_down.clear();
+								self.console.open();
+							},
+							_ => (),
+						}
+						if c != '/' {
+							continue;
+						}
+					}
+					self.console.edit(&typed);
+				},
+
+				// While rebinding, a click binds the button rather than going to the menu
+				&Event::WindowEvent {
+					event: WindowEvent::MouseInput {
+						state: ElementState::Pressed,
+						button,
+						modifiers,
+						..
+					},
+					..
+				} if self.menu.is_open() && self.menu.rebinding().is_some() => {
+					let action = self.menu.rebinding().unwrap();
+					self.rebind(action, Chord::pressed(Input::Mouse(button), modifiers));
+				},
+
+				&Event::WindowEvent {
+					event: WindowEvent::MouseInput {
+						state: ElementState::Pressed,
+						button,
+						modifiers,
+						..
+					},
+					..
+				} if !self.cursor_free() => self.press(services, Input::Mouse(button), modifiers),
+
+				&Event::WindowEvent {
+					event: WindowEvent::MouseInput {
+						state: ElementState::Released,
+						button,
+						..
+					},
+					..
+				} => self.release(Input::Mouse(button)),
+
+				&Event::WindowEvent {
+					event: WindowEvent::MouseWheel { delta, .. },
+					..
+				} if !self.cursor_free() => {
+					use glium::glutin::MouseScrollDelta;
+					if let Some(ref mut map) = self.map {
+						let notches = match delta {
+							MouseScrollDelta::LineDelta(_, y) => y,
+							MouseScrollDelta::PixelDelta(position) => position.y as f32 / 16.0,
+						};
+						map.zoom(MAP_ZOOM_STEP.powf(notches));
+					}
+				},
+
+				&Event::WindowEvent {
+					event: WindowEvent::CursorMoved{
+						position: LogicalPosition{x: raw_x, y: raw_y},
+						..
+					},
+					..
+				} => {
+					// The cursor is left free to use the menu
+					if self.cursor_free() {
+						continue;
+					}
+					let size = services.input_service.size().unwrap();
+					let mid: LogicalPosition = (size.width / 2.0, size.height / 2.0).into();
+					services.input_service.set_cursor_position(mid);
+
+					// The map doesn't turn
+					if self.map.is_some() {
+						continue;
+					}
+					let (delta_x, delta_y) = (raw_x - mid.x, raw_y - mid.y);
+
+					let radians_per_pixel = self.settings.look_sensitivity.to_radians();
+					let dir = &mut self.camera.target.direction;
+					let up  = &self.camera.target.up;
+
+					*dir = Rotation3::new(up               * -delta_x as f32 * radians_per_pixel)
+					     * Rotation3::new(up.cross(dir) * -delta_y as f32 * radians_per_pixel)
+					     * (*dir);
+
+					*dir = dir.normalize();
+
+					dir[2] = f32::max(-0.9, f32::min(0.9, dir[2]));
+				},
+
+				_ => ()
+			}
+		}
+		services.input_service.set_text_mode(self.console.is_open() || self.picker.is_open());
+
+		let seconds = time_elapsed.num_microseconds().unwrap() as f32 / 1_000_000.0;
+		let velocity = if self.map.is_some() {
+			// The movement keys move the map instead
+			self.pan_map(seconds);
+			[0.0; 2]
+		} else if self.walking {
+			self.walk()
+		} else {
+			self.sprinting = false;
+			// The path has the camera
+			if self.playback.is_none() {
+				self.fly(time_elapsed);
+			}
+			[0.0; 2]
+		};
+		let input = PlayerInput {
+			velocity: velocity,
+			jump: self.actions.held(Action::Jump, &self.keys_down),
+			crouch: self.walking && self.actions.held(Action::Crouch, &self.keys_down),
+			eye: self.eye(),
+			look: self.look(),
+			breaking: if self.breaking && self.map.is_none() && !self.cursor_free() { Some(self.look()) } else { None },
+		};
+		self.send(ClientMessage::Input(input));
+		if self.remote.is_some() {
+			let eye = self.eye();
+			let sent = self.remote.as_mut().map(|remote| remote.send_position(eye));
+			if let Some(Err(err)) = sent {
+				self.disconnect(format!("Lost the connection: {:?}", err));
+			}
+		}
+
+		self.motion.bobbing = self.settings.camera_motion;
+		self.motion.widening = self.settings.camera_motion;
+		self.motion.widen_from = self.settings.walk_speed;
+		self.motion.widen_to = self.settings.sprint_speed;
+		let fov = self.settings.fov.to_radians() * self.motion.fov_scale();
+		self.fov += (fov - self.fov) * (1.0 - (-FOV_SMOOTHING * seconds).exp());
+
+		if self.debug_physics {
+			self.debug_panel(services, seconds);
+		}
+		if self.show_profiler {
+			self.profiler_panel(services);
+		}
+
+		if self.picker.is_open() {
+			self.picker.build(&services.gui_service, &self.block_properties, &mut self.hotbar);
+		}
+		if self.prefab_browser.is_open() {
+			if let Some(name) = self.prefab_browser.build(&services.gui_service) {
+				self.send(ClientMessage::LoadPrefab(name));
+			}
+		}
+		if self.menu.is_open() {
+			let (render_distance, reach) = (self.settings.render_distance, self.settings.reach);
+			let modes: Vec<bool> = ACTIONS.iter().map(|&action| self.actions.toggles(action)).collect();
+			let choice = self.menu.build(&services.gui_service, &mut self.settings, &mut self.actions, &self.stats);
+			if ACTIONS.iter().map(|&action| self.actions.toggles(action)).ne(modes.into_iter()) {
+				self.save_keybindings();
+			}
+			if self.settings.render_distance != render_distance {
+				self.send(ClientMessage::SetRenderDistance(self.settings.render_distance));
+			}
+			if self.settings.reach != reach {
+				self.send(ClientMessage::SetReach(self.settings.reach));
+			}
+			match choice {
+				MenuChoice::Resume => self.close_menu(services),
+				MenuChoice::Quit => {
+					self.close_menu(services);
+					return UpdateResult::Quit;
+				},
+				MenuChoice::None => (),
+			}
+		}
+
+		UpdateResult::None
+	}
+
+	// A fresh press of a key or button, for toggling actions and for those that act on the press
+	// itself rather than while held
+	fn press(&mut self, services: &GameServices, input: Input, modifiers: ModifiersState) {
+		self.actions.press(input, modifiers);
+		let on_gui = match input {
+			Input::Mouse(_) => services.gui_service.wants_mouse(),
+			Input::Key(_) => false,
+		};
+		if self.actions.triggers(Action::Break, input, modifiers) && !on_gui {
+			if self.selecting {
+				if let Some(pos) = self.aimed_block(SELECTION_DISTANCE) {
+					self.selection.set_first(pos);
+				}
+			} else if self.remote.is_some() {
+				if let Some(pos) = self.aimed_block(self.settings.reach) {
+					self.remote_edit(pos, AIR);
+				}
+			} else {
+				self.breaking = true;
+			}
+		}
+		if self.actions.triggers(Action::Place, input, modifiers) && !on_gui {
+			if self.selecting {
+				if let Some(pos) = self.aimed_block(SELECTION_DISTANCE) {
+					self.selection.set_second(pos);
+				}
+			} else if self.remote.is_some() {
+				if let Some(pos) = self.placed_cell() {
+					let block = self.hotbar.held();
+					self.remote_edit(pos, block);
+				}
+			} else {
+				self.send(ClientMessage::PlaceBlock { origin: self.eye(), direction: self.look(), block: self.hotbar.held() });
+			}
+		}
+		if self.actions.triggers(Action::PickBlock, input, modifiers) && self.map.is_none() {
+			self.pick_block();
+		}
+	}
+
+	// Letting go stops breaking, whatever modifiers are down by then
+	fn release(&mut self, input: Input) {
+		if self.actions.uses(Action::Break, input) {
+			self.breaking = false;
+		}
+	}
+
+	// While a menu wants the mouse rather than the camera
+	fn cursor_free(&self) -> bool {
+		self.menu.is_open() || self.picker.is_open() || self.prefab_browser.is_open()
+	}
+
+	// Into the hotbar, from whatever's in reach where the player looks
+	fn pick_block(&mut self) {
+		if let Some(pos) = self.aimed_block(self.settings.reach) {
+			self.hotbar.pick(self.world.get_block(pos));
+		}
+	}
+
+	// The first solid block within `distance` where the player looks
+	fn aimed_block(&self, distance: f32) -> Option<[i64; 3]> {
+		let render = self.block_render_types.clone();
+		let solid = |block: usize| render.get(block).map(|data| data.should_render && !data.liquid).unwrap_or(false);
+		self.world.raycast(self.eye(), self.look(), distance, solid).map(|(pos, _)| pos)
+	}
+
+	// The empty cell against the face of the block in reach where the player looks
+	fn placed_cell(&self) -> Option<[i64; 3]> {
+		let render = self.block_render_types.clone();
+		let solid = |block: usize| render.get(block).map(|data| data.should_render && !data.liquid).unwrap_or(false);
+		self.world.raycast(self.eye(), self.look(), self.settings.reach, solid)
+			.map(|(_, before)| before)
+			.filter(|&before| self.world.get_block(before) == AIR)
+	}
+
+	// Where pastes go and copies are measured from: against the face of the block looked at, or
+	// where the player is if they look at nothing
+	fn target_cell(&self) -> [i64; 3] {
+		let render = self.block_render_types.clone();
+		let solid = |block: usize| render.get(block).map(|data| data.should_render && !data.liquid).unwrap_or(false);
+		match self.world.raycast(self.eye(), self.look(), SELECTION_DISTANCE, solid) {
+			Some((_, before)) => before,
+			None => {
+				let eye = self.eye();
+				[eye[0].floor() as i64, eye[1].floor() as i64, eye[2].floor() as i64]
+			},
+		}
+	}
+
+	fn toggle_selecting(&mut self) {
+		self.selecting = !self.selecting;
+		self.breaking = false;
+		self.print(if self.selecting {
+			"Selecting: left click picks one corner and right click the other".to_string()
+		} else {
+			"Stopped selecting".to_string()
+		});
+	}
+
+	fn block_named(&self, name: &str) -> Result<usize, String> {
+		self.block_properties.iter().position(|props| props.name == name).ok_or(format!("Unknown block: {}", name))
+	}
+
+	// Escape backs out of rebinding, then out of the menu
+	fn menu_key(&mut self, services: &GameServices, key: VirtualKeyCode, modifiers: ModifiersState) {
+		match (key, self.menu.rebinding()) {
+			(VirtualKeyCode::Escape, Some(_)) => self.menu.cancel_rebinding(),
+			(VirtualKeyCode::Escape, None) => self.close_menu(services),
+			(key, Some(action)) => self.rebind(action, Chord::pressed(Input::Key(key), modifiers)),
+			(_, None) => (),
+		}
+	}
+
+	// Warning about whatever else the chord sets off, which still works, but together
+	fn rebind(&mut self, action: Action, chord: Chord) {
+		self.menu.bind(&mut self.actions, chord);
+		let conflicts = self.actions.conflicts(action);
+		if !conflicts.is_empty() {
+			let names: Vec<&str> = conflicts.iter().map(|other| other.name()).collect();
+			self.print(format!("{} is also bound to {}", chord, names.join(", ")));
+		}
+		self.save_keybindings();
+	}
+
+	fn save_keybindings(&mut self) {
+		if let Err(err) = self.actions.save(::std::path::Path::new(KEYBINDINGS_FILE)) {
+			self.print(format!("Failed to save keybindings: {:?}", err));
+		}
+	}
+
+	fn close_menu(&mut self, services: &GameServices) {
+		self.menu.close();
+		match self.settings.save(::std::path::Path::new(SETTINGS_FILE)) {
+			Ok(()) => services.toast_service.push("Settings saved"),
+			Err(err) => self.print(format!("Failed to save settings: {:?}", err)),
+		}
+		let pending = self.settings.restart_required(&self.started_with);
+		if !pending.is_empty() {
+			services.toast_service.push(format!("Restart to apply {}", pending.join(", ")));
+		}
+	}
+
+	// Alongside the physics overlay
+	fn debug_panel(&self, services: &GameServices, seconds: f32) {
+		let eye = self.eye();
+		services.gui_service.window("Debug", [8.0, 8.0], 440.0, |ui| {
+			ui.label(&format!("Frame: {:.1} ms", seconds * 1000.0));
+			ui.label(&format!("Eye: {:.1} {:.1} {:.1}", eye[0], eye[1], eye[2]));
+			ui.label(if self.walking { "Walking" } else { "Flying" });
+			if let Some(ref snapshot) = self.snapshot {
+				ui.label(&format!("Entities: {}", snapshot.entities.len()));
+				ui.label(&format!("Falling blocks: {}", snapshot.falling.len()));
+				ui.label(&format!("Weather: {}", snapshot.weather));
+				ui.label(&format!("Time: {}", environment::describe_time(snapshot.time)));
+			}
+			for (name, metric, peak) in services.metrics_service.all() {
+				ui.label(&format!("{}: {} (peak {})", name, metric, peak));
+			}
+		});
+	}
+
+	// The last frame's timings, F4 to toggle. Nested scopes are indented under the ones they ran in.
+	fn profiler_panel(&self, services: &GameServices) {
+		let frame_time = profile::last_frame_time();
+		services.gui_service.window("Profiler", [456.0, 8.0], 380.0, |ui| {
+			ui.label(&format!("Frame: {:.2} ms", milliseconds(frame_time)));
+			for timing in profile::last_frame() {
+				let indent: String = ::std::iter::repeat("  ").take(timing.depth).collect();
+				if timing.calls > 1 {
+					ui.label(&format!("{}{}: {:.2} ms ({}x)", indent, timing.name, milliseconds(timing.total), timing.calls));
+				} else {
+					ui.label(&format!("{}{}: {:.2} ms", indent, timing.name, milliseconds(timing.total)));
+				}
+			}
+			// From a few frames back, as the GPU runs behind
+			let gpu_times = services.draw_service.gpu_pass_times();
+			if !gpu_times.is_empty() {
+				let total = gpu_times.iter().fold(Duration::zero(), |total, &(_, time)| total + time);
+				ui.label(&format!("GPU: {:.2} ms", milliseconds(total)));
+				for &(pass, time) in gpu_times {
+					ui.label(&format!("  {}: {:.2} ms", pass.name(), milliseconds(time)));
+				}
+			}
+		});
+	}
+
+	// Takes in what the server sent since the last call, and catches the camera up with it
+	pub fn receive(&mut self, services: &GameServices, time_elapsed: &Duration) {
+		loop {
+			let message = match self.inbox.try_recv() {
+				Ok(message) => message,
+				Err(TryRecvError::Empty) => break,
+				Err(TryRecvError::Disconnected) => {
+					self.connected = false;
+					break;
+				},
+			};
+			match message {
+				ServerMessage::Snapshot(snapshot) => self.snapshot = Some(snapshot),
+				// The remote server's world is in `world` instead
+				ServerMessage::World(_) if self.remote.is_some() => (),
+				ServerMessage::World(update) => mirror::apply(&self.world, update),
+				ServerMessage::Stats(stats) => self.world_stats = stats,
+				ServerMessage::Prefabs(prefabs) => self.prefab_browser.set_prefabs(prefabs),
+				ServerMessage::PlayerStats(stats) => self.stats = stats,
+				ServerMessage::Teleported { eye } => {
+					self.camera.target.position = Point3::new(eye[0], eye[1], eye[2]);
+					self.camera.snap();
+					self.motion.reset();
+				},
+				ServerMessage::Log(line) => self.print(line),
+				ServerMessage::Chat { from, text } => self.chat.push(format!("<{}> {}", from, text)),
+				ServerMessage::Sound { sound, position } => services.audio_service.play_at(position, sound),
+				ServerMessage::Notify(text) => services.toast_service.push(text),
+			}
+		}
+		self.receive_remote();
+		// Nothing listens to the copy's block updates, but they still queue up
+		self.world.update_blocks();
+
+		// Flying may have been forbidden while the player was at it. These are the local world's
+		// rules, which don't apply on another server.
+		if !self.walking && !self.rules().fly && self.playback.is_none() && self.remote.is_none() {
+			self.toggle_walking();
+		}
+
+		if let Some(player) = self.snapshot.as_ref().and_then(|snapshot| snapshot.player.as_ref()) {
+			let eye = player.eye();
+			self.camera.target.position = Point3::new(eye[0], eye[1], eye[2]);
+		}
+		let seconds = time_elapsed.num_microseconds().unwrap() as f32 / 1_000_000.0;
+		if let Some(camera) = self.follow_path(services, seconds) {
+			self.camera.target = camera;
+			self.camera.snap();
+		}
+		self.camera.update(seconds);
+		self.motion.update(self.camera.current().position, self.walking, seconds);
+
+		let camera = self.camera.current();
+		let weather = self.weather();
+		self.precipitation.update(weather, [camera.position.x, camera.position.y, camera.position.z], seconds);
+		services.audio_service.set_listener(camera.position, camera.direction, camera.up);
+		services.audio_service.set_volume(self.settings.master_volume, self.settings.effects_volume);
+		services.music_service.set_volume(self.settings.master_volume, self.settings.music_volume);
+		services.music_service.set_ducked(self.console.is_open() || self.menu.is_open());
+		let now_playing = services.music_service.now_playing().map(|name| name.to_string());
+		if now_playing != self.now_playing {
+			if let Some(ref name) = now_playing {
+				services.toast_service.push(format!("Now playing {}", name));
+			}
+			self.now_playing = now_playing;
+		}
+	}
+
+	// Where the camera path puts the camera this frame, moving on along it. Ends playback at the
+	// end of the path.
+	fn follow_path(&mut self, services: &GameServices, seconds: f32) -> Option<SimpleCamera<f32>> {
+		let camera = match self.playback {
+			Some(ref mut playback) => {
+				let camera = self.camera_path.at(playback.distance);
+				// Recordings move on a frame's worth at a time, so they play back smoothly however
+				// slowly they were drawn
+				let seconds = if playback.frames_dir.is_some() { 1.0 / CAPTURE_FRAME_RATE } else { seconds };
+				playback.distance += playback.speed * seconds;
+				if camera.is_some() {
+					playback.frames_shown += 1;
+				}
+				camera
+			},
+			None => return None,
+		};
+		if camera.is_none() {
+			let playback = self.playback.take().unwrap();
+			self.motion.reset();
+			match playback.frames_dir {
+				Some(dir) => {
+					services.toast_service.push("Recording finished");
+					self.print(format!("Saved {} frames to {}", playback.frames_shown, dir.display()));
+				},
+				None => self.print("Reached the end of the path".to_string()),
+			}
+		}
+		camera
+	}
+
+	fn toggle_map(&mut self) {
+		self.map = match self.map {
+			Some(_) => None,
+			None => {
+				let position = self.camera.current().position;
+				Some(MapView { center: [position.x, position.y], height: MAP_HEIGHT })
+			},
+		};
+	}
+
+	// Pans the map with the movement keys and zooms it with up and down, north being up
+	fn pan_map(&mut self, seconds: f32) {
+		let (pan, zoom) = {
+			let held = |action| self.actions.held(action, &self.keys_down);
+			let mut pan = [0.0, 0.0];
+			if held(Action::Forward) { pan[1] += 1.0; }
+			if held(Action::Back) { pan[1] -= 1.0; }
+			if held(Action::Right) { pan[0] += 1.0; }
+			if held(Action::Left) { pan[0] -= 1.0; }
+			let zoom = match (held(Action::Up), held(Action::Down)) {
+				(true, false) => MAP_ZOOM_SPEED.powf(seconds),
+				(false, true) => MAP_ZOOM_SPEED.powf(-seconds),
+				_ => 1.0,
+			};
+			(pan, zoom)
+		};
+		if let Some(ref mut map) = self.map {
+			let speed = map.height * MAP_PAN_SPEED * seconds;
+			map.center[0] += pan[0] * speed;
+			map.center[1] += pan[1] * speed;
+			map.zoom(zoom);
+		}
+	}
+
+	// Free camera motion, relative to where the camera is facing
+	fn fly(&mut self, time_elapsed: &Duration) {
+		let speed = if self.actions.held(Action::FlyFast, &self.keys_down) {
+			self.settings.fly_fast_speed
+		} else {
+			self.settings.fly_speed
+		};
+		let step = speed * time_elapsed.num_microseconds().unwrap() as f32 / 1_000_000.0;
+
+		match (self.actions.held(Action::Left, &self.keys_down), self.actions.held(Action::Right, &self.keys_down)) {
+			(true, true) => (),
+			(false, false) => (),
+
+			(true, false) => {
+				self.camera.target.position -= self.camera.target.direction.cross(&self.camera.target.up) * step;
+			},
+			(false, true) => {
+				self.camera.target.position -= -1.0 * self.camera.target.direction.cross(&self.camera.target.up) * step;
+			},
+		}
+
+		match (self.actions.held(Action::Forward, &self.keys_down), self.actions.held(Action::Back, &self.keys_down)) {
+			(true, true) => (),
+			(false, false) => (),
+
+			(true, false) => {
+				self.camera.target.position -= -1.0 * self.camera.target.direction * step;
+			},
+			(false, true) => {
+				self.camera.target.position -= self.camera.target.direction * step;
+			},
+		}
+
+		match (self.actions.held(Action::Up, &self.keys_down), self.actions.held(Action::Down, &self.keys_down)) {
+			(true, true) => (),
+			(false, false) => (),
+
+			(true, false) => {
+				self.camera.target.position -= -1.0 * self.camera.target.up * step;
+
+			},
+			(false, true) => {
+				self.camera.target.position -= self.camera.target.up * step;
+			},
+		}
+
+		if let Some(border) = self.border() {
+			let border = border as f32;
+			let position = &mut self.camera.target.position;
+			position.x = position.x.max(-border).min(border);
+			position.y = position.y.max(-border).min(border);
+		}
+	}
+
+	// The horizontal velocity the player's body should walk at
+	fn walk(&mut self) -> [f32; 2] {
+		let crouching = self.crouching();
+		let forward_held = self.actions.held(Action::Forward, &self.keys_down);
+		self.sprinting = (self.sprinting || self.actions.held(Action::Sprint, &self.keys_down)) && forward_held && !crouching;
+		let speed = if crouching {
+			self.settings.crouch_speed
+		} else if self.sprinting {
+			self.settings.sprint_speed
+		} else {
+			self.settings.walk_speed
+		};
+
+		let up = self.camera.target.up;
+		let direction = self.camera.target.direction;
+		let forward = (direction - up * direction.dot(&up)).try_normalize(1.0e-6).unwrap_or(Vector3::zeros());
+		let right = forward.cross(&up);
+
+		let mut wish = Vector3::zeros();
+		if forward_held { wish += forward; }
+		if self.actions.held(Action::Back, &self.keys_down) { wish -= forward; }
+		if self.actions.held(Action::Right, &self.keys_down) { wish += right; }
+		if self.actions.held(Action::Left, &self.keys_down) { wish -= right; }
+		let wish = wish.try_normalize(1.0e-6).unwrap_or(Vector3::zeros()) * speed;
+		[wish.x, wish.y]
+	}
+
+	fn toggle_walking(&mut self) {
+		if !self.walking && self.remote.is_some() {
+			return self.print("Walking isn't possible on another server yet".to_string());
+		}
+		if self.walking && !self.rules().fly {
+			return self.print("Flying isn't allowed in this world".to_string());
+		}
+		self.walking = !self.walking;
+		if self.walking {
+			let eye = self.eye();
+			self.send(ClientMessage::Walk([eye[0], eye[1], eye[2] - EYE_HEIGHT]));
+		} else {
+			self.send(ClientMessage::Fly);
+		}
+	}
+
+	fn print(&mut self, line: String) {
+		self.chat.push(line.clone());
+		self.console.print(line);
+	}
+
+	// Lines starting with a slash are commands, and anything else is said in chat
+	fn submit(&mut self, services: &GameServices, line: &str) {
+		let (name, args) = match console::parse_command(line) {
+			Some(command) => command,
+			None => {
+				let text = line.trim();
+				if text.is_empty() {
+					return;
+				}
+				let said = self.remote.as_mut().map(|remote| remote.say(text));
+				match said {
+					Some(Ok(())) => (),
+					Some(Err(err)) => self.disconnect(format!("Lost the connection: {:?}", err)),
+					None => self.send(ClientMessage::Chat { from: self.settings.name.clone(), text: text.to_string() }),
+				}
+				return;
+			},
+		};
+
+		let result = match name {
+			"spawn" => self.spawn_command(&args),
+			"smoothing" => self.smoothing_command(&args),
+			"host" => self.host_command(&args),
+			"connect" => self.connect_command(&args),
+			"disconnect" => match self.remote {
+				Some(_) => {
+					self.disconnect("Disconnected".to_string());
+					Ok(None)
+				},
+				None => Err("Not connected to a server".to_string()),
+			},
+			"music" => self.music_command(services, &args),
+			"weather" => self.weather_command(&args),
+			"border" => self.border_command(&args),
+			"gamerule" => self.gamerule_command(&args),
+			"time" => self.time_command(&args),
+			"sel" => self.selection_command(&args),
+			"fill" => self.fill_command(&args),
+			"replace" => self.replace_command(&args),
+			"copy" => self.copy_command(),
+			"paste" => self.paste_command(&args),
+			"schem" => self.prefab_command(&args),
+			"measure" => self.measure_command(&args),
+			"count" => self.count_command(false),
+			"surface" => self.count_command(true),
+			"post" => self.post_command(&args),
+			"path" => self.path_command(&args),
+			"trace" => self.trace_command(services, &args),
+			"save" => {
+				self.send(ClientMessage::Save);
+				Ok(None)
+			},
+			"netstats" => {
+				self.send(ClientMessage::NetStats);
+				Ok(None)
+			},
+			_ => Err(format!("Unknown command: {}", name)),
+		};
+		// Commands carried out by the server report back themselves
+		match result {
+			Ok(Some(line)) | Err(line) => self.print(line),
+			Ok(None) => (),
+		}
+	}
+
+	// /host [port]
+	fn host_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let port = match args.get(0) {
+			Some(port) => port.parse::<u16>().map_err(|_| format!("Invalid port: {}", port))?,
+			None => DEFAULT_PORT,
+		};
+		self.send(ClientMessage::Host(port));
+		Ok(None)
+	}
+
+	// /connect host[:port] [name] plays on someone else's server, by the name in the settings
+	// unless given another. It waits for the server to answer, as the host command does for its
+	// port.
+	fn connect_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let host = args.get(0).ok_or("Usage: /connect host[:port] [name]".to_string())?;
+		let addr = if host.contains(':') { host.to_string() } else { format!("{}:{}", host, DEFAULT_PORT) };
+		let name = args.get(1).map(|name| name.to_string()).unwrap_or(self.settings.name.clone());
+		let remote = NetClient::connect(addr.as_str(), &name).map_err(|err| format!("Couldn't connect to {}: {:?}", addr, err))?;
+		if self.remote.is_some() {
+			self.disconnect(format!("Left for {}", addr));
+		}
+		if self.walking {
+			self.walking = false;
+			self.send(ClientMessage::Fly);
+		}
+		let spawn = remote.spawn();
+		self.camera.target.position = Point3::new(spawn[0] as f32 + 0.5, spawn[1] as f32 + 0.5, spawn[2] as f32 + EYE_HEIGHT);
+		self.camera.snap();
+		self.motion.reset();
+		// Said once, here; the server holds everyone to it whatever they do
+		let rules = if remote.fly_allowed() { "" } else { ". Flying isn't allowed there, so edits only reach as far as they would from the ground" };
+		self.remote = Some(remote);
+		Ok(Some(format!("Connected to {} as {}{}", addr, name, rules)))
+	}
+
+	// Goes back to the local server's world
+	fn disconnect(&mut self, reason: String) {
+		self.remote = None;
+		self.watch();
+		self.print(reason);
+	}
+
+	// Asks the remote server for an edit, showing it straight away
+	fn remote_edit(&mut self, pos: [i64; 3], block: usize) {
+		let edited = match self.remote {
+			Some(ref mut remote) => remote.edit(&self.world, pos, block),
+			None => return,
+		};
+		if let Err(err) = edited {
+			self.disconnect(format!("Lost the connection: {:?}", err));
+		}
+	}
+
+	// Takes in what the remote server sent since the last call
+	fn receive_remote(&mut self) {
+		let events = match self.remote {
+			Some(ref mut remote) => remote.tick(&self.world),
+			None => return,
+		};
+		match events {
+			Ok(events) => for event in events {
+				match event {
+					NetEvent::Chat { from, text } => self.chat.push(format!("<{}> {}", from, text)),
+					NetEvent::EditRefused { .. } => self.print("The server refused an edit".to_string()),
+					_ => (),
+				}
+			},
+			Err(err) => self.disconnect(format!("Lost the connection: {:?}", err)),
+		}
+	}
+
+	// /music shows what's playing, /music next skips it and /music volume [level] sets the music
+	// volume from 0 to 1, saving it in the settings
+	fn music_command(&mut self, services: &GameServices, args: &[&str]) -> Result<Option<String>, String> {
+		match (args.get(0), args.get(1)) {
+			(None, _) => match services.music_service.now_playing() {
+				Some(name) => Ok(Some(format!("Playing {}", name))),
+				None => Ok(Some("No music playing".to_string())),
+			},
+			(Some(&"next"), _) => {
+				services.music_service.skip();
+				Ok(None)
+			},
+			(Some(&"volume"), Some(level)) => {
+				let level = level.parse::<f32>().ok().filter(|level| *level >= 0.0 && *level <= 1.0).ok_or(format!("Invalid volume: {}", level))?;
+				self.settings.music_volume = level;
+				self.settings.save(::std::path::Path::new(SETTINGS_FILE)).map_err(|err| format!("Failed to save settings: {:?}", err))?;
+				Ok(Some(format!("Music volume set to {}", level)))
+			},
+			(Some(&"volume"), None) => Ok(Some(format!("Music volume is {}", self.settings.music_volume))),
+			(Some(other), _) => Err(format!("Unknown music command: {}", other)),
+		}
+	}
+
+	// /trace [file] writes the last few seconds of profiling for chrome://tracing
+	fn trace_command(&mut self, services: &GameServices, args: &[&str]) -> Result<Option<String>, String> {
+		let path = ::std::path::Path::new(args.get(0).cloned().unwrap_or(TRACE_FILE));
+		let spans = profile::save_trace(path).map_err(|err| format!("Failed to write trace: {}", err))?;
+		services.toast_service.push("Trace captured");
+		Ok(Some(format!("Wrote {} spans to {}", spans, path.display())))
+	}
+
+	// /weather [clear|rain|snow]
+	fn weather_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		match args.get(0) {
+			Some(weather) => {
+				self.send(ClientMessage::SetWeather(weather.parse()?));
+				Ok(None)
+			},
+			None => Ok(Some(format!("The weather is {}", self.weather()))),
+		}
+	}
+
+	// /border [radius|off]
+	fn border_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		match args.get(0) {
+			Some(&"off") => {
+				self.send(ClientMessage::SetBorder(None));
+				Ok(None)
+			},
+			Some(radius) => {
+				let radius = radius.parse::<i64>().ok().filter(|radius| *radius > 0).ok_or(format!("Invalid radius: {}", radius))?;
+				self.send(ClientMessage::SetBorder(Some(radius)));
+				Ok(None)
+			},
+			None => Ok(Some(match self.border() {
+				Some(border) => format!("The border is {} blocks from the origin", border),
+				None => "There's no border".to_string(),
+			})),
+		}
+	}
+
+	// /time shows the time; /time set [day|noon|night|midnight|seconds] sets it, the names to the
+	// next time it's then, and /time add [seconds] moves it on
+	fn time_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let seconds = |raw: &str| raw.parse::<f64>().ok().filter(|seconds| *seconds >= 0.0).ok_or(format!("Invalid time: {}", raw));
+		match (args.get(0), args.get(1)) {
+			(None, _) => Ok(Some(format!("It's {}", environment::describe_time(self.time())))),
+			(Some(&"set"), Some(time)) => {
+				let time_of_day = match *time {
+					"day" => SUNRISE + 0.05,
+					"noon" => NOON,
+					"night" => SUNSET + 0.05,
+					"midnight" => MIDNIGHT,
+					time => {
+						self.send(ClientMessage::SetTime(seconds(time)?));
+						return Ok(None);
+					},
+				};
+				let now = self.time();
+				let mut time = (now / DAY_LENGTH).floor() * DAY_LENGTH + time_of_day as f64 * DAY_LENGTH;
+				if time < now {
+					time += DAY_LENGTH;
+				}
+				self.send(ClientMessage::SetTime(time));
+				Ok(None)
+			},
+			(Some(&"add"), Some(time)) => {
+				self.send(ClientMessage::AddTime(seconds(time)?));
+				Ok(None)
+			},
+			(Some(&"set"), None) | (Some(&"add"), None) => Err("Missing time".to_string()),
+			(Some(other), _) => Err(format!("Unknown time command: {}", other)),
+		}
+	}
+
+	// /sel shows the selection, /sel start and /sel stop turn selecting with the mouse on and off
+	// and /sel clear forgets it
+	fn selection_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		match args.get(0) {
+			None => Ok(Some(match (self.selection.bounds(), self.selection.size()) {
+				(Some((min, max)), Some(size)) => format!("Selected {:?} to {:?}, {}x{}x{}", min, max, size[0], size[1], size[2]),
+				_ => "Nothing selected".to_string(),
+			})),
+			Some(&"start") | Some(&"stop") if (args[0] == "start") != self.selecting => {
+				self.toggle_selecting();
+				Ok(None)
+			},
+			Some(&"start") | Some(&"stop") => Ok(None),
+			Some(&"clear") => {
+				self.selection.clear();
+				Ok(Some("Selection cleared".to_string()))
+			},
+			Some(other) => Err(format!("Unknown selection command: {}", other)),
+		}
+	}
+
+	// /fill [block] fills the selection
+	fn fill_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let (min, max) = self.selection.bounds().ok_or("Nothing selected".to_string())?;
+		let block = self.block_named(args.get(0).ok_or("Missing block".to_string())?)?;
+		self.send(ClientMessage::FillRegion { min: min, max: max, block: block, from: None });
+		Ok(None)
+	}
+
+	// /replace [from] [to] swaps one block for another throughout the selection
+	fn replace_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let (min, max) = self.selection.bounds().ok_or("Nothing selected".to_string())?;
+		let from = self.block_named(args.get(0).ok_or("Missing block".to_string())?)?;
+		let to = self.block_named(args.get(1).ok_or("Missing block".to_string())?)?;
+		self.send(ClientMessage::FillRegion { min: min, max: max, block: to, from: Some(from) });
+		Ok(None)
+	}
+
+	// /paste [degrees] pastes what was copied where the player looks, turned anticlockwise by a
+	// multiple of 90 degrees
+	fn paste_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let degrees = match args.get(0) {
+			Some(degrees) => degrees.parse::<i32>().ok().filter(|degrees| degrees % 90 == 0).ok_or(format!("Invalid rotation: {}", degrees))?,
+			None => 0,
+		};
+		self.send(ClientMessage::Paste { origin: self.target_cell(), turns: ((degrees / 90) % 4 + 4) as u32 % 4 });
+		Ok(None)
+	}
+
+	// /copy copies the selection, to be pasted where the player looks as it sits from where they
+	// look now
+	fn copy_command(&mut self) -> Result<Option<String>, String> {
+		let (min, max) = self.selection.bounds().ok_or("Nothing selected".to_string())?;
+		self.send(ClientMessage::Copy { min: min, max: max, origin: self.target_cell() });
+		Ok(None)
+	}
+
+	// /schem or /schem list opens the prefab browser, /schem save [name] [global] saves the selection as a prefab
+	// for this world or, with global, for every world, and /schem load [name] copies one to paste
+	fn prefab_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		match (args.get(0), args.get(1)) {
+			(None, _) | (Some(&"list"), _) => {
+				self.send(ClientMessage::ListPrefabs);
+				self.keys_down.clear();
+				self.breaking = false;
+				self.prefab_browser.open();
+				Ok(None)
+			},
+			(Some(&"save"), Some(name)) => {
+				let (min, max) = self.selection.bounds().ok_or("Nothing selected".to_string())?;
+				let global = match args.get(2) {
+					Some(&"global") => true,
+					Some(other) => return Err(format!("Expected global, not {}", other)),
+					None => false,
+				};
+				self.send(ClientMessage::SavePrefab { name: name.to_string(), min: min, max: max, global: global });
+				Ok(None)
+			},
+			(Some(&"load"), Some(name)) => {
+				self.send(ClientMessage::LoadPrefab(name.to_string()));
+				Ok(None)
+			},
+			(Some(&"save"), None) | (Some(&"load"), None) => Err("Missing name".to_string()),
+			(Some(other), _) => Err(format!("Unknown prefab command: {}", other)),
+		}
+	}
+
+	// /measure [x y z x y z] gives the distance between the centers of two blocks, by default the
+	// selection's corners
+	fn measure_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let (a, b) = match args.len() {
+			0 => self.selection.bounds().ok_or("Nothing selected".to_string())?,
+			6 => {
+				let coords = args.iter().map(|arg| arg.parse::<i64>().map_err(|_| format!("Invalid coordinate: {}", arg))).collect::<Result<Vec<_>, _>>()?;
+				([coords[0], coords[1], coords[2]], [coords[3], coords[4], coords[5]])
+			},
+			_ => return Err("Expected two points, as x y z x y z".to_string()),
+		};
+		let delta = [(b[0] - a[0]).abs(), (b[1] - a[1]).abs(), (b[2] - a[2]).abs()];
+		let distance = ((delta[0] * delta[0] + delta[1] * delta[1] + delta[2] * delta[2]) as f64).sqrt();
+		Ok(Some(format!("{:.2} blocks apart, {} by {} by {}; {}x{}x{} blocks including both",
+			distance, delta[0], delta[1], delta[2], delta[0] + 1, delta[1] + 1, delta[2] + 1)))
+	}
+
+	// /count counts the blocks in the selection by type, and /surface the faces of it that can be
+	// seen, as the mesher would draw them
+	fn count_command(&mut self, faces: bool) -> Result<Option<String>, String> {
+		let (min, max) = self.selection.bounds().ok_or("Nothing selected".to_string())?;
+		self.send(if faces { ClientMessage::CountFaces { min: min, max: max } } else { ClientMessage::CountBlocks { min: min, max: max } });
+		Ok(None)
+	}
+
+	// /gamerule lists the rules; /gamerule name [value] shows or sets one
+	fn gamerule_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let rules = self.rules();
+		match (args.get(0), args.get(1)) {
+			(None, _) => Ok(Some(format!("Game rules: {}", rules))),
+			(Some(name), None) => match rules.get(name) {
+				Some(value) => Ok(Some(format!("{} is {}", name, value))),
+				None => Err(format!("Unknown game rule: {}", name)),
+			},
+			(Some(name), Some(value)) => {
+				self.send(ClientMessage::SetGameRule { name: name.to_string(), value: value.to_string() });
+				Ok(None)
+			},
+		}
+	}
+
+	// /post lists the post effects that are on; /post [fxaa|tonemap] [on|off] shows or sets one
+	fn post_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let name = match args.get(0) {
+			Some(name) => name,
+			None => {
+				let names = self.post_effects.names();
+				return Ok(Some(if names.is_empty() { "No post effects".to_string() } else { format!("Post effects: {}", names.join(", ")) }));
+			},
+		};
+		let effect = PostEffects::from_name(name).ok_or(format!("Unknown post effect: {}", name))?;
+		match args.get(1) {
+			Some(&"on") => self.post_effects.insert(effect),
+			Some(&"off") => self.post_effects.remove(effect),
+			Some(other) => return Err(format!("Expected on or off, not {}", other)),
+			None => (),
+		}
+		let state = if self.post_effects.contains(effect) { "on" } else { "off" };
+		Ok(Some(format!("{} is {}", name, state)))
+	}
+
+	// /path counts the waypoints, /path add adds one where the camera is, /path clear removes them
+	// all, /path play [speed] flies through them at speed blocks per second, /path record [speed]
+	// [dir] also saves every frame to dir, and /path stop stops early
+	fn path_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		let speed = match args.get(1) {
+			Some(speed) => speed.parse::<f32>().ok().filter(|speed| *speed > 0.0).ok_or(format!("Invalid speed: {}", speed))?,
+			None => PATH_SPEED,
+		};
+		match args.get(0) {
+			None => Ok(Some(format!("{} waypoints, {:.1} blocks long", self.camera_path.len(), self.camera_path.length()))),
+			Some(&"add") => {
+				self.camera_path.push(&self.camera.target);
+				Ok(Some(format!("Added waypoint {}", self.camera_path.len())))
+			},
+			Some(&"clear") => {
+				self.playback = None;
+				self.camera_path.clear();
+				Ok(Some("Cleared the path".to_string()))
+			},
+			Some(&"play") | Some(&"record") => {
+				if self.camera_path.len() < 2 {
+					return Err("The path needs at least two waypoints".to_string());
+				}
+				let frames_dir = if args[0] == "record" {
+					let dir = PathBuf::from(args.get(2).cloned().unwrap_or(FRAMES_DIR));
+					::std::fs::create_dir_all(&dir).map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+					Some(dir)
+				} else {
+					None
+				};
+				if self.walking {
+					self.toggle_walking();
+				}
+				self.playback = Some(PathPlayback { distance: 0.0, speed: speed, frames_dir: frames_dir, frames_shown: 0 });
+				Ok(None)
+			},
+			Some(&"stop") => match self.playback.take() {
+				Some(_) => Ok(Some("Stopped".to_string())),
+				None => Err("Not following the path".to_string()),
+			},
+			Some(other) => Err(format!("Unknown path command: {}", other)),
+		}
+	}
+
+	// /smoothing [rate], where 0 turns camera smoothing off
+	fn smoothing_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		match args.get(0) {
+			Some(rate) => {
+				let rate = rate.parse::<f32>().ok().filter(|rate| *rate >= 0.0).ok_or(format!("Invalid rate: {}", rate))?;
+				self.camera.smoothing = rate;
+				Ok(Some(format!("Camera smoothing set to {}", rate)))
+			},
+			None => Ok(Some(format!("Camera smoothing is {}", self.camera.smoothing))),
+		}
+	}
+
+	// /spawn returns the player to the world spawn; /spawn mob [count] spawns on the block
+	// under the crosshair, or at the camera
+	fn spawn_command(&mut self, args: &[&str]) -> Result<Option<String>, String> {
+		match args.get(0) {
+			Some(&"mob") => (),
+			Some(kind) => return Err(format!("Can't spawn {}", kind)),
+			None => {
+				self.send(ClientMessage::Respawn);
+				return Ok(None);
+			},
+		}
+		let count = match args.get(1) {
+			Some(count) => count.parse::<usize>().map_err(|_| format!("Invalid count: {}", count))?,
+			None => 1,
+		};
+
+		self.send(ClientMessage::SpawnMobs { origin: self.eye(), direction: self.look(), count: count });
+		Ok(None)
+	}
+
+	// Entity boxes, the blocks their last step was tested against and their velocities
+	fn physics_overlay(&self, snapshot: &Snapshot) -> DebugLines {
+		let mut lines = DebugLines::new();
+		for &pos in &snapshot.collision_candidates {
+			lines.block(pos, [1.0, 0.2, 0.2]);
+		}
+
+		let player = snapshot.player.as_ref().map(|player| player.id);
+		for &(id, ref entity) in &snapshot.entities {
+			let color = if Some(id) == player { [0.2, 1.0, 0.2] } else { [0.2, 0.6, 1.0] };
+			let bounds = entity.world_bounds();
+			lines.aabb(&bounds, color);
+
+			let center = [
+				(bounds.min[0] + bounds.max[0]) / 2.0,
+				(bounds.min[1] + bounds.max[1]) / 2.0,
+				(bounds.min[2] + bounds.max[2]) / 2.0,
+			];
+			let end = [
+				center[0] + entity.velocity[0] * DEBUG_VELOCITY_SCALE,
+				center[1] + entity.velocity[1] * DEBUG_VELOCITY_SCALE,
+				center[2] + entity.velocity[2] * DEBUG_VELOCITY_SCALE,
+			];
+			lines.line(center, end, [1.0, 1.0, 0.2]);
+		}
+		lines
+	}
+
+	pub fn draw(&self, draw_service: &mut DrawService) -> Result<(), SpaceError> {
+		let weather = self.weather();
+		draw_service.set_fov(self.fov);
+		draw_service.set_anisotropy(self.settings.anisotropy);
+		draw_service.set_gpu_timing(self.show_profiler);
+		if let Some(ref map) = self.map {
+			self.draw_map(draw_service, map)?;
+			return self.draw_overlay(draw_service);
+		}
+		// The path is flown smoothly, without bobbing or widening
+		let view = match self.playback {
+			Some(_) => self.camera.current().view_matrix(),
+			None => self.motion.apply(self.camera.current()).view_matrix(),
+		};
+		let mut environment = Environment::at_time(self.time(), weather);
+		match self.region.medium_at(&self.block_render_types, &self.camera.current().position) {
+			Medium::Liquid(block) => {
+				let color = self.block_render_types[block].color;
+				environment.fog_color = [
+					color[0] * UNDERWATER_FOG_BRIGHTNESS,
+					color[1] * UNDERWATER_FOG_BRIGHTNESS,
+					color[2] * UNDERWATER_FOG_BRIGHTNESS,
+				];
+				environment.fog_density = UNDERWATER_FOG_DENSITY;
+				draw_service.set_underwater(Some(color));
+			},
+			Medium::Air => draw_service.set_underwater(None),
+		}
+		draw_service.set_environment(&environment, &view);
+		draw_service.set_post_effects(self.post_effects);
+		self.region.draw(&self.block_render_types, draw_service, view, &self.camera.current().position, self.settings.render_distance)?;
+		if let Some(border) = self.border() {
+			draw_service.draw_striped(&view, &border_wall(border, self.camera.current().position), BORDER_WALL_DISTANCE)?;
+		}
+		draw_service.draw_striped(&view, &self.selection.faces(SELECTION_COLOR), ::std::f32::INFINITY)?;
+
+		if let Some(ref snapshot) = self.snapshot {
+			self.draw_snapshot(draw_service, &view, snapshot)?;
+		}
+
+		let mut precipitation = DebugLines::new();
+		self.precipitation.draw(weather, &mut precipitation);
+		draw_service.draw_lines(&view, &precipitation)?;
+		if self.chunk_borders != ChunkBorders::Off {
+			draw_service.draw_lines(&view, &self.chunk_border_lines())?;
+		}
+		if self.playback.is_none() && !self.camera_path.is_empty() {
+			let mut path = DebugLines::new();
+			self.camera_path.draw(&mut path);
+			draw_service.draw_lines(&view, &path)?;
+		}
+		if self.overhead_view {
+			self.draw_overhead_view(draw_service, &environment)?;
+		}
+
+		self.draw_overlay(draw_service)?;
+
+		if let Some(PathPlayback { frames_dir: Some(ref dir), frames_shown, .. }) = self.playback {
+			if frames_shown > 0 {
+				draw_service.capture(dir.join(format!("frame{:05}.png", frames_shown - 1)));
+			}
+		}
+		Ok(())
+	}
+
+	fn draw_overlay(&self, draw_service: &mut DrawService) -> Result<(), SpaceError> {
+		let mut overlay = Overlay::new();
+		let (_, height) = draw_service.dimensions();
+		let input = self.console.input();
+		self.chat.draw(&mut overlay, height as f32, input.as_ref().map(|input| input.as_str()));
+		draw_service.draw_overlay(&overlay)
+	}
+
+	// The loaded chunks straight down from above the top of the region, with an arrow for the
+	// player pointing the way the camera faces. Weather and fog would only get in the way.
+	fn draw_map(&self, draw_service: &mut DrawService, map: &MapView) -> Result<(), SpaceError> {
+		draw_service.set_projection(Projection::Orthographic { height: map.height });
+		let (_, max) = self.region.chunk_bounds();
+		let top = (max[2] * CHUNK_SIZE as i64) as f32 + 1.0;
+		let camera = SimpleCamera {
+			position: Point3::new(map.center[0], map.center[1], top),
+			direction: -Vector3::z(),
+			up: Vector3::y(),
+		};
+		let view = camera.view_matrix();
+		// Without fog, the sky only shows where nothing's loaded
+		let mut environment = Environment::at_time(self.time(), self.weather());
+		environment.fog_density = 0.0;
+		draw_service.set_underwater(None);
+		draw_service.set_environment(&environment, &view);
+		draw_service.set_post_effects(self.post_effects);
+		self.region.draw_map(&self.block_render_types, draw_service, view, &Point3::new(map.center[0], map.center[1], top))?;
+
+		// Over everything in the region, so nothing hides it
+		let eye = self.camera.current();
+		let z = top - 0.5;
+		let facing = Vector3::new(eye.direction.x, eye.direction.y, 0.0).try_normalize(1.0e-6).unwrap_or(Vector3::y());
+		let length = map.height * MAP_MARKER_SIZE;
+		let tip = [eye.position.x + facing.x * length, eye.position.y + facing.y * length, z];
+		let mut marker = DebugLines::new();
+		marker.line([eye.position.x, eye.position.y, z], tip, [1.0, 0.2, 0.2]);
+		for &side in &[-1.0, 1.0] {
+			let back = facing * -0.4 + Vector3::new(-facing.y, facing.x, 0.0) * 0.3 * side;
+			marker.line(tip, [tip[0] + back.x * length, tip[1] + back.y * length, z], [1.0, 0.2, 0.2]);
+		}
+		draw_service.draw_lines(&view, &marker)
+	}
+
+	// Straight down on the camera from high above, in the top right corner of the window
+	fn draw_overhead_view(&self, draw_service: &mut DrawService, environment: &Environment) -> Result<(), SpaceError> {
+		let (width, height) = draw_service.dimensions();
+		let size = (height as f32 * OVERHEAD_VIEW_SIZE) as u32;
+		draw_service.begin_view(Some(Rect {
+			left: width.saturating_sub(size + OVERHEAD_VIEW_MARGIN),
+			bottom: height.saturating_sub(size + OVERHEAD_VIEW_MARGIN),
+			width: size,
+			height: size,
+		}))?;
+		draw_service.set_fov(OVERHEAD_VIEW_FOV);
+
+		let camera = SimpleCamera {
+			position: self.camera.current().position + Vector3::z() * OVERHEAD_VIEW_HEIGHT,
+			direction: -Vector3::z(),
+			up: Vector3::y(),
+		};
+		let view = camera.view_matrix();
+		draw_service.set_environment(environment, &view);
+		self.region.draw(&self.block_render_types, draw_service, view, &camera.position, self.settings.render_distance)?;
+		if let Some(ref snapshot) = self.snapshot {
+			self.draw_snapshot(draw_service, &view, snapshot)?;
+		}
+		Ok(())
+	}
+
+	fn chunk_border_lines(&self) -> DebugLines {
+		let mut lines = DebugLines::new();
+		let eye = self.camera.current().position;
+		let size = CHUNK_SIZE as f32;
+		let center = [(eye.x / size).floor() as i64, (eye.y / size).floor() as i64, (eye.z / size).floor() as i64];
+		for x in -1..2 {
+			for y in -1..2 {
+				for z in -1..2 {
+					lines.chunk([center[0] + x, center[1] + y, center[2] + z], [0.9, 0.3, 0.3]);
+				}
+			}
+		}
+		lines.chunk_grid(center, CHUNK_GRID_SPACING, [0.9, 0.9, 0.3]);
+		lines.chunk(center, [1.0, 1.0, 0.4]);
+
+		if self.chunk_borders == ChunkBorders::ChunksAndRegion {
+			let (min, max) = self.region.chunk_bounds();
+			let size = CHUNK_SIZE as i64;
+			lines.cuboid([min[0] * size, min[1] * size, min[2] * size], [max[0] * size, max[1] * size, max[2] * size], [0.3, 0.8, 1.0]);
+		}
+		lines
+	}
+
+	fn draw_snapshot(&self, draw_service: &mut DrawService, view: &Matrix4<f32>, snapshot: &Snapshot) -> Result<(), SpaceError> {
+		for &(_, ref entity) in &snapshot.entities {
+			draw_service.draw_entity(view, &entity.model(), &entity.transform())?;
+		}
+
+		// Falling blocks are drawn like entities until they land
+		for block in &snapshot.falling {
+			let model = BoxModel { bounds: Aabb::new([0.0; 3], [1.0; 3]), color: self.block_render_types[block.block].color };
+			let position = Vector3::new(block.position[0], block.position[1], block.position[2]);
+			draw_service.draw_entity(view, &model, &Matrix4::new_translation(&position))?;
+		}
+
+		if let Some((pos, progress)) = snapshot.breaking {
+			let mut cracks = DebugLines::new();
+			cracks.cracks(pos, progress, [0.1, 0.1, 0.1]);
+			draw_service.draw_lines(view, &cracks)?;
+		}
+
+		if self.debug_physics {
+			draw_service.draw_lines(view, &self.physics_overlay(snapshot))?;
+		}
+		Ok(())
+	}
+}
+
+// Triangles for the sides of the border near `eye`, which fade in as it comes closer
+fn border_wall(border: i64, eye: Point3<f32>) -> Vec<Vertex> {
+	let border = border as f32;
+	let mut vertices = Vec::new();
+	for axis in 0..2 {
+		let along = 1 - axis;
+		for &side in [-border, border].iter() {
+			if (eye[axis] - side).abs() > BORDER_WALL_DISTANCE {
+				continue;
+			}
+			// As far either way as it can be seen, but not past the corners
+			let from = (eye[along] - BORDER_WALL_DISTANCE).max(-border);
+			let to = (eye[along] + BORDER_WALL_DISTANCE).min(border);
+			let (bottom, top) = (eye.z - BORDER_WALL_DISTANCE, eye.z + BORDER_WALL_DISTANCE);
+			let corner = |a: f32, z: f32| {
+				let mut position = [0.0, 0.0, z];
+				position[axis] = side;
+				position[along] = a;
+				let mut normal = [0.0; 3];
+				normal[axis] = -side.signum();
+				Vertex { position: position, normal: normal, color: BORDER_COLOR }
+			};
+			vertices.extend_from_slice(&[
+				corner(from, bottom), corner(to, bottom), corner(to, top),
+				corner(from, bottom), corner(to, top), corner(from, top),
+			]);
+		}
+	}
+	vertices
+}
+
+fn milliseconds(duration: Duration) -> f32 {
+	duration.num_microseconds().unwrap_or(0) as f32 / 1000.0
+}
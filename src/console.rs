@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+use std::collections::vec_deque;
+
+use text_input::{TextEdit, TextInput};
+
+const MAX_LOG_LINES: usize = 64;
+
+// A line of typed input and a log of command output. Output is echoed to stdout as well, for
+// lines that scrolled out of the chat overlay.
+pub struct Console {
+	input: Option<TextInput>,
+	log: VecDeque<String>,
+}
+
+impl Console {
+	pub fn new() -> Console {
+		Console {
+			input: None,
+			log: VecDeque::new(),
+		}
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.input.is_some()
+	}
+
+	pub fn open(&mut self) {
+		self.input = Some(TextInput::new());
+	}
+
+	pub fn close(&mut self) {
+		self.input = None;
+	}
+
+	// The line being typed with a bar at the cursor, while open
+	pub fn input(&self) -> Option<String> {
+		self.input.as_ref().map(|input| input.with_cursor('|'))
+	}
+
+	// Edits the open console's line, returning it and closing once it's submitted
+	pub fn edit(&mut self, edit: &TextEdit) -> Option<String> {
+		let submitted = match self.input {
+			Some(ref mut input) => input.apply(edit),
+			None => None,
+		};
+		if submitted.is_some() {
+			self.input = None;
+		}
+		submitted
+	}
+
+	pub fn print<S: Into<String>>(&mut self, line: S) {
+		let line = line.into();
+		println!("{}", line);
+		if self.log.len() == MAX_LOG_LINES {
+			self.log.pop_front();
+		}
+		self.log.push_back(line);
+	}
+
+	pub fn log(&self) -> vec_deque::Iter<String> {
+		self.log.iter()
+	}
+}
+
+// Splits "/name arg arg" into the command name and its arguments. Lines without the leading
+// slash, or with nothing after it, aren't commands.
+pub fn parse_command(line: &str) -> Option<(&str, Vec<&str>)> {
+	let line = line.trim();
+	if !line.starts_with('/') {
+		return None;
+	}
+
+	let mut words = line[1..].split_whitespace();
+	words.next().map(|name| (name, words.collect()))
+}
@@ -0,0 +1,10 @@
+#version 140
+in vec2 v_tex_coords;
+
+out vec4 color;
+
+uniform sampler2D source;
+
+void main() {
+	color = texture(source, v_tex_coords);
+}
@@ -0,0 +1,141 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::thread;
+
+use time;
+
+use metrics::{Metric, MetricsService};
+use profile;
+
+pub const CRASH_DIR: &'static str = "crashes";
+
+// Shared between threads, as the renderer and metrics are recorded on the main thread but any
+// thread can crash
+static DIAGNOSTICS: Mutex<Diagnostics> = Mutex::new(Diagnostics { renderer: Vec::new(), metrics: Vec::new() });
+
+// What's known about the game's state, kept up to date for when it crashes
+struct Diagnostics {
+	renderer: Vec<(&'static str, String)>,
+	metrics: Vec<(&'static str, Metric, Metric)>,
+}
+
+// Replaces dying with a message on the terminal with a report in the crash directory and a
+// message box pointing to it. Only a panic on the thread this is called from, the main one, ends
+// the game there; other threads write their report and go on unwinding, so whoever joins them
+// can still save.
+pub fn install() {
+	let main = thread::current().id();
+	let default_hook = panic::take_hook();
+	panic::set_hook(Box::new(move |info| {
+		default_hook(info);
+		let message = match write_report(info) {
+			Ok(path) => format!("space3 has crashed: {}\n\nA crash report was saved to {}", panic_message(info), path.display()),
+			Err(err) => format!("space3 has crashed: {}\n\nThe crash report couldn't be saved: {}", panic_message(info), err),
+		};
+		if thread::current().id() != main {
+			return eprintln!("{}", message);
+		}
+		show_message("space3 crashed", &message);
+		process::exit(101);
+	}));
+}
+
+// The OpenGL driver in use, from the display once it's open
+pub fn set_renderer(vendor: &str, renderer: &str, version: &str) {
+	if let Some(mut diagnostics) = diagnostics() {
+		diagnostics.renderer = vec![
+			("Vendor", vendor.to_string()),
+			("Renderer", renderer.to_string()),
+			("Version", version.to_string()),
+		];
+	}
+}
+
+pub fn record_metrics(metrics: &MetricsService) {
+	if let Some(mut diagnostics) = diagnostics() {
+		diagnostics.metrics = metrics.all();
+	}
+}
+
+// None while another thread has them, so a crash report can't wait forever on a thread that
+// crashed holding them. A thread that crashed while changing them left them whole enough to print.
+fn diagnostics() -> Option<MutexGuard<'static, Diagnostics>> {
+	match DIAGNOSTICS.try_lock() {
+		Ok(diagnostics) => Some(diagnostics),
+		Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+		Err(TryLockError::WouldBlock) => None,
+	}
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+	let payload = info.payload();
+	let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+		.or_else(|| payload.downcast_ref::<String>().cloned())
+		.unwrap_or_else(|| "unknown error".to_string());
+	match info.location() {
+		Some(location) => format!("{} at {}:{}", message, location.file(), location.line()),
+		None => message,
+	}
+}
+
+fn write_report(info: &PanicHookInfo) -> Result<PathBuf, String> {
+	let mut report = String::new();
+	let _ = writeln!(report, "space3 {} crashed at {}", env!("CARGO_PKG_VERSION"), time::now().rfc3339());
+	let _ = writeln!(report, "{}", panic_message(info));
+
+	let _ = writeln!(report, "Thread: {}", thread::current().name().unwrap_or("unnamed"));
+	let _ = writeln!(report, "\nBacktrace:\n{}", Backtrace::force_capture());
+
+	// Whatever was being recorded when the panic happened is left out rather than waited for
+	if let Some(diagnostics) = diagnostics() {
+		let _ = writeln!(report, "\nOpenGL:");
+		if diagnostics.renderer.is_empty() {
+			let _ = writeln!(report, "  No display was open");
+		}
+		for &(name, ref value) in &diagnostics.renderer {
+			let _ = writeln!(report, "  {}: {}", name, value);
+		}
+		let _ = writeln!(report, "\nMetrics:");
+		for &(name, metric, peak) in &diagnostics.metrics {
+			let _ = writeln!(report, "  {}: {} (peak {})", name, metric, peak);
+		}
+	}
+	if let Some(timings) = profile::try_last_frame() {
+		let _ = writeln!(report, "\nLast frame:");
+		for timing in timings {
+			let indent: String = ::std::iter::repeat("  ").take(timing.depth + 1).collect();
+			let _ = writeln!(report, "{}{}: {} us ({}x)", indent, timing.name, timing.total.num_microseconds().unwrap_or(0), timing.calls);
+		}
+	}
+
+	fs::create_dir_all(CRASH_DIR).map_err(|err| format!("{}", err))?;
+	let name = format!("crash-{}.txt", time::now().strftime("%Y%m%d-%H%M%S").map(|t| t.to_string()).unwrap_or_default());
+	let path = Path::new(CRASH_DIR).join(name);
+	fs::write(&path, report).map_err(|err| format!("{}", err))?;
+	Ok(path)
+}
+
+// There's no windowing toolkit to show one with, so this asks the platform's own tools, and
+// the report's already on the terminal if none of them are there
+fn show_message(title: &str, message: &str) {
+	let result = if cfg!(target_os = "windows") {
+		let script = format!(
+			"Add-Type -AssemblyName System.Windows.Forms; [System.Windows.Forms.MessageBox]::Show('{}', '{}')",
+			message.replace('\'', "''"), title.replace('\'', "''"));
+		Command::new("powershell").args(&["-NoProfile", "-Command", &script]).status()
+	} else if cfg!(target_os = "macos") {
+		let script = format!("display alert \"{}\" message \"{}\"", title.replace('"', "\\\""), message.replace('"', "\\\""));
+		Command::new("osascript").args(&["-e", &script]).status()
+	} else {
+		Command::new("zenity").args(&["--error", "--title", title, "--text", message]).status()
+			.or_else(|_| Command::new("kdialog").args(&["--title", title, "--error", message]).status())
+	};
+	if result.is_err() {
+		eprintln!("{}", message);
+	}
+}
@@ -0,0 +1,125 @@
+use gl_util::Vertex;
+use entity::Aabb;
+use block::CHUNK_SIZE;
+
+// Colored line segments built up over a frame for debugging overlays, drawn in one batch by
+// `DrawService::draw_lines`
+pub struct DebugLines {
+	vertices: Vec<Vertex>,
+}
+
+impl DebugLines {
+	pub fn new() -> DebugLines {
+		DebugLines { vertices: Vec::new() }
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.vertices.is_empty()
+	}
+
+	// Pairs of vertices, one pair per segment
+	pub fn vertices(&self) -> &[Vertex] {
+		&self.vertices
+	}
+
+	pub fn line(&mut self, from: [f32; 3], to: [f32; 3], color: [f32; 3]) {
+		self.vertices.push(Vertex { position: from, normal: [0.0; 3], color: color });
+		self.vertices.push(Vertex { position: to, normal: [0.0; 3], color: color });
+	}
+
+	// The twelve edges of a box
+	pub fn aabb(&mut self, bounds: &Aabb, color: [f32; 3]) {
+		let corner = |i: usize| [
+			if i & 1 == 0 { bounds.min[0] } else { bounds.max[0] },
+			if i & 2 == 0 { bounds.min[1] } else { bounds.max[1] },
+			if i & 4 == 0 { bounds.min[2] } else { bounds.max[2] },
+		];
+		for i in 0..8 {
+			for bit in [1, 2, 4].iter() {
+				if i & bit == 0 {
+					self.line(corner(i), corner(i | bit), color);
+				}
+			}
+		}
+	}
+
+	pub fn block(&mut self, pos: [i64; 3], color: [f32; 3]) {
+		let min = [pos[0] as f32, pos[1] as f32, pos[2] as f32];
+		self.aabb(&Aabb::new(min, [min[0] + 1.0, min[1] + 1.0, min[2] + 1.0]), color);
+	}
+
+	// The edges of the chunk at chunk coordinates `pos`
+	pub fn chunk(&mut self, pos: [i64; 3], color: [f32; 3]) {
+		let size = CHUNK_SIZE as i64;
+		self.cuboid([pos[0] * size, pos[1] * size, pos[2] * size], [(pos[0] + 1) * size, (pos[1] + 1) * size, (pos[2] + 1) * size], color);
+	}
+
+	// Lines across every face of the chunk at chunk coordinates `pos`, every `spacing` blocks
+	pub fn chunk_grid(&mut self, pos: [i64; 3], spacing: usize, color: [f32; 3]) {
+		let size = CHUNK_SIZE as f32;
+		let min = [pos[0] as f32 * size, pos[1] as f32 * size, pos[2] as f32 * size];
+		for axis in 0..3 {
+			// Each face across `axis` gets lines along the other two axes
+			let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+			for &side in [0.0, size].iter() {
+				for step in (spacing..CHUNK_SIZE).step_by(spacing.max(1)) {
+					let offset = step as f32;
+					let mut from = min;
+					from[axis] += side;
+					let (mut a, mut b) = (from, from);
+					a[u] += offset;
+					b[u] += offset;
+					b[v] += size;
+					self.line(a, b, color);
+					let (mut a, mut b) = (from, from);
+					a[v] += offset;
+					b[v] += offset;
+					b[u] += size;
+					self.line(a, b, color);
+				}
+			}
+		}
+	}
+
+	// Cracks spreading out from the middle of each face of a block as `progress` goes from 0 to
+	// 1, more of them and longer. Drawn just off the faces so they aren't hidden by them.
+	pub fn cracks(&mut self, pos: [i64; 3], progress: f32, color: [f32; 3]) {
+		// Towards the corners and the middles of the edges, each bending once on the way
+		const CRACKS: [([f32; 2], [f32; 2]); 8] = [
+			([0.15, 0.2], [0.5, 0.45]),
+			([-0.2, -0.1], [-0.5, -0.5]),
+			([0.2, -0.15], [0.45, -0.5]),
+			([-0.15, 0.2], [-0.5, 0.4]),
+			([0.05, 0.2], [0.1, 0.5]),
+			([-0.2, 0.05], [-0.5, -0.05]),
+			([0.2, 0.0], [0.5, 0.1]),
+			([0.0, -0.2], [-0.05, -0.5]),
+		];
+		let progress = progress.max(0.0).min(1.0);
+		let count = (progress * CRACKS.len() as f32).ceil() as usize;
+		let center = [pos[0] as f32 + 0.5, pos[1] as f32 + 0.5, pos[2] as f32 + 0.5];
+		for axis in 0..3 {
+			let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+			for &side in [-0.501, 0.501].iter() {
+				let point = |p: [f32; 2]| {
+					let mut point = center;
+					point[axis] += side;
+					point[u] += p[0] * progress;
+					point[v] += p[1] * progress;
+					point
+				};
+				for &(bend, end) in CRACKS[..count].iter() {
+					self.line(point([0.0, 0.0]), point(bend), color);
+					self.line(point(bend), point(end), color);
+				}
+			}
+		}
+	}
+
+	// The edges of the box from `min` to `max`, in blocks
+	pub fn cuboid(&mut self, min: [i64; 3], max: [i64; 3], color: [f32; 3]) {
+		let min = [min[0] as f32, min[1] as f32, min[2] as f32];
+		let max = [max[0] as f32, max[1] as f32, max[2] as f32];
+		self.aabb(&Aabb::new(min, max), color);
+	}
+}
@@ -1,24 +1,48 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::slice::Iter;
-use std::mem::replace;
+use std::mem::{replace, size_of};
+use std::path::{Path, PathBuf};
 
-use glium::{Program, Display, Frame, Surface, VertexBuffer, IndexBuffer};
-use glium::glutin::{Window, VirtualKeyCode};
-use glium::glutin::{EventsLoop, Event, WindowEvent};
-use glium::index::IndicesSource;
+use glium::{Program, Display, Frame, Surface, VertexBuffer, IndexBuffer, Rect, DrawParameters};
+use glium::glutin::Window;
+use glium::glutin::{EventsLoop, Event};
+use glium::{Api, Version, CapabilitiesSource};
+use glium::backend::Facade;
+use glium::index::{IndicesSource, DrawCommandsIndicesBuffer, DrawCommandIndices};
 use glium::vertex::MultiVerticesSource;
-use glium::uniforms::Uniforms;
+use glium::uniforms::{Uniforms, Sampler, UniformBuffer};
 use glium::draw_parameters::PolygonMode;
+use glium::texture::{Texture2d, RawImage2d};
 
-use time::Duration;
+use time::{Duration, PreciseTime};
 
-use na::{Point3, Vector3, Matrix3, Matrix4, Perspective3, Rotation3};
+use image::{imageops, RgbaImage};
 
-use gl_util::{Camera, Vertex, SimpleCamera};
-use block::{BlockRenderData, Chunk, CHUNK_SIZE, CuboidRegion};
+use na::{Matrix3, Matrix4, Perspective3, Orthographic3};
+
+use gl_util::{srgb_to_linear, Vertex, Frustum, Mesh, MeshVertex, MeshVertices, MeshPool, MeshCreationError, ChunkInstance, VertexFormat};
+use chunk_batch::{self, ChunkArena, ChunkMesh, ArenaVertex, Batch, Page};
+use block::{self, BlockRenderData, Chunk, CHUNK_SIZE, CuboidRegion};
+use entity::BoxModel;
+use debug_draw::DebugLines;
+use overlay::Overlay;
+use environment::{Environment, EnvironmentBlock};
+use post::{PostProcessor, PostPrograms, PostEffects};
+use render_pass::{RenderPass, PassTarget, PassTimers, DrawStats, RENDER_PASSES};
+use audio::AudioService;
+use music::{MusicService, MUSIC_DIR};
+use gui::GuiService;
+use toast::ToastService;
+use metrics::{MetricsService, Metric};
+use profile;
+use crash;
+use error::SpaceError;
+use text_input::TextEdit;
+
+use clipboard::{ClipboardContext, ClipboardProvider};
 
 pub struct Game {
 	state: Box<GameState>,
@@ -29,20 +53,84 @@ pub struct Game {
 pub struct GameServices {
 	pub draw_service: DrawService,
 	pub input_service: InputService,
+	pub audio_service: AudioService,
+	pub music_service: MusicService,
+	pub gui_service: GuiService,
+	pub toast_service: ToastService,
+	pub metrics_service: MetricsService,
+}
+
+pub struct Shaders {
+	// Whether the lit programs output linear color for the framebuffer to encode as sRGB
+	pub linear: bool,
+	pub standard: Program,
+	pub chunk: Program,
+	pub chunk_packed: Program,
+	pub liquid: Program,
+	pub lines: Program,
+	pub border: Program,
+	pub overlay: Program,
+	pub post: PostPrograms,
+}
+
+impl Shaders {
+	// Colors are authored in sRGB. With `linear` the lit programs decode them and light in linear
+	// space, and the unlit ones write them through untouched. Post effects see whatever the scene
+	// was drawn in, and the last of them hands it to the framebuffer the same way.
+	pub fn compile(display: &Display, linear: bool) -> Result<Shaders, SpaceError> {
+		use glium::program::ProgramCreationInput;
+		let program = |name, vertex, fragment, lit: bool| {
+			let input = ProgramCreationInput::SourceCode {
+				vertex_shader: vertex,
+				tessellation_control_shader: None,
+				tessellation_evaluation_shader: None,
+				geometry_shader: None,
+				fragment_shader: fragment,
+				transform_feedback_varyings: None,
+				// Glium only has the framebuffer encode to sRGB for programs that don't already
+				outputs_srgb: !(lit && linear),
+				uses_point_size: false,
+			};
+			Program::new(display, input).map_err(|err| SpaceError::Shader(name, err))
+		};
+		Ok(Shaders {
+			linear:       linear,
+			standard:     program("standard", include_str!("standard.vert"), include_str!("standard.frag"), true)?,
+			chunk:        program("chunk", include_str!("chunk.vert"), include_str!("standard.frag"), true)?,
+			chunk_packed: program("packed chunk", include_str!("chunk_packed.vert"), include_str!("standard.frag"), true)?,
+			liquid:       program("liquid", include_str!("liquid.vert"), include_str!("liquid.frag"), true)?,
+			lines:        program("lines", include_str!("full_bright.vert"), include_str!("full_bright.frag"), false)?,
+			border:       program("border", include_str!("border.vert"), include_str!("border.frag"), false)?,
+			overlay:      program("overlay", include_str!("overlay.vert"), include_str!("overlay.frag"), false)?,
+			post: PostPrograms {
+				copy:       program("copy", include_str!("post.vert"), include_str!("copy.frag"), true)?,
+				underwater: program("underwater", include_str!("post.vert"), include_str!("underwater.frag"), true)?,
+				tonemap:    program("tonemap", include_str!("post.vert"), include_str!("tonemap.frag"), true)?,
+				fxaa:       program("fxaa", include_str!("post.vert"), include_str!("fxaa.frag"), true)?,
+			},
+		})
+	}
 }
 
 impl Game {
-	pub fn new(start_state: Box<GameState>, display: Display, ev_loop: EventsLoop, shaders: Program)
-			-> Game {
+	// `msaa` is the samples per pixel the scene is drawn with, 0 for none
+	pub fn new(start_state: Box<GameState>, display: Display, ev_loop: EventsLoop, linear_lighting: bool, msaa: u32)
+			-> Result<Game, SpaceError> {
+		let shaders = Shaders::compile(&display, linear_lighting)?;
 		let disp = Rc::new(display);
-		Game {
+		Ok(Game {
 			state: start_state,
 			services: GameServices {
-				draw_service: DrawService::new(disp.clone(), shaders),
+				draw_service: DrawService::new(disp.clone(), shaders, msaa)?,
 				input_service: InputService::new(disp, ev_loop),
+				audio_service: AudioService::new(),
+				music_service: MusicService::new(Path::new(MUSIC_DIR)),
+				gui_service: GuiService::new(),
+				toast_service: ToastService::new(),
+				metrics_service: MetricsService::new(),
 			},
 			running: true,
-		}
+		})
 	}
 	pub fn is_running(&self) -> bool { self.running }
 	pub fn quit(&mut self) -> () { self.running = false }
@@ -55,8 +143,14 @@ impl Game {
 	}
 
 	pub fn update(&mut self, time_elapsed: &Duration) -> () {
+		// A frame runs from one update to the next, through the draw in between
+		profile::end_frame();
+		profile_scope!("update");
 		self.services.input_service.flush_event_queue();
+		self.services.gui_service.begin_frame(&self.services.input_service);
 		let result = self.state.update(&self.services, time_elapsed);
+		self.services.music_service.update(time_elapsed);
+		crash::record_metrics(&self.services.metrics_service);
 		match result {
 			UpdateResult::ChangeState(new_state) => { self.swap_state(new_state); },
 			UpdateResult::Quit => self.quit(),
@@ -64,16 +158,27 @@ impl Game {
 		};
 	}
 
-	pub fn draw(&mut self) {
-		self.state.draw(&mut self.services.draw_service);
-		self.services.draw_service.flush();
+	pub fn draw(&mut self) -> Result<(), SpaceError> {
+		profile_scope!("draw");
+		self.state.draw(&mut self.services.draw_service)?;
+		let mut toasts = Overlay::new();
+		let (width, _) = self.services.draw_service.dimensions();
+		self.services.toast_service.draw(&mut toasts, width as f32);
+		self.services.draw_service.draw_overlay(&toasts)?;
+		self.services.draw_service.draw_overlay(&self.services.gui_service.overlay())?;
+		self.services.draw_service.flush()
 	}
 }
 
+// While in text mode, characters and editing keys become text edits instead of events, and
+// other key presses are held back so typing doesn't also move the player. Escape and key
+// releases still come through.
 pub struct InputService {
 	display: Rc<Display>,
 	events_loop: EventsLoop,
 	events: Vec<Event>,
+	text_mode: Cell<bool>,
+	text_edits: Vec<TextEdit>,
 }
 
 use glium::glutin::dpi::{LogicalSize, LogicalPosition};
@@ -83,6 +188,8 @@ impl InputService {
 			display: display,
 			events_loop: events_loop,
 			events: Vec::new(),
+			text_mode: Cell::new(false),
+			text_edits: Vec::new(),
 		};
 		ret.flush_event_queue();
 		ret
@@ -91,6 +198,12 @@ impl InputService {
 	pub fn flush_event_queue(&mut self) {
 		let mut new_events = Vec::new();
 		self.events_loop.poll_events(|ev| new_events.push(ev));
+		self.text_edits.clear();
+		if self.text_mode.get() {
+			let mut edits = Vec::new();
+			new_events.retain(|ev| !take_text_event(ev, &mut edits));
+			self.text_edits = edits;
+		}
 		replace(&mut self.events, new_events);
 	}
 
@@ -98,20 +211,163 @@ impl InputService {
 		self.events.iter()
 	}
 
+	// Takes effect from the next frame's events
+	pub fn set_text_mode(&self, on: bool) {
+		self.text_mode.set(on);
+	}
+
+	pub fn text_mode(&self) -> bool {
+		self.text_mode.get()
+	}
+
+	// This frame's, in the order they were typed
+	pub fn text_edits(&self) -> Iter<TextEdit> {
+		self.text_edits.iter()
+	}
+
 	pub fn size(&self) -> Option<LogicalSize> {
 		self.display.gl_window().get_inner_size()
 	}
 
+	// Physical pixels per logical pixel
+	pub fn hidpi_factor(&self) -> f64 {
+		self.display.gl_window().get_hidpi_factor()
+	}
+
 	pub fn set_cursor_position(&self, pos: LogicalPosition) {
 		self.display.gl_window().set_cursor_position(pos);
 	}
 }
 
+// Whether a text mode event was turned into edits rather than left for the game. Characters
+// come through ReceivedCharacter, which input methods compose into, so only keys that edit
+// rather than type are read from their key codes.
+fn take_text_event(ev: &Event, edits: &mut Vec<TextEdit>) -> bool {
+	use glium::glutin::{WindowEvent, KeyboardInput, ElementState, VirtualKeyCode as K};
+	match *ev {
+		Event::WindowEvent { event: WindowEvent::ReceivedCharacter(c), .. } => {
+			// Enter and Backspace arrive as control characters too, but are read as keys
+			if !c.is_control() {
+				edits.push(TextEdit::Insert(c.to_string()));
+			}
+			true
+		},
+		Event::WindowEvent {
+			event: WindowEvent::KeyboardInput {
+				input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), modifiers, .. },
+				..
+			},
+			..
+		} => {
+			match key {
+				K::Escape => return false,
+				K::Return | K::NumpadEnter => edits.push(TextEdit::Submit),
+				K::Back => edits.push(TextEdit::Backspace),
+				K::Delete => edits.push(TextEdit::Delete),
+				K::Left => edits.push(TextEdit::Left),
+				K::Right => edits.push(TextEdit::Right),
+				K::Home => edits.push(TextEdit::Home),
+				K::End => edits.push(TextEdit::End),
+				K::V if modifiers.ctrl || modifiers.logo => edits.extend(clipboard_text().map(TextEdit::Insert)),
+				K::Paste => edits.extend(clipboard_text().map(TextEdit::Insert)),
+				_ => (),
+			}
+			true
+		},
+		_ => false,
+	}
+}
+
+// Pasting does nothing when there's no clipboard or nothing in it
+fn clipboard_text() -> Option<String> {
+	let mut context: ClipboardContext = ClipboardProvider::new().ok()?;
+	context.get_contents().ok()
+}
+
+pub const DEFAULT_FOV: f32 = ::std::f32::consts::PI / 3.0;
+
+// Nothing nearer or further than these from the camera is drawn
+const Z_NEAR: f32 = 0.001;
+const Z_FAR: f32 = 1024.0;
+
+// How a view maps the world onto the window
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Projection {
+	// `fov` is vertical, in radians
+	Perspective { fov: f32 },
+	// Straight on, without anything shrinking with distance, `height` blocks from the bottom of
+	// the view to the top. Views from high above should keep the camera within Z_FAR of the
+	// ground.
+	Orthographic { height: f32 },
+}
+
+impl Projection {
+	fn matrix(&self, (width, height): (u32, u32)) -> Matrix4<f32> {
+		let aspect = width as f32 / height.max(1) as f32;
+		match *self {
+			Projection::Perspective { fov } => Perspective3::new(aspect, fov, Z_NEAR, Z_FAR).to_homogeneous(),
+			Projection::Orthographic { height } => {
+				let (half_width, half_height) = (height * aspect / 2.0, height / 2.0);
+				Orthographic3::new(-half_width, half_width, -half_height, half_height, Z_NEAR, Z_FAR).to_homogeneous()
+			},
+		}
+	}
+}
+
+// Where the scene is being drawn from in the window, and how it's projected there
+struct ViewState {
+	// In pixels from the bottom left; None for the whole window
+	viewport: Option<Rect>,
+	projection: Projection,
+	matrix: Matrix4<f32>,
+}
+
+// What every batch of a draw_chunks call is drawn with
+struct BatchBuffers<'a> {
+	instances: &'a VertexBuffer<ChunkInstance>,
+	instance_count: usize,
+	commands: Option<&'a DrawCommandsIndicesBuffer>,
+}
+
 pub struct DrawService {
 	display: Rc<Display>,
 	frame: Frame,
 	program: Program,
-	perspective: Perspective3<f32>,
+	chunk_program: Program,
+	chunk_packed_program: Program,
+	liquid_program: Program,
+	lines_program: Program,
+	border_program: Program,
+	overlay_program: Program,
+	started: PreciseTime,
+	view: ViewState,
+	mesh_pool: MeshPool,
+	chunk_arena: ChunkArena,
+	instance_buffer: Option<VertexBuffer<ChunkInstance>>,
+	// Reused by every batch, with commands past a batch's own left drawing nothing
+	command_buffer: Option<DrawCommandsIndicesBuffer>,
+	// Whether a batch can be drawn by one call; without it, each of its commands is drawn alone
+	multidraw: bool,
+	chunk_vertex_format: VertexFormat,
+	palette: Option<Texture2d>,
+	palette_colors: Vec<[f32; 3]>,
+	// For every block texture sampler; 1 for none
+	anisotropy: u16,
+	entity_meshes: HashMap<[u32; 9], Mesh>,
+	environment: Environment,
+	// The environment as of the last set_environment, for every lit program
+	environment_buffer: UniformBuffer<EnvironmentBlock>,
+	linear: bool,
+	// Everything but the overlay is drawn into its scene target
+	post: PostProcessor,
+	// The one this frame is in
+	pass: RenderPass,
+	timers: PassTimers,
+	// This frame's so far, and the last one's in full
+	stats: DrawStats,
+	last_stats: DrawStats,
+	// Where to save this frame once it's presented
+	capture: Option<PathBuf>,
 }
 
 impl Drop for DrawService {
@@ -121,26 +377,51 @@ impl Drop for DrawService {
 }
 
 impl DrawService {
-	fn build_perspective(frame: &Frame) -> Perspective3<f32> {
-		let (width, height) = frame.get_dimensions();
-
-		let fov: f32 = ::std::f32::consts::PI / 3.0;
-		let zfar = 1024.0;
-		let znear = 0.001;
-
-		Perspective3::new(width as f32 / height as f32, fov, znear, zfar)
-	}
-
-	pub fn new(display: Rc<Display>, program: Program) -> DrawService {
-		let mut frame = display.draw();
-		frame.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
-		let perspective = DrawService::build_perspective(&frame);
-		DrawService {
+	pub fn new(display: Rc<Display>, shaders: Shaders, msaa: u32) -> Result<DrawService, SpaceError> {
+		let frame = display.draw();
+		let projection = Projection::Perspective { fov: DEFAULT_FOV };
+		let matrix = projection.matrix(frame.get_dimensions());
+		let post = PostProcessor::new(&display, shaders.post, frame.get_dimensions(), msaa)?;
+		let environment = Environment::default();
+		let environment_buffer = UniformBuffer::dynamic(&*display, EnvironmentBlock::new(&environment, &Matrix4::identity()))?;
+		post.scene_target(&display)?.clear_color_srgb_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+		let multidraw = {
+			let context = display.get_context();
+			let extensions = context.get_extensions();
+			*context.get_version() >= Version(Api::Gl, 4, 3) || (extensions.gl_arb_multi_draw_indirect && extensions.gl_arb_base_instance)
+		};
+		Ok(DrawService {
 			display: display,
-			program: program,
+			program: shaders.standard,
+			chunk_program: shaders.chunk,
+			chunk_packed_program: shaders.chunk_packed,
+			liquid_program: shaders.liquid,
+			lines_program: shaders.lines,
+			border_program: shaders.border,
+			overlay_program: shaders.overlay,
+			started: PreciseTime::now(),
 			frame: frame,
-			perspective: perspective,
-		}
+			view: ViewState { viewport: None, projection: projection, matrix: matrix },
+			mesh_pool: MeshPool::new(),
+			chunk_arena: ChunkArena::new(),
+			instance_buffer: None,
+			command_buffer: None,
+			multidraw: multidraw,
+			chunk_vertex_format: VertexFormat::Packed,
+			palette: None,
+			palette_colors: Vec::new(),
+			anisotropy: 1,
+			entity_meshes: HashMap::new(),
+			environment: environment,
+			environment_buffer: environment_buffer,
+			linear: shaders.linear,
+			post: post,
+			pass: RENDER_PASSES[0],
+			timers: PassTimers::new(),
+			stats: DrawStats::default(),
+			last_stats: DrawStats::default(),
+			capture: None,
+		})
 	}
 
 	// TODO: Switch to trait object when glium updates.
@@ -148,40 +429,505 @@ impl DrawService {
 		&self.display
 	}
 
+	// For the current view, which spans the window unless it's been given a viewport
 	pub fn update_perspective(&mut self) {
-		self.perspective = DrawService::build_perspective(&self.frame);
+		let dimensions = match self.view.viewport {
+			Some(rect) => (rect.width, rect.height),
+			None => self.frame.get_dimensions(),
+		};
+		self.view.matrix = self.view.projection.matrix(dimensions);
+	}
+
+	// Of the window, in pixels
+	pub fn dimensions(&self) -> (u32, u32) {
+		self.frame.get_dimensions()
+	}
+
+	pub fn frustum(&self, view: &Matrix4<f32>) -> Frustum {
+		Frustum::from_matrix(&(self.view.matrix * view))
+	}
+
+	// Vertical field of view, in radians, switching the view back to perspective if it isn't
+	pub fn set_fov(&mut self, fov: f32) {
+		self.set_projection(Projection::Perspective { fov: fov });
+	}
+
+	pub fn set_projection(&mut self, projection: Projection) {
+		if projection != self.view.projection {
+			self.view.projection = projection;
+			self.update_perspective();
+		}
 	}
 
-	pub fn flush(&mut self) {
+	pub fn flush(&mut self) -> Result<(), SpaceError> {
+		if self.pass < RenderPass::Post {
+			self.enter(RenderPass::Post)?;
+		}
 		// TODO: Update framerate
-		self.frame.set_finish();
+		{
+			profile_scope!("swap");
+			self.frame.set_finish()?;
+		}
+		if let Some(path) = self.capture.take() {
+			if let Err(err) = self.save_front_buffer(&path) {
+				println!("Failed to capture {}: {}", path.display(), err);
+			}
+		}
+		self.timers.end_frame();
+		self.last_stats = replace(&mut self.stats, DrawStats::default());
 
 		self.frame = self.display.draw();
-		self.frame.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+		self.post.resize(&*self.display, self.frame.get_dimensions())?;
+		let sky = self.sky();
+		self.post.scene_target(&*self.display)?.clear_color_srgb_and_depth(sky, 1.0);
+		self.pass = RENDER_PASSES[0];
+		self.view.viewport = None;
+		self.update_perspective();
+		Ok(())
+	}
+
+	// The sky color is sRGB, and the scene target isn't, so it's decoded by hand to match a scene
+	// lit in linear space
+	fn sky(&self) -> (f32, f32, f32, f32) {
+		let fog = self.environment.fog_color;
+		let sky = if self.linear { [srgb_to_linear(fog[0]), srgb_to_linear(fog[1]), srgb_to_linear(fog[2])] } else { fog };
+		(sky[0], sky[1], sky[2], 1.0)
+	}
+
+	// Starts the scene over from another camera, drawn into `viewport` or the whole window, for
+	// split screens and pictures in pictures. Each view goes through the scene's passes in order
+	// from the first again; the frame starts out in a view of the whole window. The projection
+	// carries over, and the environment should be set again for the new camera. Views go through
+	// post processing together.
+	pub fn begin_view(&mut self, viewport: Option<Rect>) -> Result<(), SpaceError> {
+		assert!(self.pass < RenderPass::Post, "Began a view after the scene was finished");
+		if self.pass > RENDER_PASSES[0] {
+			let sky = self.sky();
+			self.post.scene_target(&*self.display)?.clear(viewport.as_ref(), Some(sky), true, Some(1.0), None);
+			self.pass = RENDER_PASSES[0];
+		}
+		self.view.viewport = viewport;
+		self.update_perspective();
+		Ok(())
+	}
+
+	// Saves this frame to `path` as it appears on screen, overlay and all, once it's finished
+	pub fn capture(&mut self, path: PathBuf) {
+		self.capture = Some(path);
+	}
+
+	fn save_front_buffer(&self, path: &Path) -> Result<(), String> {
+		let raw: RawImage2d<u8> = self.display.read_front_buffer().map_err(|err| format!("{:?}", err))?;
+		let image = RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned()).ok_or("Frame size mismatch".to_string())?;
+		// GL rows run bottom to top
+		imageops::flip_vertical(&image).save(path).map_err(|err| err.to_string())
+	}
+
+	pub fn pass(&self) -> RenderPass {
+		self.pass
+	}
+
+	// Moves the frame on to `pass`, sending the scene through the post effects on the way past
+	// them. Going back to an earlier pass is a bug in the order things are drawn.
+	fn enter(&mut self, pass: RenderPass) -> Result<(), SpaceError> {
+		assert!(pass >= self.pass, "Drew in the {} pass after the {} pass", pass.name(), self.pass.name());
+		if pass == self.pass {
+			return Ok(());
+		}
+		if self.pass < RenderPass::Post && pass >= RenderPass::Post {
+			profile_scope!("post");
+			self.timers.begin(&*self.display, RenderPass::Post);
+			self.post.time = self.started.to(PreciseTime::now()).num_milliseconds() as f32 / 1000.0;
+			let passes = self.post.apply(&*self.display, &mut self.frame, self.timers.query(RenderPass::Post))?;
+			for _ in 0..passes {
+				self.stats.record(4, 2);
+			}
+		}
+		if pass != RenderPass::Post {
+			self.timers.begin(&*self.display, pass);
+		}
+		self.pass = pass;
+		Ok(())
+	}
+
+	// Times each pass on the GPU, for finding out whether a slow frame is waiting on it
+	pub fn set_gpu_timing(&mut self, enabled: bool) {
+		if enabled != self.timers.enabled() {
+			self.timers.set_enabled(enabled);
+		}
+	}
+
+	pub fn last_frame_stats(&self) -> DrawStats {
+		self.last_stats
+	}
+
+	// How long each pass took on the GPU a few frames ago, while timing is on
+	pub fn gpu_pass_times(&self) -> &[(RenderPass, Duration)] {
+		self.timers.last_frame()
+	}
+
+	// The color of the liquid the camera is in, as authored in sRGB, or None out of any
+	pub fn set_underwater(&mut self, tint: Option<[f32; 3]>) {
+		let linear = self.linear;
+		self.post.underwater = tint.map(|tint| if linear { [srgb_to_linear(tint[0]), srgb_to_linear(tint[1]), srgb_to_linear(tint[2])] } else { tint });
+	}
+
+	pub fn post_effects(&self) -> PostEffects {
+		self.post.effects
+	}
+
+	// Takes effect from the next time the scene is finished, which may be this frame
+	pub fn set_post_effects(&mut self, effects: PostEffects) {
+		self.post.effects = effects;
+	}
+
+	// `view` is the camera the frame is drawn from. The background changes to the new fog color
+	// from the next frame; everything else from the next draw.
+	pub fn set_environment(&mut self, environment: &Environment, view: &Matrix4<f32>) {
+		self.environment = *environment;
+		self.environment_buffer.write(&EnvironmentBlock::new(environment, view));
+	}
+
+	pub fn environment(&self) -> &Environment {
+		&self.environment
+	}
+
+	pub fn chunk_vertex_format(&self) -> VertexFormat {
+		self.chunk_vertex_format
+	}
+
+	// Only affects meshes built after the change; existing meshes keep drawing in their own format
+	pub fn set_chunk_vertex_format(&mut self, format: VertexFormat) {
+		self.chunk_vertex_format = format;
+	}
+
+	// Packed vertices index their color from this palette, one entry per block type
+	pub fn set_palette(&mut self, block_render_data: &[BlockRenderData]) -> Result<(), SpaceError> {
+		let colors = block::palette(block_render_data);
+		if self.palette.is_some() && colors == self.palette_colors {
+			return Ok(());
+		}
+
+		let texels: Vec<u8> = colors.iter()
+			.flat_map(|color| color.iter().map(|c| (c * 255.0).round() as u8).collect::<Vec<_>>())
+			.collect();
+		let width = block_render_data.len() as u32;
+		let image = RawImage2d::from_raw_rgb(texels, (width, colors.len() as u32 / width));
+		self.palette = Some(Texture2d::new(&*self.display, image)?);
+		self.palette_colors = colors;
+		Ok(())
+	}
+
+	// Block textures are sampled with up to this many samples along the direction they're seen
+	// at. The palette is the only one so far, and, fetched by index, isn't affected.
+	pub fn set_anisotropy(&mut self, anisotropy: u16) {
+		self.anisotropy = anisotropy.max(1);
+	}
+
+	pub fn upload_mesh<V: MeshVertex>(&mut self, vertices: &[V], indices: &[u16]) -> Result<Mesh, MeshCreationError> {
+		profile_scope!("upload");
+		self.mesh_pool.upload(&*self.display, vertices, indices)
+	}
+
+	// Buffers pooled for reuse, and their size in bytes
+	pub fn mesh_pool_usage(&self) -> (usize, usize) {
+		self.mesh_pool.usage()
+	}
+
+	pub fn recycle_mesh(&mut self, mesh: Mesh) {
+		self.mesh_pool.recycle(mesh);
 	}
 
-	pub fn draw_buffer<'a, 'b, I, V>(&mut self, model_view: &Matrix4<f32>, vertices: V, indices: I)
+	// Opaque chunk meshes go into the chunk arena's shared pages, and give their space back when
+	// they're dropped
+	pub fn upload_chunk_mesh<V: ArenaVertex>(&mut self, vertices: &[V], indices: &[u16]) -> Result<ChunkMesh, MeshCreationError> {
+		profile_scope!("upload");
+		self.chunk_arena.upload(&*self.display, vertices, indices)
+	}
+
+	// Arena pages, and their size in bytes
+	pub fn chunk_arena_usage(&self) -> (usize, usize) {
+		self.chunk_arena.usage()
+	}
+
+	// Draws chunk meshes with their offsets supplied as per-instance data, so no model matrices
+	// are built and the uniforms are set up once for all of them. The meshes sharing a page of the
+	// chunk arena are drawn by one multi-draw, so there's a call per page in use rather than per
+	// chunk.
+	pub fn draw_chunks(&mut self, view: &Matrix4<f32>, chunks: &[(&ChunkMesh, [f32; 3])]) -> Result<(), SpaceError> {
+		if chunks.is_empty() {
+			return Ok(());
+		}
+
+		self.enter(RenderPass::Opaque)?;
+		self.write_instances(chunks)?;
+		let batches = chunk_batch::plan_batches(chunks);
+		if self.multidraw {
+			let most = batches.iter().map(|batch| batch.commands.len()).max().unwrap_or(0);
+			let needs_growth = self.command_buffer.as_ref().map(|b| b.len() < most).unwrap_or(true);
+			if needs_growth {
+				self.command_buffer = Some(DrawCommandsIndicesBuffer::empty_dynamic(&*self.display, most.next_power_of_two())?);
+			}
+		}
+		let shared = BatchBuffers {
+			instances: self.instance_buffer.as_ref().unwrap(),
+			instance_count: chunks.len(),
+			commands: if self.multidraw { self.command_buffer.as_ref() } else { None },
+		};
+
+		let uniforms = uniform! {
+			environment: &self.environment_buffer,
+			linear: self.linear,
+			view: view.as_ref().clone(),
+			perspective: self.view.matrix.as_ref().clone(),
+		};
+		let packed = batches.iter().any(|batch| batch.format == VertexFormat::Packed);
+		let palette = if packed {
+			Some(Sampler::new(self.palette.as_ref().expect("Packed chunk meshes require a palette")).anisotropy(self.anisotropy))
+		} else {
+			None
+		};
+		let packed_uniforms = palette.map(|palette| uniform! {
+			environment: &self.environment_buffer,
+			linear: self.linear,
+			view: view.as_ref().clone(),
+			perspective: self.view.matrix.as_ref().clone(),
+			palette: palette,
+		});
+		let mut params = RenderPass::Opaque.draw_parameters();
+		params.time_elapsed_query = self.timers.query(RenderPass::Opaque);
+		params.viewport = self.view.viewport;
+		let mut target = self.post.scene_target(&*self.display)?;
+
+		for batch in &batches {
+			let index_count: usize = batch.commands.iter().map(|command| command.index_count).sum();
+			match batch.format {
+				VertexFormat::Standard => {
+					let page = self.chunk_arena.standard_page(batch.page);
+					DrawService::draw_batch(&mut target, batch, page, &shared, &self.chunk_program, &uniforms, &params)?;
+				},
+				VertexFormat::Packed => {
+					let page = self.chunk_arena.packed_page(batch.page);
+					DrawService::draw_batch(&mut target, batch, page, &shared, &self.chunk_packed_program, packed_uniforms.as_ref().unwrap(), &params)?;
+				},
+			}
+			if self.multidraw {
+				self.stats.record(index_count, index_count / 3);
+			} else {
+				for command in &batch.commands {
+					self.stats.record(command.index_count, command.index_count / 3);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	// With commands to write it into, the batch is drawn by one call; without, each of its meshes
+	// is drawn by a call of its own from slices of the page
+	fn draw_batch<S, V, U>(target: &mut S, batch: &Batch, page: &Page<V>, shared: &BatchBuffers, program: &Program, uniforms: &U,
+			params: &DrawParameters) -> Result<(), SpaceError> where S: Surface, V: Copy, U: Uniforms {
+		let (vertices, indices) = (&page.vertices, &page.indices);
+		match shared.commands {
+			Some(buffer) => {
+				let mut written: Vec<DrawCommandIndices> = batch.commands.iter().map(|command| DrawCommandIndices {
+					count: command.index_count as u32,
+					instance_count: 1,
+					first_index: command.first_index as u32,
+					base_vertex: command.base_vertex as u32,
+					base_instance: command.instance as u32,
+				}).collect();
+				let unused = DrawCommandIndices { count: 0, instance_count: 0, first_index: 0, base_vertex: 0, base_instance: 0 };
+				written.resize(buffer.len(), unused);
+				buffer.write(&written);
+				let instances = shared.instances.slice(0..shared.instance_count).unwrap();
+				target.draw((vertices, instances.per_instance().unwrap()), buffer.with_index_buffer(indices), program, uniforms, params)?;
+			},
+			None => for command in &batch.commands {
+				let mesh_vertices = vertices.slice(command.base_vertex..vertices.len()).unwrap();
+				let mesh_indices = indices.slice(command.first_index..command.first_index + command.index_count).unwrap();
+				let instance = shared.instances.slice(command.instance..command.instance + 1).unwrap();
+				target.draw((mesh_vertices, instance.per_instance().unwrap()), mesh_indices, program, uniforms, params)?;
+			},
+		}
+		Ok(())
+	}
+
+	// Liquid meshes are blended over the opaque scene without writing depth, so `chunks` should
+	// come after every opaque draw of the frame and be sorted back to front.
+	pub fn draw_liquid_chunks(&mut self, view: &Matrix4<f32>, chunks: &[(&Mesh, [f32; 3])]) -> Result<(), SpaceError> {
+		if chunks.is_empty() {
+			return Ok(());
+		}
+
+		self.enter(RenderPass::Translucent)?;
+		self.write_instances(chunks)?;
+		let instance_buffer = self.instance_buffer.as_ref().unwrap();
+
+		let time = self.started.to(PreciseTime::now()).num_milliseconds() as f32 / 1000.0;
+		let uniforms = uniform! {
+			environment: &self.environment_buffer,
+			linear: self.linear,
+			view: view.as_ref().clone(),
+			perspective: self.view.matrix.as_ref().clone(),
+			time: time,
+		};
+		let mut params = RenderPass::Translucent.draw_parameters();
+		params.time_elapsed_query = self.timers.query(RenderPass::Translucent);
+		params.viewport = self.view.viewport;
+		let mut target = self.post.scene_target(&*self.display)?;
+
+		for (i, &(mesh, _)) in chunks.iter().enumerate() {
+			let vertices = match mesh.vertices {
+				MeshVertices::Liquid(ref vertices) => vertices,
+				_ => panic!("draw_liquid_chunks only draws liquid meshes"),
+			};
+
+			let instance = instance_buffer.slice(i..i + 1).unwrap();
+			let indices = mesh.indices.slice(0..mesh.index_count).unwrap();
+			let vertices = (vertices.slice(0..mesh.vertex_count).unwrap(), instance.per_instance().unwrap());
+			target.draw(vertices, indices, &self.liquid_program, &uniforms, &params)?;
+			self.stats.record(mesh.index_count, mesh.index_count / 3);
+		}
+		Ok(())
+	}
+
+	fn write_instances<M>(&mut self, chunks: &[(&M, [f32; 3])]) -> Result<(), SpaceError> {
+		let needs_growth = self.instance_buffer.as_ref().map(|b| b.len() < chunks.len()).unwrap_or(true);
+		if needs_growth {
+			self.instance_buffer = Some(VertexBuffer::empty_dynamic(&*self.display, chunks.len().next_power_of_two())?);
+		}
+		let instances: Vec<ChunkInstance> = chunks.iter().map(|&(_, offset)| ChunkInstance { chunk_offset: offset }).collect();
+		self.instance_buffer.as_ref().unwrap().slice(0..instances.len()).unwrap().write(&instances);
+		Ok(())
+	}
+
+	// Lit like any block, in whichever pass it belongs to
+	pub fn draw_buffer<'a, 'b, I, V>(&mut self, pass: RenderPass, model_view: &Matrix4<f32>, vertices: V, indices: I) -> Result<(), SpaceError>
 			where I: Into<IndicesSource<'a>>, V: MultiVerticesSource<'b> {
+		use glium::index::PrimitiveType;
+		self.enter(pass)?;
+		let indices = indices.into();
+		let (count, triangles) = match indices {
+			IndicesSource::IndexBuffer { ref buffer, primitives: PrimitiveType::TrianglesList, .. } => (buffer.get_elements_count(), buffer.get_elements_count() / 3),
+			IndicesSource::IndexBuffer { ref buffer, .. } => (buffer.get_elements_count(), 0),
+			// Without indices, the count is the vertices', which aren't looked into
+			_ => (0, 0),
+		};
 		let uniforms = uniform! {
-			u_light: [0.0, 0.0, 1.0f32],
+			environment: &self.environment_buffer,
+			linear: self.linear,
 			model_view: model_view.as_ref().clone(),
-			perspective: self.perspective.as_matrix().as_ref().clone(),
+			perspective: self.view.matrix.as_ref().clone(),
 		};
 
-		use glium::{DrawParameters, Depth};
-		use glium::draw_parameters::{DepthTest, BackfaceCullingMode};
-		let params = DrawParameters {
-			depth: Depth {
-				test: DepthTest::IfLess,
-				write: true,
-				.. Default::default()
-			},
-			backface_culling: BackfaceCullingMode::CullClockwise,
-//			polygon_mode: PolygonMode::Line,
-			.. Default::default()
+		let mut params = pass.draw_parameters();
+		params.time_elapsed_query = self.timers.query(pass);
+		if pass.target() == PassTarget::Scene {
+			params.viewport = self.view.viewport;
+		}
+
+		match pass.target() {
+			PassTarget::Scene => self.post.scene_target(&*self.display)?.draw(vertices, indices, &self.program, &uniforms, &params)?,
+			PassTarget::Frame => self.frame.draw(vertices, indices, &self.program, &uniforms, &params)?,
+			PassTarget::ShadowMap => panic!("There's no shadow map to draw into yet"),
+		}
+		self.stats.record(count, triangles);
+		Ok(())
+	}
+
+	// Entities draw through their own small meshes with a model transform each, apart from the
+	// chunk path, so moving them never touches chunk meshes. Each distinct model is uploaded once.
+	pub fn draw_entity(&mut self, view: &Matrix4<f32>, model: &BoxModel, transform: &Matrix4<f32>) -> Result<(), SpaceError> {
+		self.enter(RenderPass::Entities)?;
+		let key = model.key();
+		if !self.entity_meshes.contains_key(&key) {
+			let (vertices, indices) = model.mesh_data();
+			let mesh = self.upload_mesh(&vertices, &indices)?;
+			self.entity_meshes.insert(key, mesh);
+		}
+
+		let mesh = &self.entity_meshes[&key];
+		let vertices = match mesh.vertices {
+			MeshVertices::Standard(ref vertices) => vertices.slice(0..mesh.vertex_count).unwrap(),
+			_ => unreachable!(),
+		};
+		let indices = mesh.indices.slice(0..mesh.index_count).unwrap();
+
+		let uniforms = uniform! {
+			environment: &self.environment_buffer,
+			linear: self.linear,
+			model_view: (view * transform).as_ref().clone(),
+			perspective: self.view.matrix.as_ref().clone(),
+		};
+		let mut params = RenderPass::Entities.draw_parameters();
+		params.time_elapsed_query = self.timers.query(RenderPass::Entities);
+		params.viewport = self.view.viewport;
+		self.post.scene_target(&*self.display)?.draw(vertices, indices, &self.program, &uniforms, &params)?;
+		self.stats.record(mesh.index_count, mesh.index_count / 3);
+		Ok(())
+	}
+
+	pub fn draw_lines(&mut self, view: &Matrix4<f32>, lines: &DebugLines) -> Result<(), SpaceError> {
+		use glium::index::{NoIndices, PrimitiveType};
+		if lines.is_empty() {
+			return Ok(());
+		}
+		self.enter(RenderPass::DebugLines)?;
+
+		let vertices = VertexBuffer::new(&*self.display, lines.vertices())?;
+		let uniforms = uniform! {
+			model_view: view.as_ref().clone(),
+			perspective: self.view.matrix.as_ref().clone(),
+		};
+		let mut params = RenderPass::DebugLines.draw_parameters();
+		params.time_elapsed_query = self.timers.query(RenderPass::DebugLines);
+		params.viewport = self.view.viewport;
+		self.post.scene_target(&*self.display)?.draw(&vertices, NoIndices(PrimitiveType::LinesList), &self.lines_program, &uniforms, &params)?;
+		self.stats.record(vertices.len(), 0);
+		Ok(())
+	}
+
+	// Unlit striped triangles blended over the scene like liquids, fading out `fade_distance`
+	// blocks from the camera, as for the world border and the selection
+	pub fn draw_striped(&mut self, view: &Matrix4<f32>, triangles: &[Vertex], fade_distance: f32) -> Result<(), SpaceError> {
+		use glium::index::{NoIndices, PrimitiveType};
+		if triangles.is_empty() {
+			return Ok(());
+		}
+		self.enter(RenderPass::Translucent)?;
+
+		let vertices = VertexBuffer::new(&*self.display, triangles)?;
+		let uniforms = uniform! {
+			model_view: view.as_ref().clone(),
+			perspective: self.view.matrix.as_ref().clone(),
+			fade_distance: fade_distance,
 		};
+		let mut params = RenderPass::Translucent.draw_parameters();
+		params.time_elapsed_query = self.timers.query(RenderPass::Translucent);
+		params.viewport = self.view.viewport;
+		self.post.scene_target(&*self.display)?.draw(&vertices, NoIndices(PrimitiveType::TrianglesList), &self.border_program, &uniforms, &params)?;
+		self.stats.record(vertices.len(), vertices.len() / 3);
+		Ok(())
+	}
 
-		self.frame.draw(vertices, indices, &self.program, &uniforms, &params).unwrap();
+	// Drawn last, over the scene once it's been post processed, with a pixel for each unit
+	pub fn draw_overlay(&mut self, overlay: &Overlay) -> Result<(), SpaceError> {
+		use glium::index::{NoIndices, PrimitiveType};
+		if overlay.is_empty() {
+			return Ok(());
+		}
+		self.enter(RenderPass::Hud)?;
+
+		let (width, height) = self.frame.get_dimensions();
+		let vertices = VertexBuffer::new(&*self.display, overlay.vertices())?;
+		let uniforms = uniform! {
+			screen_size: [width as f32, height as f32],
+		};
+		let mut params = RenderPass::Hud.draw_parameters();
+		params.time_elapsed_query = self.timers.query(RenderPass::Hud);
+		self.frame.draw(&vertices, NoIndices(PrimitiveType::TrianglesList), &self.overlay_program, &uniforms, &params)?;
+		self.stats.record(vertices.len(), vertices.len() / 3);
+		Ok(())
 	}
 }
 
@@ -196,48 +942,59 @@ pub trait GameState {
 	fn leaving(&mut self) -> ();
 
 	fn update(&mut self, services: &GameServices, time_elapsed: &Duration) -> UpdateResult;
-	fn draw(&self, draw_service: &mut DrawService) -> ();
+	fn draw(&self, draw_service: &mut DrawService) -> Result<(), SpaceError>;
 }
 
 pub struct StatePlaying {
-	world: World,
-	block_render_types: Vec<BlockRenderData>,
-	camera: SimpleCamera<f32>,
-	keys_down: HashSet<VirtualKeyCode>,
-	region: CuboidRegion,
+	client: Client,
+	simulation: Simulation,
 }
 
-const MOUSE_SENSITIVITY:  f32 = 0.00000001;
-const MOTION_SENSITIVITY: f32 = 0.00001;
-const MOTION_SENSITIVITY_FAST: f32 = 0.001;
+const SAVE_DIR: &'static str = "saves/world";
 
-use block::World;
+use std::sync::mpsc::channel;
+use block::{World, block_properties, block_render_types};
+use worldgen::WorldPreset;
+use client::Client;
+use simulation::Simulation;
 impl StatePlaying {
-	pub fn new() -> StatePlaying {
-		let world = World::new();
+	pub fn new() -> Result<StatePlaying, SpaceError> {
+		let (to_server, server_inbox) = channel();
+		let (to_client, client_inbox) = channel();
+		let simulation = Simulation::spawn(PathBuf::from(SAVE_DIR), 12, server_inbox, to_client)?;
+
+		// The client's copy starts out empty, and fills in as the server sends it chunks. The region
+		// holds on to the copy's chunks, so whatever arrives is what gets drawn.
+		let world = World::from_preset(12, &WorldPreset::Void);
 		let region = CuboidRegion::new(&world, -5, -5, -5, 5, 5, 5);
-		let mut ret = StatePlaying {
-			world: World::new(),
-			block_render_types: Vec::with_capacity(2),
-			camera: SimpleCamera {
-				position:   Point3::new( 0.0,   0.0,  50.0),
-				direction: Vector3::new(-0.5,  -0.5,  -4.0).normalize(),
-				up:        Vector3::new( 0.0,   0.0,   1.0),
-			},
-			keys_down: HashSet::new(),
-			region: region,
-		};
-		ret.block_render_types.push(BlockRenderData {
-			obscures: 0,
-			color: [0.0f32; 3],
-			should_render: false,
-		});
-		ret.block_render_types.push(BlockRenderData {
-			obscures: 0b111111,
-			color: [0.3, 0.4, 0.2],
-			should_render: true,
-		});
-		ret
+		Ok(StatePlaying {
+			client: Client::new(world, region, Rc::new(block_properties()), Rc::new(block_render_types()?), client_inbox, to_server),
+			simulation: simulation,
+		})
+	}
+
+	fn record_metrics(&self, services: &GameServices) {
+		let metrics = &services.metrics_service;
+		// The server's, as of its last tick
+		let world = self.client.world_stats();
+		metrics.set("Indexed chunks", Metric::Count(world.indexed_chunks));
+		metrics.set("Loaded chunks", Metric::Count(world.live_chunks));
+		metrics.set("Chunk memory", Metric::Bytes(world.live_chunks * size_of::<Chunk>()));
+		metrics.set("Cached chunks", Metric::Count(world.cached_chunks));
+		metrics.set("Cache memory", Metric::Bytes(world.cache_bytes));
+		let (meshes, mesh_bytes) = self.client.region().mesh_usage();
+		metrics.set("Meshes", Metric::Count(meshes));
+		metrics.set("Mesh memory", Metric::Bytes(mesh_bytes));
+		let (pooled, pool_bytes) = services.draw_service.mesh_pool_usage();
+		metrics.set("Pooled buffers", Metric::Count(pooled));
+		metrics.set("Pool memory", Metric::Bytes(pool_bytes));
+		let (pages, page_bytes) = services.draw_service.chunk_arena_usage();
+		metrics.set("Chunk pages", Metric::Count(pages));
+		metrics.set("Page memory", Metric::Bytes(page_bytes));
+		let stats = services.draw_service.last_frame_stats();
+		metrics.set("Draw calls", Metric::Count(stats.draw_calls));
+		metrics.set("Vertices drawn", Metric::Count(stats.vertices));
+		metrics.set("Triangles drawn", Metric::Count(stats.triangles));
 	}
 }
 
@@ -246,115 +1003,25 @@ impl GameState for StatePlaying {
 	fn leaving(&mut self) -> () {}
 
 	fn update(&mut self, services: &GameServices, time_elapsed: &Duration) -> UpdateResult {
-		for ev in services.input_service.events() {
-			use glium::glutin::ElementState;
-			use glium::glutin::dpi::LogicalPosition;
-			match ev {
-				&Event::WindowEvent {
-					event: WindowEvent::CloseRequested,
-					..
-				} => return UpdateResult::Quit,   // the window has been closed by the user
-
-				&Event::WindowEvent {
-					event: WindowEvent::KeyboardInput {
-						input: input,
-						..
-					},
-					..
-				} => {
-					let ::glium::glutin::KeyboardInput {
-						virtual_keycode: opt_key,
-						state: state,
-						..
-					} = input;
-					match opt_key {
-						None => (),
-						Some(key) => match key {
-							VirtualKeyCode::Escape => return UpdateResult::Quit,
-							code => match state {
-								ElementState::Pressed => { self.keys_down.insert(code); },
-								ElementState::Released => { self.keys_down.remove(&code); },
-							},
-						}
-					}
-				},
-
-				&Event::WindowEvent {
-					event: WindowEvent::CursorMoved{
-						position: LogicalPosition{x: raw_x, y: raw_y},
-						..
-					},
-					..
-				} => {
-					let size = services.input_service.size().unwrap();
-					let mid: LogicalPosition = (size.width / 2.0, size.height / 2.0).into();
-					services.input_service.set_cursor_position(mid);
-
-					let (delta_x, delta_y) = (raw_x - mid.x, raw_y - mid.y);
-
-					let dir = &mut self.camera.direction;
-					let up  = &self.camera.up;
-
-					*dir = Rotation3::new(up               * -delta_x as f32 * MOUSE_SENSITIVITY * time_elapsed.num_microseconds().unwrap() as f32)
-					     * Rotation3::new(up.cross(dir) * -delta_y as f32 * MOUSE_SENSITIVITY * time_elapsed.num_microseconds().unwrap() as f32)
-					     * (*dir);
-
-					*dir = dir.normalize();
-
-					dir[2] = f32::max(-0.9, f32::min(0.9, dir[2]));
-				},
-
-				_ => ()
-			}
-		}
-
-		let dolly_speed = if self.keys_down.contains(&VirtualKeyCode::LShift) || self.keys_down.contains(&VirtualKeyCode::RShift) {
-			MOTION_SENSITIVITY_FAST
-		} else {
-			MOTION_SENSITIVITY
-		};
-
-		match (self.keys_down.contains(&VirtualKeyCode::A), self.keys_down.contains(&VirtualKeyCode::D)) {
-			(true, true) => (),
-			(false, false) => (),
-
-			(true, false) => {
-				self.camera.position -= self.camera.direction.cross(&self.camera.up) * time_elapsed.num_microseconds().unwrap() as f32 * dolly_speed;
-			},
-			(false, true) => {
-				self.camera.position -= -1.0 * self.camera.direction.cross(&self.camera.up) * time_elapsed.num_microseconds().unwrap() as f32 * dolly_speed;
-			},
-		}
-
-		match (self.keys_down.contains(&VirtualKeyCode::W), self.keys_down.contains(&VirtualKeyCode::S)) {
-			(true, true) => (),
-			(false, false) => (),
-
-			(true, false) => {
-				self.camera.position -= -1.0 * self.camera.direction * time_elapsed.num_microseconds().unwrap() as f32 * dolly_speed;
-			},
-			(false, true) => {
-				self.camera.position -= self.camera.direction * time_elapsed.num_microseconds().unwrap() as f32 * dolly_speed;
-			},
-		}
-
-		match (self.keys_down.contains(&VirtualKeyCode::E), self.keys_down.contains(&VirtualKeyCode::Q)) {
-			(true, true) => (),
-			(false, false) => (),
-
-			(true, false) => {
-				self.camera.position -= -1.0 * self.camera.up * time_elapsed.num_microseconds().unwrap() as f32 * dolly_speed;
-
-			},
-			(false, true) => {
-				self.camera.position -= self.camera.up * time_elapsed.num_microseconds().unwrap() as f32 * dolly_speed;
-			},
+		let result = self.client.update(services, time_elapsed);
+		self.client.receive(services, time_elapsed);
+		if !self.client.connected() {
+			println!("The simulation stopped");
+			return UpdateResult::Quit;
 		}
+		self.record_metrics(services);
+		result
+	}
 
-		UpdateResult::None
+	fn draw(&self, draw_service: &mut DrawService) -> Result<(), SpaceError> {
+		self.client.draw(draw_service)
 	}
+}
 
-	fn draw(&self, draw_service: &mut DrawService) {
-		self.region.draw(&self.block_render_types, draw_service, self.camera.to_isometry().to_homogeneous());
+// The world is saved as the server stops, so it's waited for
+impl Drop for StatePlaying {
+	fn drop(&mut self) {
+		self.client.quit();
+		self.simulation.join();
 	}
 }
@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+use std::rc::Rc;
+
+use time::Duration;
+use na::{Matrix4, Vector3};
+
+use block::{World, BlockProperties};
+use gl_util::Vertex;
+
+// In blocks per second (squared)
+const GRAVITY: f32 = 32.0;
+// Long frames are integrated in several steps, so gravity and friction act smoothly
+const MAX_STEP: f32 = 0.05;
+// Contacts per step; each stops motion along one axis, so three stop it entirely
+const MAX_CONTACTS: usize = 3;
+// Gap kept between an entity and the blocks it rests against, so float error doesn't sink it into them
+const SKIN: f32 = 0.001;
+// How quickly horizontal speed decays while on the ground, per second
+const GROUND_FRICTION: f32 = 8.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(u64);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+	pub min: [f32; 3],
+	pub max: [f32; 3],
+}
+
+impl Aabb {
+	pub fn new(min: [f32; 3], max: [f32; 3]) -> Aabb {
+		Aabb { min: min, max: max }
+	}
+
+	// Centered horizontally on the origin, with its bottom on it
+	pub fn standing(width: f32, height: f32) -> Aabb {
+		let half = width / 2.0;
+		Aabb::new([-half, -half, 0.0], [half, half, height])
+	}
+
+	pub fn offset(&self, by: [f32; 3]) -> Aabb {
+		Aabb::new(
+			[self.min[0] + by[0], self.min[1] + by[1], self.min[2] + by[2]],
+			[self.max[0] + by[0], self.max[1] + by[1], self.max[2] + by[2]],
+		)
+	}
+
+	pub fn intersects(&self, other: &Aabb) -> bool {
+		(0..3).all(|axis| self.min[axis] < other.max[axis] && other.min[axis] < self.max[axis])
+	}
+}
+
+// Where a moving box first touches a block: the fraction of the motion made before touching,
+// the axis it was stopped along and the coordinate of the block face it met
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Contact {
+	pub time: f32,
+	pub axis: usize,
+	pub face: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+	pub position: [f32; 3],
+	pub velocity: [f32; 3],
+	// Relative to `position`
+	pub bounds: Aabb,
+	pub color: [f32; 3],
+	// Rotation about the vertical axis through `position`, in radians. Only affects drawing.
+	pub yaw: f32,
+	pub gravity: bool,
+	pub on_ground: bool,
+	// Ledges up to this high are stepped onto while walking into them, rather than stopping
+	pub step_height: f32,
+}
+
+impl Entity {
+	pub fn new(position: [f32; 3], bounds: Aabb, color: [f32; 3]) -> Entity {
+		Entity {
+			position: position,
+			velocity: [0.0; 3],
+			bounds: bounds,
+			color: color,
+			yaw: 0.0,
+			gravity: true,
+			on_ground: false,
+			step_height: 0.0,
+		}
+	}
+
+	pub fn world_bounds(&self) -> Aabb {
+		self.bounds.offset(self.position)
+	}
+
+	pub fn model(&self) -> BoxModel {
+		BoxModel { bounds: self.bounds, color: self.color }
+	}
+
+	pub fn transform(&self) -> Matrix4<f32> {
+		let position = Vector3::new(self.position[0], self.position[1], self.position[2]);
+		Matrix4::new_translation(&position) * Matrix4::new_rotation(Vector3::new(0.0, 0.0, self.yaw))
+	}
+}
+
+// A flat colored box in model space, the only kind of entity model so far
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoxModel {
+	pub bounds: Aabb,
+	pub color: [f32; 3],
+}
+
+impl BoxModel {
+	// Identifies the model for mesh caching, which f32s can't do by themselves
+	pub fn key(&self) -> [u32; 9] {
+		let b = &self.bounds;
+		let c = &self.color;
+		[
+			b.min[0].to_bits(), b.min[1].to_bits(), b.min[2].to_bits(),
+			b.max[0].to_bits(), b.max[1].to_bits(), b.max[2].to_bits(),
+			c[0].to_bits(), c[1].to_bits(), c[2].to_bits(),
+		]
+	}
+
+	// Wound to match the chunk meshes
+	pub fn mesh_data(&self) -> (Vec<Vertex>, Vec<u16>) {
+		use block::NormalDirection as ND;
+		let (bounds, color) = (&self.bounds, self.color);
+		let (mut vertices, mut indices) = (Vec::with_capacity(24), Vec::with_capacity(36));
+		for dir in [ND::Front, ND::Up, ND::Right, ND::Back, ND::Down, ND::Left].iter() {
+			let base = vertices.len() as u16;
+			let normal = dir.to_vec_arr();
+			for corner in dir.face_corners().iter() {
+				let position = [
+					bounds.min[0] + corner[0] * (bounds.max[0] - bounds.min[0]),
+					bounds.min[1] + corner[1] * (bounds.max[1] - bounds.min[1]),
+					bounds.min[2] + corner[2] * (bounds.max[2] - bounds.min[2]),
+				];
+				vertices.push(Vertex { position: position, normal: normal, color: color });
+			}
+			indices.extend_from_slice(&[base, base + 1, base + 2, base + 3, base + 2, base + 1]);
+		}
+		(vertices, indices)
+	}
+}
+
+// Dynamic objects, kept apart from the block data. Each tick they fall, move by their velocity
+// and are stopped by solid blocks.
+pub struct Entities {
+	properties: Rc<Vec<BlockProperties>>,
+	next_id: u64,
+	entities: BTreeMap<EntityId, Entity>,
+}
+
+impl Entities {
+	pub fn new(properties: Rc<Vec<BlockProperties>>) -> Entities {
+		Entities {
+			properties: properties,
+			next_id: 0,
+			entities: BTreeMap::new(),
+		}
+	}
+
+	pub fn spawn(&mut self, entity: Entity) -> EntityId {
+		let id = EntityId(self.next_id);
+		self.next_id += 1;
+		self.entities.insert(id, entity);
+		id
+	}
+
+	pub fn despawn(&mut self, id: EntityId) -> Option<Entity> {
+		self.entities.remove(&id)
+	}
+
+	pub fn get(&self, id: EntityId) -> Option<&Entity> {
+		self.entities.get(&id)
+	}
+
+	pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+		self.entities.get_mut(&id)
+	}
+
+	pub fn len(&self) -> usize {
+		self.entities.len()
+	}
+
+	pub fn iter(&self) -> btree_map::Iter<EntityId, Entity> {
+		self.entities.iter()
+	}
+
+	// Whether `bounds` overlaps any solid block
+	pub fn collides(&self, world: &World, bounds: &Aabb) -> bool {
+		let lo = [bounds.min[0].floor() as i64, bounds.min[1].floor() as i64, bounds.min[2].floor() as i64];
+		let hi = [bounds.max[0].ceil() as i64, bounds.max[1].ceil() as i64, bounds.max[2].ceil() as i64];
+		(lo[0]..hi[0]).any(|x| (lo[1]..hi[1]).any(|y| (lo[2]..hi[2]).any(|z| self.solid(world.get_block([x, y, z])))))
+	}
+
+	// Whether a solid block lies directly beneath any part of `bounds`
+	pub fn has_support(&self, world: &World, bounds: &Aabb) -> bool {
+		let below = Aabb::new(
+			[bounds.min[0], bounds.min[1], bounds.min[2] - 2.0 * SKIN],
+			[bounds.max[0], bounds.max[1], bounds.min[2]],
+		);
+		self.collides(world, &below)
+	}
+
+	fn solid(&self, block: usize) -> bool {
+		self.properties.get(block).map(|p| p.solid).unwrap_or(true)
+	}
+
+	pub fn sweep(&self, world: &World, bounds: &Aabb, delta: [f32; 3]) -> Option<Contact> {
+		sweep(&self.properties, world, bounds, delta)
+	}
+
+	// The solid blocks a sweep of `entity` over `seconds` would test against
+	pub fn collision_candidates(&self, world: &World, entity: &Entity, seconds: f32) -> Vec<[i64; 3]> {
+		let delta = [entity.velocity[0] * seconds, entity.velocity[1] * seconds, entity.velocity[2] * seconds];
+		let (lo, hi) = swept_cells(&entity.world_bounds(), delta);
+		let mut candidates = Vec::new();
+		for x in lo[0]..hi[0] {
+			for y in lo[1]..hi[1] {
+				for z in lo[2]..hi[2] {
+					if self.solid(world.get_block([x, y, z])) {
+						candidates.push([x, y, z]);
+					}
+				}
+			}
+		}
+		candidates
+	}
+
+	pub fn tick(&mut self, world: &World, time_elapsed: &Duration) {
+		let dt = time_elapsed.num_microseconds().unwrap_or(0) as f32 / 1_000_000.0;
+		if dt <= 0.0 {
+			return;
+		}
+		let steps = (dt / MAX_STEP).ceil();
+		let step = dt / steps;
+
+		for entity in self.entities.values_mut() {
+			for _ in 0..steps as usize {
+				if entity.gravity {
+					entity.velocity[2] -= GRAVITY * step;
+				}
+
+				let grounded = entity.on_ground;
+				entity.on_ground = false;
+				let mut delta = [entity.velocity[0] * step, entity.velocity[1] * step, entity.velocity[2] * step];
+				let (mut tried_step, mut stepped) = (false, false);
+				let mut contacts = 0;
+				// Whatever motion is left after a contact slides along the block face
+				while contacts < MAX_CONTACTS {
+					if delta == [0.0; 3] {
+						break;
+					}
+					let contact = match sweep(&self.properties, world, &entity.world_bounds(), delta) {
+						Some(contact) => contact,
+						None => {
+							for axis in 0..3 {
+								entity.position[axis] += delta[axis];
+							}
+							break;
+						},
+					};
+
+					let axis = contact.axis;
+					for a in 0..3 {
+						entity.position[a] += delta[a] * contact.time;
+					}
+
+					// Only one step per integration step, however many ledges are walked into
+					if axis != 2 && grounded && !tried_step && entity.step_height > 0.0 {
+						tried_step = true;
+						let mut remaining = delta;
+						for a in 0..3 {
+							remaining[a] *= 1.0 - contact.time;
+						}
+						if step_up(&self.properties, world, entity, axis, remaining) {
+							stepped = true;
+							delta = remaining;
+							continue;
+						}
+					}
+
+					contacts += 1;
+					entity.position[axis] = if delta[axis] > 0.0 {
+						contact.face - SKIN - entity.bounds.max[axis]
+					} else {
+						contact.face + SKIN - entity.bounds.min[axis]
+					};
+					entity.on_ground |= axis == 2 && delta[2] < 0.0;
+					entity.velocity[axis] = 0.0;
+
+					for a in 0..3 {
+						delta[a] *= 1.0 - contact.time;
+					}
+					delta[axis] = 0.0;
+				}
+
+				// Back down onto the ledge just stepped up
+				if stepped {
+					let bounds = entity.world_bounds();
+					match sweep(&self.properties, world, &bounds, [0.0, 0.0, -entity.step_height]) {
+						Some(contact) => {
+							entity.position[2] = contact.face + SKIN - entity.bounds.min[2];
+							entity.on_ground = true;
+							entity.velocity[2] = 0.0;
+						},
+						None => entity.position[2] -= entity.step_height,
+					}
+				}
+
+				if entity.on_ground {
+					let keep = (1.0 - GROUND_FRICTION * step).max(0.0);
+					entity.velocity[0] *= keep;
+					entity.velocity[1] *= keep;
+				}
+			}
+		}
+	}
+}
+
+// Lifts `entity` by its step height if there's headroom and that clears whatever stopped it
+// along `axis`, so the rest of `delta` can carry it onto the ledge. Returns whether it did.
+fn step_up(properties: &[BlockProperties], world: &World, entity: &mut Entity, axis: usize, delta: [f32; 3]) -> bool {
+	let bounds = entity.world_bounds();
+	let lift = [0.0, 0.0, entity.step_height];
+	if sweep(properties, world, &bounds, lift).is_some() {
+		return false;
+	}
+
+	let raised = bounds.offset(lift);
+	match sweep(properties, world, &raised, [delta[0], delta[1], 0.0]) {
+		Some(contact) if contact.axis == axis && contact.time == 0.0 => false,
+		_ => {
+			entity.position[2] += entity.step_height;
+			true
+		},
+	}
+}
+
+// Swept box against block test: finds the first solid block `bounds` would touch moving by
+// `delta`, however far that is. Blocks it already overlaps are ignored, so it can't get stuck
+// inside terrain, but it may start up to SKIN inside a block's face to allow for float error.
+fn sweep(properties: &[BlockProperties], world: &World, bounds: &Aabb, delta: [f32; 3]) -> Option<Contact> {
+	let (lo, hi) = swept_cells(bounds, delta);
+
+	let mut first: Option<Contact> = None;
+	for x in lo[0]..hi[0] {
+		for y in lo[1]..hi[1] {
+			for z in lo[2]..hi[2] {
+				let cell = [x, y, z];
+				let contact = match cell_contact(bounds, delta, cell) {
+					Some(contact) => contact,
+					None => continue,
+				};
+				if first.map(|first| contact.time >= first.time).unwrap_or(false) {
+					continue;
+				}
+				let solid = properties.get(world.get_block(cell)).map(|p| p.solid).unwrap_or(true);
+				if solid {
+					first = Some(contact);
+				}
+			}
+		}
+	}
+	first
+}
+
+// The range of cells, end exclusive, that `bounds` passes through moving by `delta`
+fn swept_cells(bounds: &Aabb, delta: [f32; 3]) -> ([i64; 3], [i64; 3]) {
+	let mut lo = [0; 3];
+	let mut hi = [0; 3];
+	for axis in 0..3 {
+		lo[axis] = (bounds.min[axis] + delta[axis].min(0.0)).floor() as i64;
+		hi[axis] = (bounds.max[axis] + delta[axis].max(0.0)).ceil() as i64;
+	}
+	(lo, hi)
+}
+
+fn cell_contact(bounds: &Aabb, delta: [f32; 3], cell: [i64; 3]) -> Option<Contact> {
+	let mut entry = ::std::f32::NEG_INFINITY;
+	let mut exit = ::std::f32::INFINITY;
+	let mut hit = None;
+
+	for axis in 0..3 {
+		let (near, far) = (cell[axis] as f32, (cell[axis] + 1) as f32);
+		if delta[axis] == 0.0 {
+			if bounds.max[axis] <= near || bounds.min[axis] >= far {
+				return None;
+			}
+			continue;
+		}
+
+		// Times at which the box starts and stops overlapping the cell along this axis
+		let (enter, leave, face) = if delta[axis] > 0.0 {
+			((near - bounds.max[axis]) / delta[axis], (far - bounds.min[axis]) / delta[axis], near)
+		} else {
+			((far - bounds.min[axis]) / delta[axis], (near - bounds.max[axis]) / delta[axis], far)
+		};
+		if enter > entry {
+			entry = enter;
+			hit = Some((axis, face));
+		}
+		exit = exit.min(leave);
+	}
+
+	let (axis, face) = match hit {
+		Some(hit) => hit,
+		None => return None,
+	};
+	let penetration = -entry * delta[axis].abs();
+	if entry >= exit || entry > 1.0 || penetration > SKIN {
+		return None;
+	}
+	Some(Contact { time: entry.max(0.0), axis: axis, face: face })
+}
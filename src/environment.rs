@@ -0,0 +1,126 @@
+use std::f32::consts::PI;
+
+use na::{Matrix4, Vector3, U3};
+
+use weather::Weather;
+
+// In seconds of world time
+pub const DAY_LENGTH: f64 = 1200.0;
+// Fractions of a day, which starts at midnight
+pub const SUNRISE: f32 = 0.25;
+pub const NOON: f32 = 0.5;
+pub const SUNSET: f32 = 0.75;
+pub const MIDNIGHT: f32 = 0.0;
+
+const NIGHT_SKY: [f32; 3] = [0.02, 0.03, 0.08];
+const NIGHT_AMBIENT: [f32; 3] = [0.06, 0.06, 0.1];
+const DAY_AMBIENT: [f32; 3] = [0.33, 0.33, 0.33];
+// The sun reddens as it nears the horizon
+const LOW_SUN_COLOR: [f32; 3] = [1.0, 0.6, 0.35];
+// Moonlight, as a fraction of the sky light at noon
+const NIGHT_SKY_LIGHT: f32 = 0.2;
+
+// How the scene is lit and how it fades into the distance, set once a frame. Light colors are
+// plain multipliers; the fog color is sRGB like every other authored color.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Environment {
+	// Towards the sun, in world space
+	pub sun_direction: [f32; 3],
+	pub sun_color: [f32; 3],
+	// Reaches every face, whichever way it points
+	pub ambient_color: [f32; 3],
+	// Also the background, which distant geometry fades into at `fog_density`
+	pub fog_color: [f32; 3],
+	// How quickly things fade into the fog with distance; 0 for not at all
+	pub fog_density: f32,
+	// Scales the light cells get from the sky, from NIGHT_SKY_LIGHT at night to 1 by day. Light
+	// from blocks is the same at any time.
+	pub sky_light: f32,
+}
+
+impl Default for Environment {
+	// High in the sky and a little to the south east, with no fog
+	fn default() -> Environment {
+		Environment {
+			sun_direction: [0.3, -0.4, 0.87],
+			sun_color: [1.0, 1.0, 1.0],
+			ambient_color: [0.33, 0.33, 0.33],
+			fog_color: [0.0, 0.0, 0.0],
+			fog_density: 0.0,
+			sky_light: 1.0,
+		}
+	}
+}
+
+impl Environment {
+	// Under the sun or the night sky at `time` into the world, with fog and sky colored by the
+	// weather
+	pub fn at_time(time: f64, weather: Weather) -> Environment {
+		let angle = (time_of_day(time) - SUNRISE) * 2.0 * PI;
+		let sun = Vector3::new(angle.cos(), -0.4, angle.sin()).normalize();
+		let day = daylight(time);
+		let sun_color = mix(LOW_SUN_COLOR, [1.0; 3], day);
+		Environment {
+			sun_direction: [sun.x, sun.y, sun.z],
+			sun_color: [sun_color[0] * day, sun_color[1] * day, sun_color[2] * day],
+			ambient_color: mix(NIGHT_AMBIENT, DAY_AMBIENT, day),
+			fog_color: mix(NIGHT_SKY, weather.sky_color(), day),
+			fog_density: weather.fog_density(),
+			sky_light: NIGHT_SKY_LIGHT + (1.0 - NIGHT_SKY_LIGHT) * day,
+		}
+	}
+}
+
+// From 0 to 1
+pub fn time_of_day(time: f64) -> f32 {
+	(time / DAY_LENGTH).fract() as f32
+}
+
+// As the day, counting from 1, and the time on a 24 hour clock
+pub fn describe_time(time: f64) -> String {
+	let minutes = (time_of_day(time) * 24.0 * 60.0) as u32;
+	format!("day {}, {:02}:{:02}", (time / DAY_LENGTH) as u64 + 1, minutes / 60, minutes % 60)
+}
+
+// 0 with the sun just below the horizon, up to 1 once it's a little way above it
+pub fn daylight(time: f64) -> f32 {
+	let height = ((time_of_day(time) - SUNRISE) * 2.0 * PI).sin();
+	((height + 0.1) / 0.3).max(0.0).min(1.0)
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+	[a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
+
+// An Environment as the lit shaders see it, laid out to match their Environment uniform block.
+// Everything is a vec4 but the last float, so std140 needs no padding.
+#[derive(Copy, Clone, Debug)]
+pub struct EnvironmentBlock {
+	// In view space
+	sun_direction: [f32; 4],
+	sun_color: [f32; 4],
+	ambient_color: [f32; 4],
+	// The color, with the density in w
+	fog: [f32; 4],
+	sky_light: f32,
+}
+
+implement_uniform_block!(EnvironmentBlock, sun_direction, sun_color, ambient_color, fog, sky_light);
+
+impl EnvironmentBlock {
+	// Lighting is done in view space, so the sun is turned by the frame's `view`
+	pub fn new(environment: &Environment, view: &Matrix4<f32>) -> EnvironmentBlock {
+		let sun = environment.sun_direction;
+		let sun = (view.fixed_slice::<U3, U3>(0, 0) * Vector3::new(sun[0], sun[1], sun[2])).try_normalize(1.0e-6).unwrap_or(Vector3::z());
+		let color = environment.sun_color;
+		let ambient = environment.ambient_color;
+		let fog = environment.fog_color;
+		EnvironmentBlock {
+			sun_direction: [sun.x, sun.y, sun.z, 0.0],
+			sun_color: [color[0], color[1], color[2], 1.0],
+			ambient_color: [ambient[0], ambient[1], ambient[2], 1.0],
+			fog: [fog[0], fog[1], fog[2], environment.fog_density],
+			sky_light: environment.sky_light,
+		}
+	}
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+use std::io;
+
+use glium::{DisplayCreationError, DrawError, ProgramCreationError, SwapBuffersError};
+use glium::texture::TextureCreationError;
+use glium::framebuffer::ValidationError;
+use glium::buffer::BufferCreationError;
+use glium::vertex::BufferCreationError as VertexBufferCreationError;
+
+use gl_util::MeshCreationError;
+use block::InvalidRenderData;
+use save::SaveError;
+
+// Anything that stops the game from starting or from drawing a frame
+#[derive(Debug)]
+pub enum SpaceError {
+	Window(DisplayCreationError),
+	// Named after the program that failed to build
+	Shader(&'static str, ProgramCreationError),
+	Mesh(MeshCreationError),
+	Texture(TextureCreationError),
+	Framebuffer(ValidationError),
+	Buffer(BufferCreationError),
+	Draw(DrawError),
+	Swap(SwapBuffersError),
+	Save(SaveError),
+	BlockData(InvalidRenderData),
+	Io(io::Error),
+	// The simulation thread stopped before it got going
+	Simulation,
+}
+
+impl fmt::Display for SpaceError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SpaceError::Window(ref err) => write!(f, "Failed to open a window: {}", err),
+			SpaceError::Shader(name, ref err) => write!(f, "Failed to build the {} shader: {}", name, err),
+			SpaceError::Mesh(ref err) => write!(f, "Failed to upload a mesh: {:?}", err),
+			SpaceError::Texture(ref err) => write!(f, "Failed to create a texture: {}", err),
+			SpaceError::Framebuffer(ref err) => write!(f, "Failed to create a framebuffer: {:?}", err),
+			SpaceError::Buffer(ref err) => write!(f, "Failed to create a buffer: {:?}", err),
+			SpaceError::Draw(ref err) => write!(f, "Failed to draw: {}", err),
+			SpaceError::Swap(ref err) => write!(f, "Failed to present the frame: {}", err),
+			SpaceError::Save(ref err) => write!(f, "Failed to open the world: {:?}", err),
+			SpaceError::BlockData(ref err) => write!(f, "Invalid block: {:?}", err),
+			SpaceError::Io(ref err) => write!(f, "{}", err),
+			SpaceError::Simulation => write!(f, "The simulation failed to start"),
+		}
+	}
+}
+
+impl From<DisplayCreationError> for SpaceError {
+	fn from(err: DisplayCreationError) -> SpaceError {
+		SpaceError::Window(err)
+	}
+}
+
+impl From<MeshCreationError> for SpaceError {
+	fn from(err: MeshCreationError) -> SpaceError {
+		SpaceError::Mesh(err)
+	}
+}
+
+impl From<VertexBufferCreationError> for SpaceError {
+	fn from(err: VertexBufferCreationError) -> SpaceError {
+		SpaceError::Mesh(MeshCreationError::from(err))
+	}
+}
+
+impl From<TextureCreationError> for SpaceError {
+	fn from(err: TextureCreationError) -> SpaceError {
+		SpaceError::Texture(err)
+	}
+}
+
+impl From<ValidationError> for SpaceError {
+	fn from(err: ValidationError) -> SpaceError {
+		SpaceError::Framebuffer(err)
+	}
+}
+
+impl From<BufferCreationError> for SpaceError {
+	fn from(err: BufferCreationError) -> SpaceError {
+		SpaceError::Buffer(err)
+	}
+}
+
+impl From<DrawError> for SpaceError {
+	fn from(err: DrawError) -> SpaceError {
+		SpaceError::Draw(err)
+	}
+}
+
+impl From<SwapBuffersError> for SpaceError {
+	fn from(err: SwapBuffersError) -> SpaceError {
+		SpaceError::Swap(err)
+	}
+}
+
+impl From<SaveError> for SpaceError {
+	fn from(err: SaveError) -> SpaceError {
+		SpaceError::Save(err)
+	}
+}
+
+impl From<InvalidRenderData> for SpaceError {
+	fn from(err: InvalidRenderData) -> SpaceError {
+		SpaceError::BlockData(err)
+	}
+}
+
+impl From<io::Error> for SpaceError {
+	fn from(err: io::Error) -> SpaceError {
+		SpaceError::Io(err)
+	}
+}
@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::mem::replace;
+use std::rc::Rc;
+
+use time::Duration;
+
+use block::{World, BlockProperties, AIR};
+use scheduler::{TickScheduler, EventKind};
+
+// In blocks per second (squared)
+const GRAVITY: f32 = 32.0;
+const TERMINAL_VELOCITY: f32 = 64.0;
+// Blocks that fall further than this without landing are dropped, so a void doesn't keep them forever
+const MAX_FALL_DISTANCE: f32 = 1024.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FallingBlock {
+	pub block: usize,
+	// Of the block's lower corner
+	pub position: [f32; 3],
+	pub velocity: f32,
+	start_z: f32,
+}
+
+// Turns unsupported blocks with the `falls` property into falling blocks, and puts them back
+// into the world once they land on something solid.
+pub struct FallingBlocks {
+	properties: Rc<Vec<BlockProperties>>,
+	candidates: Rc<RefCell<Vec<[i64; 3]>>>,
+	falling: Vec<FallingBlock>,
+}
+
+impl FallingBlocks {
+	pub fn new(world: &World, properties: Rc<Vec<BlockProperties>>) -> FallingBlocks {
+		let candidates = Rc::new(RefCell::new(Vec::new()));
+
+		{
+			let properties = properties.clone();
+			let candidates = candidates.clone();
+			// Any change at or next to a falling block may have taken away its support
+			world.subscribe(move |world, update| {
+				if falls(&properties, world.get_block(update.pos)) {
+					candidates.borrow_mut().push(update.pos);
+				}
+			});
+		}
+
+		FallingBlocks {
+			properties: properties,
+			candidates: candidates,
+			falling: Vec::new(),
+		}
+	}
+
+	pub fn falling(&self) -> &[FallingBlock] {
+		&self.falling
+	}
+
+	// Handles a scheduled BlockFall event, letting go of the block at `pos` if nothing holds it up
+	pub fn fall(&mut self, world: &World, pos: [i64; 3]) {
+		let block = world.get_block(pos);
+		if falls(&self.properties, block) && !solid(&self.properties, world.get_block([pos[0], pos[1], pos[2] - 1])) {
+			world.set_block_unjournaled(pos, AIR);
+			let position = [pos[0] as f32, pos[1] as f32, pos[2] as f32];
+			self.falling.push(FallingBlock { block: block, position: position, velocity: 0.0, start_z: position[2] });
+		}
+	}
+
+	// Schedules blocks disturbed since the last call to be checked, and moves those falling
+	pub fn tick(&mut self, world: &World, scheduler: &mut TickScheduler, time_elapsed: &Duration) {
+		let candidates = replace(&mut *self.candidates.borrow_mut(), Vec::new());
+		for pos in candidates {
+			scheduler.schedule(pos, EventKind::BlockFall, 1);
+		}
+
+		let dt = time_elapsed.num_microseconds().unwrap_or(0) as f32 / 1_000_000.0;
+		let properties = &self.properties;
+		let falling = replace(&mut self.falling, Vec::new());
+		for mut block in falling {
+			block.velocity = (block.velocity + GRAVITY * dt).min(TERMINAL_VELOCITY);
+			let next_z = block.position[2] - block.velocity * dt;
+			let (x, y) = (block.position[0] as i64, block.position[1] as i64);
+
+			// Check every cell passed through this tick, top down
+			let landing = (next_z.floor() as i64..block.position[2].floor() as i64).rev()
+				.find(|&z| solid(properties, world.get_block([x, y, z])))
+				.map(|z| z + 1);
+
+			match landing {
+				Some(mut z) => {
+					// Something may have been placed where it would land
+					while solid(properties, world.get_block([x, y, z])) {
+						z += 1;
+					}
+					world.set_block_unjournaled([x, y, z], block.block);
+				},
+				None if block.start_z - next_z > MAX_FALL_DISTANCE => (),
+				None => {
+					block.position[2] = next_z;
+					self.falling.push(block);
+				},
+			}
+		}
+	}
+}
+
+fn falls(properties: &[BlockProperties], block: usize) -> bool {
+	properties.get(block).map(|p| p.falls).unwrap_or(false)
+}
+
+fn solid(properties: &[BlockProperties], block: usize) -> bool {
+	properties.get(block).map(|p| p.solid).unwrap_or(true)
+}
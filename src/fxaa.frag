@@ -0,0 +1,42 @@
+#version 140
+in vec2 v_tex_coords;
+
+out vec4 color;
+
+uniform sampler2D source;
+// The size of a texel, one over the source's dimensions
+uniform vec2 texel;
+
+const float reduce_min = 1.0 / 128.0;
+const float reduce_mul = 1.0 / 8.0;
+const float span_max = 8.0;
+const vec3 luma = vec3(0.299, 0.587, 0.114);
+
+// The cheap single pass FXAA: blurs along the edge found from the luminance of the four diagonal
+// neighbours, unless that would reach outside the local contrast range
+void main() {
+	float nw = dot(texture(source, v_tex_coords + vec2(-1.0, -1.0) * texel).rgb, luma);
+	float ne = dot(texture(source, v_tex_coords + vec2( 1.0, -1.0) * texel).rgb, luma);
+	float sw = dot(texture(source, v_tex_coords + vec2(-1.0,  1.0) * texel).rgb, luma);
+	float se = dot(texture(source, v_tex_coords + vec2( 1.0,  1.0) * texel).rgb, luma);
+	vec4 middle = texture(source, v_tex_coords);
+	float m = dot(middle.rgb, luma);
+
+	float luma_min = min(m, min(min(nw, ne), min(sw, se)));
+	float luma_max = max(m, max(max(nw, ne), max(sw, se)));
+
+	vec2 direction = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+	float reduce = max((nw + ne + sw + se) * 0.25 * reduce_mul, reduce_min);
+	float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+	direction = clamp(direction * scale, vec2(-span_max), vec2(span_max)) * texel;
+
+	vec3 a = 0.5 * (
+		texture(source, v_tex_coords + direction * (1.0 / 3.0 - 0.5)).rgb +
+		texture(source, v_tex_coords + direction * (2.0 / 3.0 - 0.5)).rgb);
+	vec3 b = a * 0.5 + 0.25 * (
+		texture(source, v_tex_coords + direction * -0.5).rgb +
+		texture(source, v_tex_coords + direction * 0.5).rgb);
+
+	float luma_b = dot(b, luma);
+	color = vec4((luma_b < luma_min || luma_b > luma_max) ? a : b, middle.a);
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+
+// How the game plays in a world, kept with its metadata and changed with /gamerule
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GameRules {
+	// Players can leave their bodies behind and fly; when off, flying players are put down
+	pub fly: bool,
+	// Whether time passes, moving the sun
+	pub day_night_cycle: bool,
+	// Whether mobs can be spawned
+	pub mob_spawning: bool,
+	// Seconds between saves of every loaded chunk, or 0 to only save on eviction and /save
+	pub autosave_interval: u32,
+}
+
+// As written in the world metadata and typed in /gamerule
+pub const RULE_NAMES: [&'static str; 4] = ["fly", "day_night_cycle", "mob_spawning", "autosave_interval"];
+
+impl Default for GameRules {
+	fn default() -> GameRules {
+		GameRules {
+			fly: true,
+			day_night_cycle: true,
+			mob_spawning: true,
+			autosave_interval: 300,
+		}
+	}
+}
+
+impl GameRules {
+	pub fn get(&self, name: &str) -> Option<String> {
+		match name {
+			"fly" => Some(self.fly.to_string()),
+			"day_night_cycle" => Some(self.day_night_cycle.to_string()),
+			"mob_spawning" => Some(self.mob_spawning.to_string()),
+			"autosave_interval" => Some(self.autosave_interval.to_string()),
+			_ => None,
+		}
+	}
+
+	pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+		let invalid = || format!("Invalid value for {}: {}", name, value);
+		match name {
+			"fly" => self.fly = value.parse().map_err(|_| invalid())?,
+			"day_night_cycle" => self.day_night_cycle = value.parse().map_err(|_| invalid())?,
+			"mob_spawning" => self.mob_spawning = value.parse().map_err(|_| invalid())?,
+			"autosave_interval" => self.autosave_interval = value.parse().map_err(|_| invalid())?,
+			_ => return Err(format!("Unknown game rule: {}", name)),
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Display for GameRules {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let rules: Vec<String> = RULE_NAMES.iter().map(|name| format!("{}={}", name, self.get(name).unwrap())).collect();
+		write!(f, "{}", rules.join(", "))
+	}
+}
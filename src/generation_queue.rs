@@ -0,0 +1,299 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard, Condvar};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread::{self, JoinHandle};
+
+use block::CHUNK_SIZE;
+
+// Squared, so chunks behind where the player looks come as if twice as far away
+const BEHIND_PENALTY: f32 = 4.0;
+
+// Whoever asked for chunks. Asking again replaces what they asked for last time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Requester(u64);
+
+// A chunk waiting to be generated
+struct Job {
+	priority: f32,
+	pos: [i64; 3],
+	requester: Requester,
+}
+
+impl PartialEq for Job {
+	fn eq(&self, other: &Job) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+	fn partial_cmp(&self, other: &Job) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+// Reversed, so the heap pops the lowest priority first
+impl Ord for Job {
+	fn cmp(&self, other: &Job) -> Ordering {
+		other.priority.partial_cmp(&self.priority).unwrap_or(Ordering::Equal)
+	}
+}
+
+// Chunks that whatever streams the world has asked for, lowest priority first. Everything
+// streaming shares the one queue, so the chunks nearest anyone's eye come first. Jobs go stale
+// only while they wait here; see GenerationPool for what's made of those already popped.
+pub struct GenerationQueue {
+	jobs: BinaryHeap<Job>,
+	// What each requester has queued, at the priority it was last asked for at. A job that doesn't
+	// match is stale, left in the heap until it's popped or swept. Those cancelled have nothing.
+	queued: HashMap<Requester, HashMap<[i64; 3], f32>>,
+	// Of the jobs in the heap
+	stale: usize,
+	next_requester: u64,
+	// Stale jobs dropped without being generated, ever
+	dropped: u64,
+}
+
+impl GenerationQueue {
+	pub fn new() -> GenerationQueue {
+		GenerationQueue { jobs: BinaryHeap::new(), queued: HashMap::new(), stale: 0, next_requester: 0, dropped: 0 }
+	}
+
+	pub fn requester(&mut self) -> Requester {
+		self.next_requester += 1;
+		let requester = Requester(self.next_requester);
+		self.queued.insert(requester, HashMap::new());
+		requester
+	}
+
+	// Replaces everything `requester` asked for before with `wanted`, as (priority, chunk) pairs.
+	// Only chunks that are new or whose priority changed are queued again, so asking for the same
+	// chunks every tick adds nothing. Cancelled requesters can't ask for anything more.
+	pub fn request(&mut self, requester: Requester, wanted: &[(f32, [i64; 3])]) {
+		let queued = match self.queued.get_mut(&requester) {
+			Some(queued) => queued,
+			None => return,
+		};
+		let mut now = HashMap::with_capacity(wanted.len());
+		for &(priority, pos) in wanted {
+			if now.contains_key(&pos) {
+				continue;
+			}
+			now.insert(pos, priority);
+			match queued.remove(&pos) {
+				Some(before) if before == priority => continue,
+				Some(_) => self.stale += 1,
+				None => {},
+			}
+			self.jobs.push(Job { priority: priority, pos: pos, requester: requester });
+		}
+		// No longer wanted at all
+		self.stale += queued.len();
+		*queued = now;
+
+		// Swept once at least half the heap is stale, so it's never more than twice what's wanted
+		if self.stale * 2 > self.jobs.len() {
+			self.sweep();
+		}
+	}
+
+	// Drops every job `requester` still has queued, for when they go away
+	pub fn cancel(&mut self, requester: Requester) {
+		if let Some(queued) = self.queued.remove(&requester) {
+			self.stale += queued.len();
+		}
+	}
+
+	// The next job that isn't stale
+	pub fn pop(&mut self) -> Option<[i64; 3]> {
+		while let Some(job) = self.jobs.pop() {
+			if current(&self.queued, &job) {
+				self.queued.get_mut(&job.requester).unwrap().remove(&job.pos);
+				return Some(job.pos);
+			}
+			self.stale = self.stale.saturating_sub(1);
+			self.dropped += 1;
+		}
+		None
+	}
+
+	// Including stale jobs not swept out yet
+	pub fn len(&self) -> usize {
+		self.jobs.len()
+	}
+
+	pub fn dropped(&self) -> u64 {
+		self.dropped
+	}
+
+	fn sweep(&mut self) {
+		let jobs = ::std::mem::replace(&mut self.jobs, BinaryHeap::new());
+		let before = jobs.len();
+		let kept: BinaryHeap<Job> = {
+			let queued = &self.queued;
+			jobs.into_iter().filter(|job| current(queued, job)).collect()
+		};
+		self.dropped += (before - kept.len()) as u64;
+		self.stale = 0;
+		self.jobs = kept;
+	}
+}
+
+// A GenerationQueue that worker threads take jobs from, each making something of a chunk's
+// position with `work`; the world takes what they make and turns it into chunks, as chunks
+// themselves can't leave its thread. Without workers nothing is made in the background, and the
+// world pops jobs itself.
+pub struct GenerationPool<T> {
+	shared: Arc<Shared>,
+	work: Arc<Fn([i64; 3]) -> T + Send + Sync>,
+	sender: Sender<([i64; 3], T)>,
+	results: Receiver<([i64; 3], T)>,
+	workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+	state: Mutex<PoolState>,
+	wake: Condvar,
+}
+
+struct PoolState {
+	queue: GenerationQueue,
+	// Popped by a worker and not yet taken back. What's made of anything forgotten meanwhile is
+	// thrown away.
+	in_flight: HashSet<[i64; 3]>,
+	stopping: bool,
+}
+
+impl<T: Send + 'static> GenerationPool<T> {
+	pub fn new<F>(work: F) -> GenerationPool<T> where F: Fn([i64; 3]) -> T + Send + Sync + 'static {
+		let (sender, results) = channel();
+		GenerationPool {
+			shared: Arc::new(Shared {
+				state: Mutex::new(PoolState { queue: GenerationQueue::new(), in_flight: HashSet::new(), stopping: false }),
+				wake: Condvar::new(),
+			}),
+			work: Arc::new(work),
+			sender: sender,
+			results: results,
+			workers: Vec::new(),
+		}
+	}
+
+	pub fn start_workers(&mut self, count: usize) {
+		for _ in 0..count {
+			let (shared, work, sender) = (self.shared.clone(), self.work.clone(), self.sender.clone());
+			let worker = thread::Builder::new().name(format!("generation {}", self.workers.len())).spawn(move || {
+				while let Some(pos) = next_job(&shared) {
+					if sender.send((pos, work(pos))).is_err() {
+						break;
+					}
+				}
+			});
+			match worker {
+				Ok(worker) => self.workers.push(worker),
+				Err(err) => eprintln!("Failed to start a generation worker: {}", err),
+			}
+		}
+	}
+
+	pub fn has_workers(&self) -> bool {
+		!self.workers.is_empty()
+	}
+
+	pub fn requester(&self) -> Requester {
+		self.state().queue.requester()
+	}
+
+	pub fn request(&self, requester: Requester, wanted: &[(f32, [i64; 3])]) {
+		self.state().queue.request(requester, wanted);
+		self.shared.wake.notify_all();
+	}
+
+	pub fn cancel(&self, requester: Requester) {
+		self.state().queue.cancel(requester);
+	}
+
+	// For the world to generate itself, when there are no workers
+	pub fn pop(&self) -> Option<[i64; 3]> {
+		self.state().queue.pop()
+	}
+
+	// The next thing a worker has made whose chunk hasn't been forgotten since, if any's ready
+	pub fn try_take(&self) -> Option<([i64; 3], T)> {
+		loop {
+			let (pos, made) = self.results.try_recv().ok()?;
+			if self.state().in_flight.remove(&pos) {
+				return Some((pos, made));
+			}
+		}
+	}
+
+	// For chunks loaded some other way, so whatever a worker's making of them is out of date
+	pub fn forget(&self, pos: [i64; 3]) {
+		if self.has_workers() {
+			self.state().in_flight.remove(&pos);
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.state().queue.len()
+	}
+
+	pub fn dropped(&self) -> u64 {
+		self.state().queue.dropped()
+	}
+
+	fn state(&self) -> MutexGuard<PoolState> {
+		lock(&self.shared)
+	}
+}
+
+impl<T> Drop for GenerationPool<T> {
+	fn drop(&mut self) {
+		lock(&self.shared).stopping = true;
+		self.shared.wake.notify_all();
+		for worker in self.workers.drain(..) {
+			let _ = worker.join();
+		}
+	}
+}
+
+// Waits for a job, or for the pool to stop
+fn next_job(shared: &Shared) -> Option<[i64; 3]> {
+	let mut state = lock(shared);
+	loop {
+		if state.stopping {
+			return None;
+		}
+		if let Some(pos) = state.queue.pop() {
+			state.in_flight.insert(pos);
+			return Some(pos);
+		}
+		state = shared.wake.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+	}
+}
+
+// A worker that panicked left the queue itself whole
+fn lock(shared: &Shared) -> MutexGuard<PoolState> {
+	shared.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn current(queued: &HashMap<Requester, HashMap<[i64; 3], f32>>, job: &Job) -> bool {
+	queued.get(&job.requester).and_then(|queued| queued.get(&job.pos)) == Some(&job.priority)
+}
+
+// The squared distance from `eye` to the middle of the chunk at `pos`, counting chunks behind
+// `look` as further away
+pub fn chunk_priority(pos: [i64; 3], eye: [f32; 3], look: Option<[f32; 3]>) -> f32 {
+	let size = CHUNK_SIZE as f32;
+	let dx = (pos[0] as f32 + 0.5) * size - eye[0];
+	let dy = (pos[1] as f32 + 0.5) * size - eye[1];
+	let dz = (pos[2] as f32 + 0.5) * size - eye[2];
+	let distance_squared = dx * dx + dy * dy + dz * dz;
+	match look {
+		Some(look) if dx * look[0] + dy * look[1] + dz * look[2] < 0.0 => distance_squared * BEHIND_PENALTY,
+		_ => distance_squared,
+	}
+}
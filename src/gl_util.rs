@@ -5,9 +5,39 @@ pub struct Vertex {
 	pub color:    [f32; 3],
 }
 
-use na::{Isometry3, Point3, Vector3};
+use na::{Isometry3, Point3, Vector3, Matrix4, Vector4, Perspective3};
 pub trait Camera<N: Real> {
 	fn to_isometry(&self) -> Isometry3<N>;
+
+	fn view_matrix(&self) -> Matrix4<N> {
+		self.to_isometry().to_homogeneous()
+	}
+
+	fn frustum(&self, perspective: &Perspective3<N>) -> Frustum<N> {
+		Frustum::from_matrix(&(perspective.as_matrix() * self.view_matrix()))
+	}
+
+	// From the eye along the view direction, for picking whatever is under the crosshair
+	fn forward_ray(&self) -> Ray<N> {
+		let world = self.to_isometry().inverse();
+		Ray {
+			origin: world * Point3::origin(),
+			direction: world * -Vector3::z(),
+		}
+	}
+}
+
+// `direction` is unit length
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Ray<N: Real> {
+	pub origin: Point3<N>,
+	pub direction: Vector3<N>,
+}
+
+impl <N: Real> Ray<N> {
+	pub fn at(&self, distance: N) -> Point3<N> {
+		self.origin + self.direction * distance
+	}
 }
 
 #[derive(Eq, PartialEq, Clone, Hash, Debug)]
@@ -17,9 +47,420 @@ pub struct SimpleCamera<N: PartialEq + Copy + ::std::fmt::Debug + 'static> {
 	pub up: Vector3<N>,
 }
 
-use na::Real;
+use na::{Real, convert};
 impl <N: Real> Camera<N> for SimpleCamera<N> {
 	fn to_isometry(&self) -> Isometry3<N> { Isometry3::look_at_rh(&self.position, &(self.position + self.direction), &self.up) }
 }
 
+// Eases a displayed camera towards `target` exponentially, so motion applied in uneven steps
+// looks continuous. `smoothing` is how fast it catches up, per second; zero snaps straight to
+// the target.
+pub struct SmoothedCamera<N: PartialEq + Copy + ::std::fmt::Debug + 'static> {
+	pub target: SimpleCamera<N>,
+	pub smoothing: N,
+	current: SimpleCamera<N>,
+}
+
+impl <N: Real> SmoothedCamera<N> {
+	pub fn new(camera: SimpleCamera<N>, smoothing: N) -> SmoothedCamera<N> {
+		SmoothedCamera {
+			current: camera.clone(),
+			target: camera,
+			smoothing: smoothing,
+		}
+	}
+
+	pub fn current(&self) -> &SimpleCamera<N> {
+		&self.current
+	}
+
+	pub fn snap(&mut self) {
+		self.current = self.target.clone();
+	}
+
+	pub fn update(&mut self, seconds: N) {
+		if self.smoothing <= N::zero() {
+			self.snap();
+			return;
+		}
+
+		let t = N::one() - (-self.smoothing * seconds).exp();
+		let current = &mut self.current;
+		let target = &self.target;
+		current.position += (target.position - current.position) * t;
+		// Nearly opposite directions would lerp through zero, so those snap instead
+		let direction = current.direction + (target.direction - current.direction) * t;
+		current.direction = direction.try_normalize(N::default_epsilon()).unwrap_or(target.direction);
+		let up = current.up + (target.up - current.up) * t;
+		current.up = up.try_normalize(N::default_epsilon()).unwrap_or(target.up);
+	}
+}
+
+impl <N: Real> Camera<N> for SmoothedCamera<N> {
+	fn to_isometry(&self) -> Isometry3<N> { self.current.to_isometry() }
+}
+
+// Circles `center` at a fixed distance and height, looking in at it, for slowly turning views
+// behind menus. Angles are in radians, `speed` in radians per second, and z is up.
+#[derive(Clone, Debug)]
+pub struct OrbitCamera<N: Real> {
+	pub center: Point3<N>,
+	pub distance: N,
+	pub pitch: N,
+	pub yaw: N,
+	pub speed: N,
+}
+
+impl <N: Real> OrbitCamera<N> {
+	pub fn update(&mut self, seconds: N) {
+		self.yaw = (self.yaw + self.speed * seconds) % N::two_pi();
+	}
+
+	pub fn position(&self) -> Point3<N> {
+		let offset = Vector3::new(
+			self.yaw.cos() * self.pitch.cos(),
+			self.yaw.sin() * self.pitch.cos(),
+			self.pitch.sin(),
+		);
+		self.center + offset * self.distance
+	}
+}
+
+impl <N: Real> Camera<N> for OrbitCamera<N> {
+	fn to_isometry(&self) -> Isometry3<N> { Isometry3::look_at_rh(&self.position(), &self.center, &Vector3::z()) }
+}
+
+// Head bob while walking and a wider view at speed, both worked out from how fast the camera
+// actually moves, so they follow the player whether they're walking, sprinting or flying. The
+// widening ramps in from `widen_from` to its full amount at `widen_to`, in blocks per second.
+pub struct MotionEffects<N: Real> {
+	pub bobbing: bool,
+	pub widening: bool,
+	pub widen_from: N,
+	pub widen_to: N,
+	last_position: Option<Point3<N>>,
+	// Horizontal, eased so uneven frames don't jitter the effects
+	speed: N,
+	// Radians through the bob cycle, a cycle being two steps
+	phase: N,
+	// From 0 to 1, easing out when the player stops walking
+	bob: N,
+}
+
+impl <N: Real> MotionEffects<N> {
+	pub fn new(widen_from: N, widen_to: N) -> MotionEffects<N> {
+		MotionEffects {
+			bobbing: true,
+			widening: true,
+			widen_from: widen_from,
+			widen_to: widen_to,
+			last_position: None,
+			speed: N::zero(),
+			phase: N::zero(),
+			bob: N::zero(),
+		}
+	}
+
+	// Forgets the last position, so a teleport isn't taken for great speed
+	pub fn reset(&mut self) {
+		self.last_position = None;
+		self.speed = N::zero();
+	}
+
+	// `walking` is whether the camera is on someone's shoulders rather than flying
+	pub fn update(&mut self, position: Point3<N>, walking: bool, seconds: N) {
+		if seconds <= N::zero() {
+			return;
+		}
+		let ease = N::one() - (-convert::<f64, N>(10.0) * seconds).exp();
+
+		let speed = match self.last_position {
+			Some(last) => Vector3::new(position.x - last.x, position.y - last.y, N::zero()).norm() / seconds,
+			None => N::zero(),
+		};
+		self.last_position = Some(position);
+		self.speed += (speed - self.speed) * ease;
+
+		// Two steps a block and a half apart per cycle
+		let stride: N = convert(1.5);
+		self.phase = (self.phase + self.speed * seconds * N::pi() / stride) % N::two_pi();
+		let bob = if walking { (self.speed / self.widen_from).min(N::one()) } else { N::zero() };
+		self.bob += (bob - self.bob) * ease;
+	}
+
+	// `camera` moved along its bob, which sways side to side once a cycle and dips with each step
+	pub fn apply(&self, camera: &SimpleCamera<N>) -> SimpleCamera<N> {
+		let mut camera = camera.clone();
+		if self.bobbing {
+			let side = camera.direction.cross(&camera.up).try_normalize(N::default_epsilon()).unwrap_or(Vector3::zeros());
+			let sway = self.phase.sin() * convert::<f64, N>(0.03) * self.bob;
+			let dip = -self.phase.sin().abs() * convert::<f64, N>(0.05) * self.bob;
+			camera.position += side * sway + camera.up * dip;
+		}
+		camera
+	}
+
+	// What to multiply the field of view by
+	pub fn fov_scale(&self) -> N {
+		if !self.widening || self.widen_to <= self.widen_from {
+			return N::one();
+		}
+		let t = ((self.speed - self.widen_from) / (self.widen_to - self.widen_from)).max(N::zero()).min(N::one());
+		N::one() + convert::<f64, N>(0.15) * t
+	}
+}
+
+// The six clip planes of a projection, for culling and prioritizing by what's in view
+pub struct Frustum<N: Real = f32> {
+	// Normals point inwards and are unit length, so w is the distance from the origin
+	planes: [Vector4<N>; 6],
+}
+
+impl <N: Real> Frustum<N> {
+	// `projection` is the combined perspective and view matrix
+	pub fn from_matrix(projection: &Matrix4<N>) -> Frustum<N> {
+		let row = |i: usize| projection.row(i).transpose();
+		let mut planes = [
+			row(3) + row(0), row(3) - row(0),
+			row(3) + row(1), row(3) - row(1),
+			row(3) + row(2), row(3) - row(2),
+		];
+		for plane in planes.iter_mut() {
+			let length = Vector3::new(plane.x, plane.y, plane.z).norm();
+			*plane /= length;
+		}
+		Frustum { planes: planes }
+	}
+
+	pub fn intersects_sphere(&self, center: &Point3<N>, radius: N) -> bool {
+		self.planes.iter().all(|plane| plane.x * center.x + plane.y * center.y + plane.z * center.z + plane.w >= -radius)
+	}
+}
+
 implement_vertex!(Vertex, position, normal, color);
+
+// The same curve as decode() in standard.frag, for colors that reach the GPU without passing
+// through a lit program
+pub fn srgb_to_linear(c: f32) -> f32 {
+	if c <= 0.04045 {
+		c / 12.92
+	} else {
+		((c + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+// Chunk-local position plus normal index, a block palette index and the face's light as packed by
+// `Light`. Decoded in chunk_packed.vert.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct PackedVertex {
+	pub packed_position: [u8; 4],
+	pub color_index: u16,
+	pub light: u16,
+}
+
+implement_vertex!(PackedVertex, packed_position, color_index, light);
+
+// `wave` is 1 on the surface of a liquid and 0 elsewhere, scaling the offset applied in liquid.vert
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+pub struct LiquidVertex {
+	pub position: [f32; 3],
+	pub normal:   [f32; 3],
+	pub color:    [f32; 3],
+	pub wave:     f32,
+}
+
+implement_vertex!(LiquidVertex, position, normal, color, wave);
+
+// In pixels from the top left of the window
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+pub struct OverlayVertex {
+	pub position: [f32; 2],
+	pub color:    [f32; 4],
+}
+
+implement_vertex!(OverlayVertex, position, color);
+
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+pub struct ChunkInstance {
+	pub chunk_offset: [f32; 3],
+}
+
+implement_vertex!(ChunkInstance, chunk_offset);
+
+use glium::vertex::BufferCreationError as VertexBufferCreationError;
+use glium::index::BufferCreationError as IndexBufferCreationError;
+
+#[derive(Debug, Copy, Clone)]
+pub enum MeshCreationError {
+	VertexBufferCreationFailed(VertexBufferCreationError),
+	IndexBufferCreationFailed(IndexBufferCreationError),
+	// More vertices or indices than a chunk arena page holds
+	TooLarge,
+}
+
+impl From<VertexBufferCreationError> for MeshCreationError {
+	fn from(err: VertexBufferCreationError) -> MeshCreationError {
+		MeshCreationError::VertexBufferCreationFailed(err)
+	}
+}
+
+impl From<IndexBufferCreationError> for MeshCreationError {
+	fn from(err: IndexBufferCreationError) -> MeshCreationError {
+		MeshCreationError::IndexBufferCreationFailed(err)
+	}
+}
+
+use std::collections::HashMap;
+use std::cmp::max;
+use std::mem::size_of;
+use glium::{VertexBuffer, IndexBuffer};
+use glium::index::PrimitiveType;
+use glium::backend::Facade;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VertexFormat {
+	Standard,
+	Packed,
+}
+
+pub enum MeshVertices {
+	Standard(VertexBuffer<Vertex>),
+	Packed(VertexBuffer<PackedVertex>),
+	Liquid(VertexBuffer<LiquidVertex>),
+}
+
+pub struct Mesh {
+	pub vertices: MeshVertices,
+	pub indices: IndexBuffer<u16>,
+	pub vertex_count: usize,
+	pub index_count: usize,
+}
+
+impl Mesh {
+	// Of GPU memory, whether or not all of it is in use
+	pub fn byte_size(&self) -> usize {
+		let vertices = match self.vertices {
+			MeshVertices::Standard(ref buffer) => buffer.len() * size_of::<Vertex>(),
+			MeshVertices::Packed(ref buffer) => buffer.len() * size_of::<PackedVertex>(),
+			MeshVertices::Liquid(ref buffer) => buffer.len() * size_of::<LiquidVertex>(),
+		};
+		vertices + self.indices.len() * size_of::<u16>()
+	}
+}
+
+pub trait MeshVertex: ::glium::Vertex {
+	fn pool(pool: &mut MeshPool) -> &mut HashMap<usize, Vec<VertexBuffer<Self>>>;
+	fn wrap(buffer: VertexBuffer<Self>) -> MeshVertices;
+}
+
+impl MeshVertex for Vertex {
+	fn pool(pool: &mut MeshPool) -> &mut HashMap<usize, Vec<VertexBuffer<Vertex>>> { &mut pool.vertex_buffers }
+	fn wrap(buffer: VertexBuffer<Vertex>) -> MeshVertices { MeshVertices::Standard(buffer) }
+}
+
+impl MeshVertex for PackedVertex {
+	fn pool(pool: &mut MeshPool) -> &mut HashMap<usize, Vec<VertexBuffer<PackedVertex>>> { &mut pool.packed_vertex_buffers }
+	fn wrap(buffer: VertexBuffer<PackedVertex>) -> MeshVertices { MeshVertices::Packed(buffer) }
+}
+
+impl MeshVertex for LiquidVertex {
+	fn pool(pool: &mut MeshPool) -> &mut HashMap<usize, Vec<VertexBuffer<LiquidVertex>>> { &mut pool.liquid_vertex_buffers }
+	fn wrap(buffer: VertexBuffer<LiquidVertex>) -> MeshVertices { MeshVertices::Liquid(buffer) }
+}
+
+const MIN_POOLED_LEN: usize = 256;
+const MAX_POOLED_PER_SIZE: usize = 16;
+
+// Recycles dynamic buffers between remeshes. Buffers are bucketed by power-of-two capacity and
+// only the used prefix of each is written and drawn.
+pub struct MeshPool {
+	vertex_buffers: HashMap<usize, Vec<VertexBuffer<Vertex>>>,
+	packed_vertex_buffers: HashMap<usize, Vec<VertexBuffer<PackedVertex>>>,
+	liquid_vertex_buffers: HashMap<usize, Vec<VertexBuffer<LiquidVertex>>>,
+	index_buffers: HashMap<usize, Vec<IndexBuffer<u16>>>,
+}
+
+impl MeshPool {
+	pub fn new() -> MeshPool {
+		MeshPool {
+			vertex_buffers: HashMap::new(),
+			packed_vertex_buffers: HashMap::new(),
+			liquid_vertex_buffers: HashMap::new(),
+			index_buffers: HashMap::new(),
+		}
+	}
+
+	// How many buffers are waiting to be reused, and how many bytes of GPU memory they hold
+	pub fn usage(&self) -> (usize, usize) {
+		fn add<T>(pool: &HashMap<usize, Vec<T>>, element_size: usize, usage: &mut (usize, usize)) {
+			for (&capacity, buffers) in pool {
+				usage.0 += buffers.len();
+				usage.1 += buffers.len() * capacity * element_size;
+			}
+		}
+
+		let mut usage = (0, 0);
+		add(&self.vertex_buffers, size_of::<Vertex>(), &mut usage);
+		add(&self.packed_vertex_buffers, size_of::<PackedVertex>(), &mut usage);
+		add(&self.liquid_vertex_buffers, size_of::<LiquidVertex>(), &mut usage);
+		add(&self.index_buffers, size_of::<u16>(), &mut usage);
+		usage
+	}
+
+	fn bucket(len: usize) -> usize {
+		max(len, MIN_POOLED_LEN).next_power_of_two()
+	}
+
+	fn release<T>(pool: &mut HashMap<usize, Vec<T>>, capacity: usize, buffer: T) {
+		let bucket = pool.entry(capacity).or_insert_with(Vec::new);
+		if bucket.len() < MAX_POOLED_PER_SIZE {
+			bucket.push(buffer);
+		}
+	}
+
+	pub fn upload<F: Facade, V: MeshVertex>(&mut self, facade: &F, vertices: &[V], indices: &[u16]) -> Result<Mesh, MeshCreationError> {
+		let vertex_capacity = MeshPool::bucket(vertices.len());
+		let vertex_buffer = match V::pool(self).get_mut(&vertex_capacity).and_then(|pool| pool.pop()) {
+			Some(buffer) => buffer,
+			None => VertexBuffer::empty_dynamic(facade, vertex_capacity)?,
+		};
+
+		let index_capacity = MeshPool::bucket(indices.len());
+		let index_buffer = match self.index_buffers.get_mut(&index_capacity).and_then(|pool| pool.pop()) {
+			Some(buffer) => buffer,
+			None => IndexBuffer::empty_dynamic(facade, PrimitiveType::TrianglesList, index_capacity)?,
+		};
+
+		if !vertices.is_empty() {
+			vertex_buffer.slice(0..vertices.len()).unwrap().write(vertices);
+			index_buffer.slice(0..indices.len()).unwrap().write(indices);
+		}
+
+		Ok(Mesh {
+			vertices: V::wrap(vertex_buffer),
+			indices: index_buffer,
+			vertex_count: vertices.len(),
+			index_count: indices.len(),
+		})
+	}
+
+	pub fn recycle(&mut self, mesh: Mesh) {
+		match mesh.vertices {
+			MeshVertices::Standard(buffer) => {
+				let capacity = buffer.len();
+				MeshPool::release(&mut self.vertex_buffers, capacity, buffer);
+			},
+			MeshVertices::Packed(buffer) => {
+				let capacity = buffer.len();
+				MeshPool::release(&mut self.packed_vertex_buffers, capacity, buffer);
+			},
+			MeshVertices::Liquid(buffer) => {
+				let capacity = buffer.len();
+				MeshPool::release(&mut self.liquid_vertex_buffers, capacity, buffer);
+			},
+		}
+
+		let capacity = mesh.indices.len();
+		MeshPool::release(&mut self.index_buffers, capacity, mesh.indices);
+	}
+}
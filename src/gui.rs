@@ -0,0 +1,217 @@
+use std::cell::{Ref, RefCell};
+
+use glium::glutin::{Event, WindowEvent, ElementState, MouseButton};
+use glium::glutin::dpi::LogicalPosition;
+
+use engine::InputService;
+use overlay::{Overlay, GLYPH_ADVANCE, GLYPH_HEIGHT};
+
+// Font pixels are drawn this many screen pixels across
+const SCALE: f32 = 2.0;
+const PADDING: f32 = 6.0;
+const ROW_HEIGHT: f32 = (GLYPH_HEIGHT + 4) as f32 * SCALE;
+const SPACING: f32 = 4.0;
+
+const WINDOW: [f32; 4] = [0.08, 0.08, 0.1, 0.85];
+const TITLE_BAR: [f32; 4] = [0.2, 0.3, 0.5, 0.95];
+const WIDGET: [f32; 4] = [0.25, 0.25, 0.3, 1.0];
+const HOVERED: [f32; 4] = [0.35, 0.35, 0.45, 1.0];
+const FILLED: [f32; 4] = [0.4, 0.5, 0.75, 1.0];
+const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// Immediate mode widgets: every frame, whatever's on screen is built again from scratch by
+// calling `window` during the update, and reports what was clicked or dragged since the last
+// frame. Everything built is drawn on top of the frame by `Game::draw`.
+pub struct GuiService {
+	gui: RefCell<Gui>,
+}
+
+struct Gui {
+	overlay: Overlay,
+	// In pixels from the top left, like the overlay
+	mouse: [f32; 2],
+	mouse_down: bool,
+	// Whether the button went down since the last frame
+	pressed: bool,
+	// Widgets are numbered in the order they're built, which stays the same from frame to frame
+	// while the layout does
+	next_id: u32,
+	// The slider being dragged
+	dragging: Option<u32>,
+	over_window: bool,
+	over_window_last_frame: bool,
+}
+
+impl GuiService {
+	pub fn new() -> GuiService {
+		GuiService {
+			gui: RefCell::new(Gui {
+				overlay: Overlay::new(),
+				mouse: [-1.0; 2],
+				mouse_down: false,
+				pressed: false,
+				next_id: 0,
+				dragging: None,
+				over_window: false,
+				over_window_last_frame: false,
+			}),
+		}
+	}
+
+	// Takes in the frame's mouse events and clears away the last frame's widgets
+	pub fn begin_frame(&self, input: &InputService) {
+		let mut gui = self.gui.borrow_mut();
+		let scale = input.hidpi_factor() as f32;
+		gui.pressed = false;
+		for ev in input.events() {
+			match ev {
+				&Event::WindowEvent { event: WindowEvent::CursorMoved { position: LogicalPosition { x, y }, .. }, .. } => {
+					gui.mouse = [x as f32 * scale, y as f32 * scale];
+				},
+				&Event::WindowEvent { event: WindowEvent::MouseInput { state, button: MouseButton::Left, .. }, .. } => {
+					gui.mouse_down = state == ElementState::Pressed;
+					gui.pressed |= gui.mouse_down;
+					if !gui.mouse_down {
+						gui.dragging = None;
+					}
+				},
+				_ => (),
+			}
+		}
+
+		gui.overlay = Overlay::new();
+		gui.next_id = 0;
+		gui.over_window_last_frame = gui.over_window;
+		gui.over_window = false;
+	}
+
+	// Whether the mouse was over a window last frame, in which case clicks are meant for it
+	pub fn wants_mouse(&self) -> bool {
+		self.gui.borrow().over_window_last_frame
+	}
+
+	// A titled panel with its top left at `position`, as tall as the widgets `build` puts in it
+	pub fn window<F: FnOnce(&mut Ui)>(&self, title: &str, position: [f32; 2], width: f32, build: F) {
+		let mut gui = self.gui.borrow_mut();
+		let top = position[1] + ROW_HEIGHT;
+		let height = {
+			let mut ui = Ui {
+				gui: &mut *gui,
+				overlay: Overlay::new(),
+				x: position[0] + PADDING,
+				y: top + PADDING,
+				width: width - PADDING * 2.0,
+			};
+			build(&mut ui);
+			let height = ui.y - SPACING + PADDING - top;
+			let contents = ui.overlay;
+
+			ui.gui.overlay.rect(position[0], position[1], width, ROW_HEIGHT, TITLE_BAR);
+			ui.gui.overlay.text(position[0] + PADDING, position[1] + SCALE * 2.0, SCALE, title, TEXT);
+			ui.gui.overlay.rect(position[0], top, width, height, WINDOW);
+			ui.gui.overlay.append(&contents);
+			height
+		};
+
+		if contains([position[0], position[1], width, ROW_HEIGHT + height], gui.mouse) {
+			gui.over_window = true;
+		}
+	}
+
+	pub fn overlay(&self) -> Ref<Overlay> {
+		Ref::map(self.gui.borrow(), |gui| &gui.overlay)
+	}
+}
+
+// Lays widgets out top to bottom within a window
+pub struct Ui<'a> {
+	gui: &'a mut Gui,
+	overlay: Overlay,
+	x: f32,
+	y: f32,
+	width: f32,
+}
+
+impl<'a> Ui<'a> {
+	pub fn label(&mut self, text: &str) {
+		let y = self.y;
+		self.text(self.x, y, text);
+		self.next_row();
+	}
+
+	// True when clicked
+	pub fn button(&mut self, text: &str) -> bool {
+		let area = self.row();
+		let hovered = contains(area, self.gui.mouse);
+		self.overlay.rect(area[0], area[1], area[2], area[3], if hovered { HOVERED } else { WIDGET });
+		self.text(area[0] + PADDING, area[1], text);
+		self.next_row();
+		hovered && self.gui.pressed
+	}
+
+	// True when toggled
+	pub fn checkbox(&mut self, text: &str, value: &mut bool) -> bool {
+		let area = self.row();
+		let hovered = contains([area[0], area[1], ROW_HEIGHT, ROW_HEIGHT], self.gui.mouse);
+		self.overlay.rect(area[0], area[1], ROW_HEIGHT, ROW_HEIGHT, if hovered { HOVERED } else { WIDGET });
+		if *value {
+			let inset = ROW_HEIGHT / 4.0;
+			self.overlay.rect(area[0] + inset, area[1] + inset, ROW_HEIGHT - inset * 2.0, ROW_HEIGHT - inset * 2.0, FILLED);
+		}
+		self.text(area[0] + ROW_HEIGHT + PADDING, area[1], text);
+		self.next_row();
+
+		let toggled = hovered && self.gui.pressed;
+		if toggled {
+			*value = !*value;
+		}
+		toggled
+	}
+
+	// Dragged to anywhere from `min` to `max`; true while it's changing
+	pub fn slider(&mut self, text: &str, value: &mut f32, min: f32, max: f32) -> bool {
+		let id = self.gui.next_id;
+		self.gui.next_id += 1;
+		let area = self.row();
+		let hovered = contains(area, self.gui.mouse);
+		if hovered && self.gui.pressed {
+			self.gui.dragging = Some(id);
+		}
+
+		let mut changed = false;
+		if self.gui.dragging == Some(id) {
+			let fraction = ((self.gui.mouse[0] - area[0]) / area[2]).max(0.0).min(1.0);
+			let dragged = min + fraction * (max - min);
+			changed = dragged != *value;
+			*value = dragged;
+		}
+
+		let fraction = ((*value - min) / (max - min)).max(0.0).min(1.0);
+		self.overlay.rect(area[0], area[1], area[2], area[3], if hovered { HOVERED } else { WIDGET });
+		self.overlay.rect(area[0], area[1], area[2] * fraction, area[3], FILLED);
+		self.text(area[0] + PADDING, area[1], &format!("{}: {:.2}", text, value));
+		self.next_row();
+		changed
+	}
+
+	// x, y, width and height of the next widget
+	fn row(&self) -> [f32; 4] {
+		[self.x, self.y, self.width, ROW_HEIGHT]
+	}
+
+	fn next_row(&mut self) {
+		self.y += ROW_HEIGHT + SPACING;
+	}
+
+	// Vertically centred in a row starting at y
+	fn text(&mut self, x: f32, y: f32, text: &str) {
+		// Whatever doesn't fit is cut off
+		let fits = ((self.x + self.width - x) / (GLYPH_ADVANCE as f32 * SCALE)).max(0.0) as usize;
+		let text: String = text.chars().take(fits).collect();
+		self.overlay.text(x, y + SCALE * 2.0, SCALE, &text, TEXT);
+	}
+}
+
+fn contains(area: [f32; 4], point: [f32; 2]) -> bool {
+	point[0] >= area[0] && point[0] < area[0] + area[2] && point[1] >= area[1] && point[1] < area[1] + area[3]
+}
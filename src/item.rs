@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+
+use rand::Rng;
+use time::Duration;
+
+use entity::{Entities, Entity, EntityId, Aabb};
+use random::StreamRng;
+
+const DROP_SIZE: f32 = 0.25;
+// In radians per second
+const SPIN_SPEED: f32 = 2.0;
+const PICKUP_RADIUS: f32 = 1.5;
+const POP_SPEED: f32 = 4.0;
+
+// Drops can't be collected straight away, so they're seen popping out of the block
+fn pickup_delay() -> Duration { Duration::milliseconds(500) }
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Inventory {
+	counts: BTreeMap<usize, u32>,
+}
+
+impl Inventory {
+	pub fn new() -> Inventory {
+		Inventory { counts: BTreeMap::new() }
+	}
+
+	pub fn count(&self, block: usize) -> u32 {
+		self.counts.get(&block).cloned().unwrap_or(0)
+	}
+
+	pub fn add(&mut self, block: usize, count: u32) {
+		*self.counts.entry(block).or_insert(0) += count;
+	}
+
+	// Fails without removing anything if there aren't `count` of the block
+	pub fn remove(&mut self, block: usize, count: u32) -> bool {
+		let remaining = match self.counts.get(&block) {
+			Some(&have) if have >= count => have - count,
+			_ => return false,
+		};
+		if remaining == 0 {
+			self.counts.remove(&block);
+		} else {
+			self.counts.insert(block, remaining);
+		}
+		true
+	}
+
+	pub fn iter(&self) -> btree_map::Iter<usize, u32> {
+		self.counts.iter()
+	}
+}
+
+struct ItemDrop {
+	entity: EntityId,
+	block: usize,
+	age: Duration,
+}
+
+// Small spinning cubes left behind by broken blocks, which go into the inventory once the
+// player comes close
+pub struct ItemDrops {
+	drops: Vec<ItemDrop>,
+	rng: StreamRng,
+}
+
+impl ItemDrops {
+	pub fn new(rng: StreamRng) -> ItemDrops {
+		ItemDrops { drops: Vec::new(), rng: rng }
+	}
+
+	pub fn len(&self) -> usize {
+		self.drops.len()
+	}
+
+	pub fn contains(&self, entity: EntityId) -> bool {
+		self.drops.iter().any(|drop| drop.entity == entity)
+	}
+
+	pub fn spawn(&mut self, entities: &mut Entities, pos: [i64; 3], block: usize, color: [f32; 3]) -> EntityId {
+		let center = [pos[0] as f32 + 0.5, pos[1] as f32 + 0.5, pos[2] as f32 + 0.5 - DROP_SIZE / 2.0];
+		let mut entity = Entity::new(center, Aabb::standing(DROP_SIZE, DROP_SIZE), color);
+		entity.velocity = [
+			(self.rng.gen::<f32>() - 0.5) * POP_SPEED / 2.0,
+			(self.rng.gen::<f32>() - 0.5) * POP_SPEED / 2.0,
+			POP_SPEED,
+		];
+		entity.yaw = self.rng.gen::<f32>() * ::std::f32::consts::PI * 2.0;
+
+		let id = entities.spawn(entity);
+		self.drops.push(ItemDrop { entity: id, block: block, age: Duration::zero() });
+		id
+	}
+
+	// Returns how many drops the player collected
+	pub fn tick(&mut self, entities: &mut Entities, player: [f32; 3], inventory: &mut Inventory, time_elapsed: &Duration) -> usize {
+		let dt = time_elapsed.num_microseconds().unwrap_or(0) as f32 / 1_000_000.0;
+		self.drops.retain(|drop| entities.get(drop.entity).is_some());
+
+		let mut collected = Vec::new();
+		for (i, drop) in self.drops.iter_mut().enumerate() {
+			drop.age = drop.age + *time_elapsed;
+			let entity = entities.get_mut(drop.entity).unwrap();
+			entity.yaw = (entity.yaw + SPIN_SPEED * dt) % (::std::f32::consts::PI * 2.0);
+
+			let center = [entity.position[0], entity.position[1], entity.position[2] + DROP_SIZE / 2.0];
+			let distance_squared: f32 = (0..3).map(|axis| (center[axis] - player[axis]).powi(2)).sum();
+			if drop.age >= pickup_delay() && distance_squared <= PICKUP_RADIUS * PICKUP_RADIUS {
+				collected.push(i);
+			}
+		}
+
+		let count = collected.len();
+		for i in collected.into_iter().rev() {
+			let drop = self.drops.remove(i);
+			entities.despawn(drop.entity);
+			inventory.add(drop.block, 1);
+		}
+		count
+	}
+}
@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+pub const DEFAULT_JOURNAL_BUDGET: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlockChange {
+	pub pos: [i64; 3],
+	pub old: usize,
+	pub new: usize,
+}
+
+// Records block changes grouped into undoable actions. Actions nest, only the outermost one
+// ending commits it. The oldest actions are forgotten once the history outgrows its budget.
+pub struct EditJournal {
+	budget: usize,
+	usage: usize,
+	undo: VecDeque<Vec<BlockChange>>,
+	redo: Vec<Vec<BlockChange>>,
+	open: Vec<BlockChange>,
+	depth: usize,
+}
+
+impl EditJournal {
+	pub fn new(budget: usize) -> EditJournal {
+		EditJournal {
+			budget: budget,
+			usage: 0,
+			undo: VecDeque::new(),
+			redo: Vec::new(),
+			open: Vec::new(),
+			depth: 0,
+		}
+	}
+
+	pub fn memory_usage(&self) -> usize { self.usage }
+	pub fn can_undo(&self) -> bool { !self.undo.is_empty() }
+	pub fn can_redo(&self) -> bool { !self.redo.is_empty() }
+
+	pub fn begin(&mut self) {
+		self.depth += 1;
+	}
+
+	// Changes made outside of any action are not journaled
+	pub fn record(&mut self, change: BlockChange) {
+		if self.depth > 0 {
+			self.open.push(change);
+		}
+	}
+
+	pub fn end(&mut self) {
+		assert!(self.depth > 0, "Ended an edit action that was never begun");
+		self.depth -= 1;
+		if self.depth > 0 || self.open.is_empty() {
+			return;
+		}
+
+		let action = ::std::mem::replace(&mut self.open, Vec::new());
+		self.redo.clear();
+		self.push_undo(action);
+	}
+
+	pub fn take_undo(&mut self) -> Option<Vec<BlockChange>> {
+		let action = self.undo.pop_back()?;
+		self.usage -= EditJournal::cost(&action);
+		Some(action)
+	}
+
+	pub fn take_redo(&mut self) -> Option<Vec<BlockChange>> {
+		self.redo.pop()
+	}
+
+	// An undone action, ready to be redone
+	pub fn push_redo(&mut self, action: Vec<BlockChange>) {
+		self.redo.push(action);
+	}
+
+	pub fn push_undo(&mut self, action: Vec<BlockChange>) {
+		self.usage += EditJournal::cost(&action);
+		self.undo.push_back(action);
+		while self.usage > self.budget {
+			match self.undo.pop_front() {
+				Some(oldest) => self.usage -= EditJournal::cost(&oldest),
+				None => break,
+			}
+		}
+	}
+
+	fn cost(action: &[BlockChange]) -> usize {
+		action.len() * size_of::<BlockChange>()
+	}
+}
@@ -0,0 +1,77 @@
+#[macro_use]
+extern crate glium;
+
+#[macro_use]
+extern crate bitflags;
+
+extern crate time;
+extern crate nalgebra as na;
+extern crate rand;
+extern crate noise;
+extern crate ndarray;
+extern crate image;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate bincode;
+extern crate zstd;
+extern crate rodio;
+extern crate clipboard;
+
+#[macro_use]
+pub mod profile;
+pub mod gl_util;
+pub mod chunk_batch;
+pub mod camera_path;
+pub mod block;
+pub mod orientation;
+pub mod biome;
+pub mod light;
+pub mod visibility;
+pub mod engine;
+pub mod save;
+pub mod chunk_cache;
+pub mod chunk_map;
+pub mod generation_queue;
+pub mod worldgen;
+pub mod random;
+pub mod preview;
+pub mod schematic;
+pub mod vox;
+pub mod journal;
+pub mod block_update;
+pub mod scheduler;
+pub mod falling;
+pub mod water;
+pub mod entity;
+pub mod item;
+pub mod mob;
+pub mod console;
+pub mod text_input;
+pub mod settings;
+pub mod actions;
+pub mod debug_draw;
+pub mod overlay;
+pub mod post;
+pub mod render_pass;
+pub mod environment;
+pub mod chat;
+pub mod server;
+pub mod simulation;
+pub mod client;
+pub mod net;
+pub mod mirror;
+pub mod audio;
+pub mod music;
+pub mod weather;
+pub mod game_rules;
+pub mod stats;
+pub mod gui;
+pub mod settings_menu;
+pub mod block_picker;
+pub mod selection;
+pub mod prefabs;
+pub mod toast;
+pub mod metrics;
+pub mod crash;
+pub mod error;
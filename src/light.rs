@@ -0,0 +1,262 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use block::{World, BlockProperties, NEIGHBOUR_OFFSETS};
+use block_update::UpdateKind;
+
+// Light levels run from 0, dark, up to this
+pub const MAX_LIGHT: u8 = 15;
+// Cells visited beyond this are left queued for the next tick
+pub const MAX_RELIGHT_STEPS_PER_TICK: usize = 16384;
+// How far from an edit relighting may reach on any axis. Sky light falls without fading, so
+// without a limit an opening over a void would relight forever.
+pub const RELIGHT_RADIUS: i64 = 64;
+const DOWN: [i64; 3] = [0, 0, -1];
+
+// Sky light is white; block light is colored, so each primary has its own level
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Channel {
+	Sky,
+	Red,
+	Green,
+	Blue,
+}
+
+pub const CHANNELS: [Channel; 4] = [Channel::Sky, Channel::Red, Channel::Green, Channel::Blue];
+
+impl Channel {
+	// Which nibble of a packed Light holds the channel, counting from the lowest
+	fn nibble(&self) -> u16 {
+		match *self {
+			Channel::Sky => 3,
+			Channel::Red => 2,
+			Channel::Green => 1,
+			Channel::Blue => 0,
+		}
+	}
+}
+
+// A cell's light, a nibble each for the sky and the red, green and blue of light from blocks,
+// sky highest
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Light(u16);
+
+impl Light {
+	pub const DARK: Light = Light(0);
+	// Open to the sky with nothing glowing nearby. Everything is lit like this until light is
+	// propagated.
+	pub const SKY: Light = Light((MAX_LIGHT as u16) << 12);
+
+	// Levels above MAX_LIGHT are clamped
+	pub fn new(sky: u8, block: [u8; 3]) -> Light {
+		Light::DARK
+			.with(Channel::Sky, sky)
+			.with(Channel::Red, block[0])
+			.with(Channel::Green, block[1])
+			.with(Channel::Blue, block[2])
+	}
+
+	pub fn from_packed(packed: u16) -> Light {
+		Light(packed)
+	}
+
+	pub fn packed(&self) -> u16 {
+		self.0
+	}
+
+	pub fn sky(&self) -> u8 {
+		self.get(Channel::Sky)
+	}
+
+	pub fn block(&self) -> [u8; 3] {
+		[self.get(Channel::Red), self.get(Channel::Green), self.get(Channel::Blue)]
+	}
+
+	pub fn get(&self, channel: Channel) -> u8 {
+		(self.0 >> (channel.nibble() * 4) & 0x0f) as u8
+	}
+
+	pub fn with(&self, channel: Channel, level: u8) -> Light {
+		let shift = channel.nibble() * 4;
+		Light(self.0 & !(0x0f << shift) | (level.min(MAX_LIGHT) as u16) << shift)
+	}
+
+	// What a white surface looks like in this light, each component from 0 to 1
+	pub fn color(&self) -> [f32; 3] {
+		let sky = self.sky();
+		let block = self.block();
+		[
+			sky.max(block[0]) as f32 / MAX_LIGHT as f32,
+			sky.max(block[1]) as f32 / MAX_LIGHT as f32,
+			sky.max(block[2]) as f32 / MAX_LIGHT as f32,
+		]
+	}
+}
+
+impl Default for Light {
+	fn default() -> Light {
+		Light::SKY
+	}
+}
+
+// Keeps stored light up to date as blocks change, without reflooding whole chunks. Light an edit
+// takes away is removed breadth first from the edited cell, and anything lit from elsewhere that
+// the removal runs into is spread back in, so only the cells whose light depends on the edit are
+// visited.
+pub struct Lighting {
+	properties: Rc<Vec<BlockProperties>>,
+	edits: Rc<RefCell<Vec<[i64; 3]>>>,
+	// Each entry carries the edit it came from, which bounds how far it may spread
+	add: VecDeque<([i64; 3], Channel, [i64; 3])>,
+	// With the level the cell had before it was cleared
+	remove: VecDeque<([i64; 3], Channel, u8, [i64; 3])>,
+}
+
+impl Lighting {
+	pub fn new(world: &World, properties: Rc<Vec<BlockProperties>>) -> Lighting {
+		let edits = Rc::new(RefCell::new(Vec::new()));
+
+		{
+			let edits = edits.clone();
+			world.subscribe(move |_, update| {
+				if let UpdateKind::Changed { .. } = update.kind {
+					edits.borrow_mut().push(update.pos);
+				}
+			});
+		}
+
+		Lighting {
+			properties: properties,
+			edits: edits,
+			add: VecDeque::new(),
+			remove: VecDeque::new(),
+		}
+	}
+
+	pub fn pending(&self) -> usize {
+		self.edits.borrow().len() + self.add.len() + self.remove.len()
+	}
+
+	// Removals all run before any light is spread, so nothing is spread from a cell that's about
+	// to go dark
+	pub fn tick(&mut self, world: &World) {
+		let edits: Vec<[i64; 3]> = self.edits.borrow_mut().drain(..).collect();
+		for pos in edits {
+			self.block_changed(world, pos);
+		}
+
+		for _ in 0..MAX_RELIGHT_STEPS_PER_TICK {
+			if let Some((pos, channel, level, origin)) = self.remove.pop_front() {
+				self.unspread(world, pos, channel, level, origin);
+			} else if let Some((pos, channel, origin)) = self.add.pop_front() {
+				self.spread(world, pos, channel, origin);
+			} else {
+				break;
+			}
+		}
+	}
+
+	fn block_changed(&mut self, world: &World, pos: [i64; 3]) {
+		let block = world.get_block(pos);
+		for &channel in CHANNELS.iter() {
+			let light = world.get_light(pos);
+			let level = light.get(channel);
+			if level > 0 {
+				world.set_light(pos, light.with(channel, 0));
+				self.remove.push_back((pos, channel, level, pos));
+			}
+
+			let emitted = self.emission(block, channel);
+			if emitted > 0 {
+				world.set_light(pos, world.get_light(pos).with(channel, emitted));
+				self.add.push_back((pos, channel, pos));
+			}
+
+			// A cell that was dark has no removal to find its lit neighbours
+			if !self.opaque(block) {
+				for offset in NEIGHBOUR_OFFSETS.iter() {
+					let neighbour = offset_by(pos, *offset);
+					if world.get_light(neighbour).get(channel) > 0 {
+						self.add.push_back((neighbour, channel, pos));
+					}
+				}
+			}
+		}
+	}
+
+	fn unspread(&mut self, world: &World, pos: [i64; 3], channel: Channel, level: u8, origin: [i64; 3]) {
+		for offset in NEIGHBOUR_OFFSETS.iter() {
+			let neighbour = offset_by(pos, *offset);
+			if !within_reach(neighbour, origin) {
+				continue;
+			}
+
+			let light = world.get_light(neighbour);
+			let neighbour_level = light.get(channel);
+			if neighbour_level == 0 {
+				continue;
+			}
+
+			let lit_from_here = neighbour_level < level
+				|| (channel == Channel::Sky && *offset == DOWN && level == MAX_LIGHT && neighbour_level == MAX_LIGHT);
+			if lit_from_here {
+				world.set_light(neighbour, light.with(channel, 0));
+				self.remove.push_back((neighbour, channel, neighbour_level, origin));
+
+				let emitted = self.emission(world.get_block(neighbour), channel);
+				if emitted > 0 {
+					world.set_light(neighbour, light.with(channel, emitted));
+					self.add.push_back((neighbour, channel, origin));
+				}
+			} else {
+				// Lit from somewhere else, so it can light back what was just cleared
+				self.add.push_back((neighbour, channel, origin));
+			}
+		}
+	}
+
+	fn spread(&mut self, world: &World, pos: [i64; 3], channel: Channel, origin: [i64; 3]) {
+		let level = world.get_light(pos).get(channel);
+		if level <= 1 {
+			return;
+		}
+
+		for offset in NEIGHBOUR_OFFSETS.iter() {
+			let neighbour = offset_by(pos, *offset);
+			if !within_reach(neighbour, origin) || self.opaque(world.get_block(neighbour)) {
+				continue;
+			}
+
+			// Sunlight falls straight down undimmed
+			let spread = if channel == Channel::Sky && *offset == DOWN && level == MAX_LIGHT { MAX_LIGHT } else { level - 1 };
+			let light = world.get_light(neighbour);
+			if light.get(channel) < spread {
+				world.set_light(neighbour, light.with(channel, spread));
+				self.add.push_back((neighbour, channel, origin));
+			}
+		}
+	}
+
+	fn opaque(&self, block: usize) -> bool {
+		self.properties.get(block).map(|props| props.opaque).unwrap_or(true)
+	}
+
+	fn emission(&self, block: usize, channel: Channel) -> u8 {
+		let emission = self.properties.get(block).map(|props| props.emission).unwrap_or([0; 3]);
+		match channel {
+			Channel::Sky => 0,
+			Channel::Red => emission[0].min(MAX_LIGHT),
+			Channel::Green => emission[1].min(MAX_LIGHT),
+			Channel::Blue => emission[2].min(MAX_LIGHT),
+		}
+	}
+}
+
+fn offset_by(pos: [i64; 3], offset: [i64; 3]) -> [i64; 3] {
+	[pos[0] + offset[0], pos[1] + offset[1], pos[2] + offset[2]]
+}
+
+fn within_reach(pos: [i64; 3], origin: [i64; 3]) -> bool {
+	(0..3).all(|axis| (pos[axis] - origin[axis]).abs() <= RELIGHT_RADIUS)
+}
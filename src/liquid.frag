@@ -0,0 +1,51 @@
+#version 140
+
+in vec3 v_normal;
+in vec3 v_position;
+in vec3 v_color;
+
+out vec4 color;
+
+// Set once a frame from an Environment; see EnvironmentBlock
+layout(std140) uniform environment {
+	// Towards the sun, in view space
+	vec4 sun_direction;
+	vec4 sun_color;
+	vec4 ambient_color;
+	// The color, with the density in w
+	vec4 fog;
+	float sky_light;
+};
+// Colors come in as sRGB; when set, they're lit in linear space and the framebuffer encodes the
+// result back to sRGB
+uniform bool linear;
+
+const vec3 specular_color = vec3(0.7, 0.7, 0.7);
+const float opacity = 0.6;
+
+vec3 decode(vec3 srgb) {
+	if (!linear) {
+		return srgb;
+	}
+	vec3 low = srgb / 12.92;
+	vec3 high = pow((srgb + 0.055) / 1.055, vec3(2.4));
+	return mix(high, low, vec3(lessThanEqual(srgb, vec3(0.04045))));
+}
+
+void main() {
+	vec3 albedo = decode(v_color);
+	vec3 light_dir = sun_direction.xyz;
+	float diffuse = max(dot(normalize(v_normal), light_dir), 0.0);
+
+	float specular = 0.0;
+
+	if (diffuse > 0.0) {
+		vec3 camera_dir = normalize(-v_position);
+		vec3 half_direction = normalize(light_dir + camera_dir);
+		specular = diffuse * pow(max(dot(half_direction, normalize(v_normal)), 0.0), 16.0);
+	}
+
+	vec3 lit = ambient_color.rgb * albedo + sun_color.rgb * (diffuse * albedo + specular * decode(specular_color));
+	float visibility = exp(-pow(fog.w * length(v_position), 2.0));
+	color = vec4(mix(decode(fog.rgb), lit, visibility), opacity);
+}
@@ -0,0 +1,31 @@
+#version 150
+in vec3 position;
+in vec3 normal;
+in vec3 color;
+in float wave;
+in vec3 chunk_offset;
+
+out vec3 v_normal;
+out vec3 v_position;
+out vec3 v_color;
+
+uniform mat4 perspective;
+uniform mat4 view;
+uniform float time;
+
+const float wave_amplitude = 0.04;
+
+void main() {
+	vec3 position = position + chunk_offset;
+
+	// Waves only move surface vertices, and depend on world position so neighbouring chunks agree
+	position.z += wave * wave_amplitude * (sin(time * 1.3 + position.x * 0.7) + sin(time * 0.9 + position.y * 0.5));
+
+	vec4 world_position = view * vec4(position, 1.0);
+
+	v_position = vec3(world_position) / world_position.w;
+	v_color = color;
+	gl_Position = perspective * world_position;
+
+	v_normal = mat3(view) * normal;
+}
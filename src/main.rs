@@ -1,45 +1,107 @@
-#[macro_use]
 extern crate glium;
+extern crate time;
+extern crate space3;
 
-#[macro_use]
-extern crate bitflags;
+use std::env;
+use std::path::Path;
+use std::process;
 
-extern crate time;
-extern crate nalgebra as na;
-extern crate rand;
-extern crate noise;
-extern crate ndarray;
+use space3::crash;
+use space3::error::SpaceError;
+use space3::settings::{Settings, SETTINGS_FILE};
+use space3::worldgen::WorldPreset;
+
+// Longer frames are treated as this long
+fn max_frame_time() -> time::Duration { time::Duration::milliseconds(100) }
+
+// space3 --preview <out.png> [--size chunks] [--seed n] [--preset name] [--min-z chunk] [--max-z chunk]
+fn preview(args: &[String]) {
+	let mut args = args.iter();
+	let out = args.next().unwrap_or_else(|| fail("--preview needs an output path"));
+
+	let (mut size, mut seed, mut min_z, mut max_z) = (16usize, 12u32, -4i64, 4i64);
+	let mut preset = WorldPreset::all().remove(0);
+	while let Some(flag) = args.next() {
+		let value = args.next().unwrap_or_else(|| fail(&format!("{} needs a value", flag)));
+		match flag.as_str() {
+			"--size" => size = parse(flag, value),
+			"--seed" => seed = parse(flag, value),
+			"--min-z" => min_z = parse(flag, value),
+			"--max-z" => max_z = parse(flag, value),
+			"--preset" => {
+				preset = WorldPreset::all().into_iter()
+					.find(|preset| preset.label().eq_ignore_ascii_case(value))
+					.unwrap_or_else(|| fail(&format!("Unknown preset {}", value)));
+			},
+			_ => fail(&format!("Unknown option {}", flag)),
+		}
+	}
 
-mod gl_util;
-mod block;
-mod engine;
+	let origin = -(size as i64 / 2);
+	let image = space3::preview::render(&*preset.generator(seed), [origin, origin], size, min_z, max_z);
+	if let Err(err) = image.save(out) {
+		fail(&format!("Failed to write {}: {}", out, err));
+	}
+}
+
+fn parse<T: ::std::str::FromStr>(flag: &str, value: &str) -> T {
+	value.parse().unwrap_or_else(|_| fail(&format!("Invalid value for {}: {}", flag, value)))
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("{}", message);
+	process::exit(1);
+}
 
 fn main() {
-	use time::PreciseTime;
+	let args: Vec<String> = env::args().collect();
+	if let Some(i) = args.iter().position(|arg| arg == "--preview") {
+		preview(&args[i + 1..]);
+		return;
+	}
 
-	use engine::{Game, StatePlaying, DrawService};
+	crash::install();
+	if let Err(err) = run() {
+		fail(&format!("{}", err));
+	}
+}
 
-	let vertex_shader_src   = include_str!("standard.vert");
-	let fragment_shader_src = include_str!("standard.frag");
+fn run() -> Result<(), SpaceError> {
+	use time::PreciseTime;
+	use space3::engine::{Game, StatePlaying};
 
 	let events_loop = glium::glutin::EventsLoop::new();
 	let window = glium::glutin::WindowBuilder::new();
-	let context = glium::glutin::ContextBuilder::new().with_depth_buffer(24);
-	let display = glium::Display::new(window, context, &events_loop)
-			.expect("Failed to initialize display");
-
-	let program = glium::Program::from_source(&display, vertex_shader_src, fragment_shader_src, None).unwrap();
+	let settings = Settings::load_or_create(Path::new(SETTINGS_FILE)).unwrap_or_default();
+	// Glutin only takes powers of two
+	let msaa = if settings.msaa >= 2 { settings.msaa.next_power_of_two() } else { 0 };
+	let mut context = glium::glutin::ContextBuilder::new()
+		.with_depth_buffer(24)
+		.with_vsync(settings.vsync)
+		.with_srgb(settings.linear_lighting);
+	if msaa > 0 {
+		context = context.with_multisampling(msaa);
+	}
+	let display = glium::Display::new(window, context, &events_loop)?;
+	crash::set_renderer(display.get_opengl_vendor_string(), display.get_opengl_renderer_string(), display.get_opengl_version_string());
 
-	let mut game = Game::new(Box::new(StatePlaying::new()), display, events_loop, program);
+	let mut game = Game::new(Box::new(StatePlaying::new()?), display, events_loop, settings.linear_lighting, msaa as u32)?;
 
 	let mut last_tick: PreciseTime = PreciseTime::now();
 
 	while game.is_running() {
 		let old = last_tick;
 		last_tick = PreciseTime::now();
-		let time_elapsed = old.to(last_tick);
+		let mut time_elapsed = old.to(last_tick);
+		// After a stall, like a debugger pause, everything scaled by frame time would jump, so the
+		// frame pretends to have been shorter
+		if time_elapsed > max_frame_time() {
+			println!("Frame took {} ms, clamping it to {} ms", time_elapsed.num_milliseconds(), max_frame_time().num_milliseconds());
+			time_elapsed = max_frame_time();
+		}
 
 		game.update(&time_elapsed);
-		game.draw();
+		game.draw()?;
 	}
+	Ok(())
 }
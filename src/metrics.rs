@@ -0,0 +1,62 @@
+use std::cell::RefCell;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Metric {
+	Count(usize),
+	Bytes(usize),
+}
+
+impl Metric {
+	fn value(&self) -> usize {
+		match *self {
+			Metric::Count(n) | Metric::Bytes(n) => n,
+		}
+	}
+}
+
+impl fmt::Display for Metric {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Metric::Count(n) => write!(f, "{}", n),
+			Metric::Bytes(n) if n >= 1024 * 1024 => write!(f, "{:.1} MiB", n as f64 / (1024.0 * 1024.0)),
+			Metric::Bytes(n) if n >= 1024 => write!(f, "{:.1} KiB", n as f64 / 1024.0),
+			Metric::Bytes(n) => write!(f, "{} B", n),
+		}
+	}
+}
+
+// Named gauges that subsystems keep up to date, alongside the highest each has been, so that
+// something growing without bound shows up in the debug panel.
+pub struct MetricsService {
+	// In the order they were first set
+	metrics: RefCell<Vec<(&'static str, Metric, Metric)>>,
+}
+
+impl MetricsService {
+	pub fn new() -> MetricsService {
+		MetricsService { metrics: RefCell::new(Vec::new()) }
+	}
+
+	pub fn set(&self, name: &'static str, metric: Metric) {
+		let mut metrics = self.metrics.borrow_mut();
+		match metrics.iter_mut().find(|entry| entry.0 == name) {
+			Some(entry) => {
+				entry.1 = metric;
+				if metric.value() > entry.2.value() {
+					entry.2 = metric;
+				}
+			},
+			None => metrics.push((name, metric, metric)),
+		}
+	}
+
+	pub fn get(&self, name: &str) -> Option<Metric> {
+		self.metrics.borrow().iter().find(|entry| entry.0 == name).map(|entry| entry.1)
+	}
+
+	// Each metric's name, current value and peak
+	pub fn all(&self) -> Vec<(&'static str, Metric, Metric)> {
+		self.metrics.borrow().clone()
+	}
+}
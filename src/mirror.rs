@@ -0,0 +1,130 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::mem::replace;
+use std::rc::Rc;
+
+use block::{World, Chunk, ChunkStates, CHUNK_SIZE, chunk_coords};
+use biome::ChunkBiomes;
+use block_update::{SubscriptionId, UpdateKind};
+use generation_queue::{Requester, chunk_priority};
+
+// Sending a whole chunk costs a copy of it, so only this many go out each tick, and at most as
+// many are generated for it
+const CHUNKS_PER_TICK: usize = 16;
+
+pub type ChunkBlocks = [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+
+// What a copy of the world needs to catch up with the original
+#[derive(Debug, Clone, PartialEq)]
+pub enum MirrorUpdate {
+	// With the biomes the original's generator picked, which the copy's may not
+	Chunk { pos: [i64; 3], blocks: Box<ChunkBlocks>, states: Box<ChunkStates>, biomes: Box<ChunkBiomes> },
+	// Only ever to chunks already sent, latest last, each block with its state
+	Changes(Vec<([i64; 3], usize, u8)>),
+}
+
+// Keeps a copy of part of a world up to date on another thread, which can't share its chunks.
+// Chunks in the watched box are sent whole, nearest the eye first, and after that only their
+// changes, with those missing generated through the world's queue, the ones the player looks
+// towards first. Like generation, sending isn't an edit, so the copy only hears about edits. Block light
+// isn't sent, so meshing the copy with it needs it worked out there. A block's state is only sent
+// along with the block, as it's set when the block is placed, so it's read when the changes go out.
+pub struct WorldMirror {
+	// In chunk coordinates, both inclusive
+	min: [i64; 3],
+	max: [i64; 3],
+	sent: HashSet<[i64; 3]>,
+	changes: Rc<RefCell<Vec<([i64; 3], usize)>>>,
+	subscription: SubscriptionId,
+	requester: Requester,
+}
+
+impl WorldMirror {
+	pub fn new(world: &World, min: [i64; 3], max: [i64; 3]) -> WorldMirror {
+		let changes = Rc::new(RefCell::new(Vec::new()));
+		let sink = changes.clone();
+		let subscription = world.subscribe(move |_, update| {
+			if let UpdateKind::Changed { new, .. } = update.kind {
+				sink.borrow_mut().push((update.pos, new));
+			}
+		});
+		WorldMirror {
+			min: min,
+			max: max,
+			sent: HashSet::new(),
+			changes: changes,
+			subscription: subscription,
+			requester: world.generation_requester(),
+		}
+	}
+
+	// Everything still missing from the copy, or as much as fits in a tick. `world` must be the
+	// one the mirror was made with, with this tick's block updates already dispatched.
+	pub fn tick(&mut self, world: &World, eye: [f32; 3], look: Option<[f32; 3]>) -> Vec<MirrorUpdate> {
+		let mut updates = Vec::new();
+		// Chunks not sent yet will have these changes in them when they are
+		let changes: Vec<([i64; 3], usize, u8)> = replace(&mut *self.changes.borrow_mut(), Vec::new()).into_iter()
+			.filter(|&(pos, _)| self.sent.contains(&chunk_coords(pos).0))
+			.map(|(pos, block)| (pos, block, world.get_state(pos)))
+			.collect();
+		if !changes.is_empty() {
+			updates.push(MirrorUpdate::Changes(changes));
+		}
+
+		let mut wanted = Vec::new();
+		for x in self.min[0]..self.max[0] + 1 {
+			for y in self.min[1]..self.max[1] + 1 {
+				for z in self.min[2]..self.max[2] + 1 {
+					if !self.sent.contains(&[x, y, z]) {
+						wanted.push((chunk_priority([x, y, z], eye, look), [x, y, z]));
+					}
+				}
+			}
+		}
+		wanted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+		world.request_chunks(self.requester, &wanted);
+		world.generate_queued(CHUNKS_PER_TICK);
+
+		let ready: Vec<([i64; 3], Rc<RefCell<Chunk>>)> = wanted.into_iter()
+			.filter_map(|(_, pos)| world.loaded_chunk(pos).map(|chunk| (pos, chunk)))
+			.take(CHUNKS_PER_TICK)
+			.collect();
+		for (pos, chunk) in ready {
+			let chunk = chunk.borrow();
+			updates.push(MirrorUpdate::Chunk {
+				pos: pos,
+				blocks: Box::new(chunk.blocks),
+				states: Box::new(*chunk.states()),
+				biomes: Box::new(*chunk.biomes()),
+			});
+			self.sent.insert(pos);
+		}
+		updates
+	}
+
+	pub fn close(self, world: &World) {
+		world.unsubscribe(self.subscription);
+		world.cancel_chunks(self.requester);
+	}
+}
+
+// Applies updates from a WorldMirror to the copy
+pub fn apply(world: &World, update: MirrorUpdate) {
+	match update {
+		MirrorUpdate::Chunk { pos, blocks, states, biomes } => {
+			world.replace_chunk(pos, *blocks);
+			world.set_states(pos, *states);
+			world.set_biomes(pos, *biomes);
+		},
+		MirrorUpdate::Changes(changes) => {
+			// Only the last change to each block matters
+			let latest: HashMap<[i64; 3], (usize, u8)> = changes.into_iter().map(|(pos, block, state)| (pos, (block, state))).collect();
+			let blocks: Vec<([i64; 3], usize)> = latest.iter().map(|(&pos, &(block, _))| (pos, block)).collect();
+			world.set_blocks_unjournaled(&blocks);
+			for (pos, (_, state)) in latest {
+				world.set_state(pos, state);
+			}
+		},
+	}
+}
@@ -0,0 +1,138 @@
+use std::rc::Rc;
+
+use rand::Rng;
+use time::Duration;
+
+use block::{World, BlockProperties};
+use entity::{Entities, Entity, EntityId, Aabb};
+use random::StreamRng;
+
+const WIDTH: f32 = 0.8;
+const HEIGHT: f32 = 0.9;
+const COLOR: [f32; 3] = [0.7, 0.35, 0.3];
+// In blocks per second
+const WALK_SPEED: f32 = 2.0;
+// Enough to clear a one block step
+const JUMP_SPEED: f32 = 9.5;
+// How far ahead of its center a mob looks for steps and drops
+const LOOK_AHEAD: f32 = 0.7;
+
+fn min_wander_time() -> Duration { Duration::seconds(2) }
+fn max_wander_time() -> Duration { Duration::seconds(6) }
+
+struct Mob {
+	entity: EntityId,
+	// Unit heading, or None while standing still
+	heading: Option<[f32; 2]>,
+	until_turn: Duration,
+}
+
+// Mobs that wander about at random, hopping up one block steps and turning away from walls
+// and drops they couldn't get back up
+pub struct Mobs {
+	properties: Rc<Vec<BlockProperties>>,
+	mobs: Vec<Mob>,
+	rng: StreamRng,
+}
+
+impl Mobs {
+	pub fn new(properties: Rc<Vec<BlockProperties>>, rng: StreamRng) -> Mobs {
+		Mobs {
+			properties: properties,
+			mobs: Vec::new(),
+			rng: rng,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.mobs.len()
+	}
+
+	pub fn spawn(&mut self, entities: &mut Entities, position: [f32; 3]) -> EntityId {
+		let id = entities.spawn(Entity::new(position, Aabb::standing(WIDTH, HEIGHT), COLOR));
+		self.mobs.push(Mob { entity: id, heading: None, until_turn: Duration::zero() });
+		id
+	}
+
+	pub fn tick(&mut self, world: &World, entities: &mut Entities, time_elapsed: &Duration) {
+		self.mobs.retain(|mob| entities.get(mob.entity).is_some());
+
+		let properties = &self.properties;
+		let rng = &mut self.rng;
+		for mob in &mut self.mobs {
+			mob.until_turn = mob.until_turn - *time_elapsed;
+			if mob.until_turn <= Duration::zero() {
+				mob.heading = random_heading(rng);
+				mob.until_turn = random_wander_time(rng);
+			}
+
+			let entity = entities.get_mut(mob.entity).unwrap();
+			let heading = match mob.heading {
+				Some(heading) if entity.on_ground => heading,
+				// Keep pushing forward mid jump, or hitting the step's side would stop it short
+				Some(heading) => {
+					entity.velocity[0] = heading[0] * WALK_SPEED;
+					entity.velocity[1] = heading[1] * WALK_SPEED;
+					continue;
+				},
+				None => {
+					entity.velocity[0] = 0.0;
+					entity.velocity[1] = 0.0;
+					continue;
+				},
+			};
+
+			let feet = [
+				(entity.position[0] + heading[0] * LOOK_AHEAD).floor() as i64,
+				(entity.position[1] + heading[1] * LOOK_AHEAD).floor() as i64,
+				entity.position[2].floor() as i64,
+			];
+			let solid = |dz: i64| {
+				let block = world.get_block([feet[0], feet[1], feet[2] + dz]);
+				properties.get(block).map(|p| p.solid).unwrap_or(true)
+			};
+
+			let heading = if solid(0) {
+				// A single block can be jumped, a wall of two can't
+				if !solid(1) && !solid(2) {
+					entity.velocity[2] = JUMP_SPEED;
+					Some(heading)
+				} else {
+					None
+				}
+			} else if !solid(-1) && !solid(-2) {
+				// More than a block down
+				None
+			} else {
+				Some(heading)
+			};
+
+			match heading {
+				Some(heading) => {
+					entity.velocity[0] = heading[0] * WALK_SPEED;
+					entity.velocity[1] = heading[1] * WALK_SPEED;
+					entity.yaw = heading[1].atan2(heading[0]);
+				},
+				None => {
+					entity.velocity[0] = 0.0;
+					entity.velocity[1] = 0.0;
+					mob.heading = random_heading(rng);
+				},
+			}
+		}
+	}
+}
+
+// Stands still a third of the time
+fn random_heading(rng: &mut StreamRng) -> Option<[f32; 2]> {
+	if rng.gen::<f32>() < 1.0 / 3.0 {
+		return None;
+	}
+	let angle = rng.gen::<f32>() * ::std::f32::consts::PI * 2.0;
+	Some([angle.cos(), angle.sin()])
+}
+
+fn random_wander_time(rng: &mut StreamRng) -> Duration {
+	let spread = (max_wander_time() - min_wander_time()).num_milliseconds() as f32;
+	min_wander_time() + Duration::milliseconds((rng.gen::<f32>() * spread) as i64)
+}
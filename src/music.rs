@@ -0,0 +1,181 @@
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use rand::{self, Rng};
+use rand::seq::SliceRandom;
+use rodio::{self, Decoder, Device, Sink, Source};
+use time::Duration;
+
+pub const MUSIC_DIR: &'static str = "assets/music";
+
+// How long tracks take to fade in and out, overlapping while one gives way to the next
+const FADE_SECONDS: f32 = 4.0;
+// Music is turned down to this while a menu is open, and back up at DUCK_SPEED per second
+const DUCKED_VOLUME: f32 = 0.3;
+const DUCK_SPEED: f32 = 2.0;
+
+struct Track {
+	name: String,
+	sink: Sink,
+	// From 0 to 1, where the fade in or out has got to
+	fade: f32,
+	// Seconds; not every format knows how long it is
+	remaining: Option<f32>,
+}
+
+// Shuffles through the music directory, crossfading from each track to the next. Without an
+// output device or any music it stays silent.
+pub struct MusicService {
+	device: Option<Device>,
+	tracks: Vec<PathBuf>,
+	// Indices into `tracks` still to play this time round, played from the back
+	queue: Vec<usize>,
+	last: Option<usize>,
+	playing: Option<Track>,
+	fading_out: Vec<Track>,
+	duck: f32,
+	volume: Cell<f32>,
+	ducked: Cell<bool>,
+	skip: Cell<bool>,
+}
+
+impl MusicService {
+	pub fn new(dir: &Path) -> MusicService {
+		let mut tracks: Vec<PathBuf> = match fs::read_dir(dir) {
+			Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).filter(|path| is_music(path)).collect(),
+			Err(_) => Vec::new(),
+		};
+		tracks.sort();
+
+		MusicService {
+			device: if tracks.is_empty() { None } else { rodio::default_output_device() },
+			tracks: tracks,
+			queue: Vec::new(),
+			last: None,
+			playing: None,
+			fading_out: Vec::new(),
+			duck: 1.0,
+			volume: Cell::new(1.0),
+			ducked: Cell::new(false),
+			skip: Cell::new(false),
+		}
+	}
+
+	// Both from 0 to 1; music plays at their product
+	pub fn set_volume(&self, master: f32, music: f32) {
+		self.volume.set(master.max(0.0).min(1.0) * music.max(0.0).min(1.0));
+	}
+
+	// Turns the music down while a menu is open
+	pub fn set_ducked(&self, ducked: bool) {
+		self.ducked.set(ducked);
+	}
+
+	// Fades over to the next track on the next update
+	pub fn skip(&self) {
+		self.skip.set(true);
+	}
+
+	pub fn now_playing(&self) -> Option<&str> {
+		self.playing.as_ref().map(|track| &track.name[..])
+	}
+
+	pub fn update(&mut self, time_elapsed: &Duration) {
+		if self.device.is_none() {
+			return;
+		}
+		let seconds = time_elapsed.num_microseconds().unwrap_or(0) as f32 / 1_000_000.0;
+
+		let duck_target = if self.ducked.get() { DUCKED_VOLUME } else { 1.0 };
+		if self.duck < duck_target {
+			self.duck = (self.duck + DUCK_SPEED * seconds).min(duck_target);
+		} else {
+			self.duck = (self.duck - DUCK_SPEED * seconds).max(duck_target);
+		}
+
+		// Tracks of unknown length only give way once they've finished, so they fade in but
+		// never overlap
+		let ending = match self.playing {
+			Some(ref track) => track.sink.empty() || track.remaining.map(|remaining| remaining <= FADE_SECONDS).unwrap_or(false),
+			None => true,
+		};
+		if ending || self.skip.replace(false) {
+			if let Some(track) = self.playing.take() {
+				self.fading_out.push(track);
+			}
+			self.playing = self.next_track();
+		}
+
+		let volume = self.volume.get() * self.duck;
+		if let Some(ref mut track) = self.playing {
+			track.fade = (track.fade + seconds / FADE_SECONDS).min(1.0);
+			track.remaining = track.remaining.map(|remaining| remaining - seconds);
+			track.sink.set_volume(track.fade * volume);
+		}
+		for track in &mut self.fading_out {
+			track.fade -= seconds / FADE_SECONDS;
+			track.sink.set_volume(track.fade.max(0.0) * volume);
+		}
+		// Dropping the sink stops it
+		self.fading_out.retain(|track| track.fade > 0.0 && !track.sink.empty());
+	}
+
+	// Tries every track once before any repeats, and never the same one twice running
+	fn next_track(&mut self) -> Option<Track> {
+		let device = match self.device {
+			Some(ref device) => device,
+			None => return None,
+		};
+
+		for _ in 0..self.tracks.len() {
+			if self.queue.is_empty() {
+				self.queue = (0..self.tracks.len()).collect();
+				self.queue.shuffle(&mut rand::thread_rng());
+				if self.queue.len() > 1 && self.queue.last() == self.last.as_ref() {
+					let swap = rand::thread_rng().gen_range(0, self.queue.len() - 1);
+					let end = self.queue.len() - 1;
+					self.queue.swap(swap, end);
+				}
+			}
+			let index = self.queue.pop().unwrap();
+			self.last = Some(index);
+
+			let path = &self.tracks[index];
+			let source = match File::open(path).map_err(|err| format!("{}", err)).and_then(|file| {
+				Decoder::new(BufReader::new(file)).map_err(|err| format!("{:?}", err))
+			}) {
+				Ok(source) => source,
+				Err(err) => {
+					println!("Failed to play {}: {}", path.display(), err);
+					continue;
+				},
+			};
+
+			let remaining = source.total_duration().map(|length| length.as_secs() as f32 + length.subsec_nanos() as f32 / 1e9);
+			let sink = Sink::new(device);
+			sink.set_volume(0.0);
+			sink.append(source);
+			return Some(Track {
+				name: path.file_stem().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+				sink: sink,
+				fade: 0.0,
+				remaining: remaining,
+			});
+		}
+
+		// Rather than trying them all again every update
+		println!("None of the music will play, turning it off");
+		self.device = None;
+		None
+	}
+}
+
+// Whatever rodio can decode
+fn is_music(path: &Path) -> bool {
+	match path.extension().and_then(|ext| ext.to_str()) {
+		Some(ext) => ["ogg", "wav", "flac"].contains(&&ext.to_lowercase()[..]),
+		None => false,
+	}
+}
@@ -0,0 +1,758 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::mem::replace;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, SocketAddr};
+use std::rc::Rc;
+use std::thread;
+
+use bincode;
+use zstd;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use time::{Duration, PreciseTime};
+
+use block::{World, CHUNK_SIZE, AIR, WATER, chunk_coords};
+use block_update::{UpdateKind, SubscriptionId};
+use generation_queue::{Requester, chunk_priority};
+use server::EYE_HEIGHT;
+
+// Bumped whenever a message changes shape; peers on different versions refuse each other
+pub const PROTOCOL_VERSION: u32 = 5;
+pub const DEFAULT_PORT: u16 = 24680;
+// A frame claiming to be longer than this is garbage rather than something to wait for
+const MAX_FRAME_LEN: usize = 1 << 24;
+const BLOCKS_PER_CHUNK: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+// An encoded chunk is never longer than a palette entry and a run for every block
+const MAX_CHUNK_LEN: u64 = (4 + BLOCKS_PER_CHUNK * 8) as u64;
+const ZSTD_LEVEL: i32 = 3;
+// A chunk with more changes than this in a tick is sent whole instead
+const MAX_DELTA_CHANGES: usize = CHUNK_SIZE * CHUNK_SIZE;
+// Chunks up to this many chunks away from a player are streamed to them, unless the host sets
+// otherwise
+pub const DEFAULT_VIEW_RADIUS: i64 = 5;
+// Nearest first, so someone joining doesn't stall the server for everything around them at once.
+// At most as many are sent to each client, and as many generated for all of them together.
+const CHUNKS_PER_TICK: usize = 8;
+// Edits farther than this from where the player last said they were are refused
+const MAX_EDIT_DISTANCE: f32 = 16.0;
+// Blocks below a player looked through for ground to stand them on, where flying isn't allowed
+const MAX_FALL: i64 = 64;
+// Positions are kept this many blocks from the origin on every axis, or within the border where
+// there is one, so chunk coordinates stay far from overflowing
+const MAX_COORDINATE: f32 = (1 << 24) as f32;
+// Chunks a client is missing beyond this many, nearest first, wait until the nearer ones are sent
+const MAX_QUEUED_PER_CLIENT: usize = 512;
+// Longer chat messages are cut short
+pub const MAX_CHAT_LEN: usize = 256;
+
+fn handshake_timeout() -> Duration { Duration::seconds(5) }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ToServer {
+	Hello { version: u32, name: String },
+	Position([f32; 3]),
+	// Numbered by the client, in order, so it can match up the reply
+	SetBlock { seq: u32, pos: [i64; 3], block: usize },
+	Chat(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ToClient {
+	// `fly` is whether the world's rules allow it. Where they don't, players are taken to be
+	// standing on whatever's below where they say they are.
+	Welcome { spawn: [i64; 3], fly: bool },
+	// Sent before the server hangs up
+	Rejected { reason: String },
+	// See `compress_chunk`
+	Chunk { pos: [i64; 3], data: Vec<u8> },
+	// Changes to a chunk the client already has, as (index within the chunk, new block) pairs.
+	// Indices count in x, y, z order.
+	ChunkDelta { pos: [i64; 3], changes: Vec<(u16, u32)> },
+	// The reply to each SetBlock, with what the block is now whether the edit was made or not
+	Edited { seq: u32, accepted: bool, block: usize },
+	Chat { from: String, text: String },
+}
+
+#[derive(Debug)]
+pub enum NetError {
+	Io(io::Error),
+	Encoding(bincode::Error),
+	FrameTooLarge(usize),
+	Malformed(String),
+	VersionMismatch { ours: u32, theirs: u32 },
+	Rejected(String),
+	TimedOut,
+	Disconnected,
+}
+
+impl From<io::Error> for NetError {
+	fn from(err: io::Error) -> NetError {
+		NetError::Io(err)
+	}
+}
+
+impl From<bincode::Error> for NetError {
+	fn from(err: bincode::Error) -> NetError {
+		NetError::Encoding(err)
+	}
+}
+
+// A palette of the block ids present, then (run length, palette index) pairs in x, y, z order,
+// all zstd compressed. Indices take a byte each unless there are more than 256 kinds of block.
+pub fn compress_chunk(blocks: &[[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> Result<Vec<u8>, NetError> {
+	let mut palette = Vec::new();
+	let mut indices = HashMap::new();
+	let mut runs: Vec<(u16, u16)> = Vec::new();
+	for &block in blocks.iter().flat_map(|plane| plane.iter()).flat_map(|row| row.iter()) {
+		let index = *indices.entry(block).or_insert_with(|| {
+			palette.push(block);
+			palette.len() as u16 - 1
+		});
+		match runs.last_mut() {
+			Some(&mut (ref mut len, last)) if last == index && *len < u16::max_value() => *len += 1,
+			_ => runs.push((1, index)),
+		}
+	}
+
+	let wide = palette.len() > 256;
+	let mut raw = Vec::new();
+	raw.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+	for &block in &palette {
+		raw.extend_from_slice(&(block as u32).to_le_bytes());
+	}
+	for &(len, index) in &runs {
+		raw.extend_from_slice(&len.to_le_bytes());
+		if wide {
+			raw.extend_from_slice(&index.to_le_bytes());
+		} else {
+			raw.push(index as u8);
+		}
+	}
+	Ok(zstd::encode_all(&raw[..], ZSTD_LEVEL)?)
+}
+
+pub fn decompress_chunk(data: &[u8], blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> Result<(), NetError> {
+	let mut raw = Vec::new();
+	zstd::Decoder::new(data)?.take(MAX_CHUNK_LEN + 1).read_to_end(&mut raw)?;
+	let mut rest = &raw[..];
+
+	let palette_len = u32::from_le_bytes(read_array(&mut rest)?) as usize;
+	if palette_len > BLOCKS_PER_CHUNK {
+		return Err(NetError::Malformed(format!("palette of {} blocks", palette_len)));
+	}
+	let mut palette = Vec::with_capacity(palette_len);
+	for _ in 0..palette_len {
+		palette.push(u32::from_le_bytes(read_array(&mut rest)?) as usize);
+	}
+
+	let wide = palette.len() > 256;
+	let mut index = 0;
+	while !rest.is_empty() {
+		let len = u16::from_le_bytes(read_array(&mut rest)?) as usize;
+		let entry = if wide { u16::from_le_bytes(read_array(&mut rest)?) as usize } else { read_array::<[u8; 1]>(&mut rest)?[0] as usize };
+		let block = *palette.get(entry).ok_or(NetError::Malformed(format!("palette index {}", entry)))?;
+		if index + len > BLOCKS_PER_CHUNK {
+			return Err(NetError::Malformed("too many blocks".to_string()));
+		}
+		for i in index..index + len {
+			blocks[i / (CHUNK_SIZE * CHUNK_SIZE)][i / CHUNK_SIZE % CHUNK_SIZE][i % CHUNK_SIZE] = block;
+		}
+		index += len;
+	}
+
+	if index != BLOCKS_PER_CHUNK {
+		return Err(NetError::Malformed("too few blocks".to_string()));
+	}
+	Ok(())
+}
+
+// Takes the next few bytes off the front of `data`
+fn read_array<A: Default + AsMut<[u8]>>(data: &mut &[u8]) -> Result<A, NetError> {
+	let mut array = A::default();
+	let len = array.as_mut().len();
+	if data.len() < len {
+		return Err(NetError::Malformed("truncated chunk".to_string()));
+	}
+	array.as_mut().copy_from_slice(&data[..len]);
+	*data = &data[len..];
+	Ok(array)
+}
+
+fn chunk_index(local: [usize; 3]) -> u16 {
+	(local[0] * CHUNK_SIZE * CHUNK_SIZE + local[1] * CHUNK_SIZE + local[2]) as u16
+}
+
+fn chunk_position(chunk: [i64; 3], index: u16) -> [i64; 3] {
+	let index = index as usize;
+	[
+		chunk[0] * CHUNK_SIZE as i64 + (index / (CHUNK_SIZE * CHUNK_SIZE)) as i64,
+		chunk[1] * CHUNK_SIZE as i64 + (index / CHUNK_SIZE % CHUNK_SIZE) as i64,
+		chunk[2] * CHUNK_SIZE as i64 + (index % CHUNK_SIZE) as i64,
+	]
+}
+
+// Length prefixed bincode messages over a non-blocking socket. Neither end ever waits on the
+// other; whatever can't be read or written yet is buffered until the next call.
+pub struct Connection {
+	stream: TcpStream,
+	incoming: Vec<u8>,
+	outgoing: Vec<u8>,
+	closed: bool,
+	// Since the last `take_traffic`
+	bytes_sent: u64,
+	bytes_received: u64,
+}
+
+impl Connection {
+	pub fn new(stream: TcpStream) -> Result<Connection, NetError> {
+		stream.set_nonblocking(true)?;
+		stream.set_nodelay(true)?;
+		Ok(Connection {
+			stream: stream,
+			incoming: Vec::new(),
+			outgoing: Vec::new(),
+			closed: false,
+			bytes_sent: 0,
+			bytes_received: 0,
+		})
+	}
+
+	pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+		self.stream.peer_addr()
+	}
+
+	pub fn send<T: Serialize>(&mut self, message: &T) -> Result<(), NetError> {
+		let body = bincode::serialize(message)?;
+		self.outgoing.extend_from_slice(&(body.len() as u32).to_le_bytes());
+		self.outgoing.extend_from_slice(&body);
+		self.flush()
+	}
+
+	// Writes as much of what's queued as the socket takes
+	pub fn flush(&mut self) -> Result<(), NetError> {
+		while !self.outgoing.is_empty() {
+			match self.stream.write(&self.outgoing) {
+				Ok(0) => return Err(NetError::Disconnected),
+				Ok(written) => {
+					self.outgoing.drain(..written);
+					self.bytes_sent += written as u64;
+				},
+				Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+				Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+				Err(err) => return Err(err.into()),
+			}
+		}
+		Ok(())
+	}
+
+	// Bytes sent and received since the last call
+	pub fn take_traffic(&mut self) -> (u64, u64) {
+		(replace(&mut self.bytes_sent, 0), replace(&mut self.bytes_received, 0))
+	}
+
+	// The next whole message, if one has arrived. Messages that arrived before the peer hung up
+	// are still handed out before the disconnect is reported.
+	pub fn receive<T: DeserializeOwned>(&mut self) -> Result<Option<T>, NetError> {
+		self.fill()?;
+		if self.incoming.len() >= 4 {
+			let len = u32::from_le_bytes([self.incoming[0], self.incoming[1], self.incoming[2], self.incoming[3]]) as usize;
+			if len > MAX_FRAME_LEN {
+				return Err(NetError::FrameTooLarge(len));
+			}
+			if self.incoming.len() >= 4 + len {
+				let message = bincode::deserialize(&self.incoming[4..4 + len])?;
+				self.incoming.drain(..4 + len);
+				return Ok(Some(message));
+			}
+		}
+
+		if self.closed {
+			return Err(NetError::Disconnected);
+		}
+		Ok(None)
+	}
+
+	fn fill(&mut self) -> Result<(), NetError> {
+		let mut buffer = [0u8; 4096];
+		while !self.closed {
+			match self.stream.read(&mut buffer) {
+				Ok(0) => self.closed = true,
+				Ok(read) => {
+					self.incoming.extend_from_slice(&buffer[..read]);
+					self.bytes_received += read as u64;
+				},
+				Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+				Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+				Err(err) => return Err(err.into()),
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetEvent {
+	Joined(String),
+	// The name is None for connections that left before saying hello
+	Left { name: Option<String>, reason: String },
+	Chat { from: String, text: String },
+	// An edit of ours the server wouldn't make, which has been undone locally
+	EditRefused { seq: u32, pos: [i64; 3] },
+	// A player's edit the server made
+	Edited { pos: [i64; 3], old: usize, new: usize },
+}
+
+// Running totals over everyone the server has talked to
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct NetStats {
+	pub bytes_sent: u64,
+	pub bytes_received: u64,
+	pub chunks_sent: u64,
+	// Compressed, not counting framing
+	pub chunk_bytes: u64,
+	pub deltas_sent: u64,
+	pub changes_sent: u64,
+}
+
+// Averaged over the last whole second
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Bandwidth {
+	pub sent_per_second: f32,
+	pub received_per_second: f32,
+}
+
+fn bandwidth_interval() -> Duration { Duration::seconds(1) }
+
+struct RemoteClient {
+	connection: Connection,
+	// Set by the handshake
+	name: Option<String>,
+	position: Option<[f32; 3]>,
+	// Chunks the client has a copy of, which it's kept up to date on
+	sent: HashSet<[i64; 3]>,
+	// For generating the chunks the client is missing
+	requester: Requester,
+}
+
+// Streams a world to clients over TCP, and carries out the edits they ask for
+pub struct NetServer {
+	listener: TcpListener,
+	clients: Vec<RemoteClient>,
+	// Ids at or past this aren't blocks, and aren't accepted from clients
+	block_types: usize,
+	view_radius: i64,
+	changes: Rc<RefCell<Vec<([i64; 3], usize)>>>,
+	subscription: SubscriptionId,
+	stats: NetStats,
+	// The totals as of the start of the current bandwidth interval
+	sample: (PreciseTime, NetStats),
+	bandwidth: Bandwidth,
+}
+
+impl NetServer {
+	pub fn bind<A: ToSocketAddrs>(addr: A, world: &World, block_types: usize) -> Result<NetServer, NetError> {
+		let listener = TcpListener::bind(addr)?;
+		listener.set_nonblocking(true)?;
+
+		let changes = Rc::new(RefCell::new(Vec::new()));
+		let sink = changes.clone();
+		let subscription = world.subscribe(move |_, update| {
+			if let UpdateKind::Changed { new, .. } = update.kind {
+				sink.borrow_mut().push((update.pos, new));
+			}
+		});
+
+		Ok(NetServer {
+			listener: listener,
+			clients: Vec::new(),
+			block_types: block_types,
+			view_radius: DEFAULT_VIEW_RADIUS,
+			changes: changes,
+			subscription: subscription,
+			stats: NetStats::default(),
+			sample: (PreciseTime::now(), NetStats::default()),
+			bandwidth: Bandwidth::default(),
+		})
+	}
+
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+
+	// In chunks. Chunks that fall out of range when it shrinks stop being kept up to date.
+	pub fn set_view_radius(&mut self, radius: i64) {
+		self.view_radius = radius.max(1);
+	}
+
+	pub fn players(&self) -> usize {
+		self.clients.iter().filter(|client| client.name.is_some()).count()
+	}
+
+	pub fn stats(&self) -> NetStats {
+		self.stats
+	}
+
+	pub fn bandwidth(&self) -> Bandwidth {
+		self.bandwidth
+	}
+
+	// `world` must be the one the server was bound with. Changes reach clients once the world's
+	// block updates have been dispatched, so this goes after `World::update_blocks`.
+	pub fn tick(&mut self, world: &World) -> Vec<NetEvent> {
+		loop {
+			match self.listener.accept() {
+				Ok((stream, _)) => {
+					if let Ok(connection) = Connection::new(stream) {
+						self.clients.push(RemoteClient {
+							connection: connection,
+							name: None,
+							position: None,
+							sent: HashSet::new(),
+							requester: world.generation_requester(),
+						});
+					}
+				},
+				Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+				Err(_) => break,
+			}
+		}
+
+		// Later changes to a block replace earlier ones
+		let mut deltas: HashMap<[i64; 3], HashMap<u16, u32>> = HashMap::new();
+		for (pos, block) in replace(&mut *self.changes.borrow_mut(), Vec::new()) {
+			let (chunk, local) = chunk_coords(pos);
+			deltas.entry(chunk).or_insert_with(HashMap::new).insert(chunk_index(local), block as u32);
+		}
+		let deltas: HashMap<[i64; 3], Vec<(u16, u32)>> = deltas.into_iter()
+			.map(|(chunk, changes)| (chunk, changes.into_iter().collect()))
+			.collect();
+
+		let mut events = Vec::new();
+		for mut client in replace(&mut self.clients, Vec::new()) {
+			let result = self.serve(&mut client, world, &deltas, &mut events);
+			let (sent, received) = client.connection.take_traffic();
+			self.stats.bytes_sent += sent;
+			self.stats.bytes_received += received;
+			match result {
+				Ok(()) => self.clients.push(client),
+				Err(err) => {
+					world.cancel_chunks(client.requester);
+					events.push(NetEvent::Left { name: client.name, reason: format!("{:?}", err) });
+				},
+			}
+		}
+		// Once everyone's asked, so the queue has them all to choose from. What's made is sent
+		// next tick.
+		world.generate_queued(CHUNKS_PER_TICK);
+
+		let now = PreciseTime::now();
+		let elapsed = self.sample.0.to(now);
+		if elapsed >= bandwidth_interval() {
+			let seconds = elapsed.num_microseconds().unwrap() as f32 / 1_000_000.0;
+			let last = self.sample.1;
+			self.bandwidth = Bandwidth {
+				sent_per_second: (self.stats.bytes_sent - last.bytes_sent) as f32 / seconds,
+				received_per_second: (self.stats.bytes_received - last.bytes_received) as f32 / seconds,
+			};
+			self.sample = (now, self.stats);
+		}
+
+		// Everyone hears everyone's chat, the sender included
+		for event in &events {
+			if let NetEvent::Chat { ref from, ref text } = *event {
+				self.say(from, text);
+			}
+		}
+		events
+	}
+
+	// Chat from the host. Clients that fail to take it are dropped on the next tick.
+	pub fn say(&mut self, from: &str, text: &str) {
+		let message = ToClient::Chat { from: from.to_string(), text: truncate(text) };
+		for client in self.clients.iter_mut().filter(|client| client.name.is_some()) {
+			let _ = client.connection.send(&message);
+		}
+	}
+
+	pub fn close(self, world: &World) {
+		world.unsubscribe(self.subscription);
+		for client in &self.clients {
+			world.cancel_chunks(client.requester);
+		}
+	}
+
+	fn serve(&mut self, client: &mut RemoteClient, world: &World, deltas: &HashMap<[i64; 3], Vec<(u16, u32)>>, events: &mut Vec<NetEvent>) -> Result<(), NetError> {
+		while let Some(message) = client.connection.receive()? {
+			match message {
+				ToServer::Hello { version, name } => {
+					if version != PROTOCOL_VERSION {
+						let reason = format!("This server speaks protocol version {}, not {}", PROTOCOL_VERSION, version);
+						client.connection.send(&ToClient::Rejected { reason: reason })?;
+						return Err(NetError::VersionMismatch { ours: PROTOCOL_VERSION, theirs: version });
+					}
+					client.connection.send(&ToClient::Welcome { spawn: world.spawn(), fly: world.rules().fly })?;
+					events.push(NetEvent::Joined(name.clone()));
+					client.name = Some(name);
+				},
+				_ if client.name.is_none() => return Err(NetError::Malformed("message before hello".to_string())),
+				ToServer::Position(position) => {
+					if !position.iter().all(|c| c.is_finite()) {
+						return Err(NetError::Malformed(format!("position {:?}", position)));
+					}
+					let position = clamp_position(position, world.border());
+					// Remote players can only fly, so where that's not allowed it's their feet that count
+					client.position = Some(if world.rules().fly { position } else { grounded(world, position) });
+				},
+				ToServer::SetBlock { seq, pos, block } => {
+					// Whoever's edit arrives last wins; there's no merging
+					let accepted = block < self.block_types && within_reach(client.position, pos);
+					if accepted {
+						let old = world.get_block(pos);
+						world.set_block(pos, block);
+						events.push(NetEvent::Edited { pos: pos, old: old, new: block });
+					}
+					client.connection.send(&ToClient::Edited { seq: seq, accepted: accepted, block: world.get_block(pos) })?;
+				},
+				ToServer::Chat(text) => events.push(NetEvent::Chat { from: client.name.clone().unwrap(), text: truncate(&text) }),
+			}
+		}
+
+		// Clients only hear about changes to chunks they have. The rest they'll get whole.
+		for (&pos, changes) in deltas {
+			if !client.sent.contains(&pos) {
+				continue;
+			}
+			if changes.len() > MAX_DELTA_CHANGES {
+				self.send_chunk(client, world, pos)?;
+			} else {
+				client.connection.send(&ToClient::ChunkDelta { pos: pos, changes: changes.clone() })?;
+				self.stats.deltas_sent += 1;
+				self.stats.changes_sent += changes.len() as u64;
+			}
+		}
+
+		if let Some(position) = client.position {
+			let radius = self.view_radius;
+			let (center, _) = chunk_coords([position[0].floor() as i64, position[1].floor() as i64, position[2].floor() as i64]);
+
+			// Once out of range a chunk stops being kept up to date, so it has to be sent again
+			client.sent.retain(|pos| (0..3).all(|i| (pos[i] - center[i]).abs() <= radius));
+
+			let mut wanted = Vec::new();
+			for x in center[0] - radius..center[0] + radius + 1 {
+				for y in center[1] - radius..center[1] + radius + 1 {
+					for z in center[2] - radius..center[2] + radius + 1 {
+						if !client.sent.contains(&[x, y, z]) {
+							wanted.push((chunk_priority([x, y, z], position, None), [x, y, z]));
+						}
+					}
+				}
+			}
+			wanted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+			wanted.truncate(MAX_QUEUED_PER_CLIENT);
+			world.request_chunks(client.requester, &wanted);
+
+			let ready: Vec<[i64; 3]> = wanted.into_iter()
+				.map(|(_, pos)| pos)
+				.filter(|&pos| world.loaded_chunk(pos).is_some())
+				.take(CHUNKS_PER_TICK)
+				.collect();
+			for pos in ready {
+				self.send_chunk(client, world, pos)?;
+			}
+		}
+
+		client.connection.flush()
+	}
+}
+
+impl NetServer {
+	fn send_chunk(&mut self, client: &mut RemoteClient, world: &World, pos: [i64; 3]) -> Result<(), NetError> {
+		let data = compress_chunk(&world.get_chunk(pos[0], pos[1], pos[2]).borrow().blocks)?;
+		self.stats.chunks_sent += 1;
+		self.stats.chunk_bytes += data.len() as u64;
+		client.connection.send(&ToClient::Chunk { pos: pos, data: data })?;
+		client.sent.insert(pos);
+		Ok(())
+	}
+}
+
+// Within the border horizontally, where there is one, and within MAX_COORDINATE everywhere
+fn clamp_position(position: [f32; 3], border: Option<i64>) -> [f32; 3] {
+	let horizontal = border.map(|border| (border as f32).min(MAX_COORDINATE)).unwrap_or(MAX_COORDINATE);
+	let limits = [horizontal, horizontal, MAX_COORDINATE];
+	let mut clamped = position;
+	for i in 0..3 {
+		clamped[i] = position[i].max(-limits[i]).min(limits[i]);
+	}
+	clamped
+}
+
+// Where a player whose eye is at `position` would be standing: on the first block below that
+// isn't air or water, or as far down as MAX_FALL reaches if there's none
+fn grounded(world: &World, position: [f32; 3]) -> [f32; 3] {
+	let (x, y) = (position[0].floor() as i64, position[1].floor() as i64);
+	let feet = (position[2] - EYE_HEIGHT).floor() as i64;
+	let ground = (feet - MAX_FALL..feet).rev()
+		.find(|&z| match world.get_block([x, y, z]) {
+			AIR | WATER => false,
+			_ => true,
+		})
+		.map(|z| z + 1)
+		.unwrap_or(feet - MAX_FALL);
+	[position[0], position[1], ground as f32 + EYE_HEIGHT]
+}
+
+fn within_reach(position: Option<[f32; 3]>, pos: [i64; 3]) -> bool {
+	let position = match position {
+		Some(position) => position,
+		None => return false,
+	};
+	let distance = (0..3).map(|i| {
+		let d = pos[i] as f32 + 0.5 - position[i];
+		d * d
+	}).sum::<f32>().sqrt();
+	distance <= MAX_EDIT_DISTANCE
+}
+
+// An edit shown locally before the server has had its say
+struct Prediction {
+	seq: u32,
+	pos: [i64; 3],
+	block: usize,
+}
+
+// The other end of a NetServer, mirroring its world into a local one
+pub struct NetClient {
+	connection: Connection,
+	spawn: [i64; 3],
+	fly: bool,
+	next_seq: u32,
+	// Oldest first, which is the order the server replies in
+	predictions: VecDeque<Prediction>,
+}
+
+impl NetClient {
+	// Blocks until the server has accepted or refused the handshake
+	pub fn connect<A: ToSocketAddrs>(addr: A, name: &str) -> Result<NetClient, NetError> {
+		let mut connection = Connection::new(TcpStream::connect(addr)?)?;
+		connection.send(&ToServer::Hello { version: PROTOCOL_VERSION, name: name.to_string() })?;
+
+		let start = PreciseTime::now();
+		loop {
+			match connection.receive()? {
+				Some(ToClient::Welcome { spawn, fly }) => return Ok(NetClient {
+					connection: connection,
+					spawn: spawn,
+					fly: fly,
+					next_seq: 0,
+					predictions: VecDeque::new(),
+				}),
+				Some(ToClient::Rejected { reason }) => return Err(NetError::Rejected(reason)),
+				Some(_) => return Err(NetError::Malformed("expected a welcome".to_string())),
+				None if start.to(PreciseTime::now()) > handshake_timeout() => return Err(NetError::TimedOut),
+				None => {
+					connection.flush()?;
+					thread::sleep(::std::time::Duration::from_millis(10));
+				},
+			}
+		}
+	}
+
+	pub fn spawn(&self) -> [i64; 3] {
+		self.spawn
+	}
+
+	// As of joining. Edits are only in reach from the ground where it's not allowed.
+	pub fn fly_allowed(&self) -> bool {
+		self.fly
+	}
+
+	pub fn send_position(&mut self, position: [f32; 3]) -> Result<(), NetError> {
+		self.connection.send(&ToServer::Position(position))
+	}
+
+	// Makes the edit in `world` straight away and asks the server to make it too. If the server
+	// refuses, the block goes back to whatever the server has there.
+	pub fn edit(&mut self, world: &World, pos: [i64; 3], block: usize) -> Result<u32, NetError> {
+		let seq = self.next_seq;
+		self.next_seq = self.next_seq.wrapping_add(1);
+		self.connection.send(&ToServer::SetBlock { seq: seq, pos: pos, block: block })?;
+		world.set_block_unjournaled(pos, block);
+		self.predictions.push_back(Prediction { seq: seq, pos: pos, block: block });
+		Ok(seq)
+	}
+
+	// Edits the server hasn't replied to yet
+	pub fn pending_edits(&self) -> usize {
+		self.predictions.len()
+	}
+
+	fn predicted(&self, pos: [i64; 3]) -> Option<usize> {
+		self.predictions.iter().rev().find(|prediction| prediction.pos == pos).map(|prediction| prediction.block)
+	}
+
+	pub fn say(&mut self, text: &str) -> Result<(), NetError> {
+		self.connection.send(&ToServer::Chat(truncate(text)))
+	}
+
+	// Applies everything the server has sent so far to `world`, returning the chat and refused
+	// edits that came with it
+	pub fn tick(&mut self, world: &World) -> Result<Vec<NetEvent>, NetError> {
+		let mut events = Vec::new();
+		while let Some(message) = self.connection.receive()? {
+			match message {
+				ToClient::Chunk { pos, data } => {
+					let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+					decompress_chunk(&data, &mut blocks)?;
+					// The server's copy doesn't have the edits still on their way to it
+					for prediction in self.predictions.iter().filter(|prediction| chunk_coords(prediction.pos).0 == pos) {
+						let (_, local) = chunk_coords(prediction.pos);
+						blocks[local[0]][local[1]][local[2]] = prediction.block;
+					}
+					world.replace_chunk(pos, blocks);
+				},
+				// Predicted blocks are left alone until their replies come in
+				ToClient::ChunkDelta { pos, changes } => {
+					let mut blocks = Vec::with_capacity(changes.len());
+					for (index, block) in changes {
+						if index as usize >= BLOCKS_PER_CHUNK {
+							return Err(NetError::Malformed(format!("block index {}", index)));
+						}
+						let pos = chunk_position(pos, index);
+						if self.predicted(pos).is_none() {
+							blocks.push((pos, block as usize));
+						}
+					}
+					world.set_blocks_unjournaled(&blocks);
+				},
+				ToClient::Edited { seq, accepted, block } => {
+					let prediction = match self.predictions.pop_front() {
+						Some(ref prediction) if prediction.seq == seq => prediction.pos,
+						_ => return Err(NetError::Malformed(format!("reply to unknown edit {}", seq))),
+					};
+					// A later edit to the same block still stands until its own reply. Otherwise the
+					// block takes the server's word; setting it to what it already is doesn't
+					// remesh, so only refused edits cost a remesh of the chunk and its neighbours.
+					if self.predicted(prediction).is_none() {
+						world.set_block_unjournaled(prediction, block);
+					}
+					if !accepted {
+						events.push(NetEvent::EditRefused { seq: seq, pos: prediction });
+					}
+				},
+				ToClient::Chat { from, text } => events.push(NetEvent::Chat { from: from, text: text }),
+				ToClient::Rejected { reason } => return Err(NetError::Rejected(reason)),
+				ToClient::Welcome { .. } => return Err(NetError::Malformed("second welcome".to_string())),
+			}
+		}
+		self.connection.flush()?;
+		Ok(events)
+	}
+}
+
+fn truncate(text: &str) -> String {
+	text.chars().take(MAX_CHAT_LEN).collect()
+}
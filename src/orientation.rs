@@ -0,0 +1,110 @@
+use block::NormalDirection;
+use block::NormalDirection as ND;
+
+// In the order of NormalDirection::to_index, which is how an orientation is stored
+const DIRECTIONS: [ND; 6] = [ND::Front, ND::Up, ND::Right, ND::Back, ND::Down, ND::Left];
+
+// Which way a block is turned, kept in the state layer of its chunk. Blocks are modelled facing
+// front with their top up; turned, the front faces another way. Turned to face up or down, the
+// block tips over backwards or forwards, so its right stays right.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Orientation {
+	// Into DIRECTIONS
+	front: usize,
+}
+
+impl Orientation {
+	pub const DEFAULT: Orientation = Orientation { front: 0 };
+
+	pub fn facing(front: NormalDirection) -> Orientation {
+		Orientation { front: front.to_index() }
+	}
+
+	// States that aren't an orientation, as of blocks that don't turn, are taken as the default
+	pub fn from_state(state: u8) -> Orientation {
+		if (state as usize) < DIRECTIONS.len() {
+			Orientation { front: state as usize }
+		} else {
+			Orientation::DEFAULT
+		}
+	}
+
+	pub fn state(&self) -> u8 {
+		self.front as u8
+	}
+
+	pub fn front(&self) -> NormalDirection {
+		DIRECTIONS[self.front]
+	}
+
+	// Where the model's right, front and top point in the world
+	fn axes(&self) -> ([i32; 3], [i32; 3], [i32; 3]) {
+		let front = vector(&self.front());
+		let up = match front {
+			[0, 0, 1] => [0, -1, 0],
+			[0, 0, -1] => [0, 1, 0],
+			_ => [0, 0, 1],
+		};
+		let right = [
+			front[1] * up[2] - front[2] * up[1],
+			front[2] * up[0] - front[0] * up[2],
+			front[0] * up[1] - front[1] * up[0],
+		];
+		(right, front, up)
+	}
+
+	// The way a face of the model faces once the block is turned
+	pub fn to_world(&self, model: &NormalDirection) -> NormalDirection {
+		let (right, front, up) = self.axes();
+		let m = vector(model);
+		direction([
+			m[0] * right[0] + m[1] * front[0] + m[2] * up[0],
+			m[0] * right[1] + m[1] * front[1] + m[2] * up[1],
+			m[0] * right[2] + m[1] * front[2] + m[2] * up[2],
+		])
+	}
+
+	// The face of the model that ends up facing `world`
+	pub fn to_model(&self, world: &NormalDirection) -> NormalDirection {
+		let (right, front, up) = self.axes();
+		let w = vector(world);
+		let dot = |axis: [i32; 3]| axis[0] * w[0] + axis[1] * w[1] + axis[2] * w[2];
+		direction([dot(right), dot(front), dot(up)])
+	}
+}
+
+// How a block is turned when placed, going by the way the player looks
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Orienting {
+	Fixed,
+	// Front and back along the axis looked along, like the ends of a log
+	Axis,
+	// Front towards the player, but never up or down
+	Horizontal,
+	// Front towards the player
+	Any,
+}
+
+impl Orienting {
+	pub fn place(&self, look: [f32; 3]) -> Orientation {
+		let (x, y, z) = (look[0].abs(), look[1].abs(), look[2].abs());
+		match *self {
+			Orienting::Fixed => Orientation::DEFAULT,
+			Orienting::Axis if z >= x && z >= y => Orientation::facing(ND::Up),
+			Orienting::Axis if x >= y => Orientation::facing(ND::Right),
+			Orienting::Axis => Orientation::facing(ND::Front),
+			Orienting::Any if z >= x && z >= y => Orientation::facing(if look[2] > 0.0 { ND::Down } else { ND::Up }),
+			Orienting::Horizontal | Orienting::Any if x >= y => Orientation::facing(if look[0] > 0.0 { ND::Left } else { ND::Right }),
+			Orienting::Horizontal | Orienting::Any => Orientation::facing(if look[1] > 0.0 { ND::Back } else { ND::Front }),
+		}
+	}
+}
+
+fn vector(dir: &NormalDirection) -> [i32; 3] {
+	let v = dir.to_vec_arr();
+	[v[0] as i32, v[1] as i32, v[2] as i32]
+}
+
+fn direction(v: [i32; 3]) -> NormalDirection {
+	*DIRECTIONS.iter().find(|dir| vector(dir) == v).expect("not a unit axis")
+}
@@ -0,0 +1,13 @@
+#version 140
+in vec2 position;
+in vec4 color;
+
+out vec4 v_color;
+
+// In pixels
+uniform vec2 screen_size;
+
+void main() {
+	v_color = color;
+	gl_Position = vec4(position.x / screen_size.x * 2.0 - 1.0, 1.0 - position.y / screen_size.y * 2.0, 0.0, 1.0);
+}
@@ -0,0 +1,206 @@
+use glium::{Display, Program, Surface, VertexBuffer, BlitTarget, DrawParameters};
+use glium::draw_parameters::TimeElapsedQuery;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{Texture2d, DepthTexture2d, Texture2dMultisample, DepthTexture2dMultisample};
+use glium::texture::{UncompressedFloatFormat, DepthFormat, MipmapsOption};
+use glium::uniforms::{Sampler, MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
+
+use error::SpaceError;
+
+bitflags! {
+	// Full screen passes run over the finished scene, before the overlay goes on top. They run
+	// in the order listed.
+	pub struct PostEffects: u8 {
+		const TONEMAP = 0b01;
+		const FXAA    = 0b10;
+	}
+}
+
+impl PostEffects {
+	pub fn from_name(name: &str) -> Option<PostEffects> {
+		match name {
+			"tonemap" => Some(PostEffects::TONEMAP),
+			"fxaa" => Some(PostEffects::FXAA),
+			_ => None,
+		}
+	}
+
+	pub fn names(&self) -> Vec<&'static str> {
+		let mut names = Vec::new();
+		if self.contains(PostEffects::TONEMAP) {
+			names.push("tonemap");
+		}
+		if self.contains(PostEffects::FXAA) {
+			names.push("fxaa");
+		}
+		names
+	}
+}
+
+impl Default for PostEffects {
+	fn default() -> PostEffects {
+		PostEffects::FXAA
+	}
+}
+
+#[derive(Copy, Clone)]
+struct ScreenVertex {
+	position: [f32; 2],
+}
+
+implement_vertex!(ScreenVertex, position);
+
+pub struct PostPrograms {
+	// Used when no effect is on, to get the scene to the screen at all
+	pub copy: Program,
+	pub underwater: Program,
+	pub tonemap: Program,
+	pub fxaa: Program,
+}
+
+// Where the scene is drawn. Multisampled targets can't be sampled, so they're resolved into the
+// plain scene texture before any effect runs.
+enum SceneTarget {
+	Single(DepthTexture2d),
+	Multisampled(Texture2dMultisample, DepthTexture2dMultisample),
+}
+
+// The scene is drawn offscreen rather than straight to the window, then run through each enabled
+// effect on its way there. Colors are kept in half floats so tonemapping has something above 1 to
+// work with.
+pub struct PostProcessor {
+	programs: PostPrograms,
+	quad: VertexBuffer<ScreenVertex>,
+	scene: Texture2d,
+	target: SceneTarget,
+	// 0 for none
+	samples: u32,
+	// Written by every pass but the last, swapping between them
+	intermediates: [Texture2d; 2],
+	dimensions: (u32, u32),
+	pub effects: PostEffects,
+	pub exposure: f32,
+	// The color of the liquid the camera is in, which runs before the other effects, and isn't one
+	// of them as it's not up to the player
+	pub underwater: Option<[f32; 3]>,
+	// Seconds, for anything that moves
+	pub time: f32,
+}
+
+impl PostProcessor {
+	pub fn new(display: &Display, programs: PostPrograms, dimensions: (u32, u32), samples: u32) -> Result<PostProcessor, SpaceError> {
+		let quad = VertexBuffer::new(display, &[
+			ScreenVertex { position: [-1.0, -1.0] },
+			ScreenVertex { position: [ 1.0, -1.0] },
+			ScreenVertex { position: [-1.0,  1.0] },
+			ScreenVertex { position: [ 1.0,  1.0] },
+		])?;
+		let (scene, target, intermediates) = PostProcessor::targets(display, dimensions, samples)?;
+		Ok(PostProcessor {
+			programs: programs,
+			quad: quad,
+			scene: scene,
+			target: target,
+			samples: samples,
+			intermediates: intermediates,
+			dimensions: dimensions,
+			effects: PostEffects::default(),
+			exposure: 1.0,
+			underwater: None,
+			time: 0.0,
+		})
+	}
+
+	fn targets(display: &Display, (width, height): (u32, u32), samples: u32) -> Result<(Texture2d, SceneTarget, [Texture2d; 2]), SpaceError> {
+		let color = || Texture2d::empty_with_format(display, UncompressedFloatFormat::F16F16F16F16, MipmapsOption::NoMipmap, width, height);
+		let target = if samples > 1 {
+			SceneTarget::Multisampled(
+				Texture2dMultisample::empty_with_format(display, UncompressedFloatFormat::F16F16F16F16, MipmapsOption::NoMipmap, width, height, samples)?,
+				DepthTexture2dMultisample::empty_with_format(display, DepthFormat::I24, MipmapsOption::NoMipmap, width, height, samples)?,
+			)
+		} else {
+			SceneTarget::Single(DepthTexture2d::empty_with_format(display, DepthFormat::I24, MipmapsOption::NoMipmap, width, height)?)
+		};
+		Ok((color()?, target, [color()?, color()?]))
+	}
+
+	// Keeps the targets the size of the window, throwing away whatever was drawn if it changed
+	pub fn resize(&mut self, display: &Display, dimensions: (u32, u32)) -> Result<(), SpaceError> {
+		if dimensions == self.dimensions {
+			return Ok(());
+		}
+		let (scene, target, intermediates) = PostProcessor::targets(display, dimensions, self.samples)?;
+		self.scene = scene;
+		self.target = target;
+		self.intermediates = intermediates;
+		self.dimensions = dimensions;
+		Ok(())
+	}
+
+	// Where everything but the overlay is drawn
+	pub fn scene_target<'a>(&'a self, display: &Display) -> Result<SimpleFrameBuffer<'a>, SpaceError> {
+		Ok(match self.target {
+			SceneTarget::Single(ref depth) => SimpleFrameBuffer::with_depth_buffer(display, &self.scene, depth)?,
+			SceneTarget::Multisampled(ref color, ref depth) => SimpleFrameBuffer::with_depth_buffer(display, color, depth)?,
+		})
+	}
+
+	fn resolve(&self, display: &Display) -> Result<(), SpaceError> {
+		if let SceneTarget::Multisampled(ref color, ref depth) = self.target {
+			let whole = BlitTarget { left: 0, bottom: 0, width: self.dimensions.0 as i32, height: self.dimensions.1 as i32 };
+			let multisampled = SimpleFrameBuffer::with_depth_buffer(display, color, depth)?;
+			multisampled.blit_whole_color_to(&SimpleFrameBuffer::new(display, &self.scene)?, &whole, MagnifySamplerFilter::Nearest);
+		}
+		Ok(())
+	}
+
+	// `query` times every pass. Returns how many were drawn.
+	pub fn apply<S: Surface>(&self, display: &Display, target: &mut S, query: Option<&TimeElapsedQuery>) -> Result<usize, SpaceError> {
+		self.resolve(display)?;
+		let params = DrawParameters {
+			time_elapsed_query: query,
+			.. Default::default()
+		};
+
+		let mut passes = Vec::new();
+		if self.underwater.is_some() {
+			passes.push(&self.programs.underwater);
+		}
+		if self.effects.contains(PostEffects::TONEMAP) {
+			passes.push(&self.programs.tonemap);
+		}
+		if self.effects.contains(PostEffects::FXAA) {
+			passes.push(&self.programs.fxaa);
+		}
+		if passes.is_empty() {
+			passes.push(&self.programs.copy);
+		}
+
+		let texel = [1.0 / self.dimensions.0 as f32, 1.0 / self.dimensions.1 as f32];
+		let mut source = &self.scene;
+		for (i, program) in passes.iter().enumerate() {
+			let sampler = Sampler::new(source)
+				.magnify_filter(MagnifySamplerFilter::Linear)
+				.minify_filter(MinifySamplerFilter::Linear)
+				.wrap_function(SamplerWrapFunction::Clamp);
+			let uniforms = uniform! {
+				source: sampler,
+				texel: texel,
+				exposure: self.exposure,
+				time: self.time,
+				tint: self.underwater.unwrap_or([1.0; 3]),
+			};
+
+			let indices = NoIndices(PrimitiveType::TriangleStrip);
+			if i + 1 == passes.len() {
+				target.draw(&self.quad, indices, program, &uniforms, &params)?;
+			} else {
+				let destination = &self.intermediates[i % 2];
+				SimpleFrameBuffer::new(display, destination)?.draw(&self.quad, indices, program, &uniforms, &params)?;
+				source = destination;
+			}
+		}
+		Ok(passes.len())
+	}
+}
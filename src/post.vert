@@ -0,0 +1,10 @@
+#version 140
+in vec2 position;
+
+out vec2 v_tex_coords;
+
+// A quad covering the screen, from -1 to 1 on both axes
+void main() {
+	v_tex_coords = position * 0.5 + 0.5;
+	gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use gui::GuiService;
+use save::SaveError;
+use schematic::Schematic;
+
+// Prefabs every world can use, next to the settings
+pub const GLOBAL_PREFABS_DIR: &'static str = "schematics";
+// Inside a world's save directory, for that world's own prefabs
+const WORLD_PREFABS_DIR: &'static str = "schematics";
+const EXTENSION: &'static str = "schem";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Prefab {
+	pub name: String,
+	pub global: bool,
+}
+
+// Named schematics on disk. A world's own prefab hides a global one of the same name.
+pub struct PrefabLibrary {
+	world_dir: Option<PathBuf>,
+	global_dir: PathBuf,
+}
+
+impl PrefabLibrary {
+	// Worlds that aren't saved only have the global prefabs
+	pub fn new(save_dir: Option<&Path>, global_dir: &Path) -> PrefabLibrary {
+		PrefabLibrary {
+			world_dir: save_dir.map(|dir| dir.join(WORLD_PREFABS_DIR)),
+			global_dir: global_dir.to_path_buf(),
+		}
+	}
+
+	pub fn save(&self, name: &str, schematic: &Schematic, global: bool) -> Result<(), SaveError> {
+		check_name(name)?;
+		let dir = match (global, self.world_dir.as_ref()) {
+			(false, Some(dir)) => dir,
+			(false, None) => return Err(SaveError::Malformed("This world isn't saved to disk".to_string())),
+			(true, _) => &self.global_dir,
+		};
+		fs::create_dir_all(dir)?;
+		schematic.save(&dir.join(name).with_extension(EXTENSION))
+	}
+
+	// Prefabs with blocks beyond the first `block_types` are refused
+	pub fn load(&self, name: &str, block_types: usize) -> Result<Schematic, SaveError> {
+		check_name(name)?;
+		for dir in self.world_dir.iter().chain(Some(&self.global_dir)) {
+			let path = dir.join(name).with_extension(EXTENSION);
+			if path.is_file() {
+				return Schematic::load(&path, block_types);
+			}
+		}
+		Err(SaveError::Malformed(format!("No prefab named {}", name)))
+	}
+
+	// Sorted by name
+	pub fn list(&self) -> Vec<Prefab> {
+		let mut prefabs: Vec<Prefab> = Vec::new();
+		for (dir, global) in self.world_dir.iter().map(|dir| (dir, false)).chain(Some((&self.global_dir, true))) {
+			let entries = match fs::read_dir(dir) {
+				Ok(entries) => entries,
+				Err(_) => continue,
+			};
+			for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+				if path.extension().map(|ext| ext != EXTENSION).unwrap_or(true) {
+					continue;
+				}
+				let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+					Some(name) => name.to_string(),
+					None => continue,
+				};
+				if !prefabs.iter().any(|prefab| prefab.name == name) {
+					prefabs.push(Prefab { name: name, global: global });
+				}
+			}
+		}
+		prefabs.sort();
+		prefabs
+	}
+}
+
+// Names become file names, so they're kept to letters, digits, dashes and underscores
+fn check_name(name: &str) -> Result<(), SaveError> {
+	if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+		return Err(SaveError::Malformed(format!("Invalid prefab name: {}", name)));
+	}
+	Ok(())
+}
+
+// The prefabs there are, as last listed by the server. Picking one copies it, ready to paste.
+pub struct PrefabBrowser {
+	open: bool,
+	prefabs: Vec<Prefab>,
+}
+
+impl PrefabBrowser {
+	pub fn new() -> PrefabBrowser {
+		PrefabBrowser { open: false, prefabs: Vec::new() }
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.open
+	}
+
+	pub fn open(&mut self) {
+		self.open = true;
+	}
+
+	pub fn close(&mut self) {
+		self.open = false;
+	}
+
+	pub fn set_prefabs(&mut self, prefabs: Vec<Prefab>) {
+		self.prefabs = prefabs;
+	}
+
+	// The name of the prefab picked this frame, if any
+	pub fn build(&mut self, gui: &GuiService) -> Option<String> {
+		let mut picked = None;
+		let prefabs = &self.prefabs;
+		gui.window("Prefabs", [40.0, 40.0], 360.0, |ui| {
+			if prefabs.is_empty() {
+				ui.label("No prefabs saved yet; select something and use /schem save [name]");
+			}
+			for prefab in prefabs {
+				let label = if prefab.global { format!("{} (global)", prefab.name) } else { prefab.name.clone() };
+				if ui.button(&label) {
+					picked = Some(prefab.name.clone());
+				}
+			}
+		});
+		if picked.is_some() {
+			self.open = false;
+		}
+		picked
+	}
+}
@@ -0,0 +1,69 @@
+use image::{Rgb, RgbImage};
+
+use block::{CHUNK_SIZE, AIR, GROUND, WATER, SAND};
+use worldgen::WorldGenerator;
+
+// Renders a top-down map of the `size` by `size` chunks starting at chunk `origin`, one pixel per
+// column. Only chunk layers `min_z..=max_z` are searched for the surface; columns with nothing
+// in that range are left black. Higher surfaces are drawn lighter.
+pub fn render(generator: &WorldGenerator, origin: [i64; 2], size: usize, min_z: i64, max_z: i64) -> RgbImage {
+	let pixels = (size * CHUNK_SIZE) as u32;
+	let mut image = RgbImage::new(pixels, pixels);
+	let mut blocks = Box::new([[[AIR; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+
+	let bottom = (min_z * CHUNK_SIZE as i64) as f32;
+	let span = ((max_z - min_z + 1) * CHUNK_SIZE as i64) as f32;
+
+	for i in 0..size {
+		for j in 0..size {
+			let (chunk_x, chunk_y) = (origin[0] + i as i64, origin[1] + j as i64);
+			let mut surface = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+			let mut remaining = CHUNK_SIZE * CHUNK_SIZE;
+
+			// Work down from the top of the range, stopping once every column has hit something
+			let mut chunk_z = max_z;
+			while chunk_z >= min_z && remaining > 0 {
+				generator.generate(chunk_x, chunk_y, chunk_z, &mut blocks);
+				for x in 0..CHUNK_SIZE {
+					for y in 0..CHUNK_SIZE {
+						if surface[x][y].is_some() {
+							continue;
+						}
+						if let Some(z) = (0..CHUNK_SIZE).rev().find(|&z| blocks[x][y][z] != AIR) {
+							surface[x][y] = Some((chunk_z * CHUNK_SIZE as i64 + z as i64, blocks[x][y][z]));
+							remaining -= 1;
+						}
+					}
+				}
+				chunk_z -= 1;
+			}
+
+			for x in 0..CHUNK_SIZE {
+				for y in 0..CHUNK_SIZE {
+					let color = match surface[x][y] {
+						Some((z, block)) => shade(block, (z as f32 - bottom) / span),
+						None => Rgb([0, 0, 0]),
+					};
+					// Image rows run top to bottom, world y runs the other way
+					let pixel_x = (i * CHUNK_SIZE + x) as u32;
+					let pixel_y = pixels - 1 - (j * CHUNK_SIZE + y) as u32;
+					image.put_pixel(pixel_x, pixel_y, color);
+				}
+			}
+		}
+	}
+
+	image
+}
+
+fn shade(block: usize, height: f32) -> Rgb<u8> {
+	let base = match block {
+		GROUND => [0.3, 0.4, 0.2],
+		WATER => [0.1, 0.3, 0.6],
+		SAND => [0.8, 0.75, 0.5],
+		_ => [0.5, 0.5, 0.5],
+	};
+	let brightness = 0.4 + 1.2 * height.max(0.0).min(1.0);
+	let channel = |c: f32| (c * brightness * 255.0).max(0.0).min(255.0) as u8;
+	Rgb([channel(base[0]), channel(base[1]), channel(base[2])])
+}
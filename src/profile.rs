@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use time::{Duration, PreciseTime};
+
+pub const TRACE_FILE: &'static str = "trace.json";
+// Spans are kept for this many of the latest frames, to be written out after a hitch
+const HISTORY_FRAMES: usize = 600;
+
+// Times the rest of the enclosing block, nested under whatever scope it's inside of
+#[macro_export]
+macro_rules! profile_scope {
+	($name:expr) => {
+		let _profile_scope = $crate::profile::scope($name);
+	};
+}
+
+thread_local! {
+	static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::new());
+}
+
+// One entry into a scope, in microseconds since the profiler started
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+	pub name: &'static str,
+	pub depth: usize,
+	pub start: i64,
+	pub duration: i64,
+}
+
+// Everything spent in one scope over a frame, under one particular parent
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeTiming {
+	pub name: &'static str,
+	pub depth: usize,
+	pub total: Duration,
+	pub calls: u32,
+}
+
+// Scopes are timed on whichever thread they're on, and a frame is whatever happens between two
+// calls to `end_frame`, which only the main loop's thread makes.
+struct Profiler {
+	epoch: PreciseTime,
+	frame_start: i64,
+	spans: Vec<Span>,
+	// Indices into `spans` of the scopes still open, innermost last
+	open: Vec<usize>,
+	last_frame: Vec<ScopeTiming>,
+	last_frame_time: Duration,
+	// Each recent frame's spans, oldest first, led by a span for the whole frame
+	history: VecDeque<Vec<Span>>,
+}
+
+impl Profiler {
+	fn new() -> Profiler {
+		Profiler {
+			epoch: PreciseTime::now(),
+			frame_start: 0,
+			spans: Vec::new(),
+			open: Vec::new(),
+			last_frame: Vec::new(),
+			last_frame_time: Duration::zero(),
+			history: VecDeque::new(),
+		}
+	}
+
+	fn now(&self) -> i64 {
+		self.epoch.to(PreciseTime::now()).num_microseconds().unwrap_or(0)
+	}
+}
+
+// Closes its scope when dropped. Scopes are blocks, so the innermost open one is always the
+// first to close.
+pub struct Scope {
+	_private: (),
+}
+
+impl Drop for Scope {
+	fn drop(&mut self) {
+		PROFILER.with(|profiler| {
+			let mut profiler = profiler.borrow_mut();
+			let now = profiler.now();
+			if let Some(index) = profiler.open.pop() {
+				let span = &mut profiler.spans[index];
+				span.duration = now - span.start;
+			}
+		});
+	}
+}
+
+pub fn scope(name: &'static str) -> Scope {
+	PROFILER.with(|profiler| {
+		let mut profiler = profiler.borrow_mut();
+		let span = Span { name: name, depth: profiler.open.len(), start: profiler.now(), duration: 0 };
+		let index = profiler.spans.len();
+		profiler.spans.push(span);
+		profiler.open.push(index);
+		Scope { _private: () }
+	})
+}
+
+// Totals up the frame that's just finished and starts the next. Scopes still open carry on
+// into the next frame.
+pub fn end_frame() {
+	PROFILER.with(|profiler| {
+		let mut profiler = profiler.borrow_mut();
+		let now = profiler.now();
+		let spans = ::std::mem::replace(&mut profiler.spans, Vec::new());
+		let mut finished = Vec::with_capacity(spans.len());
+		for (i, span) in spans.into_iter().enumerate() {
+			if profiler.open.contains(&i) {
+				profiler.spans.push(span);
+			} else {
+				finished.push(span);
+			}
+		}
+		profiler.open = (0..profiler.spans.len()).collect();
+
+		profiler.last_frame = aggregate(&finished);
+		profiler.last_frame_time = Duration::microseconds(now - profiler.frame_start);
+
+		let frame = Span { name: "frame", depth: 0, start: profiler.frame_start, duration: now - profiler.frame_start };
+		finished.insert(0, frame);
+		if profiler.history.len() == HISTORY_FRAMES {
+			profiler.history.pop_front();
+		}
+		profiler.history.push_back(finished);
+		profiler.frame_start = now;
+	})
+}
+
+// Scopes in the last frame, each followed by the ones nested inside it
+pub fn last_frame() -> Vec<ScopeTiming> {
+	PROFILER.with(|profiler| profiler.borrow().last_frame.clone())
+}
+
+// For when the profiler might be mid-update, as it is if a scope panics
+pub fn try_last_frame() -> Option<Vec<ScopeTiming>> {
+	PROFILER.with(|profiler| profiler.try_borrow().ok().map(|profiler| profiler.last_frame.clone()))
+}
+
+pub fn last_frame_time() -> Duration {
+	PROFILER.with(|profiler| profiler.borrow().last_frame_time)
+}
+
+// The spans of the latest frames, each frame's led by a span named "frame" covering all of it
+pub fn recent_spans() -> Vec<Span> {
+	PROFILER.with(|profiler| profiler.borrow().history.iter().flat_map(|frame| frame.iter().cloned()).collect())
+}
+
+// Writes the latest frames for chrome://tracing, returning how many spans there were
+pub fn save_trace(path: &Path) -> io::Result<usize> {
+	let spans = recent_spans();
+	let mut out = BufWriter::new(File::create(path)?);
+	write_trace(&spans, &mut out)?;
+	out.flush()?;
+	Ok(spans.len())
+}
+
+// Chrome's trace event format, as complete events in microseconds
+pub fn write_trace<W: Write>(spans: &[Span], out: &mut W) -> io::Result<()> {
+	write!(out, "{{\"traceEvents\":[")?;
+	for (i, span) in spans.iter().enumerate() {
+		if i > 0 {
+			write!(out, ",")?;
+		}
+		write!(out, "\n{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":1}}",
+			escape(span.name), span.start, span.duration)?;
+	}
+	write!(out, "\n],\"displayTimeUnit\":\"ms\"}}\n")
+}
+
+fn escape(s: &str) -> String {
+	s.chars().flat_map(|c| match c {
+		'"' | '\\' => vec!['\\', c],
+		_ => vec![c],
+	}).collect()
+}
+
+// Merges spans with the same name under the same parent. The spans must be in the order they
+// were entered.
+fn aggregate(spans: &[Span]) -> Vec<ScopeTiming> {
+	// Timings in the order they were first seen, with the index of their parent
+	let mut timings: Vec<(Option<usize>, ScopeTiming)> = Vec::new();
+	// The timing each span in the current path was counted under, by depth
+	let mut path: Vec<usize> = Vec::new();
+	for span in spans {
+		path.truncate(span.depth);
+		let parent = path.last().cloned();
+		let index = match timings.iter().position(|&(p, ref timing)| p == parent && timing.name == span.name) {
+			Some(index) => index,
+			None => {
+				timings.push((parent, ScopeTiming { name: span.name, depth: span.depth, total: Duration::zero(), calls: 0 }));
+				timings.len() - 1
+			},
+		};
+		timings[index].1.total = timings[index].1.total + Duration::microseconds(span.duration);
+		timings[index].1.calls += 1;
+		path.push(index);
+	}
+
+	let mut ordered = Vec::with_capacity(timings.len());
+	add_children(&timings, None, &mut ordered);
+	ordered
+}
+
+fn add_children(timings: &[(Option<usize>, ScopeTiming)], parent: Option<usize>, ordered: &mut Vec<ScopeTiming>) {
+	for (i, &(p, ref timing)) in timings.iter().enumerate() {
+		if p == parent {
+			ordered.push(timing.clone());
+			add_children(timings, Some(i), ordered);
+		}
+	}
+}
@@ -0,0 +1,98 @@
+use rand::{RngCore, Error};
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// SplitMix64's finalizer, which spreads every input bit over the whole output
+fn mix(mut z: u64) -> u64 {
+	z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+	z ^ (z >> 31)
+}
+
+// Whatever draws random numbers on behalf of the world. Each gets its own stream, so one drawing
+// more or fewer numbers than it used to doesn't change what the others see.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Stream {
+	Mobs,
+	ItemDrops,
+	Snowfall,
+}
+
+impl Stream {
+	fn id(&self) -> u64 {
+		// 1 was decoration's, which was never drawn from. Ids aren't reused, so existing worlds
+		// keep their streams.
+		match *self {
+			Stream::Mobs => 2,
+			Stream::ItemDrops => 3,
+			Stream::Snowfall => 4,
+		}
+	}
+}
+
+// All randomness in a world, derived from its seed so the same seed plays out the same way
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct WorldRandom {
+	seed: u32,
+}
+
+impl WorldRandom {
+	pub fn new(seed: u32) -> WorldRandom {
+		WorldRandom { seed: seed }
+	}
+
+	pub fn seed(&self) -> u32 {
+		self.seed
+	}
+
+	// The nth noise function's seed. These are the world seed plus n, as they were before there
+	// was a WorldRandom, so existing worlds keep generating the same terrain.
+	pub fn noise_seed(&self, n: u32) -> u32 {
+		self.seed.wrapping_add(n)
+	}
+
+	pub fn stream(&self, stream: Stream) -> StreamRng {
+		StreamRng::new(mix(self.seed as u64 ^ mix(stream.id())))
+	}
+}
+
+// SplitMix64. Not cryptographic, but quick and the same on every platform and every version of
+// the rand crate.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StreamRng {
+	state: u64,
+}
+
+impl StreamRng {
+	pub fn new(seed: u64) -> StreamRng {
+		StreamRng { state: seed }
+	}
+
+	// An independent generator, advancing this one by a single step
+	pub fn split(&mut self) -> StreamRng {
+		StreamRng::new(mix(self.next_u64()))
+	}
+}
+
+impl RngCore for StreamRng {
+	fn next_u32(&mut self) -> u32 {
+		(self.next_u64() >> 32) as u32
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+		mix(self.state)
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		for chunk in dest.chunks_mut(8) {
+			let bytes = self.next_u64().to_le_bytes();
+			chunk.copy_from_slice(&bytes[..chunk.len()]);
+		}
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+		self.fill_bytes(dest);
+		Ok(())
+	}
+}
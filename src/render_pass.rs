@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+
+use glium::{Blend, Depth, Display, DrawParameters};
+use glium::draw_parameters::{DepthTest, BackfaceCullingMode, TimeElapsedQuery};
+use time::Duration;
+
+// The stages of a frame, in the order they're drawn. A frame only moves forwards through them;
+// drawing into a pass runs any skipped over on the way.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum RenderPass {
+	// Depth from the light's point of view. Nothing draws here yet.
+	Shadow,
+	Opaque,
+	// Liquids, blended over everything opaque
+	Translucent,
+	Entities,
+	DebugLines,
+	// The scene's trip through the post effects onto the frame
+	Post,
+	Hud,
+}
+
+pub const RENDER_PASSES: [RenderPass; 7] = [
+	RenderPass::Shadow,
+	RenderPass::Opaque,
+	RenderPass::Translucent,
+	RenderPass::Entities,
+	RenderPass::DebugLines,
+	RenderPass::Post,
+	RenderPass::Hud,
+];
+
+// Where a pass's draws end up
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PassTarget {
+	ShadowMap,
+	// The offscreen scene the post effects read from
+	Scene,
+	Frame,
+}
+
+impl RenderPass {
+	pub fn name(&self) -> &'static str {
+		match *self {
+			RenderPass::Shadow => "shadow",
+			RenderPass::Opaque => "opaque",
+			RenderPass::Translucent => "translucent",
+			RenderPass::Entities => "entities",
+			RenderPass::DebugLines => "debug lines",
+			RenderPass::Post => "post",
+			RenderPass::Hud => "hud",
+		}
+	}
+
+	pub fn target(&self) -> PassTarget {
+		match *self {
+			RenderPass::Shadow => PassTarget::ShadowMap,
+			RenderPass::Opaque | RenderPass::Translucent | RenderPass::Entities | RenderPass::DebugLines => PassTarget::Scene,
+			RenderPass::Post | RenderPass::Hud => PassTarget::Frame,
+		}
+	}
+
+	pub fn draw_parameters(&self) -> DrawParameters<'static> {
+		match *self {
+			RenderPass::Shadow => DrawParameters {
+				depth: Depth {
+					test: DepthTest::IfLess,
+					write: true,
+					.. Default::default()
+				},
+				color_mask: (false, false, false, false),
+				.. Default::default()
+			},
+			RenderPass::Opaque | RenderPass::Entities => DrawParameters {
+				depth: Depth {
+					test: DepthTest::IfLess,
+					write: true,
+					.. Default::default()
+				},
+				backface_culling: BackfaceCullingMode::CullClockwise,
+				.. Default::default()
+			},
+			// Liquid surfaces are visible from below, and mustn't hide each other or whatever is
+			// behind them
+			RenderPass::Translucent => DrawParameters {
+				depth: Depth {
+					test: DepthTest::IfLess,
+					write: false,
+					.. Default::default()
+				},
+				blend: Blend::alpha_blending(),
+				backface_culling: BackfaceCullingMode::CullingDisabled,
+				.. Default::default()
+			},
+			// Unlit and drawn over everything else, so nothing hides what's being debugged
+			RenderPass::DebugLines => DrawParameters {
+				line_width: Some(2.0),
+				.. Default::default()
+			},
+			RenderPass::Post => Default::default(),
+			RenderPass::Hud => DrawParameters {
+				blend: Blend::alpha_blending(),
+				.. Default::default()
+			},
+		}
+	}
+}
+
+// What a frame submitted, so culling and batching can be measured. Vertices are as submitted,
+// so indexed meshes count each index.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct DrawStats {
+	pub draw_calls: usize,
+	pub vertices: usize,
+	pub triangles: usize,
+}
+
+impl DrawStats {
+	pub fn record(&mut self, vertices: usize, triangles: usize) {
+		self.draw_calls += 1;
+		self.vertices += vertices;
+		self.triangles += triangles;
+	}
+}
+
+// Frames of queries kept waiting for their results before they're given up on
+const MAX_PENDING_FRAMES: usize = 4;
+
+// How long the GPU spends on each pass, from timer queries wrapped around every draw in it.
+// Results come back a few frames late, so each frame's queries wait until they're ready rather
+// than stalling the frame. Queries can't be reused once read, so new ones are made each frame,
+// and only while timing is on.
+pub struct PassTimers {
+	enabled: bool,
+	current: Vec<(RenderPass, TimeElapsedQuery)>,
+	// Oldest first
+	pending: VecDeque<Vec<(RenderPass, TimeElapsedQuery)>>,
+	last: Vec<(RenderPass, Duration)>,
+}
+
+impl PassTimers {
+	pub fn new() -> PassTimers {
+		PassTimers {
+			enabled: false,
+			current: Vec::new(),
+			pending: VecDeque::new(),
+			last: Vec::new(),
+		}
+	}
+
+	pub fn enabled(&self) -> bool {
+		self.enabled
+	}
+
+	pub fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+		if !enabled {
+			self.current.clear();
+			self.pending.clear();
+			self.last.clear();
+		}
+	}
+
+	// Every pass begun has to be drawn in before the next, or its query would never be started.
+	// Turns timing off if the driver can't do timer queries.
+	pub fn begin(&mut self, display: &Display, pass: RenderPass) {
+		if !self.enabled {
+			return;
+		}
+		match TimeElapsedQuery::new(display) {
+			Ok(query) => self.current.push((pass, query)),
+			Err(err) => {
+				println!("GPU timing unavailable: {:?}", err);
+				self.set_enabled(false);
+			},
+		}
+	}
+
+	// For the draw parameters of every draw in `pass`
+	pub fn query(&self, pass: RenderPass) -> Option<&TimeElapsedQuery> {
+		self.current.iter().rev().find(|&&(p, _)| p == pass).map(|&(_, ref query)| query)
+	}
+
+	// Called once the frame is submitted. Picks up the newest frame whose results are all in.
+	pub fn end_frame(&mut self) {
+		if !self.enabled {
+			return;
+		}
+		let frame = ::std::mem::replace(&mut self.current, Vec::new());
+		self.pending.push_back(frame);
+
+		while let Some(ready) = self.pending.front().map(|frame| frame.iter().all(|&(_, ref query)| query.is_ready())) {
+			if !ready && self.pending.len() <= MAX_PENDING_FRAMES {
+				break;
+			}
+			let frame = self.pending.pop_front().unwrap();
+			if ready {
+				// Passes drawn more than once, as for each view, are added up
+				self.last.clear();
+				for (pass, query) in frame {
+					let time = Duration::nanoseconds(query.get() as i64);
+					match self.last.iter().position(|&(p, _)| p == pass) {
+						Some(i) => self.last[i].1 = self.last[i].1 + time,
+						None => self.last.push((pass, time)),
+					}
+				}
+				self.last.sort_by_key(|&(pass, _)| pass);
+			}
+		}
+	}
+
+	// From the latest frame measured, in pass order; empty while timing is off
+	pub fn last_frame(&self) -> &[(RenderPass, Duration)] {
+		&self.last
+	}
+}
@@ -0,0 +1,311 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use time;
+
+use game_rules::{GameRules, RULE_NAMES};
+use environment::{DAY_LENGTH, SUNRISE};
+
+pub const FORMAT_VERSION: u32 = 1;
+pub const METADATA_FILE: &'static str = "level.dat";
+const GENERATOR_OPTION_PREFIX: &'static str = "generator.";
+const RULE_PREFIX: &'static str = "rule.";
+// New worlds, and those saved before there was a time of day, start a little after sunrise
+const START_TIME: f64 = DAY_LENGTH * (SUNRISE as f64 + 0.05);
+
+#[derive(Debug)]
+pub enum SaveError {
+	Io(io::Error),
+	Malformed(String),
+	MissingField(&'static str),
+	UnsupportedVersion(u32),
+	UnknownGenerator(String),
+}
+
+impl From<io::Error> for SaveError {
+	fn from(err: io::Error) -> SaveError {
+		SaveError::Io(err)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldMetadata {
+	pub format_version: u32,
+	pub seed: u32,
+	pub generator: String,
+	pub generator_options: BTreeMap<String, String>,
+	pub created: i64,
+	// Where players appear, as the block their feet are in. Worlds saved before spawn points
+	// existed have none until they're next opened.
+	pub spawn: Option<[i64; 3]>,
+	// Half the width of the square around the origin that the world is confined to, in blocks;
+	// None for no border
+	pub border: Option<i64>,
+	// Rules missing from the file keep their defaults
+	pub rules: GameRules,
+	// Seconds of world time passed, as of the last save
+	pub time: f64,
+}
+
+// MIGRATIONS[n] upgrades a save from version `n + 1` to `n + 2`. A migration may rewrite the
+// raw metadata fields as well as any other files in the save directory.
+pub type Migration = fn(&Path, &mut HashMap<String, String>) -> Result<(), SaveError>;
+
+const MIGRATIONS: &'static [Migration] = &[];
+
+impl WorldMetadata {
+	pub fn new(seed: u32, generator: &str) -> WorldMetadata {
+		WorldMetadata {
+			format_version: FORMAT_VERSION,
+			seed: seed,
+			generator: generator.to_string(),
+			generator_options: BTreeMap::new(),
+			created: time::get_time().sec,
+			spawn: None,
+			border: None,
+			rules: GameRules::default(),
+			time: START_TIME,
+		}
+	}
+
+	pub fn path(dir: &Path) -> PathBuf {
+		dir.join(METADATA_FILE)
+	}
+
+	pub fn exists(dir: &Path) -> bool {
+		WorldMetadata::path(dir).is_file()
+	}
+
+	pub fn load(dir: &Path) -> Result<WorldMetadata, SaveError> {
+		let mut contents = String::new();
+		File::open(WorldMetadata::path(dir))?.read_to_string(&mut contents)?;
+
+		let mut fields = parse_fields(&contents)?;
+		let version = parse_field::<u32>(&fields, "format_version")?;
+
+		if version == 0 || version > FORMAT_VERSION {
+			return Err(SaveError::UnsupportedVersion(version));
+		}
+
+		if version < FORMAT_VERSION {
+			fs::copy(WorldMetadata::path(dir), dir.join(format!("{}.v{}.bak", METADATA_FILE, version)))?;
+			for migration in &MIGRATIONS[(version - 1) as usize..(FORMAT_VERSION - 1) as usize] {
+				migration(dir, &mut fields)?;
+			}
+			fields.insert("format_version".to_string(), FORMAT_VERSION.to_string());
+		}
+
+		let mut rules = GameRules::default();
+		for name in RULE_NAMES.iter() {
+			if let Some(raw) = fields.get(&format!("{}{}", RULE_PREFIX, name)) {
+				rules.set(name, raw).map_err(SaveError::Malformed)?;
+			}
+		}
+
+		let meta = WorldMetadata {
+			format_version: FORMAT_VERSION,
+			seed: parse_field(&fields, "seed")?,
+			generator: fields.get("generator").cloned().ok_or(SaveError::MissingField("generator"))?,
+			generator_options: fields.iter()
+				.filter(|&(key, _)| key.starts_with(GENERATOR_OPTION_PREFIX))
+				.map(|(key, value)| (key[GENERATOR_OPTION_PREFIX.len()..].to_string(), value.clone()))
+				.collect(),
+			created: parse_field(&fields, "created")?,
+			spawn: match fields.get("spawn") {
+				Some(raw) => Some(parse_position(raw).ok_or(SaveError::Malformed(format!("spawn={}", raw)))?),
+				None => None,
+			},
+			border: match fields.get("border") {
+				Some(_) => Some(parse_field(&fields, "border")?),
+				None => None,
+			},
+			rules: rules,
+			time: match fields.get("time") {
+				Some(_) => parse_field(&fields, "time")?,
+				None => START_TIME,
+			},
+		};
+
+		if version < FORMAT_VERSION {
+			meta.save(dir)?;
+		}
+
+		Ok(meta)
+	}
+
+	pub fn save(&self, dir: &Path) -> Result<(), SaveError> {
+		fs::create_dir_all(dir)?;
+
+		// Write to a scratch file and rename so a crash mid-write can't leave a truncated level.dat
+		let tmp = dir.join(format!("{}.tmp", METADATA_FILE));
+		{
+			let mut file = File::create(&tmp)?;
+			write!(file, "format_version={}\n", self.format_version)?;
+			write!(file, "seed={}\n", self.seed)?;
+			write!(file, "generator={}\n", self.generator)?;
+			for (key, value) in &self.generator_options {
+				write!(file, "{}{}={}\n", GENERATOR_OPTION_PREFIX, key, value)?;
+			}
+			write!(file, "created={}\n", self.created)?;
+			if let Some(spawn) = self.spawn {
+				write!(file, "spawn={},{},{}\n", spawn[0], spawn[1], spawn[2])?;
+			}
+			if let Some(border) = self.border {
+				write!(file, "border={}\n", border)?;
+			}
+			for name in RULE_NAMES.iter() {
+				write!(file, "{}{}={}\n", RULE_PREFIX, name, self.rules.get(name).unwrap())?;
+			}
+			write!(file, "time={}\n", self.time)?;
+			file.sync_all()?;
+		}
+		fs::rename(&tmp, WorldMetadata::path(dir))?;
+		Ok(())
+	}
+}
+
+pub fn parse_fields(contents: &str) -> Result<HashMap<String, String>, SaveError> {
+	let mut fields = HashMap::new();
+	for line in contents.lines() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let mut split = line.splitn(2, '=');
+		match (split.next(), split.next()) {
+			(Some(key), Some(value)) => { fields.insert(key.trim().to_string(), value.trim().to_string()); },
+			_ => return Err(SaveError::Malformed(line.to_string())),
+		}
+	}
+	Ok(fields)
+}
+
+use std::str::FromStr;
+fn parse_field<T: FromStr>(fields: &HashMap<String, String>, key: &'static str) -> Result<T, SaveError> {
+	let raw = fields.get(key).ok_or(SaveError::MissingField(key))?;
+	raw.parse().map_err(|_| SaveError::Malformed(format!("{}={}", key, raw)))
+}
+
+fn parse_position(raw: &str) -> Option<[i64; 3]> {
+	let coords: Vec<i64> = raw.split(',').map(|c| c.trim().parse().ok()).collect::<Option<_>>()?;
+	if coords.len() != 3 {
+		return None;
+	}
+	Some([coords[0], coords[1], coords[2]])
+}
+
+use block::{CHUNK_SIZE, ChunkStates};
+
+pub fn chunk_path(dir: &Path, pos: [i64; 3]) -> PathBuf {
+	dir.join("chunks").join(format!("{}.{}.{}.chunk", pos[0], pos[1], pos[2]))
+}
+
+// Chunks are stored as little-endian (run length, block id) pairs in x, y, z order, followed
+// the same way by (run length, state) pairs if any block has a state. Chunks saved before blocks
+// had states, or with none, end after the blocks.
+pub fn save_chunk(dir: &Path, pos: [i64; 3], blocks: &[[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE], states: &ChunkStates) -> Result<(), SaveError> {
+	let path = chunk_path(dir, pos);
+	fs::create_dir_all(path.parent().unwrap())?;
+
+	let mut data = encode_blocks(blocks);
+	if states.iter().flat_map(|plane| plane.iter()).flat_map(|row| row.iter()).any(|&state| state != 0) {
+		encode_runs(states.iter().flat_map(|plane| plane.iter()).flat_map(|row| row.iter()).map(|&state| state as u32), &mut data);
+	}
+	let tmp = path.with_extension("chunk.tmp");
+	File::create(&tmp)?.write_all(&data)?;
+	fs::rename(&tmp, &path)?;
+	Ok(())
+}
+
+// Renames the chunk's file to end in .corrupt, returning where it went
+pub fn set_aside_chunk(dir: &Path, pos: [i64; 3]) -> Result<PathBuf, SaveError> {
+	let path = chunk_path(dir, pos);
+	let aside = path.with_extension("chunk.corrupt");
+	fs::rename(&path, &aside)?;
+	Ok(aside)
+}
+
+// Chunks with blocks of `block_types` or more are malformed
+pub fn load_chunk(dir: &Path, pos: [i64; 3], block_types: usize, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE], states: &mut ChunkStates) -> Result<bool, SaveError> {
+	let path = chunk_path(dir, pos);
+	if !path.is_file() {
+		return Ok(false);
+	}
+
+	let mut data = Vec::new();
+	File::open(&path)?.read_to_end(&mut data)?;
+	let malformed = |err: String| SaveError::Malformed(format!("{}: {}", path.display(), err));
+	let used = decode_runs(&data, block_types as u64, |i, id| blocks[i / (CHUNK_SIZE * CHUNK_SIZE)][i / CHUNK_SIZE % CHUNK_SIZE][i % CHUNK_SIZE] = id as usize)
+		.map_err(&malformed)?;
+	if used < data.len() {
+		let rest = decode_runs(&data[used..], 1 << 8, |i, state| states[i / (CHUNK_SIZE * CHUNK_SIZE)][i / CHUNK_SIZE % CHUNK_SIZE][i % CHUNK_SIZE] = state as u8)
+			.map_err(&malformed)?;
+		if used + rest < data.len() {
+			return Err(malformed("trailing data".to_string()));
+		}
+	}
+	Ok(true)
+}
+
+pub fn encode_blocks(blocks: &[[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> Vec<u8> {
+	let mut data = Vec::new();
+	encode_runs(blocks.iter().flat_map(|plane| plane.iter()).flat_map(|row| row.iter()).map(|&block| block as u32), &mut data);
+	data
+}
+
+fn encode_runs<I: Iterator<Item = u32>>(values: I, data: &mut Vec<u8>) {
+	let mut run: Option<(u32, u32)> = None;
+	for value in values {
+		run = match run {
+			Some((len, id)) if id == value => Some((len + 1, id)),
+			Some((len, id)) => {
+				data.extend_from_slice(&len.to_le_bytes());
+				data.extend_from_slice(&id.to_le_bytes());
+				Some((1, value))
+			},
+			None => Some((1, value)),
+		};
+	}
+	if let Some((len, id)) = run {
+		data.extend_from_slice(&len.to_le_bytes());
+		data.extend_from_slice(&id.to_le_bytes());
+	}
+}
+
+pub fn decode_blocks(data: &[u8], block_types: usize, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> Result<(), String> {
+	let used = decode_runs(data, block_types as u64, |i, id| blocks[i / (CHUNK_SIZE * CHUNK_SIZE)][i / CHUNK_SIZE % CHUNK_SIZE][i % CHUNK_SIZE] = id as usize)?;
+	if used != data.len() {
+		return Err("too many blocks".to_string());
+	}
+	Ok(())
+}
+
+// Reads runs until they cover the chunk, calling `put` with the index of each value in x, y, z
+// order. Values of `limit` or more are refused. Returns how many bytes that took.
+fn decode_runs<F: FnMut(usize, u32)>(data: &[u8], limit: u64, mut put: F) -> Result<usize, String> {
+	let total = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+	let mut index = 0;
+	let mut used = 0;
+	while index < total {
+		if data.len() < used + 8 {
+			return Err(if used == data.len() { "too few blocks" } else { "truncated run" }.to_string());
+		}
+		let pair = &data[used..used + 8];
+		let len = u32::from_le_bytes([pair[0], pair[1], pair[2], pair[3]]) as usize;
+		let id = u32::from_le_bytes([pair[4], pair[5], pair[6], pair[7]]);
+		if index + len > total {
+			return Err("too many blocks".to_string());
+		}
+		if id as u64 >= limit {
+			return Err(format!("value {} out of range", id));
+		}
+		for i in index..index + len {
+			put(i, id);
+		}
+		index += len;
+		used += 8;
+	}
+	Ok(used)
+}
@@ -0,0 +1,117 @@
+use std::collections::{BTreeSet, HashMap};
+
+use time::Duration;
+
+// World simulation runs in fixed ticks, however long frames take
+pub const TICKS_PER_SECOND: i64 = 20;
+// Ticks beyond this in one frame are dropped, so a long stall doesn't take a long catch up
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+pub fn tick_length() -> Duration { Duration::milliseconds(1000 / TICKS_PER_SECOND) }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EventKind {
+	LiquidFlow,
+	BlockFall,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScheduledEvent {
+	pub tick: u64,
+	pub pos: [i64; 3],
+	pub kind: EventKind,
+}
+
+// Delayed and repeating events at block positions. Events due on the same tick run ordered by
+// position and kind, never by when they were scheduled, so a given world always plays out the
+// same way.
+pub struct TickScheduler {
+	tick: u64,
+	accumulated: Duration,
+	queue: BTreeSet<ScheduledEvent>,
+	// The tick each queued event is due, so one can't be queued twice
+	pending: HashMap<([i64; 3], EventKind), u64>,
+	intervals: HashMap<([i64; 3], EventKind), u64>,
+}
+
+impl TickScheduler {
+	pub fn new() -> TickScheduler {
+		TickScheduler {
+			tick: 0,
+			accumulated: Duration::zero(),
+			queue: BTreeSet::new(),
+			pending: HashMap::new(),
+			intervals: HashMap::new(),
+		}
+	}
+
+	pub fn tick(&self) -> u64 {
+		self.tick
+	}
+
+	pub fn len(&self) -> usize {
+		self.queue.len()
+	}
+
+	// Runs `kind` at `pos` `delay` ticks from now, at least one. An event already waiting there
+	// keeps its place instead.
+	pub fn schedule(&mut self, pos: [i64; 3], kind: EventKind, delay: u64) {
+		if self.pending.contains_key(&(pos, kind)) {
+			return;
+		}
+		let tick = self.tick + delay.max(1);
+		self.pending.insert((pos, kind), tick);
+		self.queue.insert(ScheduledEvent { tick: tick, pos: pos, kind: kind });
+	}
+
+	// Runs `kind` at `pos` every `interval` ticks until cancelled
+	pub fn schedule_repeating(&mut self, pos: [i64; 3], kind: EventKind, interval: u64) {
+		let interval = interval.max(1);
+		self.intervals.insert((pos, kind), interval);
+		self.schedule(pos, kind, interval);
+	}
+
+	pub fn cancel(&mut self, pos: [i64; 3], kind: EventKind) {
+		self.intervals.remove(&(pos, kind));
+		if let Some(tick) = self.pending.remove(&(pos, kind)) {
+			self.queue.remove(&ScheduledEvent { tick: tick, pos: pos, kind: kind });
+		}
+	}
+
+	// How many ticks have passed with this frame
+	pub fn advance(&mut self, time_elapsed: &Duration) -> u32 {
+		self.accumulated = self.accumulated + *time_elapsed;
+		let mut ticks = 0;
+		while self.accumulated >= tick_length() {
+			self.accumulated = self.accumulated - tick_length();
+			ticks += 1;
+		}
+		if ticks > MAX_TICKS_PER_FRAME {
+			ticks = MAX_TICKS_PER_FRAME;
+		}
+		ticks
+	}
+
+	// Moves on a tick and returns the events now due, in the order they should run. Repeating
+	// events are queued again before they're returned.
+	pub fn step(&mut self) -> Vec<ScheduledEvent> {
+		self.tick += 1;
+
+		let mut due = Vec::new();
+		while let Some(&event) = self.queue.iter().next() {
+			if event.tick > self.tick {
+				break;
+			}
+			self.queue.remove(&event);
+			self.pending.remove(&(event.pos, event.kind));
+			due.push(event);
+		}
+
+		for event in &due {
+			if let Some(&interval) = self.intervals.get(&(event.pos, event.kind)) {
+				self.schedule(event.pos, event.kind, interval);
+			}
+		}
+		due
+	}
+}
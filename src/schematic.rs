@@ -0,0 +1,148 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use save::SaveError;
+
+const MAGIC: &'static [u8; 4] = b"S3SC";
+const VERSION: u32 = 1;
+// Files claiming more cells than this are refused rather than allocated for
+pub const MAX_VOLUME: usize = 1 << 22;
+
+// A standalone block volume that can be pasted into a world. Cells left as None don't
+// overwrite whatever is already there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schematic {
+	size: [usize; 3],
+	blocks: Vec<Option<usize>>,
+}
+
+impl Schematic {
+	pub fn new(size: [usize; 3]) -> Schematic {
+		Schematic {
+			size: size,
+			blocks: vec![None; size[0] * size[1] * size[2]],
+		}
+	}
+
+	pub fn size(&self) -> [usize; 3] {
+		self.size
+	}
+
+	// The number of cells in a schematic of `size`, or None if that's more than MAX_VOLUME
+	pub fn volume(size: [usize; 3]) -> Option<usize> {
+		size[0].checked_mul(size[1]).and_then(|area| area.checked_mul(size[2])).filter(|&volume| volume <= MAX_VOLUME)
+	}
+
+	pub fn get(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+		self.blocks[self.index(x, y, z)]
+	}
+
+	pub fn set(&mut self, x: usize, y: usize, z: usize, block: Option<usize>) {
+		let index = self.index(x, y, z);
+		self.blocks[index] = block;
+	}
+
+	// Turned a quarter turn anticlockwise, seen from above, `turns` times
+	pub fn rotated(&self, turns: u32) -> Schematic {
+		let mut rotated = self.clone();
+		for _ in 0..turns % 4 {
+			let from = rotated;
+			let size = from.size;
+			rotated = Schematic::new([size[1], size[0], size[2]]);
+			for x in 0..size[0] {
+				for y in 0..size[1] {
+					for z in 0..size[2] {
+						rotated.set(size[1] - 1 - y, x, z, from.get(x, y, z));
+					}
+				}
+			}
+		}
+		rotated
+	}
+
+	fn index(&self, x: usize, y: usize, z: usize) -> usize {
+		assert!(x < self.size[0] && y < self.size[1] && z < self.size[2], "Schematic position out of bounds");
+		(x * self.size[1] + y) * self.size[2] + z
+	}
+
+	// The file is the magic, a version and the x, y, z size as little-endian u32s, followed by
+	// (run length, block id + 1) pairs in x, y, z order, where id 0 marks an empty cell. Ids of
+	// `block_types` or more are refused.
+	pub fn load(path: &Path, block_types: usize) -> Result<Schematic, SaveError> {
+		let mut data = Vec::new();
+		File::open(path)?.read_to_end(&mut data)?;
+
+		let malformed = |reason: &str| SaveError::Malformed(format!("{}: {}", path.display(), reason));
+		if data.len() < 20 || &data[0..4] != MAGIC {
+			return Err(malformed("not a schematic"));
+		}
+
+		let word = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+		let version = word(4);
+		if version != VERSION {
+			return Err(SaveError::UnsupportedVersion(version));
+		}
+
+		let size = [word(8) as usize, word(12) as usize, word(16) as usize];
+		if Schematic::volume(size).is_none() {
+			return Err(malformed("too large"));
+		}
+		let mut schematic = Schematic::new(size);
+		if (data.len() - 20) % 8 != 0 {
+			return Err(malformed("truncated run"));
+		}
+
+		let mut index = 0;
+		for pair in data[20..].chunks(8) {
+			let len = u32::from_le_bytes([pair[0], pair[1], pair[2], pair[3]]) as usize;
+			let id = u32::from_le_bytes([pair[4], pair[5], pair[6], pair[7]]) as usize;
+			if index + len > schematic.blocks.len() {
+				return Err(malformed("too many blocks"));
+			}
+			if id > block_types {
+				return Err(malformed(&format!("unknown block {}", id - 1)));
+			}
+			for block in &mut schematic.blocks[index..index + len] {
+				*block = id.checked_sub(1);
+			}
+			index += len;
+		}
+
+		if index != schematic.blocks.len() {
+			return Err(malformed("too few blocks"));
+		}
+		Ok(schematic)
+	}
+
+	pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+		let mut data = Vec::new();
+		data.extend_from_slice(MAGIC);
+		for word in &[VERSION, self.size[0] as u32, self.size[1] as u32, self.size[2] as u32] {
+			data.extend_from_slice(&word.to_le_bytes());
+		}
+
+		let mut run: Option<(u32, u32)> = None;
+		for block in &self.blocks {
+			let id = block.map(|b| b as u32 + 1).unwrap_or(0);
+			run = match run {
+				Some((len, run_id)) if run_id == id => Some((len + 1, run_id)),
+				Some((len, run_id)) => {
+					data.extend_from_slice(&len.to_le_bytes());
+					data.extend_from_slice(&run_id.to_le_bytes());
+					Some((1, id))
+				},
+				None => Some((1, id)),
+			};
+		}
+		if let Some((len, id)) = run {
+			data.extend_from_slice(&len.to_le_bytes());
+			data.extend_from_slice(&id.to_le_bytes());
+		}
+
+		let tmp = path.with_extension("tmp");
+		File::create(&tmp)?.write_all(&data)?;
+		fs::rename(&tmp, path)?;
+		Ok(())
+	}
+}
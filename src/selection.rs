@@ -0,0 +1,78 @@
+use gl_util::Vertex;
+
+// Pushed out this far from the blocks selected, so the box doesn't fight with their faces
+const MARGIN: f32 = 0.01;
+
+// A box of blocks for the region commands to work on, picked WorldEdit style by clicking one
+// corner block and then the opposite one while selecting
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Selection {
+	first: Option<[i64; 3]>,
+	second: Option<[i64; 3]>,
+}
+
+impl Selection {
+	pub fn new() -> Selection {
+		Selection::default()
+	}
+
+	pub fn set_first(&mut self, pos: [i64; 3]) {
+		self.first = Some(pos);
+	}
+
+	pub fn set_second(&mut self, pos: [i64; 3]) {
+		self.second = Some(pos);
+	}
+
+	pub fn clear(&mut self) {
+		self.first = None;
+		self.second = None;
+	}
+
+	// The lowest and highest blocks in the box, both inclusive, once both corners are picked
+	pub fn bounds(&self) -> Option<([i64; 3], [i64; 3])> {
+		let (a, b) = (self.first?, self.second?);
+		Some((
+			[a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+			[a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+		))
+	}
+
+	pub fn size(&self) -> Option<[i64; 3]> {
+		let (min, max) = self.bounds()?;
+		Some([max[0] - min[0] + 1, max[1] - min[1] + 1, max[2] - min[2] + 1])
+	}
+
+	// Triangles for the sides of the box, or of just the first corner's block until there's a
+	// second
+	pub fn faces(&self, color: [f32; 3]) -> Vec<Vertex> {
+		let (min, max) = match (self.bounds(), self.first) {
+			(Some(bounds), _) => bounds,
+			(None, Some(first)) => (first, first),
+			(None, None) => return Vec::new(),
+		};
+		let min = [min[0] as f32 - MARGIN, min[1] as f32 - MARGIN, min[2] as f32 - MARGIN];
+		let max = [max[0] as f32 + 1.0 + MARGIN, max[1] as f32 + 1.0 + MARGIN, max[2] as f32 + 1.0 + MARGIN];
+
+		let mut vertices = Vec::new();
+		for axis in 0..3 {
+			let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+			for &(side, sign) in [(min[axis], -1.0), (max[axis], 1.0)].iter() {
+				let corner = |a: f32, b: f32| {
+					let mut position = [0.0; 3];
+					position[axis] = side;
+					position[u] = a;
+					position[v] = b;
+					let mut normal = [0.0; 3];
+					normal[axis] = sign;
+					Vertex { position: position, normal: normal, color: color }
+				};
+				vertices.extend_from_slice(&[
+					corner(min[u], min[v]), corner(max[u], min[v]), corner(max[u], max[v]),
+					corner(min[u], min[v]), corner(max[u], max[v]), corner(min[u], max[v]),
+				]);
+			}
+		}
+		vertices
+	}
+}
@@ -0,0 +1,907 @@
+use std::rc::Rc;
+use std::path::Path;
+use std::sync::mpsc::{Sender, Receiver, TryRecvError};
+
+use time::Duration;
+
+use block::{World, BlockProperties, BlockRenderData, AIR};
+use falling::{FallingBlocks, FallingBlock};
+use water::WaterSimulation;
+use light::Lighting;
+use scheduler::{TickScheduler, EventKind};
+use entity::{Entities, Entity, EntityId, Aabb};
+use item::{ItemDrops, Inventory};
+use mob::Mobs;
+use random::Stream;
+use net::{NetServer, NetEvent, DEFAULT_VIEW_RADIUS};
+use audio::{Sound, BlockSounds};
+use weather::{Weather, Snowfall};
+use game_rules::GameRules;
+use stats::{Stats, GameEvent, EventHook};
+use environment::describe_time;
+use schematic::Schematic;
+use prefabs::{PrefabLibrary, Prefab, GLOBAL_PREFABS_DIR};
+use mirror::{WorldMirror, MirrorUpdate};
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+pub const CROUCH_HEIGHT: f32 = 1.5;
+// Below the top of the player's box
+pub const EYE_DEPTH: f32 = 0.18;
+pub const EYE_HEIGHT: f32 = PLAYER_HEIGHT - EYE_DEPTH;
+// Ledges up to a block high are walked onto without jumping
+const STEP_HEIGHT: f32 = 1.0;
+const JUMP_SPEED: f32 = 9.5;
+// How far from the eye blocks can be broken or placed against, unless the client asks for less
+pub const DEFAULT_REACH: f32 = 5.0;
+pub const MAX_REACH: f32 = 8.0;
+// The most blocks a region command may change, so a slip of the mouse can't stall the server
+pub const MAX_EDIT_VOLUME: i64 = 1 << 21;
+// How far below the spawn point a walking player can fall before being put back there
+const KILL_PLANE_DEPTH: i64 = 256;
+const SPAWN_DISTANCE: f32 = 64.0;
+// Blocks walked between footsteps
+const STRIDE_LENGTH: f32 = 1.6;
+
+fn maintenance_interval() -> Duration { Duration::seconds(5) }
+// Warn when a tick spends longer than this generating chunks, but at most once per notice interval
+fn generation_lag() -> Duration { Duration::milliseconds(50) }
+fn lag_notice_interval() -> Duration { Duration::seconds(10) }
+
+// What the player is doing this frame, as decided by the client from its input
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PlayerInput {
+	// Horizontal walking velocity, in blocks per second
+	pub velocity: [f32; 2],
+	pub jump: bool,
+	pub crouch: bool,
+	// Where the camera is, which is where the player is while flying
+	pub eye: [f32; 3],
+	// The way the camera faces, for streaming what's in front of the player first
+	pub look: [f32; 3],
+	// The way the player looks while holding the break button, None when it's not held
+	pub breaking: Option<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+	Input(PlayerInput),
+	// Gives the player a body standing at `feet`
+	Walk([f32; 3]),
+	Fly,
+	// Against the face of the block the ray meets, turned by the way the ray runs
+	PlaceBlock { origin: [f32; 3], direction: [f32; 3], block: usize },
+	// Over the inclusive box from `min` to `max`, replacing only `from` if it's given, as one
+	// action to undo
+	FillRegion { min: [i64; 3], max: [i64; 3], block: usize, from: Option<usize> },
+	// Into the clipboard, remembering where the box is from `origin`
+	Copy { min: [i64; 3], max: [i64; 3], origin: [i64; 3] },
+	// The clipboard, turned a quarter turn anticlockwise `turns` times about its origin, with the
+	// origin at `origin`
+	Paste { origin: [i64; 3], turns: u32 },
+	// Saves the box as a prefab, with the world or for every world
+	SavePrefab { name: String, min: [i64; 3], max: [i64; 3], global: bool },
+	// Into the clipboard, to paste with its lowest corner where the player looks
+	LoadPrefab(String),
+	ListPrefabs,
+	// Reports how many of each block are in the box, and how many faces the mesher draws there
+	CountBlocks { min: [i64; 3], max: [i64; 3] },
+	CountFaces { min: [i64; 3], max: [i64; 3] },
+	SpawnMobs { origin: [f32; 3], direction: [f32; 3], count: usize },
+	Respawn,
+	Undo,
+	Redo,
+	SetPhysicsDebug(bool),
+	// Opens the world to other players on this port
+	Host(u16),
+	// Asks for a report on the hosted server's traffic
+	NetStats,
+	Chat { from: String, text: String },
+	SetWeather(Weather),
+	// Half the width of the square around the origin the world is kept to, or None to lift it
+	SetBorder(Option<i64>),
+	// The value as typed, parsed by GameRules::set
+	SetGameRule { name: String, value: String },
+	// In seconds of world time
+	SetTime(f64),
+	AddTime(f64),
+	// In chunks; other players are streamed as far as the host can see
+	SetRenderDistance(i64),
+	// In blocks, up to MAX_REACH
+	SetReach(f32),
+	// Writes every changed chunk to the world's save directory now rather than on eviction
+	Save,
+	// Keeps the client's own copy of the world up to date on these chunks, both corners inclusive
+	Watch { min: [i64; 3], max: [i64; 3] },
+	// Stops the server, saving the world
+	Quit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerState {
+	pub id: EntityId,
+	pub position: [f32; 3],
+	pub bounds: Aabb,
+	pub crouching: bool,
+}
+
+impl PlayerState {
+	pub fn eye(&self) -> [f32; 3] {
+		[self.position[0], self.position[1], self.position[2] + self.bounds.max[2] - EYE_DEPTH]
+	}
+}
+
+// Everything the client needs to draw a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+	pub player: Option<PlayerState>,
+	pub entities: Vec<(EntityId, Entity)>,
+	pub falling: Vec<FallingBlock>,
+	// Only gathered while the client's physics overlay is on
+	pub collision_candidates: Vec<[i64; 3]>,
+	pub weather: Weather,
+	pub border: Option<i64>,
+	pub rules: GameRules,
+	// Seconds of world time passed
+	pub time: f64,
+	// The block the player is breaking, and how far along it is from 0 to 1
+	pub breaking: Option<([i64; 3], f32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+	Snapshot(Snapshot),
+	// The player was moved somewhere new, so the camera shouldn't glide there
+	Teleported { eye: [f32; 3] },
+	Log(String),
+	Chat { from: String, text: String },
+	Sound { sound: Sound, position: [f32; 3] },
+	// Shown briefly as a toast
+	Notify(String),
+	// For the client's copy of the world, once it's asked for one
+	World(MirrorUpdate),
+	Stats(WorldStats),
+	Prefabs(Vec<Prefab>),
+	PlayerStats(Stats),
+}
+
+// How much of the world the server has in memory
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct WorldStats {
+	// Chunks the index still has entries for, and how many of those are still alive
+	pub indexed_chunks: usize,
+	pub live_chunks: usize,
+	pub cached_chunks: usize,
+	pub cache_bytes: usize,
+}
+
+// The world and everything simulated in it. Clients only see it through the messages it sends,
+// and only change it through the messages it receives.
+pub struct Server {
+	world: World,
+	block_properties: Rc<Vec<BlockProperties>>,
+	block_render_types: Rc<Vec<BlockRenderData>>,
+	falling_blocks: FallingBlocks,
+	water: WaterSimulation,
+	snowfall: Snowfall,
+	lighting: Lighting,
+	scheduler: TickScheduler,
+	entities: Entities,
+	item_drops: ItemDrops,
+	inventory: Inventory,
+	mobs: Mobs,
+	// The player's body while walking, None while flying
+	player: Option<EntityId>,
+	crouching: bool,
+	// Walked since the last footstep
+	stride: f32,
+	// Where the player was last tick while flying, to measure how far they fly
+	flown_from: Option<[f32; 3]>,
+	stats: Stats,
+	// What was last copied, and where its lowest corner was from the origin it was copied at
+	clipboard: Option<(Schematic, [i64; 3])>,
+	prefabs: PrefabLibrary,
+	// The block being broken, as it was when the player started on it, and for how many seconds
+	breaking: Option<([i64; 3], usize, f32)>,
+	input: Option<PlayerInput>,
+	debug_physics: bool,
+	since_maintenance: Duration,
+	since_lag_notice: Duration,
+	since_autosave: Duration,
+	net: Option<NetServer>,
+	view_radius: i64,
+	reach: f32,
+	mirror: Option<WorldMirror>,
+	running: bool,
+	inbox: Receiver<ClientMessage>,
+	outbox: Sender<ServerMessage>,
+}
+
+impl Server {
+	pub fn new(
+		world: World,
+		block_properties: Rc<Vec<BlockProperties>>,
+		block_render_types: Rc<Vec<BlockRenderData>>,
+		inbox: Receiver<ClientMessage>,
+		outbox: Sender<ServerMessage>
+	) -> Server {
+		Server {
+			falling_blocks: FallingBlocks::new(&world, block_properties.clone()),
+			water: WaterSimulation::new(&world, block_properties.clone()),
+			lighting: Lighting::new(&world, block_properties.clone()),
+			snowfall: Snowfall::new(block_properties.clone(), world.random().stream(Stream::Snowfall)),
+			scheduler: TickScheduler::new(),
+			entities: Entities::new(block_properties.clone()),
+			item_drops: ItemDrops::new(world.random().stream(Stream::ItemDrops)),
+			inventory: Inventory::new(),
+			mobs: Mobs::new(block_properties.clone(), world.random().stream(Stream::Mobs)),
+			stats: match world.save_dir().map(Stats::load) {
+				Some(Ok(stats)) => stats,
+				Some(Err(err)) => {
+					println!("Failed to load stats, starting over: {:?}", err);
+					Stats::default()
+				},
+				None => Stats::default(),
+			},
+			prefabs: PrefabLibrary::new(world.save_dir(), Path::new(GLOBAL_PREFABS_DIR)),
+			world: world,
+			block_properties: block_properties,
+			block_render_types: block_render_types,
+			player: None,
+			crouching: false,
+			stride: 0.0,
+			flown_from: None,
+			clipboard: None,
+			breaking: None,
+			input: None,
+			debug_physics: false,
+			since_maintenance: Duration::zero(),
+			since_lag_notice: lag_notice_interval(),
+			since_autosave: Duration::zero(),
+			net: None,
+			view_radius: DEFAULT_VIEW_RADIUS,
+			reach: DEFAULT_REACH,
+			mirror: None,
+			running: true,
+			inbox: inbox,
+			outbox: outbox,
+		}
+	}
+
+	pub fn world(&self) -> &World {
+		&self.world
+	}
+
+	pub fn inventory(&self) -> &Inventory {
+		&self.inventory
+	}
+
+	pub fn stats(&self) -> &Stats {
+		&self.stats
+	}
+
+	// Until the client quits or goes away
+	pub fn running(&self) -> bool {
+		self.running
+	}
+
+	pub fn tick(&mut self, time_elapsed: &Duration) {
+		profile_scope!("server");
+		let seconds = time_elapsed.num_microseconds().unwrap() as f32 / 1_000_000.0;
+		loop {
+			match self.inbox.try_recv() {
+				Ok(message) => self.handle(message),
+				Err(TryRecvError::Empty) => break,
+				Err(TryRecvError::Disconnected) => {
+					self.running = false;
+					break;
+				},
+			}
+		}
+		self.walk(seconds);
+		self.break_held(seconds);
+		if self.world.rules().day_night_cycle {
+			let time = self.world.time() + seconds as f64;
+			self.world.set_time(time);
+		}
+
+		self.world.update_blocks();
+		let events = match self.net {
+			Some(ref mut net) => net.tick(&self.world),
+			None => Vec::new(),
+		};
+		for event in events {
+			match event {
+				NetEvent::Joined(name) => self.log(format!("{} joined", name)),
+				NetEvent::Left { name: Some(name), reason } => self.log(format!("{} left: {}", name, reason)),
+				NetEvent::Left { name: None, .. } | NetEvent::EditRefused { .. } => (),
+				NetEvent::Chat { from, text } => self.send(ServerMessage::Chat { from: from, text: text }),
+				NetEvent::Edited { pos, old, new } => {
+					let sound = if new == AIR { self.sounds(old).broken } else { self.sounds(new).placed };
+					self.play_at_block(pos, sound);
+				},
+			}
+		}
+		self.falling_blocks.tick(&self.world, &mut self.scheduler, time_elapsed);
+		self.water.tick(&mut self.scheduler);
+		for _ in 0..self.scheduler.advance(time_elapsed) {
+			for event in self.scheduler.step() {
+				match event.kind {
+					EventKind::LiquidFlow => self.water.flow(&self.world, event.pos),
+					EventKind::BlockFall => self.falling_blocks.fall(&self.world, event.pos),
+				}
+			}
+		}
+		self.lighting.tick(&self.world);
+		self.mobs.tick(&self.world, &mut self.entities, time_elapsed);
+		let before = self.player.and_then(|id| self.entities.get(id)).map(|body| (body.position, body.on_ground));
+		self.entities.tick(&self.world, time_elapsed);
+		self.keep_within_border();
+		if let Some((position, on_ground)) = before {
+			self.footsteps(position, on_ground);
+		}
+		self.track_movement(before.map(|(position, _)| position));
+		self.stats.playtime += seconds as f64;
+		let fallen = self.player.and_then(|id| self.entities.get(id))
+			.map(|body| body.position[2] < (self.world.spawn()[2] - KILL_PLANE_DEPTH) as f32)
+			.unwrap_or(false);
+		if fallen {
+			self.respawn();
+		}
+
+		let player = match self.player_state() {
+			Some(state) => Some(state.eye()),
+			None => self.input.map(|input| input.eye),
+		};
+		if let (Some(player), Some(mirror)) = (player, self.mirror.as_mut()) {
+			for update in mirror.tick(&self.world, player, self.input.map(|input| input.look)) {
+				let _ = self.outbox.send(ServerMessage::World(update));
+			}
+		}
+		if let Some(player) = player {
+			self.snowfall.tick(&self.world, player, time_elapsed);
+			if self.item_drops.tick(&mut self.entities, player, &mut self.inventory, time_elapsed) > 0 {
+				self.send(ServerMessage::Sound { sound: Sound::ItemPickup, position: player });
+			}
+		}
+
+		self.since_lag_notice = self.since_lag_notice + *time_elapsed;
+		if self.world.take_generation_time() > generation_lag() && self.since_lag_notice >= lag_notice_interval() {
+			self.notify("Chunk generation lagging");
+			self.since_lag_notice = Duration::zero();
+		}
+
+		self.since_maintenance = self.since_maintenance + *time_elapsed;
+		if self.since_maintenance >= maintenance_interval() {
+			self.world.maintain();
+			self.since_maintenance = Duration::zero();
+		}
+
+		let autosave_interval = self.world.rules().autosave_interval;
+		self.since_autosave = self.since_autosave + *time_elapsed;
+		if autosave_interval > 0 && self.since_autosave >= Duration::seconds(autosave_interval as i64) {
+			self.save();
+			self.since_autosave = Duration::zero();
+		}
+
+		let snapshot = self.snapshot(seconds);
+		self.send(ServerMessage::Snapshot(snapshot));
+		self.send(ServerMessage::Stats(self.world_stats()));
+		self.send(ServerMessage::PlayerStats(self.stats));
+	}
+
+	fn save(&self) {
+		self.world.save();
+		if let Some(dir) = self.world.save_dir() {
+			if let Err(err) = self.stats.save(dir) {
+				self.log(format!("Failed to save stats: {:?}", err));
+			}
+		}
+	}
+
+	// Where interaction and movement report what the player did
+	fn emit(&mut self, event: GameEvent) {
+		self.stats.on_event(&event);
+	}
+
+	// From where the player's body was before this tick's physics, if they have one
+	fn track_movement(&mut self, before: Option<[f32; 3]>) {
+		let after = self.player.and_then(|id| self.entities.get(id)).map(|body| body.position);
+		if let (Some(before), Some(after)) = (before, after) {
+			self.emit(GameEvent::Walked(distance(before, after)));
+		}
+
+		let eye = match self.input {
+			Some(input) if self.player.is_none() => Some(input.eye),
+			_ => None,
+		};
+		if let (Some(from), Some(eye)) = (self.flown_from, eye) {
+			self.emit(GameEvent::Flew(distance(from, eye)));
+		}
+		self.flown_from = eye;
+	}
+
+	fn world_stats(&self) -> WorldStats {
+		let (indexed, live) = self.world.chunk_counts();
+		let (cached, cache_bytes) = self.world.cache_usage();
+		WorldStats { indexed_chunks: indexed, live_chunks: live, cached_chunks: cached, cache_bytes: cache_bytes }
+	}
+
+	fn send(&self, message: ServerMessage) {
+		// A client that's gone has nothing left to be told
+		let _ = self.outbox.send(message);
+	}
+
+	fn log<S: Into<String>>(&self, line: S) {
+		self.send(ServerMessage::Log(line.into()));
+	}
+
+	fn notify<S: Into<String>>(&self, text: S) {
+		self.send(ServerMessage::Notify(text.into()));
+	}
+
+	fn handle(&mut self, message: ClientMessage) {
+		match message {
+			ClientMessage::Input(input) => self.input = Some(input),
+			ClientMessage::Walk(feet) => {
+				if self.player.is_none() {
+					let mut body = Entity::new(feet, Aabb::standing(PLAYER_WIDTH, PLAYER_HEIGHT), [1.0; 3]);
+					body.step_height = STEP_HEIGHT;
+					self.player = Some(self.entities.spawn(body));
+					self.crouching = false;
+				}
+			},
+			ClientMessage::Fly if !self.world.rules().fly => self.log("Flying isn't allowed in this world"),
+			ClientMessage::Fly => {
+				if let Some(id) = self.player.take() {
+					self.entities.despawn(id);
+				}
+			},
+			ClientMessage::PlaceBlock { origin, direction, block } => self.place_block(origin, direction, block),
+			ClientMessage::FillRegion { min, max, block, from } => self.fill_region(min, max, block, from),
+			ClientMessage::Copy { min, max, origin } => self.copy(min, max, origin),
+			ClientMessage::Paste { origin, turns } => self.paste(origin, turns),
+			ClientMessage::SavePrefab { name, min, max, global } => self.save_prefab(&name, min, max, global),
+			ClientMessage::LoadPrefab(name) => match self.prefabs.load(&name, self.block_properties.len()) {
+				Ok(schematic) => {
+					self.clipboard = Some((schematic, [0; 3]));
+					self.log(format!("Copied {}, ready to paste", name));
+				},
+				Err(err) => self.log(format!("Failed to load {}: {:?}", name, err)),
+			},
+			ClientMessage::ListPrefabs => self.send(ServerMessage::Prefabs(self.prefabs.list())),
+			ClientMessage::CountBlocks { min, max } => self.count_blocks(min, max),
+			ClientMessage::CountFaces { min, max } => {
+				if volume(min, max) > MAX_EDIT_VOLUME {
+					return self.log(format!("The region is more than the {} blocks that can be measured at once", MAX_EDIT_VOLUME));
+				}
+				let faces = self.world.exposed_faces(min, max, &self.block_render_types);
+				self.log(format!("{} exposed face(s)", faces));
+			},
+			ClientMessage::SpawnMobs { .. } if !self.world.rules().mob_spawning => self.log("Mob spawning is off in this world"),
+			ClientMessage::SpawnMobs { origin, direction, count } => self.spawn_mobs(origin, direction, count),
+			ClientMessage::Respawn => {
+				self.respawn();
+				self.log("Returned to spawn");
+			},
+			ClientMessage::Undo => { self.world.undo(); },
+			ClientMessage::Redo => { self.world.redo(); },
+			ClientMessage::SetPhysicsDebug(on) => self.debug_physics = on,
+			ClientMessage::Host(port) => self.host(port),
+			ClientMessage::NetStats => self.report_net_stats(),
+			ClientMessage::SetReach(reach) => self.reach = reach.max(0.0).min(MAX_REACH),
+			ClientMessage::SetRenderDistance(radius) => {
+				self.view_radius = radius;
+				if let Some(ref mut net) = self.net {
+					net.set_view_radius(radius);
+				}
+			},
+			ClientMessage::Save => match self.world.save_dir() {
+				Some(_) => {
+					self.save();
+					self.notify("World saved");
+				},
+				None => self.notify("This world isn't saved to disk"),
+			},
+			ClientMessage::SetWeather(weather) => {
+				self.world.set_weather(weather);
+				self.log(format!("Weather set to {}", weather));
+			},
+			ClientMessage::SetBorder(border) => {
+				if let Err(err) = self.world.set_border(border) {
+					self.log(format!("Failed to save the border: {:?}", err));
+				}
+				match border {
+					Some(border) => self.log(format!("Border set to {} blocks from the origin", border)),
+					None => self.log("Border removed"),
+				}
+			},
+			ClientMessage::SetGameRule { name, value } => self.set_game_rule(&name, &value),
+			ClientMessage::SetTime(time) => {
+				self.world.set_time(time);
+				self.log(format!("Time set to {}", describe_time(self.world.time())));
+			},
+			ClientMessage::AddTime(seconds) => {
+				let time = self.world.time() + seconds;
+				self.world.set_time(time);
+				self.log(format!("Time set to {}", describe_time(self.world.time())));
+			},
+			ClientMessage::Watch { min, max } => {
+				if let Some(mirror) = self.mirror.take() {
+					mirror.close(&self.world);
+				}
+				self.mirror = Some(WorldMirror::new(&self.world, min, max));
+			},
+			ClientMessage::Quit => self.running = false,
+			ClientMessage::Chat { from, text } => {
+				if let Some(ref mut net) = self.net {
+					net.say(&from, &text);
+				}
+				self.send(ServerMessage::Chat { from: from, text: text });
+			},
+		}
+	}
+
+	fn host(&mut self, port: u16) {
+		if let Some(addr) = self.net.as_ref().and_then(|net| net.local_addr().ok()) {
+			return self.log(format!("Already hosting on {}", addr));
+		}
+		match NetServer::bind(("0.0.0.0", port), &self.world, self.block_render_types.len()) {
+			Ok(mut net) => {
+				net.set_view_radius(self.view_radius);
+				self.net = Some(net);
+				self.log(format!("Hosting on port {}", port));
+			},
+			Err(err) => self.log(format!("Failed to host: {:?}", err)),
+		}
+	}
+
+	fn set_game_rule(&mut self, name: &str, value: &str) {
+		let mut rules = self.world.rules();
+		if let Err(err) = rules.set(name, value) {
+			return self.log(err);
+		}
+		if let Err(err) = self.world.set_rules(rules) {
+			self.log(format!("Failed to save the game rules: {:?}", err));
+		}
+		self.log(format!("Game rule {} set to {}", name, rules.get(name).unwrap()));
+	}
+
+	fn report_net_stats(&self) {
+		let net = match self.net {
+			Some(ref net) => net,
+			None => return self.log("Not hosting"),
+		};
+		let stats = net.stats();
+		let bandwidth = net.bandwidth();
+		self.log(format!("{} player(s); sending {:.1} KB/s, receiving {:.1} KB/s",
+			net.players(), bandwidth.sent_per_second / 1024.0, bandwidth.received_per_second / 1024.0));
+		self.log(format!("{} chunk(s) sent, {} bytes each on average; {} change(s) sent in {} delta(s)",
+			stats.chunks_sent, stats.chunk_bytes / stats.chunks_sent.max(1), stats.changes_sent, stats.deltas_sent));
+	}
+
+	fn player_state(&self) -> Option<PlayerState> {
+		let id = self.player?;
+		self.entities.get(id).map(|body| PlayerState {
+			id: id,
+			position: body.position,
+			bounds: body.bounds,
+			crouching: self.crouching,
+		})
+	}
+
+	fn snapshot(&self, seconds: f32) -> Snapshot {
+		let mut collision_candidates = Vec::new();
+		if self.debug_physics {
+			for (_, entity) in self.entities.iter() {
+				collision_candidates.extend(self.entities.collision_candidates(&self.world, entity, seconds));
+			}
+		}
+
+		Snapshot {
+			player: self.player_state(),
+			entities: self.entities.iter().map(|(&id, entity)| (id, entity.clone())).collect(),
+			falling: self.falling_blocks.falling().to_vec(),
+			collision_candidates: collision_candidates,
+			weather: self.world.weather(),
+			border: self.world.border(),
+			rules: self.world.rules(),
+			time: self.world.time(),
+			breaking: self.breaking.map(|(pos, block, held)| {
+				let hardness = self.block_properties.get(block).map(|props| props.hardness).unwrap_or(0.0);
+				(pos, if hardness > 0.0 { (held / hardness).min(1.0) } else { 1.0 })
+			}),
+		}
+	}
+
+	// Stops the player's body at the border, as though it were a wall
+	fn keep_within_border(&mut self) {
+		let (border, id) = match (self.world.border(), self.player) {
+			(Some(border), Some(id)) => (border as f32, id),
+			_ => return,
+		};
+		if let Some(body) = self.entities.get_mut(id) {
+			for axis in 0..2 {
+				let (min, max) = (-border - body.bounds.min[axis], border - body.bounds.max[axis]);
+				if body.position[axis] < min || body.position[axis] > max {
+					body.position[axis] = body.position[axis].max(min).min(max);
+					body.velocity[axis] = 0.0;
+				}
+			}
+		}
+	}
+
+	// Moves the player's body by the latest input; the entity tick then carries it out
+	fn walk(&mut self, seconds: f32) {
+		let input = match self.input {
+			Some(input) => input,
+			None => return,
+		};
+		let mut body = match self.player.and_then(|id| self.entities.get(id)) {
+			Some(body) => body.clone(),
+			None => return,
+		};
+
+		// Standing back up needs headroom
+		if input.crouch != self.crouching {
+			let bounds = Aabb::standing(PLAYER_WIDTH, if input.crouch { CROUCH_HEIGHT } else { PLAYER_HEIGHT });
+			if input.crouch || !self.entities.collides(&self.world, &bounds.offset(body.position)) {
+				body.bounds = bounds;
+				self.crouching = input.crouch;
+			}
+		}
+
+		body.velocity[0] = input.velocity[0];
+		body.velocity[1] = input.velocity[1];
+		// Crouching stops at edges rather than walking off them
+		if self.crouching && body.on_ground {
+			for axis in 0..2 {
+				let mut step = [0.0; 3];
+				step[axis] = body.velocity[axis] * seconds;
+				if !self.entities.has_support(&self.world, &body.world_bounds().offset(step)) {
+					body.velocity[axis] = 0.0;
+				}
+			}
+		}
+		if body.on_ground && input.jump {
+			body.velocity[2] = JUMP_SPEED;
+		}
+
+		if let Some(entity) = self.player.and_then(|id| self.entities.get_mut(id)) {
+			*entity = body;
+		}
+	}
+
+	// Puts the player at the world spawn
+	fn respawn(&mut self) {
+		let spawn = self.world.spawn();
+		let feet = [spawn[0] as f32 + 0.5, spawn[1] as f32 + 0.5, spawn[2] as f32];
+		if let Some(body) = self.player.and_then(|id| self.entities.get_mut(id)) {
+			body.position = feet;
+			body.velocity = [0.0; 3];
+		}
+		let eye = match self.player_state() {
+			Some(state) => state.eye(),
+			None => [feet[0], feet[1], feet[2] + EYE_HEIGHT],
+		};
+		self.send(ServerMessage::Teleported { eye: eye });
+		// Not flown
+		self.flown_from = None;
+	}
+
+	// Works at the first block along the ray while the break button is held, breaking it once
+	// it's been held on it for as long as it's hard. Looking away, or the block changing, starts
+	// over.
+	fn break_held(&mut self, seconds: f32) {
+		let (origin, direction) = match self.input {
+			Some(PlayerInput { eye, breaking: Some(direction), .. }) => (eye, direction),
+			_ => {
+				self.breaking = None;
+				return;
+			},
+		};
+		let render = self.block_render_types.clone();
+		let breakable = |block: usize| render.get(block).map(|data| data.should_render && !data.liquid).unwrap_or(false);
+		let pos = match self.world.raycast(origin, direction, self.reach, breakable) {
+			Some((pos, _)) => pos,
+			None => {
+				self.breaking = None;
+				return;
+			},
+		};
+
+		let block = self.world.get_block(pos);
+		let held = match self.breaking {
+			Some((target, target_block, held)) if target == pos && target_block == block => held + seconds,
+			_ => seconds,
+		};
+		let hardness = self.block_properties.get(block).map(|props| props.hardness).unwrap_or(0.0);
+		if held >= hardness {
+			self.break_block(pos);
+			self.breaking = None;
+		} else {
+			self.breaking = Some((pos, block, held));
+		}
+	}
+
+	// Leaves a drop behind
+	fn break_block(&mut self, pos: [i64; 3]) {
+		let block = self.world.get_block(pos);
+		self.world.set_block(pos, AIR);
+		self.emit(GameEvent::BlockBroken { pos: pos, block: block });
+		self.item_drops.spawn(&mut self.entities, pos, block, self.block_render_types[block].color);
+		let sound = self.sounds(block).broken;
+		self.play_at_block(pos, sound);
+	}
+
+	// Only into air, so placing never replaces anything, and solid blocks not where they'd
+	// trap the player or anything else that moves. Drops just get pushed out.
+	fn place_block(&mut self, origin: [f32; 3], direction: [f32; 3], block: usize) {
+		let (orienting, solid_block) = match self.block_properties.get(block) {
+			Some(props) if block != AIR => (props.orienting, props.solid),
+			_ => return,
+		};
+		let render = self.block_render_types.clone();
+		let solid = |block: usize| render.get(block).map(|data| data.should_render && !data.liquid).unwrap_or(false);
+
+		if let Some((_, before)) = self.world.raycast(origin, direction, self.reach, solid) {
+			if self.world.get_block(before) != AIR || !self.world.within_border(before[0], before[1]) {
+				return;
+			}
+			let min = [before[0] as f32, before[1] as f32, before[2] as f32];
+			let cell = Aabb::new(min, [min[0] + 1.0, min[1] + 1.0, min[2] + 1.0]);
+			let occupied = self.entities.iter()
+				.any(|(&id, entity)| !self.item_drops.contains(id) && entity.world_bounds().intersects(&cell));
+			if solid_block && occupied {
+				return;
+			}
+			self.world.set_block(before, block);
+			self.world.set_state(before, orienting.place(direction).state());
+			self.emit(GameEvent::BlockPlaced { pos: before, block: block });
+			let sound = self.sounds(block).placed;
+			self.play_at_block(before, sound);
+		}
+	}
+
+	fn sounds(&self, block: usize) -> BlockSounds {
+		self.block_properties.get(block).map(|props| props.sounds).unwrap_or(BlockSounds::silent())
+	}
+
+	fn play_at_block(&self, pos: [i64; 3], sound: Option<Sound>) {
+		if let Some(sound) = sound {
+			self.send(ServerMessage::Sound { sound: sound, position: [pos[0] as f32 + 0.5, pos[1] as f32 + 0.5, pos[2] as f32 + 0.5] });
+		}
+	}
+
+	// A step every stride walked along the ground, and one on landing, sounding like whatever's
+	// underfoot. `before` is where the player's feet were before this tick's move.
+	fn footsteps(&mut self, before: [f32; 3], was_on_ground: bool) {
+		let (position, on_ground) = match self.player.and_then(|id| self.entities.get(id)) {
+			Some(body) => (body.position, body.on_ground),
+			None => return,
+		};
+		if !on_ground {
+			return;
+		}
+
+		self.stride += ((position[0] - before[0]).powi(2) + (position[1] - before[1]).powi(2)).sqrt();
+		if was_on_ground && self.stride < STRIDE_LENGTH {
+			return;
+		}
+		self.stride = 0.0;
+
+		// Just under the feet, which rest on the top of the block
+		let below = [position[0].floor() as i64, position[1].floor() as i64, (position[2] - 0.1).floor() as i64];
+		if let Some(sound) = self.sounds(self.world.get_block(below)).step {
+			self.send(ServerMessage::Sound { sound: sound, position: position });
+		}
+	}
+
+	fn fill_region(&mut self, min: [i64; 3], max: [i64; 3], block: usize, from: Option<usize>) {
+		if block >= self.block_properties.len() || from.map(|from| from >= self.block_properties.len()).unwrap_or(false) {
+			return self.log("No such block");
+		}
+		let volume = volume(min, max);
+		if volume > MAX_EDIT_VOLUME {
+			return self.log(format!("The region is {} blocks, more than the {} that can be edited at once", volume, MAX_EDIT_VOLUME));
+		}
+		if !self.world.within_border(min[0], min[1]) || !self.world.within_border(max[0], max[1]) {
+			return self.log("The region runs past the border");
+		}
+		let changed = match from {
+			Some(from) => self.world.replace_region(min, max, from, block),
+			None => self.world.fill_region(min, max, block),
+		};
+		self.log(format!("Changed {} block(s)", changed));
+	}
+
+	fn copy(&mut self, min: [i64; 3], max: [i64; 3], origin: [i64; 3]) {
+		let volume = volume(min, max);
+		if volume > MAX_EDIT_VOLUME {
+			return self.log(format!("The region is {} blocks, more than the {} that can be copied at once", volume, MAX_EDIT_VOLUME));
+		}
+		let schematic = self.world.capture_structure(min, max);
+		self.clipboard = Some((schematic, [min[0] - origin[0], min[1] - origin[1], min[2] - origin[2]]));
+		self.log(format!("Copied {} block(s)", volume));
+	}
+
+	// Air in the copy replaces what's there. Blocks come out unturned, whichever way they faced.
+	fn paste(&mut self, origin: [i64; 3], turns: u32) {
+		let (mut schematic, mut offset) = match self.clipboard.clone() {
+			Some(clipboard) => clipboard,
+			None => return self.log("Nothing copied"),
+		};
+		// Each turn takes a block at (x, y) from the origin to (-y, x)
+		for _ in 0..turns % 4 {
+			offset = [-(offset[1] + schematic.size()[1] as i64 - 1), offset[0], offset[2]];
+			schematic = schematic.rotated(1);
+		}
+		let min = [origin[0] + offset[0], origin[1] + offset[1], origin[2] + offset[2]];
+		let size = schematic.size();
+		let max = [min[0] + size[0] as i64 - 1, min[1] + size[1] as i64 - 1, min[2] + size[2] as i64 - 1];
+		if !self.world.within_border(min[0], min[1]) || !self.world.within_border(max[0], max[1]) {
+			return self.log("The paste runs past the border");
+		}
+		self.world.place_structure(min, &schematic);
+		self.log(format!("Pasted at {:?}", min));
+	}
+
+	fn count_blocks(&mut self, min: [i64; 3], max: [i64; 3]) {
+		let volume = volume(min, max);
+		if volume > MAX_EDIT_VOLUME {
+			return self.log(format!("The region is {} blocks, more than the {} that can be measured at once", volume, MAX_EDIT_VOLUME));
+		}
+		let counts = self.world.count_blocks(min, max, self.block_properties.len());
+		self.log(format!("{} block(s) in all:", volume));
+		for (block, &count) in counts.iter().enumerate().filter(|&(_, &count)| count > 0) {
+			self.log(format!("  {}: {}", self.block_properties[block].name, count));
+		}
+	}
+
+	fn save_prefab(&mut self, name: &str, min: [i64; 3], max: [i64; 3], global: bool) {
+		let volume = volume(min, max);
+		if volume > MAX_EDIT_VOLUME {
+			return self.log(format!("The region is {} blocks, more than the {} that can be saved at once", volume, MAX_EDIT_VOLUME));
+		}
+		let schematic = self.world.capture_structure(min, max);
+		match self.prefabs.save(name, &schematic, global) {
+			Ok(()) => self.log(format!("Saved {}", name)),
+			Err(err) => self.log(format!("Failed to save {}: {:?}", name, err)),
+		}
+	}
+
+	// On the block the ray meets, or at its origin if it meets nothing
+	fn spawn_mobs(&mut self, origin: [f32; 3], direction: [f32; 3], count: usize) {
+		let render = &self.block_render_types;
+		let solid = |block: usize| render.get(block).map(|data| data.should_render && !data.liquid).unwrap_or(false);
+		let position = match self.world.raycast(origin, direction, SPAWN_DISTANCE, solid) {
+			Some((_, before)) => [before[0] as f32 + 0.5, before[1] as f32 + 0.5, before[2] as f32],
+			None => origin,
+		};
+
+		for _ in 0..count {
+			self.mobs.spawn(&mut self.entities, position);
+		}
+		self.log(format!("Spawned {} mob(s)", count));
+	}
+}
+
+// The world saves itself when it's dropped, but the stats are the server's
+impl Drop for Server {
+	fn drop(&mut self) {
+		if let Some(dir) = self.world.save_dir() {
+			if let Err(err) = self.stats.save(dir) {
+				println!("Failed to save stats: {:?}", err);
+			}
+		}
+	}
+}
+
+// Of the inclusive box from `min` to `max`
+fn volume(min: [i64; 3], max: [i64; 3]) -> i64 {
+	(0..3).map(|axis| (max[axis] - min[axis] + 1).max(0)).product()
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+	((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use save::{self, SaveError};
+
+pub const SETTINGS_FILE: &'static str = "settings.cfg";
+
+// Speeds are in blocks per second
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+	pub fly_speed: f32,
+	pub fly_fast_speed: f32,
+	pub walk_speed: f32,
+	pub sprint_speed: f32,
+	pub crouch_speed: f32,
+	// Shown to other players
+	pub name: String,
+	// In chunks
+	pub render_distance: i64,
+	// In blocks, from the eye to the furthest block that can be broken or placed against
+	pub reach: f32,
+	// Vertical, in degrees
+	pub fov: f32,
+	// In degrees the view turns for each pixel the mouse moves. Saved under a new name since it
+	// replaced a multiplier, which would make for a very fast mouse read as degrees.
+	pub look_sensitivity: f32,
+	// Bobs the view while walking and widens it at speed. Off for anyone that makes queasy.
+	pub camera_motion: bool,
+	// Only takes effect on the next start
+	pub vsync: bool,
+	// Lights the scene in linear color and lets an sRGB framebuffer encode the result, rather
+	// than doing the math on sRGB values directly. Only takes effect on the next start.
+	pub linear_lighting: bool,
+	// Samples per pixel, 0 for none. Only takes effect on the next start.
+	pub msaa: u16,
+	// Most samples taken along a texture seen at an angle, 1 for plain filtering. Applies
+	// immediately.
+	pub anisotropy: u16,
+	// From 0 to 1
+	pub master_volume: f32,
+	pub effects_volume: f32,
+	pub music_volume: f32,
+}
+
+impl Default for Settings {
+	fn default() -> Settings {
+		Settings {
+			fly_speed: 10.0,
+			fly_fast_speed: 1000.0,
+			walk_speed: 4.3,
+			sprint_speed: 5.6,
+			crouch_speed: 1.3,
+			name: "Player".to_string(),
+			render_distance: 5,
+			reach: 5.0,
+			fov: 60.0,
+			look_sensitivity: 0.15,
+			camera_motion: true,
+			vsync: false,
+			linear_lighting: true,
+			msaa: 4,
+			anisotropy: 8,
+			master_volume: 1.0,
+			effects_volume: 1.0,
+			music_volume: 0.5,
+		}
+	}
+}
+
+impl Settings {
+	// Settings missing from the file keep their defaults, so files from before a setting was
+	// added still load
+	pub fn load(path: &Path) -> Result<Settings, SaveError> {
+		let mut contents = String::new();
+		File::open(path)?.read_to_string(&mut contents)?;
+		let fields = save::parse_fields(&contents)?;
+
+		let defaults = Settings::default();
+		Ok(Settings {
+			fly_speed: field(&fields, "fly_speed", defaults.fly_speed)?,
+			fly_fast_speed: field(&fields, "fly_fast_speed", defaults.fly_fast_speed)?,
+			walk_speed: field(&fields, "walk_speed", defaults.walk_speed)?,
+			sprint_speed: field(&fields, "sprint_speed", defaults.sprint_speed)?,
+			crouch_speed: field(&fields, "crouch_speed", defaults.crouch_speed)?,
+			name: field(&fields, "name", defaults.name)?,
+			render_distance: field(&fields, "render_distance", defaults.render_distance)?,
+			reach: field(&fields, "reach", defaults.reach)?,
+			fov: field(&fields, "fov", defaults.fov)?,
+			look_sensitivity: field(&fields, "look_sensitivity", defaults.look_sensitivity)?,
+			camera_motion: field(&fields, "camera_motion", defaults.camera_motion)?,
+			vsync: field(&fields, "vsync", defaults.vsync)?,
+			linear_lighting: field(&fields, "linear_lighting", defaults.linear_lighting)?,
+			msaa: field(&fields, "msaa", defaults.msaa)?,
+			anisotropy: field(&fields, "anisotropy", defaults.anisotropy)?,
+			master_volume: field(&fields, "master_volume", defaults.master_volume)?,
+			effects_volume: field(&fields, "effects_volume", defaults.effects_volume)?,
+			music_volume: field(&fields, "music_volume", defaults.music_volume)?,
+		})
+	}
+
+	// Writes out the defaults when there is no settings file yet, so there's one to edit
+	pub fn load_or_create(path: &Path) -> Result<Settings, SaveError> {
+		if path.is_file() {
+			return Settings::load(path);
+		}
+		let settings = Settings::default();
+		settings.save(path)?;
+		Ok(settings)
+	}
+
+	// The settings that only take effect on the next start and differ from those the game started
+	// with, by the names they're saved under
+	pub fn restart_required(&self, started_with: &Settings) -> Vec<&'static str> {
+		let mut changed = Vec::new();
+		if self.vsync != started_with.vsync {
+			changed.push("vsync");
+		}
+		if self.linear_lighting != started_with.linear_lighting {
+			changed.push("linear_lighting");
+		}
+		if self.msaa != started_with.msaa {
+			changed.push("msaa");
+		}
+		changed
+	}
+
+	pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+		let tmp = path.with_extension("tmp");
+		{
+			let mut file = File::create(&tmp)?;
+			write!(file, "fly_speed={}\n", self.fly_speed)?;
+			write!(file, "fly_fast_speed={}\n", self.fly_fast_speed)?;
+			write!(file, "walk_speed={}\n", self.walk_speed)?;
+			write!(file, "sprint_speed={}\n", self.sprint_speed)?;
+			write!(file, "crouch_speed={}\n", self.crouch_speed)?;
+			write!(file, "name={}\n", self.name)?;
+			write!(file, "render_distance={}\n", self.render_distance)?;
+			write!(file, "reach={}\n", self.reach)?;
+			write!(file, "fov={}\n", self.fov)?;
+			write!(file, "look_sensitivity={}\n", self.look_sensitivity)?;
+			write!(file, "camera_motion={}\n", self.camera_motion)?;
+			write!(file, "vsync={}\n", self.vsync)?;
+			write!(file, "linear_lighting={}\n", self.linear_lighting)?;
+			write!(file, "msaa={}\n", self.msaa)?;
+			write!(file, "anisotropy={}\n", self.anisotropy)?;
+			write!(file, "master_volume={}\n", self.master_volume)?;
+			write!(file, "effects_volume={}\n", self.effects_volume)?;
+			write!(file, "music_volume={}\n", self.music_volume)?;
+		}
+		fs::rename(&tmp, path)?;
+		Ok(())
+	}
+}
+
+pub fn field<T: FromStr>(fields: &HashMap<String, String>, key: &str, default: T) -> Result<T, SaveError> {
+	match fields.get(key) {
+		Some(raw) => raw.parse().map_err(|_| SaveError::Malformed(format!("{}={}", key, raw))),
+		None => Ok(default),
+	}
+}
@@ -0,0 +1,166 @@
+use gui::GuiService;
+use settings::Settings;
+use actions::{Action, ActionMap, ACTIONS, Chord};
+use server::MAX_REACH;
+use stats::Stats;
+
+const MIN_RENDER_DISTANCE: i64 = 1;
+const MAX_RENDER_DISTANCE: i64 = 16;
+const MIN_REACH: f32 = 1.0;
+// Degrees
+const MIN_FOV: f32 = 40.0;
+const MAX_FOV: f32 = 110.0;
+// Degrees per pixel
+const MIN_SENSITIVITY: f32 = 0.01;
+const MAX_SENSITIVITY: f32 = 1.0;
+// Blocks per second
+const MIN_SPEED: f32 = 0.5;
+const MAX_WALK_SPEED: f32 = 15.0;
+const MAX_FLY_SPEED: f32 = 2000.0;
+// Rounded down to a power of two
+const MAX_MSAA: f32 = 16.0;
+const MAX_ANISOTROPY: f32 = 16.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MenuChoice {
+	None,
+	Resume,
+	Quit,
+}
+
+// Opened with Escape. Changes apply as they're made; settings are saved when the menu closes,
+// and keybindings as soon as a key is bound.
+pub struct SettingsMenu {
+	open: bool,
+	// Waiting for the key to bind this action to
+	rebinding: Option<Action>,
+	stats_shown: bool,
+}
+
+impl SettingsMenu {
+	pub fn new() -> SettingsMenu {
+		SettingsMenu { open: false, rebinding: None, stats_shown: false }
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.open
+	}
+
+	pub fn open(&mut self) {
+		self.open = true;
+	}
+
+	pub fn close(&mut self) {
+		self.open = false;
+		self.rebinding = None;
+		self.stats_shown = false;
+	}
+
+	pub fn rebinding(&self) -> Option<Action> {
+		self.rebinding
+	}
+
+	pub fn cancel_rebinding(&mut self) {
+		self.rebinding = None;
+	}
+
+	// Replaces all of the action's chords with the one pressed
+	pub fn bind(&mut self, actions: &mut ActionMap, chord: Chord) {
+		if let Some(action) = self.rebinding.take() {
+			actions.bind(action, &[chord]);
+		}
+	}
+
+	pub fn build(&mut self, gui: &GuiService, settings: &mut Settings, actions: &mut ActionMap, stats: &Stats) -> MenuChoice {
+		let mut choice = MenuChoice::None;
+		let mut toggle_stats = false;
+		gui.window("Settings", [40.0, 40.0], 420.0, |ui| {
+			let mut render_distance = settings.render_distance as f32;
+			if ui.slider("Render distance", &mut render_distance, MIN_RENDER_DISTANCE as f32, MAX_RENDER_DISTANCE as f32) {
+				settings.render_distance = render_distance.round() as i64;
+			}
+			ui.slider("Reach", &mut settings.reach, MIN_REACH, MAX_REACH);
+			ui.slider("Field of view", &mut settings.fov, MIN_FOV, MAX_FOV);
+			ui.slider("Look sensitivity (degrees per pixel)", &mut settings.look_sensitivity, MIN_SENSITIVITY, MAX_SENSITIVITY);
+			ui.slider("Walk speed (blocks per second)", &mut settings.walk_speed, MIN_SPEED, MAX_WALK_SPEED);
+			ui.slider("Sprint speed", &mut settings.sprint_speed, MIN_SPEED, MAX_WALK_SPEED);
+			ui.slider("Crouch speed", &mut settings.crouch_speed, MIN_SPEED, MAX_WALK_SPEED);
+			ui.slider("Fly speed", &mut settings.fly_speed, MIN_SPEED, MAX_FLY_SPEED);
+			ui.slider("Fast fly speed", &mut settings.fly_fast_speed, MIN_SPEED, MAX_FLY_SPEED);
+			ui.checkbox("View bobbing and speed zoom", &mut settings.camera_motion);
+			ui.checkbox("VSync (on restart)", &mut settings.vsync);
+			ui.checkbox("Linear lighting (on restart)", &mut settings.linear_lighting);
+			let mut msaa = settings.msaa as f32;
+			if ui.slider("Multisampling (on restart)", &mut msaa, 0.0, MAX_MSAA) {
+				settings.msaa = power_of_two_below(msaa);
+			}
+			let mut anisotropy = settings.anisotropy as f32;
+			if ui.slider("Anisotropic filtering", &mut anisotropy, 1.0, MAX_ANISOTROPY) {
+				settings.anisotropy = power_of_two_below(anisotropy).max(1);
+			}
+			ui.slider("Master volume", &mut settings.master_volume, 0.0, 1.0);
+			ui.slider("Effects volume", &mut settings.effects_volume, 0.0, 1.0);
+			ui.slider("Music volume", &mut settings.music_volume, 0.0, 1.0);
+			if ui.button("Statistics") {
+				toggle_stats = true;
+			}
+			if ui.button("Resume") {
+				choice = MenuChoice::Resume;
+			}
+			if ui.button("Quit") {
+				choice = MenuChoice::Quit;
+			}
+		});
+
+		let rebinding = self.rebinding;
+		let mut clicked = None;
+		gui.window("Controls", [480.0, 40.0], 360.0, |ui| {
+			for &action in ACTIONS.iter() {
+				let mut label = if rebinding == Some(action) {
+					format!("{}: press a key or button", action.name())
+				} else {
+					format!("{}: {}", action.name(), actions.chords(action).iter().map(|chord| chord.to_string()).collect::<Vec<_>>().join(", "))
+				};
+				let conflicts = actions.conflicts(action);
+				if !conflicts.is_empty() {
+					label.push_str(&format!(" (also {})", conflicts.iter().map(|other| other.name()).collect::<Vec<_>>().join(", ")));
+				}
+				if ui.button(&label) {
+					clicked = Some(action);
+				}
+			}
+			// Otherwise they're held only while their keys are
+			for &action in ACTIONS.iter() {
+				let mut toggles = actions.toggles(action);
+				if ui.checkbox(&format!("{} toggles", action.name()), &mut toggles) {
+					actions.set_toggles(action, toggles);
+				}
+			}
+		});
+		if clicked.is_some() {
+			self.rebinding = clicked;
+		}
+
+		if toggle_stats {
+			self.stats_shown = !self.stats_shown;
+		}
+		if self.stats_shown {
+			gui.window("Statistics", [880.0, 40.0], 300.0, |ui| {
+				for line in stats.lines() {
+					ui.label(&line);
+				}
+			});
+		}
+
+		choice
+	}
+}
+
+// 0 below 1
+fn power_of_two_below(value: f32) -> u16 {
+	if value < 1.0 {
+		0
+	} else {
+		1 << (value.log2().floor() as u16)
+	}
+}
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration as StdDuration, Instant};
+
+use time::Duration;
+
+use block::{World, block_properties, block_render_types};
+use server::{Server, ClientMessage, ServerMessage};
+use error::SpaceError;
+
+pub const TICKS_PER_SECOND: u64 = 60;
+// A server this many ticks behind skips ahead rather than trying to catch up, which would only
+// put it further behind if its ticks are what's slow
+const MAX_CATCH_UP_TICKS: u32 = 5;
+// Threads generating streamed chunks for the server's world, so generation doesn't hold up ticks
+const GENERATION_WORKERS: usize = 2;
+
+// The server, ticking at a fixed rate on a thread of its own so a slow tick can't hold up a
+// frame. The world can't be shared between threads, so the server opens it there and the client
+// keeps its own copy, which it asks the server to keep up to date.
+pub struct Simulation {
+	thread: Option<JoinHandle<()>>,
+}
+
+impl Simulation {
+	// Returns once the world is open, or failed to open
+	pub fn spawn(save_dir: PathBuf, seed: u32, inbox: Receiver<ClientMessage>, outbox: Sender<ServerMessage>) -> Result<Simulation, SpaceError> {
+		let block_render_types = block_render_types()?;
+		let (started, start) = channel();
+		let thread = thread::Builder::new().name("simulation".to_string()).spawn(move || {
+			let world = match World::open_or_create(&save_dir, seed) {
+				Ok(world) => world,
+				Err(err) => return started.send(Err(err)).unwrap_or(()),
+			};
+			world.start_generation_workers(GENERATION_WORKERS);
+			let mut server = Server::new(world, Rc::new(block_properties()), Rc::new(block_render_types), inbox, outbox);
+			let _ = started.send(Ok(()));
+			run(&mut server);
+		})?;
+
+		// Without a reply the thread died before it could give one
+		match start.recv() {
+			Ok(Ok(())) => Ok(Simulation { thread: Some(thread) }),
+			Ok(Err(err)) => Err(SpaceError::from(err)),
+			Err(_) => Err(SpaceError::Simulation),
+		}
+	}
+
+	// Waits for the server to finish, which it does once told to quit or once the client's gone
+	pub fn join(&mut self) {
+		if let Some(thread) = self.thread.take() {
+			if thread.join().is_err() {
+				println!("The simulation thread panicked");
+			}
+		}
+	}
+}
+
+fn run(server: &mut Server) {
+	let tick = StdDuration::from_micros(1_000_000 / TICKS_PER_SECOND);
+	let time_elapsed = Duration::microseconds(1_000_000 / TICKS_PER_SECOND as i64);
+	// When the next tick is due
+	let mut next = Instant::now();
+	while server.running() {
+		server.tick(&time_elapsed);
+		next += tick;
+		let now = Instant::now();
+		if next > now {
+			thread::sleep(next - now);
+		} else if now - next > tick * MAX_CATCH_UP_TICKS {
+			let behind = now - next;
+			let skipped = (behind.as_secs() * 1_000_000 + behind.subsec_micros() as u64) / (1_000_000 / TICKS_PER_SECOND);
+			println!("Simulation fell {} ticks behind, skipping them", skipped);
+			next = now;
+		}
+	}
+}
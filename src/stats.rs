@@ -0,0 +1,98 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use save::{self, SaveError};
+use settings::field;
+
+pub const STATS_FILE: &'static str = "stats.dat";
+
+// Something the player did, as reported by the interaction and movement code to whatever's
+// listening
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GameEvent {
+	BlockPlaced { pos: [i64; 3], block: usize },
+	BlockBroken { pos: [i64; 3], block: usize },
+	// In blocks, over one tick
+	Walked(f32),
+	Flew(f32),
+}
+
+pub trait EventHook {
+	fn on_event(&mut self, event: &GameEvent);
+}
+
+// Kept per world, next to its metadata
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Stats {
+	pub blocks_placed: u64,
+	pub blocks_broken: u64,
+	// In blocks
+	pub distance_walked: f64,
+	pub distance_flown: f64,
+	// In seconds
+	pub playtime: f64,
+}
+
+impl EventHook for Stats {
+	fn on_event(&mut self, event: &GameEvent) {
+		match *event {
+			GameEvent::BlockPlaced { .. } => self.blocks_placed += 1,
+			GameEvent::BlockBroken { .. } => self.blocks_broken += 1,
+			GameEvent::Walked(distance) => self.distance_walked += distance as f64,
+			GameEvent::Flew(distance) => self.distance_flown += distance as f64,
+		}
+	}
+}
+
+impl Stats {
+	pub fn path(dir: &Path) -> PathBuf {
+		dir.join(STATS_FILE)
+	}
+
+	// Worlds played before stats were kept start from nothing
+	pub fn load(dir: &Path) -> Result<Stats, SaveError> {
+		let path = Stats::path(dir);
+		if !path.is_file() {
+			return Ok(Stats::default());
+		}
+		let mut contents = String::new();
+		File::open(path)?.read_to_string(&mut contents)?;
+		let fields = save::parse_fields(&contents)?;
+
+		Ok(Stats {
+			blocks_placed: field(&fields, "blocks_placed", 0)?,
+			blocks_broken: field(&fields, "blocks_broken", 0)?,
+			distance_walked: field(&fields, "distance_walked", 0.0)?,
+			distance_flown: field(&fields, "distance_flown", 0.0)?,
+			playtime: field(&fields, "playtime", 0.0)?,
+		})
+	}
+
+	pub fn save(&self, dir: &Path) -> Result<(), SaveError> {
+		fs::create_dir_all(dir)?;
+		let tmp = dir.join(format!("{}.tmp", STATS_FILE));
+		{
+			let mut file = File::create(&tmp)?;
+			write!(file, "blocks_placed={}\n", self.blocks_placed)?;
+			write!(file, "blocks_broken={}\n", self.blocks_broken)?;
+			write!(file, "distance_walked={}\n", self.distance_walked)?;
+			write!(file, "distance_flown={}\n", self.distance_flown)?;
+			write!(file, "playtime={}\n", self.playtime)?;
+		}
+		fs::rename(&tmp, Stats::path(dir))?;
+		Ok(())
+	}
+
+	// For the stats page, one line each
+	pub fn lines(&self) -> Vec<String> {
+		let playtime = self.playtime as u64;
+		vec![
+			format!("Blocks placed: {}", self.blocks_placed),
+			format!("Blocks broken: {}", self.blocks_broken),
+			format!("Distance walked: {:.0} blocks", self.distance_walked),
+			format!("Distance flown: {:.0} blocks", self.distance_flown),
+			format!("Time played: {}:{:02}:{:02}", playtime / 3600, playtime / 60 % 60, playtime % 60),
+		]
+	}
+}
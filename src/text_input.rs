@@ -0,0 +1,79 @@
+// A change to a line being typed, made from the characters and editing keys the input service
+// takes while it's in text mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextEdit {
+	// Typed characters, or pasted text
+	Insert(String),
+	Backspace,
+	Delete,
+	Left,
+	Right,
+	Home,
+	End,
+	Submit,
+}
+
+// A line being typed. The cursor counts chars rather than bytes, so it can't land inside one.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextInput {
+	chars: Vec<char>,
+	cursor: usize,
+}
+
+impl TextInput {
+	pub fn new() -> TextInput {
+		TextInput::default()
+	}
+
+	pub fn text(&self) -> String {
+		self.chars.iter().collect()
+	}
+
+	pub fn cursor(&self) -> usize {
+		self.cursor
+	}
+
+	pub fn clear(&mut self) {
+		self.chars.clear();
+		self.cursor = 0;
+	}
+
+	// The line once it's submitted, which leaves this empty for the next
+	pub fn apply(&mut self, edit: &TextEdit) -> Option<String> {
+		match *edit {
+			TextEdit::Insert(ref text) => {
+				// Pasted line breaks and tabs become spaces, since there's only the one line
+				for c in text.chars().filter(|&c| c != '\r').map(|c| if c.is_whitespace() { ' ' } else { c }) {
+					if !c.is_control() {
+						self.chars.insert(self.cursor, c);
+						self.cursor += 1;
+					}
+				}
+			},
+			TextEdit::Backspace => if self.cursor > 0 {
+				self.cursor -= 1;
+				self.chars.remove(self.cursor);
+			},
+			TextEdit::Delete => if self.cursor < self.chars.len() {
+				self.chars.remove(self.cursor);
+			},
+			TextEdit::Left => self.cursor = self.cursor.saturating_sub(1),
+			TextEdit::Right => self.cursor = (self.cursor + 1).min(self.chars.len()),
+			TextEdit::Home => self.cursor = 0,
+			TextEdit::End => self.cursor = self.chars.len(),
+			TextEdit::Submit => {
+				let line = self.text();
+				self.clear();
+				return Some(line);
+			},
+		}
+		None
+	}
+
+	// The line with `marker` where the cursor is, for drawing
+	pub fn with_cursor(&self, marker: char) -> String {
+		let mut chars = self.chars.clone();
+		chars.insert(self.cursor, marker);
+		chars.into_iter().collect()
+	}
+}
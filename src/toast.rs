@@ -0,0 +1,77 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use time::{Duration, PreciseTime};
+
+use overlay::{Overlay, GLYPH_ADVANCE, GLYPH_HEIGHT};
+
+// Older toasts give way once there are more than this many
+const MAX_TOASTS: usize = 4;
+// Font pixels are drawn this many screen pixels across
+const SCALE: f32 = 2.0;
+const LINE_HEIGHT: f32 = (GLYPH_HEIGHT + 4) as f32 * SCALE;
+const PADDING: f32 = 6.0;
+const MARGIN: f32 = 8.0;
+const SPACING: f32 = 4.0;
+const BACKGROUND: [f32; 4] = [0.1, 0.1, 0.15, 0.8];
+const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// How long a toast stays up, the last part of which it spends fading out
+fn visible_time() -> Duration { Duration::seconds(4) }
+fn fade_time() -> Duration { Duration::seconds(1) }
+
+struct Toast {
+	text: String,
+	shown: PreciseTime,
+}
+
+// Short notices stacked in the top right, newest at the top, each fading out after a few seconds.
+// Anything with the services to hand can push one.
+pub struct ToastService {
+	toasts: RefCell<VecDeque<Toast>>,
+}
+
+impl ToastService {
+	pub fn new() -> ToastService {
+		ToastService { toasts: RefCell::new(VecDeque::new()) }
+	}
+
+	// The same text pushed again while it's still up is brought back to the top rather than shown twice
+	pub fn push<S: Into<String>>(&self, text: S) {
+		let text = text.into();
+		let mut toasts = self.toasts.borrow_mut();
+		toasts.retain(|toast| toast.text != text);
+		if toasts.len() == MAX_TOASTS {
+			toasts.pop_front();
+		}
+		toasts.push_back(Toast { text: text, shown: PreciseTime::now() });
+	}
+
+	pub fn draw(&self, overlay: &mut Overlay, screen_width: f32) {
+		let now = PreciseTime::now();
+		let mut toasts = self.toasts.borrow_mut();
+		toasts.retain(|toast| toast.shown.to(now) < visible_time());
+
+		let mut y = MARGIN;
+		for toast in toasts.iter().rev() {
+			let alpha = fade(toast.shown.to(now));
+			let width = (toast.text.chars().count() * GLYPH_ADVANCE) as f32 * SCALE + PADDING * 2.0;
+			let x = screen_width - MARGIN - width;
+			overlay.rect(x, y, width, LINE_HEIGHT, [BACKGROUND[0], BACKGROUND[1], BACKGROUND[2], BACKGROUND[3] * alpha]);
+			overlay.text(x + PADDING, y + SCALE * 2.0, SCALE, &toast.text, [TEXT[0], TEXT[1], TEXT[2], TEXT[3] * alpha]);
+			y += LINE_HEIGHT + SPACING;
+		}
+	}
+}
+
+// 1 while fresh, falling to 0 over the fade time at the end of the visible time
+fn fade(age: Duration) -> f32 {
+	let remaining = visible_time() - age;
+	if remaining <= Duration::zero() {
+		0.0
+	} else if remaining >= fade_time() {
+		1.0
+	} else {
+		remaining.num_milliseconds() as f32 / fade_time().num_milliseconds() as f32
+	}
+}
@@ -0,0 +1,15 @@
+#version 140
+in vec2 v_tex_coords;
+
+out vec4 color;
+
+uniform sampler2D source;
+uniform float exposure;
+
+// Reinhard, on luminance so hues don't shift as they brighten
+void main() {
+	vec3 hdr = texture(source, v_tex_coords).rgb * exposure;
+	float luminance = dot(hdr, vec3(0.2126, 0.7152, 0.0722));
+	float mapped = luminance / (1.0 + luminance);
+	color = vec4(luminance > 0.0 ? hdr * (mapped / luminance) : hdr, 1.0);
+}
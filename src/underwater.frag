@@ -0,0 +1,21 @@
+#version 140
+in vec2 v_tex_coords;
+
+out vec4 color;
+
+uniform sampler2D source;
+uniform float time;
+// The liquid's own color, in whatever space the scene was drawn in
+uniform vec3 tint;
+
+const float distortion = 0.004;
+const float tint_strength = 0.45;
+
+// Sways the image a little, as if seen through moving water, and washes it towards the tint
+void main() {
+	vec2 offset = vec2(
+		sin(v_tex_coords.y * 40.0 + time * 2.0),
+		cos(v_tex_coords.x * 32.0 + time * 1.7)) * distortion;
+	vec3 scene = texture(source, clamp(v_tex_coords + offset, 0.0, 1.0)).rgb;
+	color = vec4(mix(scene, scene * tint, tint_strength), 1.0);
+}
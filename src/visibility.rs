@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+
+use block::{BlockRenderData, Chunk, ObscuresFaces, CHUNK_SIZE, NEIGHBOUR_OFFSETS};
+
+// Which pairs of a chunk's faces can see each other through it, faces being numbered as their
+// directions in NEIGHBOUR_OFFSETS. Two faces are connected when some run of cells that don't
+// hide what's behind them touches both.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FaceConnectivity(u64);
+
+impl FaceConnectivity {
+	pub const NONE: FaceConnectivity = FaceConnectivity(0);
+	pub const ALL: FaceConnectivity = FaceConnectivity((1 << 36) - 1);
+
+	pub fn connected(&self, a: usize, b: usize) -> bool {
+		self.0 & (1 << (a * 6 + b)) != 0
+	}
+
+	fn connect(&mut self, a: usize, b: usize) {
+		self.0 |= 1 << (a * 6 + b) | 1 << (b * 6 + a);
+	}
+
+	// Flood fills each region of see-through cells, connecting every face it reaches
+	pub fn of(chunk: &Chunk, block_render_data: &[BlockRenderData]) -> FaceConnectivity {
+		let see_through = |x: usize, y: usize, z: usize| {
+			let data = &block_render_data[chunk.blocks[x][y][z]];
+			!(data.should_render && data.obscures == ObscuresFaces::ALL)
+		};
+
+		let mut connectivity = FaceConnectivity::NONE;
+		let mut visited = vec![false; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
+		let index = |x: usize, y: usize, z: usize| (x * CHUNK_SIZE + y) * CHUNK_SIZE + z;
+		let mut queue = VecDeque::new();
+
+		for x in 0..CHUNK_SIZE {
+			for y in 0..CHUNK_SIZE {
+				for z in 0..CHUNK_SIZE {
+					if visited[index(x, y, z)] || !see_through(x, y, z) {
+						continue;
+					}
+
+					let mut faces = 0u8;
+					visited[index(x, y, z)] = true;
+					queue.push_back([x, y, z]);
+					while let Some(cell) = queue.pop_front() {
+						for (face, offset) in NEIGHBOUR_OFFSETS.iter().enumerate() {
+							let next = [cell[0] as i64 + offset[0], cell[1] as i64 + offset[1], cell[2] as i64 + offset[2]];
+							if next.iter().any(|&c| c < 0 || c >= CHUNK_SIZE as i64) {
+								faces |= 1 << face;
+								continue;
+							}
+							let (nx, ny, nz) = (next[0] as usize, next[1] as usize, next[2] as usize);
+							if !visited[index(nx, ny, nz)] && see_through(nx, ny, nz) {
+								visited[index(nx, ny, nz)] = true;
+								queue.push_back([nx, ny, nz]);
+							}
+						}
+					}
+
+					for a in 0..6 {
+						for b in 0..6 {
+							if faces & (1 << a) != 0 && faces & (1 << b) != 0 {
+								connectivity.connect(a, b);
+							}
+						}
+					}
+					if connectivity == FaceConnectivity::ALL {
+						return connectivity;
+					}
+				}
+			}
+		}
+		connectivity
+	}
+}
+
+// The face on the far side of a neighbour, as numbered in NEIGHBOUR_OFFSETS
+fn opposite(face: usize) -> usize {
+	face ^ 1
+}
+
+// Walks outwards from the camera's chunk, only ever moving away from the camera, and only on
+// through a chunk's far face if the face it was entered by can see it. Whatever the walk never
+// reaches is hidden behind something solid. It's conservative per chunk, so some hidden chunks
+// are still drawn, but caves under the player and valleys behind mountains mostly aren't.
+//
+// `size` is the region's size in chunks, `start` the camera's chunk within it and `reachable`
+// whether a chunk may be walked into at all. Returns which chunks were reached, indexed as
+// (x * size[1] + y) * size[2] + z.
+pub fn visible_chunks<C, R>(size: [usize; 3], start: [usize; 3], connectivity: C, reachable: R) -> Vec<bool>
+		where C: Fn([usize; 3]) -> FaceConnectivity, R: Fn([usize; 3]) -> bool {
+	let index = |pos: [usize; 3]| (pos[0] * size[1] + pos[1]) * size[2] + pos[2];
+	let mut visible = vec![false; size[0] * size[1] * size[2]];
+	// Each with the face it was entered by and the directions taken to reach it
+	let mut queue: VecDeque<([usize; 3], Option<usize>, u8)> = VecDeque::new();
+	visible[index(start)] = true;
+	queue.push_back((start, None, 0));
+
+	while let Some((pos, entered_by, directions)) = queue.pop_front() {
+		let connectivity = connectivity(pos);
+		for (face, offset) in NEIGHBOUR_OFFSETS.iter().enumerate() {
+			if directions & (1 << opposite(face)) != 0 {
+				continue;
+			}
+			if let Some(entered_by) = entered_by {
+				if !connectivity.connected(entered_by, face) {
+					continue;
+				}
+			}
+
+			let next = [pos[0] as i64 + offset[0], pos[1] as i64 + offset[1], pos[2] as i64 + offset[2]];
+			if (0..3).any(|axis| next[axis] < 0 || next[axis] >= size[axis] as i64) {
+				continue;
+			}
+			let next = [next[0] as usize, next[1] as usize, next[2] as usize];
+			if visible[index(next)] || !reachable(next) {
+				continue;
+			}
+			visible[index(next)] = true;
+			queue.push_back((next, Some(opposite(face)), directions | 1 << face));
+		}
+	}
+	visible
+}
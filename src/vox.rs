@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use block::BlockRenderData;
+use save::SaveError;
+use schematic::Schematic;
+
+// Loads the first model of a MagicaVoxel .vox file as a schematic. Each palette color is mapped
+// to the renderable block with the closest color; empty voxels are left empty.
+pub fn load(path: &Path, block_render_data: &[BlockRenderData]) -> Result<Schematic, SaveError> {
+	let mut data = Vec::new();
+	File::open(path)?.read_to_end(&mut data)?;
+	parse(&data, block_render_data).map_err(|reason| SaveError::Malformed(format!("{}: {}", path.display(), reason)))
+}
+
+pub fn parse(data: &[u8], block_render_data: &[BlockRenderData]) -> Result<Schematic, String> {
+	if data.len() < 8 || &data[0..4] != b"VOX " {
+		return Err("not a .vox file".to_string());
+	}
+
+	let mut main = Reader { data: data, pos: 8 };
+	let (id, _, children) = main.chunk()?;
+	if id != b"MAIN" {
+		return Err("missing MAIN chunk".to_string());
+	}
+
+	let mut size = None;
+	let mut voxels: Option<&[u8]> = None;
+	let mut palette = default_palette();
+
+	let mut reader = Reader { data: children, pos: 0 };
+	while !reader.done() {
+		let (id, content, _) = reader.chunk()?;
+		match id {
+			// Only the first model is imported
+			b"SIZE" if size.is_none() => {
+				let mut content = Reader { data: content, pos: 0 };
+				size = Some([content.u32()? as usize, content.u32()? as usize, content.u32()? as usize]);
+			},
+			b"XYZI" if voxels.is_none() => voxels = Some(content),
+			b"RGBA" => {
+				if content.len() < 256 * 4 {
+					return Err("short RGBA chunk".to_string());
+				}
+				// Palette entry i holds color index i + 1
+				for i in 0..255 {
+					palette[i + 1] = [content[i * 4], content[i * 4 + 1], content[i * 4 + 2]];
+				}
+			},
+			_ => (),
+		}
+	}
+
+	let size = size.ok_or("missing SIZE chunk")?;
+	if Schematic::volume(size).is_none() {
+		return Err("model too large".to_string());
+	}
+	let voxels = voxels.ok_or("missing XYZI chunk")?;
+
+	let blocks = map_palette(&palette, block_render_data)?;
+	let mut schematic = Schematic::new(size);
+	let mut content = Reader { data: voxels, pos: 0 };
+	let count = content.u32()? as usize;
+	for _ in 0..count {
+		let voxel = content.bytes(4)?;
+		let (x, y, z) = (voxel[0] as usize, voxel[1] as usize, voxel[2] as usize);
+		if x >= size[0] || y >= size[1] || z >= size[2] {
+			return Err("voxel outside model bounds".to_string());
+		}
+		schematic.set(x, y, z, Some(blocks[voxel[3] as usize]));
+	}
+
+	Ok(schematic)
+}
+
+struct Reader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn done(&self) -> bool {
+		self.pos >= self.data.len()
+	}
+
+	fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+		if self.pos + len > self.data.len() {
+			return Err("unexpected end of file".to_string());
+		}
+		let bytes = &self.data[self.pos..self.pos + len];
+		self.pos += len;
+		Ok(bytes)
+	}
+
+	fn u32(&mut self) -> Result<u32, String> {
+		let b = self.bytes(4)?;
+		Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+	}
+
+	// A chunk is its id, content size and children size, followed by the content and children
+	fn chunk(&mut self) -> Result<(&'a [u8], &'a [u8], &'a [u8]), String> {
+		let id = self.bytes(4)?;
+		let content_len = self.u32()? as usize;
+		let children_len = self.u32()? as usize;
+		Ok((id, self.bytes(content_len)?, self.bytes(children_len)?))
+	}
+}
+
+// Maps every color index to the nearest renderable block
+fn map_palette(palette: &[[u8; 3]; 256], block_render_data: &[BlockRenderData]) -> Result<Vec<usize>, String> {
+	let candidates: Vec<(usize, &BlockRenderData)> = block_render_data.iter().enumerate()
+		.filter(|&(_, data)| data.should_render)
+		.collect();
+	if candidates.is_empty() {
+		return Err("no renderable blocks to map colors to".to_string());
+	}
+
+	Ok(palette.iter().map(|color| {
+		let distance = |data: &BlockRenderData| -> f32 {
+			(0..3).map(|i| {
+				let d = color[i] as f32 / 255.0 - data.color[i];
+				d * d
+			}).sum()
+		};
+		candidates.iter()
+			.min_by(|a, b| distance(a.1).partial_cmp(&distance(b.1)).unwrap())
+			.map(|&(id, _)| id)
+			.unwrap()
+	}).collect())
+}
+
+// MagicaVoxel's built-in palette, used when a file has no RGBA chunk: a 6x6x6 color cube without
+// black, then red, green, blue and gray ramps. Index 0 is unused.
+fn default_palette() -> [[u8; 3]; 256] {
+	let mut palette = [[0; 3]; 256];
+	let cube = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+	let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+	let mut i = 1;
+	for &r in &cube {
+		for &g in &cube {
+			for &b in &cube {
+				if i < 216 {
+					palette[i] = [r, g, b];
+					i += 1;
+				}
+			}
+		}
+	}
+	for channel in 0..3 {
+		for &v in &ramp {
+			palette[i][channel] = v;
+			i += 1;
+		}
+	}
+	for &v in &ramp {
+		palette[i] = [v, v, v];
+		i += 1;
+	}
+
+	palette
+}
@@ -0,0 +1,144 @@
+use std::cell::RefCell;
+use std::mem::replace;
+use std::rc::Rc;
+
+use block::{World, BlockProperties, AIR, WATER};
+use block_update::UpdateKind;
+use scheduler::{TickScheduler, EventKind};
+
+// Water blocks without a level are sources. Flowing water loses a level for every block it
+// spreads sideways and dries up once nothing feeds it. Levels are kept as the water's block
+// state, so they're saved with its chunk and go when the water does; a state of 0 is a source.
+pub const SOURCE_LEVEL: u8 = 8;
+const FALLING_LEVEL: u8 = 7;
+
+// Ticks between water being disturbed and flowing on. Spreading a block at a time a few times
+// a second keeps the meshes around it from being rebuilt every frame.
+const FLOW_DELAY: u64 = 4;
+
+const HORIZONTAL_OFFSETS: [[i64; 2]; 4] = [[1, 0], [-1, 0], [0, 1], [0, -1]];
+
+pub struct WaterSimulation {
+	properties: Rc<Vec<BlockProperties>>,
+	candidates: Rc<RefCell<Vec<[i64; 3]>>>,
+}
+
+impl WaterSimulation {
+	pub fn new(world: &World, properties: Rc<Vec<BlockProperties>>) -> WaterSimulation {
+		let candidates = Rc::new(RefCell::new(Vec::new()));
+
+		{
+			let candidates = candidates.clone();
+			world.subscribe(move |world, update| {
+				// Water that was just replaced may have fed the water around it
+				let replaced = match update.kind {
+					UpdateKind::Changed { old, .. } => old == WATER,
+					_ => false,
+				};
+				if replaced || world.get_block(update.pos) == WATER {
+					candidates.borrow_mut().push(update.pos);
+				}
+			});
+		}
+
+		WaterSimulation {
+			properties: properties,
+			candidates: candidates,
+		}
+	}
+
+	pub fn level(&self, world: &World, pos: [i64; 3]) -> u8 {
+		match world.get_state(pos) {
+			0 => SOURCE_LEVEL,
+			level => level.min(SOURCE_LEVEL),
+		}
+	}
+
+	// Set after the water itself, as replacing a block clears its state
+	fn set_level(&self, world: &World, pos: [i64; 3], level: u8) {
+		world.set_state(pos, if level >= SOURCE_LEVEL { 0 } else { level });
+	}
+
+	// Schedules flow for the water disturbed since the last call
+	pub fn tick(&mut self, scheduler: &mut TickScheduler) {
+		let candidates = replace(&mut *self.candidates.borrow_mut(), Vec::new());
+		for pos in candidates {
+			scheduler.schedule(pos, EventKind::LiquidFlow, FLOW_DELAY);
+		}
+	}
+
+	// Handles a scheduled LiquidFlow event
+	pub fn flow(&mut self, world: &World, pos: [i64; 3]) {
+		if world.get_block(pos) != WATER {
+			return;
+		}
+
+		let mut level = self.level(world, pos);
+		if level < SOURCE_LEVEL {
+			let expected = self.expected_level(world, pos);
+			if expected == 0 {
+				world.set_block_unjournaled(pos, AIR);
+				return;
+			}
+			if expected != level {
+				// Levels aren't blocks, so the neighbours have to be told directly
+				self.set_level(world, pos, expected);
+				self.wake_neighbours(pos);
+				level = expected;
+			}
+		}
+
+		let below = [pos[0], pos[1], pos[2] - 1];
+		let below_block = world.get_block(below);
+		if below_block == AIR {
+			world.set_block_unjournaled(below, WATER);
+			self.set_level(world, below, FALLING_LEVEL);
+			return;
+		}
+		// Water only spreads sideways once it rests on something solid
+		if !self.solid(below_block) || level <= 1 {
+			return;
+		}
+
+		for offset in HORIZONTAL_OFFSETS.iter() {
+			let side = [pos[0] + offset[0], pos[1] + offset[1], pos[2]];
+			match world.get_block(side) {
+				AIR => {
+					world.set_block_unjournaled(side, WATER);
+					self.set_level(world, side, level - 1);
+				},
+				WATER if self.level(world, side) < level - 1 => {
+					self.set_level(world, side, level - 1);
+					self.candidates.borrow_mut().push(side);
+				},
+				_ => (),
+			}
+		}
+	}
+
+	// The level flowing water at `pos` is fed to by the water around it
+	fn expected_level(&self, world: &World, pos: [i64; 3]) -> u8 {
+		if world.get_block([pos[0], pos[1], pos[2] + 1]) == WATER {
+			return FALLING_LEVEL;
+		}
+
+		HORIZONTAL_OFFSETS.iter()
+			.map(|offset| [pos[0] + offset[0], pos[1] + offset[1], pos[2]])
+			.filter(|&side| world.get_block(side) == WATER)
+			.map(|side| self.level(world, side).saturating_sub(1))
+			.max()
+			.unwrap_or(0)
+	}
+
+	fn wake_neighbours(&self, pos: [i64; 3]) {
+		let mut candidates = self.candidates.borrow_mut();
+		for offset in HORIZONTAL_OFFSETS.iter() {
+			candidates.push([pos[0] + offset[0], pos[1] + offset[1], pos[2]]);
+		}
+		candidates.push([pos[0], pos[1], pos[2] - 1]);
+	}
+
+	fn solid(&self, block: usize) -> bool {
+		self.properties.get(block).map(|p| p.solid).unwrap_or(true)
+	}
+}
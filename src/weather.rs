@@ -0,0 +1,185 @@
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use rand::{self, Rng};
+use time::Duration;
+
+use block::{World, BlockProperties, AIR, SNOW};
+use debug_draw::DebugLines;
+use random::StreamRng;
+
+// Precipitation fills a box this many blocks either side of the camera, following it around
+const PARTICLE_RANGE: f32 = 16.0;
+const PARTICLE_COUNT: usize = 2000;
+// In blocks per second
+const RAIN_SPEED: f32 = 14.0;
+const SNOW_SPEED: f32 = 1.5;
+// Drops are drawn as streaks this long, flakes as crosses this wide
+const RAIN_LENGTH: f32 = 0.5;
+const FLAKE_SIZE: f32 = 0.08;
+const RAIN_COLOR: [f32; 3] = [0.55, 0.6, 0.7];
+const SNOW_COLOR: [f32; 3] = [0.95, 0.95, 1.0];
+
+// Snow settles on one column at a time, somewhere this near the player
+const SNOWFALL_RANGE: i64 = 24;
+fn snowfall_interval() -> Duration { Duration::milliseconds(200) }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Weather {
+	Clear,
+	Rain,
+	Snow,
+}
+
+impl Weather {
+	pub fn sky_color(&self) -> [f32; 3] {
+		match *self {
+			Weather::Clear => [0.5, 0.7, 0.9],
+			Weather::Rain => [0.3, 0.33, 0.38],
+			Weather::Snow => [0.55, 0.58, 0.62],
+		}
+	}
+
+	// How quickly things fade into the sky with distance; 0 for not at all
+	pub fn fog_density(&self) -> f32 {
+		match *self {
+			Weather::Clear => 0.0,
+			Weather::Rain => 0.015,
+			Weather::Snow => 0.025,
+		}
+	}
+}
+
+impl fmt::Display for Weather {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			Weather::Clear => "clear",
+			Weather::Rain => "rain",
+			Weather::Snow => "snow",
+		})
+	}
+}
+
+impl FromStr for Weather {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Weather, String> {
+		match s {
+			"clear" => Ok(Weather::Clear),
+			"rain" => Ok(Weather::Rain),
+			"snow" => Ok(Weather::Snow),
+			_ => Err(format!("Unknown weather: {}", s)),
+		}
+	}
+}
+
+// Rain or snow falling around the camera. Particles that leave the box around it come back in on
+// the opposite side, so the same ones are reused however far the camera goes.
+pub struct Precipitation {
+	particles: Vec<[f32; 3]>,
+	seconds: f32,
+}
+
+impl Precipitation {
+	pub fn new() -> Precipitation {
+		Precipitation {
+			particles: (0..PARTICLE_COUNT)
+				.map(|_| [random_offset(), random_offset(), random_offset()])
+				.collect(),
+			seconds: 0.0,
+		}
+	}
+
+	pub fn update(&mut self, weather: Weather, camera: [f32; 3], seconds: f32) {
+		self.seconds += seconds;
+		let min = [camera[0] - PARTICLE_RANGE, camera[1] - PARTICLE_RANGE, camera[2] - PARTICLE_RANGE];
+		for (i, particle) in self.particles.iter_mut().enumerate() {
+			match weather {
+				Weather::Clear => (),
+				Weather::Rain => particle[2] -= RAIN_SPEED * seconds,
+				Weather::Snow => {
+					// Each flake drifts from side to side on its own schedule
+					let phase = self.seconds + i as f32;
+					particle[0] += phase.sin() * 0.5 * seconds;
+					particle[1] += (phase * 0.7).cos() * 0.5 * seconds;
+					particle[2] -= SNOW_SPEED * seconds;
+				},
+			}
+			for axis in 0..3 {
+				particle[axis] = wrap(particle[axis], min[axis], PARTICLE_RANGE * 2.0);
+			}
+		}
+	}
+
+	pub fn draw(&self, weather: Weather, lines: &mut DebugLines) {
+		for p in &self.particles {
+			match weather {
+				Weather::Clear => return,
+				Weather::Rain => lines.line(*p, [p[0], p[1], p[2] + RAIN_LENGTH], RAIN_COLOR),
+				Weather::Snow => {
+					lines.line([p[0] - FLAKE_SIZE, p[1], p[2]], [p[0] + FLAKE_SIZE, p[1], p[2]], SNOW_COLOR);
+					lines.line([p[0], p[1] - FLAKE_SIZE, p[2]], [p[0], p[1] + FLAKE_SIZE, p[2]], SNOW_COLOR);
+					lines.line([p[0], p[1], p[2] - FLAKE_SIZE], [p[0], p[1], p[2] + FLAKE_SIZE], SNOW_COLOR);
+				},
+			}
+		}
+	}
+}
+
+fn random_offset() -> f32 {
+	(rand::random::<f32>() * 2.0 - 1.0) * PARTICLE_RANGE
+}
+
+// Into [min, min + size)
+fn wrap(value: f32, min: f32, size: f32) -> f32 {
+	((value - min) % size + size) % size + min
+}
+
+// While it snows, covers the ground near the player with snow a column at a time
+pub struct Snowfall {
+	properties: Rc<Vec<BlockProperties>>,
+	since: Duration,
+	rng: StreamRng,
+}
+
+impl Snowfall {
+	pub fn new(properties: Rc<Vec<BlockProperties>>, rng: StreamRng) -> Snowfall {
+		Snowfall { properties: properties, since: Duration::zero(), rng: rng }
+	}
+
+	pub fn tick(&mut self, world: &World, around: [f32; 3], time_elapsed: &Duration) {
+		if world.weather() != Weather::Snow {
+			self.since = Duration::zero();
+			return;
+		}
+		self.since = self.since + *time_elapsed;
+		while self.since >= snowfall_interval() {
+			self.since = self.since - snowfall_interval();
+
+			let x = around[0].floor() as i64 + self.rng.gen_range(-SNOWFALL_RANGE, SNOWFALL_RANGE + 1);
+			let y = around[1].floor() as i64 + self.rng.gen_range(-SNOWFALL_RANGE, SNOWFALL_RANGE + 1);
+			let top = around[2].floor() as i64 + SNOWFALL_RANGE;
+			self.settle(world, x, y, top);
+		}
+	}
+
+	// Onto the highest thing in the column below `top`, if that's solid and not snow already.
+	// Columns that aren't open to the sky at `top` are left alone.
+	fn settle(&self, world: &World, x: i64, y: i64, top: i64) {
+		if world.get_block([x, y, top]) != AIR {
+			return;
+		}
+		for z in (top - SNOWFALL_RANGE * 2..top).rev() {
+			let block = world.get_block([x, y, z]);
+			if block == AIR {
+				continue;
+			}
+			let solid = self.properties.get(block).map(|props| props.solid).unwrap_or(false);
+			if solid && block != SNOW {
+				world.set_block_unjournaled([x, y, z + 1], SNOW);
+			}
+			return;
+		}
+	}
+}
@@ -0,0 +1,649 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use noise::{Fbm, MultiFractal, NoiseFn, OpenSimplex, Perlin, RidgedMulti};
+
+use block::{CHUNK_SIZE, AIR, GROUND, WATER};
+use biome::Biome;
+use random::WorldRandom;
+use save::{SaveError, WorldMetadata};
+
+// Shared with the threads generating chunks in the background
+pub trait WorldGenerator: Send + Sync {
+	// Stored in the world metadata so a save reopens with the generator that created it
+	fn name(&self) -> &'static str;
+	fn options(&self) -> BTreeMap<String, String>;
+	fn generate(&self, x: i64, y: i64, z: i64, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+	// Of the column at block x, y
+	fn biome(&self, _x: i64, _y: i64) -> Biome {
+		Biome::Plains
+	}
+}
+
+pub fn from_metadata(metadata: &WorldMetadata) -> Result<Box<WorldGenerator>, SaveError> {
+	match metadata.generator.as_str() {
+		TerrainGenerator::NAME => {
+			let settings = TerrainSettings::from_options(&metadata.generator_options)?;
+			Ok(Box::new(TerrainGenerator::new(metadata.seed, settings)))
+		},
+		FlatGenerator::NAME => {
+			let layers = match metadata.generator_options.get("layers") {
+				Some(raw) => FlatGenerator::parse_layers(raw).ok_or_else(|| SaveError::Malformed(format!("layers={}", raw)))?,
+				None => FlatGenerator::default_layers(),
+			};
+			Ok(Box::new(FlatGenerator::new(layers)))
+		},
+		VoidGenerator::NAME => Ok(Box::new(VoidGenerator)),
+		other => Err(SaveError::UnknownGenerator(other.to_string())),
+	}
+}
+
+// The choices offered when creating a world
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorldPreset {
+	Normal(TerrainSettings),
+	Superflat(Vec<FlatLayer>),
+	Void,
+}
+
+impl WorldPreset {
+	pub fn all() -> Vec<WorldPreset> {
+		vec![
+			WorldPreset::Normal(TerrainSettings::default()),
+			WorldPreset::Superflat(FlatGenerator::default_layers()),
+			WorldPreset::Void,
+		]
+	}
+
+	pub fn label(&self) -> &'static str {
+		match *self {
+			WorldPreset::Normal(_) => "Normal",
+			WorldPreset::Superflat(_) => "Superflat",
+			WorldPreset::Void => "Void",
+		}
+	}
+
+	pub fn generator(&self, seed: u32) -> Box<WorldGenerator> {
+		match *self {
+			WorldPreset::Normal(ref settings) => Box::new(TerrainGenerator::new(seed, settings.clone())),
+			WorldPreset::Superflat(ref layers) => Box::new(FlatGenerator::new(layers.clone())),
+			WorldPreset::Void => Box::new(VoidGenerator),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseAlgorithm {
+	Perlin,
+	OpenSimplex,
+	Ridged,
+}
+
+impl NoiseAlgorithm {
+	pub fn name(&self) -> &'static str {
+		match *self {
+			NoiseAlgorithm::Perlin => "perlin",
+			NoiseAlgorithm::OpenSimplex => "open_simplex",
+			NoiseAlgorithm::Ridged => "ridged",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<NoiseAlgorithm> {
+		match name {
+			"perlin" => Some(NoiseAlgorithm::Perlin),
+			"open_simplex" => Some(NoiseAlgorithm::OpenSimplex),
+			"ridged" => Some(NoiseAlgorithm::Ridged),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseSettings {
+	pub algorithm: NoiseAlgorithm,
+	pub octaves: usize,
+	pub frequency: f64,
+	pub lacunarity: f64,
+	pub persistence: f64,
+}
+
+impl Default for NoiseSettings {
+	fn default() -> NoiseSettings {
+		NoiseSettings {
+			algorithm: NoiseAlgorithm::Perlin,
+			octaves: 6,
+			frequency: 1.0 / 128.0,
+			lacunarity: 2.0,
+			persistence: 0.5,
+		}
+	}
+}
+
+impl NoiseSettings {
+	// Perlin and OpenSimplex are summed as fBm; ridged is a ridged multifractal over Perlin
+	pub fn build(&self, seed: u32) -> Box<NoiseFn<f64, 3> + Send + Sync> {
+		match self.algorithm {
+			NoiseAlgorithm::Perlin => Box::new(
+				Fbm::<Perlin>::new(seed)
+					.set_octaves(self.octaves)
+					.set_frequency(self.frequency)
+					.set_lacunarity(self.lacunarity)
+					.set_persistence(self.persistence)
+			),
+			NoiseAlgorithm::OpenSimplex => Box::new(
+				Fbm::<OpenSimplex>::new(seed)
+					.set_octaves(self.octaves)
+					.set_frequency(self.frequency)
+					.set_lacunarity(self.lacunarity)
+					.set_persistence(self.persistence)
+			),
+			NoiseAlgorithm::Ridged => Box::new(
+				RidgedMulti::<Perlin>::new(seed)
+					.set_octaves(self.octaves)
+					.set_frequency(self.frequency)
+					.set_lacunarity(self.lacunarity)
+					.set_persistence(self.persistence)
+			),
+		}
+	}
+
+	// Options are read from `<prefix>noise`, `<prefix>octaves` and so on; missing ones keep
+	// the values in `defaults`
+	pub fn from_options(options: &BTreeMap<String, String>, prefix: &str, defaults: NoiseSettings) -> Result<NoiseSettings, SaveError> {
+		let key = format!("{}noise", prefix);
+		let algorithm = match options.get(&key) {
+			Some(name) => NoiseAlgorithm::from_name(name).ok_or_else(|| SaveError::Malformed(format!("{}={}", key, name)))?,
+			None => defaults.algorithm,
+		};
+
+		Ok(NoiseSettings {
+			algorithm: algorithm,
+			octaves: parse_option(options, &format!("{}octaves", prefix), defaults.octaves)?,
+			frequency: parse_option(options, &format!("{}frequency", prefix), defaults.frequency)?,
+			lacunarity: parse_option(options, &format!("{}lacunarity", prefix), defaults.lacunarity)?,
+			persistence: parse_option(options, &format!("{}persistence", prefix), defaults.persistence)?,
+		})
+	}
+
+	pub fn write_options(&self, options: &mut BTreeMap<String, String>, prefix: &str) {
+		options.insert(format!("{}noise", prefix), self.algorithm.name().to_string());
+		options.insert(format!("{}octaves", prefix), self.octaves.to_string());
+		options.insert(format!("{}frequency", prefix), self.frequency.to_string());
+		options.insert(format!("{}lacunarity", prefix), self.lacunarity.to_string());
+		options.insert(format!("{}persistence", prefix), self.persistence.to_string());
+	}
+}
+
+fn parse_option<T: FromStr>(options: &BTreeMap<String, String>, key: &str, default: T) -> Result<T, SaveError> {
+	match options.get(key) {
+		Some(raw) => raw.parse().map_err(|_| SaveError::Malformed(format!("{}={}", key, raw))),
+		None => Ok(default),
+	}
+}
+
+// Piecewise linear curve through `points`, extended past the end points along the end segments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spline {
+	points: Vec<(f64, f64)>,
+}
+
+impl Spline {
+	pub fn new(mut points: Vec<(f64, f64)>) -> Spline {
+		assert!(points.len() >= 2, "A spline needs at least two points");
+		assert!(points.iter().all(|p| p.0.is_finite() && p.1.is_finite()), "Spline points must be finite");
+		points.sort_by(|a, b| a.0.total_cmp(&b.0));
+		assert!(points.windows(2).all(|w| w[0].0 < w[1].0), "Spline points must have distinct x");
+		Spline { points: points }
+	}
+
+	pub fn points(&self) -> &[(f64, f64)] {
+		&self.points
+	}
+
+	pub fn get(&self, x: f64) -> f64 {
+		let last = self.points.len() - 2;
+		let i = self.points[1..last + 1].iter().position(|p| x < p.0).unwrap_or(last);
+		let ((x0, y0), (x1, y1)) = (self.points[i], self.points[i + 1]);
+		y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+	}
+
+	// Minimum and maximum of the curve over [min, max]
+	pub fn range(&self, min: f64, max: f64) -> (f64, f64) {
+		let inner = self.points.iter().filter(|p| p.0 > min && p.0 < max).map(|p| p.1);
+		let (a, b) = (self.get(min), self.get(max));
+		inner.fold((a.min(b), a.max(b)), |(lo, hi), y| (lo.min(y), hi.max(y)))
+	}
+}
+
+impl fmt::Display for Spline {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (i, &(x, y)) in self.points.iter().enumerate() {
+			if i > 0 {
+				write!(f, ",")?;
+			}
+			write!(f, "{}:{}", x, y)?;
+		}
+		Ok(())
+	}
+}
+
+// Parses the `x:y,x:y,...` form written by `Display`
+impl FromStr for Spline {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Spline, ()> {
+		let mut points = Vec::new();
+		for point in s.split(',') {
+			let mut split = point.splitn(2, ':');
+			match (split.next().map(str::parse), split.next().map(str::parse)) {
+				(Some(Ok(x)), Some(Ok(y))) => points.push((x, y)),
+				_ => return Err(()),
+			}
+		}
+
+		// Checked first, as NaN can't be sorted
+		if points.len() < 2 || points.iter().any(|p: &(f64, f64)| !p.0.is_finite() || !p.1.is_finite()) {
+			return Err(());
+		}
+		let mut xs: Vec<f64> = points.iter().map(|p| p.0).collect();
+		xs.sort_by(|a, b| a.total_cmp(b));
+		if !xs.windows(2).all(|w| w[0] < w[1]) {
+			return Err(());
+		}
+		Ok(Spline::new(points))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarpSettings {
+	pub noise: NoiseSettings,
+	// Maximum displacement of a sample point, in blocks
+	pub strength: f64,
+}
+
+impl Default for WarpSettings {
+	fn default() -> WarpSettings {
+		WarpSettings {
+			noise: NoiseSettings {
+				octaves: 3,
+				frequency: 1.0 / 256.0,
+				.. NoiseSettings::default()
+			},
+			strength: 32.0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainSettings {
+	pub noise: NoiseSettings,
+	// Scale of the noise term relative to the shape curve
+	pub amplitude: f64,
+	// Base density as a function of height, positive being solid. Steep drops pin the surface
+	// near one height, forming plateaus; flat stretches let the noise dominate, giving cliffs
+	// and overhangs.
+	pub shape: Spline,
+	pub warp: Option<WarpSettings>,
+	// Air below this height is filled with water
+	pub sea_level: Option<i64>,
+}
+
+impl Default for TerrainSettings {
+	fn default() -> TerrainSettings {
+		TerrainSettings {
+			noise: NoiseSettings::default(),
+			amplitude: 64.0,
+			shape: Spline::new(vec![(0.0, 0.0), (128.0, -1.0)]),
+			warp: None,
+			sea_level: Some(0),
+		}
+	}
+}
+
+impl TerrainSettings {
+	pub fn from_options(options: &BTreeMap<String, String>) -> Result<TerrainSettings, SaveError> {
+		let defaults = TerrainSettings::default();
+
+		// Warping is enabled by the presence of a strength
+		let warp = match options.get("warp.strength") {
+			Some(_) => {
+				let warp_defaults = WarpSettings::default();
+				Some(WarpSettings {
+					noise: NoiseSettings::from_options(options, "warp.", warp_defaults.noise)?,
+					strength: parse_option(options, "warp.strength", warp_defaults.strength)?,
+				})
+			},
+			None => None,
+		};
+
+		// Saves from before sea levels existed don't have one
+		let sea_level = match options.get("sea_level").map(String::as_str) {
+			Some("none") | None => None,
+			Some(raw) => Some(raw.parse().map_err(|_| SaveError::Malformed(format!("sea_level={}", raw)))?),
+		};
+
+		Ok(TerrainSettings {
+			noise: NoiseSettings::from_options(options, "", defaults.noise)?,
+			amplitude: parse_option(options, "amplitude", defaults.amplitude)?,
+			shape: parse_option(options, "shape", defaults.shape)?,
+			warp: warp,
+			sea_level: sea_level,
+		})
+	}
+
+	pub fn write_options(&self, options: &mut BTreeMap<String, String>) {
+		self.noise.write_options(options, "");
+		options.insert("amplitude".to_string(), self.amplitude.to_string());
+		options.insert("shape".to_string(), self.shape.to_string());
+		if let Some(ref warp) = self.warp {
+			warp.noise.write_options(options, "warp.");
+			options.insert("warp.strength".to_string(), warp.strength.to_string());
+		}
+		options.insert("sea_level".to_string(), match self.sea_level {
+			Some(sea_level) => sea_level.to_string(),
+			None => "none".to_string(),
+		});
+	}
+}
+
+const SAMPLE_STEP: usize = 4;
+const LATTICE_SIZE: usize = CHUNK_SIZE / SAMPLE_STEP + 1;
+// Noise output is clamped to this so the band early-out stays valid for any settings
+const NOISE_BOUND: f64 = 2.0;
+// Climate changes over hundreds of blocks, far more slowly than the terrain
+const CLIMATE_FREQUENCY: f64 = 1.0 / 512.0;
+const CLIMATE_OCTAVES: usize = 3;
+
+pub struct TerrainGenerator {
+	settings: TerrainSettings,
+	noise: Box<NoiseFn<f64, 3> + Send + Sync>,
+	// One noise function per axis, offsetting where `noise` is sampled
+	warp: Option<[Box<NoiseFn<f64, 3> + Send + Sync>; 3]>,
+	// Picking the biome of each column
+	temperature: Box<NoiseFn<f64, 3> + Send + Sync>,
+	humidity: Box<NoiseFn<f64, 3> + Send + Sync>,
+}
+
+impl TerrainGenerator {
+	pub const NAME: &'static str = "default";
+
+	pub fn new(seed: u32, settings: TerrainSettings) -> TerrainGenerator {
+		let random = WorldRandom::new(seed);
+		let warp = settings.warp.as_ref().map(|warp| [
+			warp.noise.build(random.noise_seed(1)),
+			warp.noise.build(random.noise_seed(2)),
+			warp.noise.build(random.noise_seed(3)),
+		]);
+
+		let climate = NoiseSettings { octaves: CLIMATE_OCTAVES, frequency: CLIMATE_FREQUENCY, .. NoiseSettings::default() };
+		TerrainGenerator {
+			noise: settings.noise.build(random.noise_seed(0)),
+			warp: warp,
+			temperature: climate.build(random.noise_seed(4)),
+			humidity: climate.build(random.noise_seed(5)),
+			settings: settings,
+		}
+	}
+
+	pub fn settings(&self) -> &TerrainSettings {
+		&self.settings
+	}
+
+	fn sample_column(&self, x: i64, y: i64, z: i64, out: &mut [f32]) {
+		for (k, density) in out.iter_mut().enumerate() {
+			let block_z = z + (k * SAMPLE_STEP) as i64;
+			let mut point = [x as f64, y as f64, block_z as f64];
+			if let (Some(warp), Some(settings)) = (self.warp.as_ref(), self.settings.warp.as_ref()) {
+				let offset = [warp[0].get(point), warp[1].get(point), warp[2].get(point)];
+				for (p, o) in point.iter_mut().zip(offset.iter()) {
+					*p += o.max(-1.0).min(1.0) * settings.strength;
+				}
+			}
+
+			let noise = self.noise.get(point).max(-NOISE_BOUND).min(NOISE_BOUND);
+			*density = (self.settings.shape.get(block_z as f64) + noise * self.settings.amplitude) as f32;
+		}
+	}
+
+	fn uniform_block(&self, min_z: i64, max_z: i64) -> Option<usize> {
+		// density = shape(z) + amplitude * noise, with |noise| <= NOISE_BOUND
+		let (lowest, highest) = self.settings.shape.range(min_z as f64, max_z as f64);
+		let reach = self.settings.amplitude.abs() * NOISE_BOUND;
+		if highest + reach <= 0.0 {
+			Some(AIR)
+		} else if lowest - reach > 0.0 {
+			Some(GROUND)
+		} else {
+			None
+		}
+	}
+
+	fn sample_terrain(&self, base: [i64; 3], blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
+		// Density is sampled on a coarse lattice a column at a time and trilinearly interpolated
+		let mut lattice = [[[0.0f32; LATTICE_SIZE]; LATTICE_SIZE]; LATTICE_SIZE];
+		for i in 0..LATTICE_SIZE {
+			for j in 0..LATTICE_SIZE {
+				let column_x = base[0] + (i * SAMPLE_STEP) as i64;
+				let column_y = base[1] + (j * SAMPLE_STEP) as i64;
+				self.sample_column(column_x, column_y, base[2], &mut lattice[i][j]);
+			}
+		}
+
+		for ci in 0..LATTICE_SIZE - 1 {
+			for cj in 0..LATTICE_SIZE - 1 {
+				for ck in 0..LATTICE_SIZE - 1 {
+					let corners = [
+						lattice[ci][cj][ck],         lattice[ci][cj][ck + 1],
+						lattice[ci][cj + 1][ck],     lattice[ci][cj + 1][ck + 1],
+						lattice[ci + 1][cj][ck],     lattice[ci + 1][cj][ck + 1],
+						lattice[ci + 1][cj + 1][ck], lattice[ci + 1][cj + 1][ck + 1],
+					];
+
+					// Interpolation can't change sign if every corner agrees
+					let uniform = if corners.iter().all(|&d| d > 0.0) {
+						Some(GROUND)
+					} else if corners.iter().all(|&d| d <= 0.0) {
+						Some(AIR)
+					} else {
+						None
+					};
+
+					for di in 0..SAMPLE_STEP {
+						for dj in 0..SAMPLE_STEP {
+							for dk in 0..SAMPLE_STEP {
+								let block = uniform.unwrap_or_else(|| {
+									let step = SAMPLE_STEP as f32;
+									let (tx, ty, tz) = (di as f32 / step, dj as f32 / step, dk as f32 / step);
+									let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+									let density = lerp(
+										lerp(lerp(corners[0], corners[1], tz), lerp(corners[2], corners[3], tz), ty),
+										lerp(lerp(corners[4], corners[5], tz), lerp(corners[6], corners[7], tz), ty),
+										tx
+									);
+									if density > 0.0 { GROUND } else { AIR }
+								});
+								blocks[ci * SAMPLE_STEP + di][cj * SAMPLE_STEP + dj][ck * SAMPLE_STEP + dk] = block;
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+
+	// Fills air below the sea level with water
+	fn flood(sea_level: i64, base_z: i64, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
+		if base_z >= sea_level {
+			return;
+		}
+
+		let depth = ((sea_level - base_z) as usize).min(CHUNK_SIZE);
+		for plane in blocks.iter_mut() {
+			for row in plane.iter_mut() {
+				for b in row[..depth].iter_mut() {
+					if *b == AIR {
+						*b = WATER;
+					}
+				}
+			}
+		}
+	}
+}
+
+impl WorldGenerator for TerrainGenerator {
+	fn name(&self) -> &'static str {
+		TerrainGenerator::NAME
+	}
+
+	fn options(&self) -> BTreeMap<String, String> {
+		let mut options = BTreeMap::new();
+		self.settings.write_options(&mut options);
+		options
+	}
+
+	fn generate(&self, x: i64, y: i64, z: i64, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
+		let base = [CHUNK_SIZE as i64 * x, CHUNK_SIZE as i64 * y, CHUNK_SIZE as i64 * z];
+
+		// Chunks entirely above or below the band the noise can reach don't need sampling at all
+		match self.uniform_block(base[2], base[2] + CHUNK_SIZE as i64 - 1) {
+			Some(block) => {
+				for plane in blocks.iter_mut() {
+					for row in plane.iter_mut() {
+						for b in row.iter_mut() {
+							*b = block;
+						}
+					}
+				}
+			},
+			None => self.sample_terrain(base, blocks),
+		}
+
+		if let Some(sea_level) = self.settings.sea_level {
+			TerrainGenerator::flood(sea_level, base[2], blocks);
+		}
+	}
+
+	fn biome(&self, x: i64, y: i64) -> Biome {
+		let point = [x as f64, y as f64, 0.0];
+		let (temperature, humidity) = (self.temperature.get(point), self.humidity.get(point));
+		if temperature < -0.25 {
+			Biome::Tundra
+		} else if temperature > 0.3 && humidity < 0.0 {
+			Biome::Desert
+		} else if humidity > 0.35 {
+			Biome::Swamp
+		} else if humidity > 0.05 {
+			Biome::Forest
+		} else {
+			Biome::Plains
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatLayer {
+	pub block: usize,
+	pub thickness: u32,
+}
+
+// Layers are stacked upwards from height 0, bottom layer first. Everything else is air.
+pub struct FlatGenerator {
+	layers: Vec<FlatLayer>,
+}
+
+impl FlatGenerator {
+	pub const NAME: &'static str = "flat";
+
+	pub fn new(layers: Vec<FlatLayer>) -> FlatGenerator {
+		FlatGenerator { layers: layers }
+	}
+
+	pub fn default_layers() -> Vec<FlatLayer> {
+		vec![FlatLayer { block: GROUND, thickness: 4 }]
+	}
+
+	pub fn layers(&self) -> &[FlatLayer] {
+		&self.layers
+	}
+
+	// Parses `block*thickness,...`, as written by `options`
+	pub fn parse_layers(raw: &str) -> Option<Vec<FlatLayer>> {
+		if raw.is_empty() {
+			return Some(Vec::new());
+		}
+
+		raw.split(',').map(|layer| {
+			let mut split = layer.splitn(2, '*');
+			match (split.next().map(str::parse), split.next().map(str::parse)) {
+				(Some(Ok(block)), Some(Ok(thickness))) => Some(FlatLayer { block: block, thickness: thickness }),
+				_ => None,
+			}
+		}).collect()
+	}
+
+	fn block_at(&self, z: i64) -> usize {
+		if z < 0 {
+			return AIR;
+		}
+
+		let mut top = 0;
+		for layer in &self.layers {
+			top += layer.thickness as i64;
+			if z < top {
+				return layer.block;
+			}
+		}
+		AIR
+	}
+}
+
+impl WorldGenerator for FlatGenerator {
+	fn name(&self) -> &'static str {
+		FlatGenerator::NAME
+	}
+
+	fn options(&self) -> BTreeMap<String, String> {
+		let layers: Vec<String> = self.layers.iter().map(|layer| format!("{}*{}", layer.block, layer.thickness)).collect();
+		let mut options = BTreeMap::new();
+		options.insert("layers".to_string(), layers.join(","));
+		options
+	}
+
+	fn generate(&self, _x: i64, _y: i64, z: i64, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
+		let mut column = [AIR; CHUNK_SIZE];
+		for (k, block) in column.iter_mut().enumerate() {
+			*block = self.block_at(CHUNK_SIZE as i64 * z + k as i64);
+		}
+
+		for plane in blocks.iter_mut() {
+			for row in plane.iter_mut() {
+				*row = column;
+			}
+		}
+	}
+}
+
+pub struct VoidGenerator;
+
+impl VoidGenerator {
+	pub const NAME: &'static str = "void";
+}
+
+impl WorldGenerator for VoidGenerator {
+	fn name(&self) -> &'static str {
+		VoidGenerator::NAME
+	}
+
+	fn options(&self) -> BTreeMap<String, String> {
+		BTreeMap::new()
+	}
+
+	fn generate(&self, _x: i64, _y: i64, _z: i64, blocks: &mut [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
+		for plane in blocks.iter_mut() {
+			for row in plane.iter_mut() {
+				*row = [AIR; CHUNK_SIZE];
+			}
+		}
+	}
+}
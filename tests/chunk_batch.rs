@@ -0,0 +1,71 @@
+extern crate space3;
+
+use space3::chunk_batch::{self, ArenaSpace, ChunkMesh, RangeAllocator, PAGE_VERTICES};
+use space3::gl_util::VertexFormat;
+
+fn draws(meshes: &[ChunkMesh]) -> Vec<(&ChunkMesh, [f32; 3])> {
+	meshes.iter().enumerate().map(|(i, mesh)| (mesh, [i as f32 * 16.0, 0.0, 0.0])).collect()
+}
+
+#[test]
+fn draw_calls_do_not_grow_with_chunks() {
+	for &chunks in &[1, 10, 100, 1000] {
+		let space = ArenaSpace::new(VertexFormat::Packed);
+		let meshes: Vec<ChunkMesh> = (0..chunks).map(|_| space.place(96, 144).unwrap()).collect();
+		let batches = chunk_batch::plan_batches(&draws(&meshes));
+		assert_eq!(batches.len(), 1, "{} chunks", chunks);
+		assert_eq!(batches[0].commands.len(), chunks);
+		assert!(batches[0].commands.iter().enumerate().all(|(i, command)| command.instance == i));
+	}
+}
+
+#[test]
+fn a_batch_per_page_and_format() {
+	let standard = ArenaSpace::new(VertexFormat::Standard);
+	let packed = ArenaSpace::new(VertexFormat::Packed);
+	// Each takes a quarter of a page's vertices, so five spill onto a second page
+	let mut meshes: Vec<ChunkMesh> = (0..5).map(|_| standard.place(PAGE_VERTICES / 4, 6).unwrap()).collect();
+	meshes.push(packed.place(4, 6).unwrap());
+	assert_eq!(standard.page_count(), 2);
+
+	let batches = chunk_batch::plan_batches(&draws(&meshes));
+	let shape: Vec<(VertexFormat, usize, usize)> = batches.iter().map(|batch| (batch.format, batch.page, batch.commands.len())).collect();
+	assert_eq!(shape, vec![(VertexFormat::Standard, 0, 4), (VertexFormat::Standard, 1, 1), (VertexFormat::Packed, 0, 1)]);
+}
+
+#[test]
+fn empty_meshes_take_no_space_and_are_not_drawn() {
+	let space = ArenaSpace::new(VertexFormat::Standard);
+	let meshes = vec![space.place(0, 0).unwrap(), space.place(4, 6).unwrap()];
+	assert_eq!(space.page_count(), 1);
+	let batches = chunk_batch::plan_batches(&draws(&meshes));
+	assert_eq!(batches[0].commands.len(), 1);
+	assert_eq!(batches[0].commands[0].instance, 1);
+}
+
+#[test]
+fn dropped_meshes_give_their_space_back() {
+	let space = ArenaSpace::new(VertexFormat::Standard);
+	let first = space.place(100, 150).unwrap();
+	let second = space.place(100, 150).unwrap();
+	assert_eq!((second.first_vertex, second.first_index), (100, 150));
+	drop(first);
+	let third = space.place(50, 60).unwrap();
+	assert_eq!((third.first_vertex, third.first_index), (0, 0));
+	assert!(space.place(PAGE_VERTICES + 1, 6).is_none());
+	assert_eq!(space.page_count(), 1);
+}
+
+#[test]
+fn freed_ranges_join_up() {
+	let mut ranges = RangeAllocator::new(30);
+	let starts: Vec<usize> = (0..3).map(|_| ranges.alloc(10).unwrap()).collect();
+	assert_eq!(starts, vec![0, 10, 20]);
+	assert_eq!(ranges.alloc(1), None);
+	ranges.free(0, 10);
+	ranges.free(20, 10);
+	assert_eq!(ranges.alloc(20), None);
+	ranges.free(10, 10);
+	assert_eq!(ranges.alloc(30), Some(0));
+	assert_eq!(ranges.free_len(), 0);
+}
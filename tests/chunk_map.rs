@@ -0,0 +1,43 @@
+extern crate space3;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use space3::chunk_map::ChunkMap;
+
+#[test]
+fn racing_inserts_all_get_the_one_value_kept() {
+	let map: Arc<ChunkMap<usize>> = Arc::new(ChunkMap::new());
+	let made = Arc::new(AtomicUsize::new(0));
+
+	let threads: Vec<_> = (0..8).map(|_| {
+		let (map, made) = (map.clone(), made.clone());
+		thread::spawn(move || {
+			(0..64).map(|i| {
+				map.get_or_insert_with([i, -i, 0], |&value| Some(value), || {
+					let value = made.fetch_add(1, Ordering::SeqCst);
+					(value, value)
+				})
+			}).collect::<Vec<usize>>()
+		})
+	}).collect();
+	let seen: Vec<Vec<usize>> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+
+	for i in 0..64 {
+		let kept = map.get([i as i64, -(i as i64), 0], |&value| Some(value)).unwrap();
+		assert!(seen.iter().all(|values| values[i] == kept), "chunk {} differs between threads", i);
+	}
+	assert_eq!(map.len(), 64);
+}
+
+#[test]
+fn values_that_are_not_accepted_are_replaced() {
+	let map = ChunkMap::new();
+	map.insert([1, 2, 3], None);
+	let value = map.get_or_insert_with([1, 2, 3], |value: &Option<u32>| *value, || (Some(7), 7));
+	assert_eq!(value, 7);
+	assert_eq!(map.get([1, 2, 3], |value| *value), Some(7));
+	assert_eq!(map.remove([1, 2, 3]), Some(Some(7)));
+	assert_eq!(map.len(), 0);
+}
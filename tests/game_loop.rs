@@ -0,0 +1,251 @@
+extern crate space3;
+extern crate time;
+
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+use time::Duration;
+
+use space3::block::{World, AIR, GROUND, SAND, block_properties, block_render_types};
+use space3::worldgen::{WorldPreset, FlatGenerator};
+use space3::server::{Server, ClientMessage, ServerMessage, PlayerInput, PlayerState, MAX_REACH};
+
+// Ground fills z 0 to 3, so anything standing on it has its feet at 4
+const SURFACE: f32 = 4.0;
+
+// The server, run without a window, with the test standing in for the client
+struct Harness {
+	server: Server,
+	to_server: Sender<ClientMessage>,
+	from_server: Receiver<ServerMessage>,
+	// From the latest snapshot
+	player: Option<PlayerState>,
+	sounds: usize,
+}
+
+impl Harness {
+	fn new() -> Harness {
+		let world = World::from_preset(12, &WorldPreset::Superflat(FlatGenerator::default_layers()));
+		let (to_server, inbox) = channel();
+		let (outbox, from_server) = channel();
+		let server = Server::new(world, Rc::new(block_properties()), Rc::new(block_render_types().unwrap()), inbox, outbox);
+		Harness { server: server, to_server: to_server, from_server: from_server, player: None, sounds: 0 }
+	}
+
+	fn send(&self, message: ClientMessage) {
+		self.to_server.send(message).unwrap();
+	}
+
+	// `ticks` fixed steps of 50 ms, as a client holding `input` the whole time would send them
+	fn run(&mut self, ticks: usize, input: PlayerInput) {
+		for _ in 0..ticks {
+			self.send(ClientMessage::Input(input));
+			self.server.tick(&Duration::milliseconds(50));
+			while let Ok(message) = self.from_server.try_recv() {
+				match message {
+					ServerMessage::Snapshot(snapshot) => self.player = snapshot.player,
+					ServerMessage::Sound { .. } => self.sounds += 1,
+					_ => (),
+				}
+			}
+		}
+	}
+
+	fn position(&self) -> [f32; 3] {
+		self.player.as_ref().expect("no player").position
+	}
+}
+
+fn standing(eye: [f32; 3]) -> PlayerInput {
+	PlayerInput { velocity: [0.0; 2], jump: false, crouch: false, eye: eye, look: [1.0, 0.0, 0.0], breaking: None }
+}
+
+fn near(a: [f32; 3], b: [f32; 3]) -> bool {
+	(0..3).all(|axis| (a[axis] - b[axis]).abs() < 1.0e-3)
+}
+
+#[test]
+fn walking_forward_moves_the_player_along_the_ground() {
+	let mut harness = Harness::new();
+	harness.send(ClientMessage::Walk([0.5, 0.5, SURFACE]));
+	harness.run(10, standing([0.5, 0.5, SURFACE]));
+	assert!(near(harness.position(), [0.5, 0.5, SURFACE]), "settled at {:?}", harness.position());
+
+	// A second at four blocks per second
+	harness.run(20, PlayerInput { velocity: [4.0, 0.0], .. standing([0.5, 0.5, SURFACE]) });
+	let position = harness.position();
+	assert!(position[0] > 4.0 && position[0] < 5.0, "walked to {:?}", position);
+	assert!(near([0.0, position[1], position[2]], [0.0, 0.5, SURFACE]), "walked to {:?}", position);
+	assert!(harness.server.stats().distance_walked > 3.5, "{:?}", harness.server.stats());
+}
+
+#[test]
+fn jumping_leaves_the_ground_and_lands_again() {
+	let mut harness = Harness::new();
+	harness.send(ClientMessage::Walk([0.5, 0.5, SURFACE]));
+	harness.run(10, standing([0.5, 0.5, SURFACE]));
+
+	harness.run(4, PlayerInput { jump: true, .. standing([0.5, 0.5, SURFACE]) });
+	assert!(harness.position()[2] > SURFACE + 0.5, "jumped to {:?}", harness.position());
+	harness.run(40, standing([0.5, 0.5, SURFACE]));
+	assert!(near(harness.position(), [0.5, 0.5, SURFACE]), "landed at {:?}", harness.position());
+}
+
+#[test]
+fn broken_blocks_leave_air_and_a_drop_to_collect() {
+	let mut harness = Harness::new();
+	// Flying just above the ground, looking straight down
+	let eye = [0.5, 0.5, SURFACE + 0.5];
+	harness.run(1, standing(eye));
+	// Ground takes half a second of holding the button, ten ticks
+	let breaking = PlayerInput { breaking: Some([0.0, 0.0, -1.0]), .. standing(eye) };
+	harness.run(9, breaking);
+	assert_eq!(harness.server.world().get_block([0, 0, 3]), GROUND);
+	assert_eq!(harness.sounds, 0);
+	// Letting go starts over
+	harness.run(1, standing(eye));
+	harness.run(9, breaking);
+	assert_eq!(harness.server.world().get_block([0, 0, 3]), GROUND);
+	harness.run(1, breaking);
+	harness.run(1, standing(eye));
+	assert_eq!(harness.server.world().get_block([0, 0, 3]), AIR);
+	assert_eq!(harness.server.world().get_block([0, 0, 2]), GROUND);
+	assert_eq!(harness.sounds, 1);
+	assert_eq!(harness.server.stats().blocks_broken, 1);
+
+	// The drop can't be picked up until it has popped out and settled
+	harness.run(30, standing(eye));
+	assert_eq!(harness.server.inventory().count(GROUND), 1);
+}
+
+#[test]
+fn blocks_are_placed_only_within_reach_and_not_inside_the_player() {
+	let mut harness = Harness::new();
+	let down = [0.0, 0.0, -1.0];
+	harness.send(ClientMessage::Walk([0.5, 0.5, SURFACE]));
+	harness.run(10, standing([0.5, 0.5, SURFACE]));
+
+	// Straight down, the only air against the ground is where the player stands
+	let eye = [0.5, 0.5, SURFACE + 1.62];
+	harness.send(ClientMessage::PlaceBlock { origin: eye, direction: down, block: GROUND });
+	harness.run(1, standing(eye));
+	assert_eq!(harness.server.world().get_block([0, 0, 4]), AIR);
+
+	// Flying, there's no body in the way, but the ground is out of reach from up here
+	harness.send(ClientMessage::Fly);
+	let eye = [3.5, 3.5, SURFACE + 6.5];
+	harness.send(ClientMessage::PlaceBlock { origin: eye, direction: down, block: GROUND });
+	harness.run(1, standing(eye));
+	assert_eq!(harness.server.world().get_block([3, 3, 4]), AIR);
+
+	harness.send(ClientMessage::SetReach(MAX_REACH));
+	harness.send(ClientMessage::PlaceBlock { origin: eye, direction: down, block: GROUND });
+	harness.run(1, standing(eye));
+	assert_eq!(harness.server.world().get_block([3, 3, 4]), GROUND);
+	assert_eq!(harness.server.stats().blocks_placed, 1);
+}
+
+#[test]
+fn the_border_stops_generation_and_the_player() {
+	let mut harness = Harness::new();
+	harness.send(ClientMessage::SetBorder(Some(40)));
+	harness.send(ClientMessage::Walk([36.5, 0.5, SURFACE]));
+	harness.run(10, standing([36.5, 0.5, SURFACE]));
+	assert_eq!(harness.server.world().get_block([39, 0, 2]), GROUND);
+	assert_eq!(harness.server.world().get_block([40, 0, 2]), AIR);
+	assert_eq!(harness.server.world().get_block([100, 0, 2]), AIR);
+
+	// Walking at it, the player stops with their side against it, on the ground still there
+	harness.run(40, PlayerInput { velocity: [4.0, 0.0], .. standing([36.5, 0.5, SURFACE]) });
+	let position = harness.position();
+	assert!(near(position, [40.0 - 0.3, 0.5, SURFACE]), "walked to {:?}", position);
+}
+
+#[test]
+fn game_rules_forbid_flying_and_are_kept_by_the_world() {
+	let mut harness = Harness::new();
+	harness.send(ClientMessage::SetGameRule { name: "fly".to_string(), value: "false".to_string() });
+	harness.send(ClientMessage::SetGameRule { name: "fly".to_string(), value: "sometimes".to_string() });
+	harness.send(ClientMessage::Walk([0.5, 0.5, SURFACE]));
+	harness.run(1, standing([0.5, 0.5, SURFACE]));
+	assert!(!harness.server.world().rules().fly);
+
+	harness.send(ClientMessage::Fly);
+	harness.run(1, standing([0.5, 0.5, SURFACE]));
+	assert!(harness.player.is_some(), "flew off despite the rule");
+}
+
+#[test]
+fn time_passes_only_while_the_day_night_cycle_is_on() {
+	let mut harness = Harness::new();
+	harness.send(ClientMessage::SetTime(100.0));
+	harness.run(20, standing([0.5, 0.5, SURFACE]));
+	assert!((harness.server.world().time() - 101.0).abs() < 1.0e-3, "{}", harness.server.world().time());
+
+	harness.send(ClientMessage::SetGameRule { name: "day_night_cycle".to_string(), value: "false".to_string() });
+	harness.send(ClientMessage::AddTime(50.0));
+	harness.run(20, standing([0.5, 0.5, SURFACE]));
+	assert!((harness.server.world().time() - 151.0).abs() < 1.0e-3, "{}", harness.server.world().time());
+}
+
+#[test]
+fn region_fills_replace_within_the_box_and_undo_together() {
+	let mut harness = Harness::new();
+	harness.send(ClientMessage::FillRegion { min: [0, 0, 2], max: [3, 3, 5], block: SAND, from: None });
+	harness.run(1, standing([0.5, 0.5, 10.0]));
+	assert_eq!(harness.server.world().get_block([3, 3, 5]), SAND);
+	assert_eq!(harness.server.world().get_block([4, 3, 5]), AIR);
+
+	// Only the sand that was ground is turned back
+	harness.send(ClientMessage::FillRegion { min: [0, 0, 0], max: [3, 3, 5], block: GROUND, from: Some(SAND) });
+	harness.run(1, standing([0.5, 0.5, 10.0]));
+	assert_eq!(harness.server.world().get_block([3, 3, 5]), GROUND);
+	assert_eq!(harness.server.world().get_block([3, 3, 1]), GROUND);
+
+	harness.send(ClientMessage::Undo);
+	harness.run(1, standing([0.5, 0.5, 10.0]));
+	assert_eq!(harness.server.world().get_block([3, 3, 5]), SAND);
+	assert_eq!(harness.server.world().get_block([2, 2, 2]), SAND);
+
+	harness.send(ClientMessage::FillRegion { min: [0, 0, 0], max: [1000, 1000, 1000], block: SAND, from: None });
+	harness.run(1, standing([0.5, 0.5, 10.0]));
+	assert_eq!(harness.server.world().get_block([0, 0, 0]), GROUND);
+}
+
+#[test]
+fn copies_paste_relative_to_where_they_were_copied_from_and_turn() {
+	let mut harness = Harness::new();
+	let eye = [0.5, 0.5, 10.0];
+	// A row of three sand along x, copied from its middle
+	harness.send(ClientMessage::FillRegion { min: [0, 0, 4], max: [2, 0, 4], block: SAND, from: None });
+	harness.send(ClientMessage::Copy { min: [0, 0, 4], max: [2, 0, 4], origin: [1, 0, 4] });
+	harness.send(ClientMessage::Paste { origin: [10, 10, 4], turns: 0 });
+	harness.run(1, standing(eye));
+	assert_eq!(harness.server.world().get_block([9, 10, 4]), SAND);
+	assert_eq!(harness.server.world().get_block([11, 10, 4]), SAND);
+	assert_eq!(harness.server.world().get_block([12, 10, 4]), AIR);
+
+	// A quarter turn lays it along y instead, still through the origin
+	harness.send(ClientMessage::Paste { origin: [20, 20, 4], turns: 1 });
+	harness.run(1, standing(eye));
+	assert_eq!(harness.server.world().get_block([20, 19, 4]), SAND);
+	assert_eq!(harness.server.world().get_block([20, 21, 4]), SAND);
+	assert_eq!(harness.server.world().get_block([21, 20, 4]), AIR);
+}
+
+#[test]
+fn selections_report_their_blocks_and_exposed_faces() {
+	let mut harness = Harness::new();
+	// Two blocks side by side in the air hide one face each from the other
+	harness.send(ClientMessage::FillRegion { min: [0, 0, 10], max: [1, 0, 10], block: GROUND, from: None });
+	harness.run(1, standing([0.5, 0.5, 20.0]));
+	let world = harness.server.world();
+	let render = block_render_types().unwrap();
+	assert_eq!(world.exposed_faces([0, 0, 10], [1, 0, 10], &render), 10);
+	// Only the blocks in the box count, though their neighbours outside it still hide faces
+	assert_eq!(world.exposed_faces([0, 0, 10], [0, 0, 11], &render), 5);
+
+	let counts = world.count_blocks([0, 0, 9], [1, 0, 10], block_properties().len());
+	assert_eq!(counts[GROUND], 2);
+	assert_eq!(counts[AIR], 2);
+}
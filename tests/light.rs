@@ -0,0 +1,126 @@
+extern crate space3;
+
+use std::rc::Rc;
+
+use space3::audio::BlockSounds;
+use space3::block::{World, BlockProperties, AIR, GROUND, block_properties};
+use space3::light::{Lighting, Channel, MAX_LIGHT, RELIGHT_RADIUS};
+use space3::orientation::Orienting;
+use space3::worldgen::WorldPreset;
+
+// Glows red, after the usual blocks
+fn properties_with_lamp() -> (Rc<Vec<BlockProperties>>, usize) {
+	let mut properties = block_properties();
+	properties.push(BlockProperties {
+		name: "lamp",
+		solid: true,
+		falls: false,
+		opaque: true,
+		emission: [14, 0, 0],
+		sounds: BlockSounds::silent(),
+		orienting: Orienting::Fixed,
+		hardness: 0.0,
+	});
+	let lamp = properties.len() - 1;
+	(Rc::new(properties), lamp)
+}
+
+// Dispatches the edits made so far and relights until there's nothing left to do, returning the
+// ticks it took
+fn settle(world: &World, lighting: &mut Lighting) -> usize {
+	for ticks in 1..1000 {
+		while world.pending_updates() > 0 {
+			world.update_blocks();
+		}
+		lighting.tick(world);
+		if lighting.pending() == 0 {
+			return ticks;
+		}
+	}
+	panic!("lighting never settled");
+}
+
+fn sky(world: &World, pos: [i64; 3]) -> u8 {
+	world.get_light(pos).sky()
+}
+
+#[test]
+fn blocks_under_open_sky_shade_and_unshade_what_is_below() {
+	let world = World::from_preset(0, &WorldPreset::Void);
+	let mut lighting = Lighting::new(&world, Rc::new(block_properties()));
+
+	world.set_block([0, 0, 10], GROUND);
+	settle(&world, &mut lighting);
+	assert_eq!(sky(&world, [0, 0, 11]), MAX_LIGHT);
+	// Lit from the side now the sun is blocked
+	assert_eq!(sky(&world, [0, 0, 9]), MAX_LIGHT - 1);
+	assert_eq!(sky(&world, [0, 0, 0]), MAX_LIGHT - 1);
+	assert_eq!(sky(&world, [1, 0, 9]), MAX_LIGHT);
+
+	world.set_block([0, 0, 10], AIR);
+	settle(&world, &mut lighting);
+	assert_eq!(sky(&world, [0, 0, 10]), MAX_LIGHT);
+	assert_eq!(sky(&world, [0, 0, 9]), MAX_LIGHT);
+	assert_eq!(sky(&world, [0, 0, 0]), MAX_LIGHT);
+}
+
+#[test]
+fn removing_an_emitter_clears_only_its_own_light() {
+	let world = World::from_preset(0, &WorldPreset::Void);
+	let (properties, lamp) = properties_with_lamp();
+	let mut lighting = Lighting::new(&world, properties);
+	// Far enough apart that their light doesn't meet
+	let (near, far) = ([0, 0, 0], [40, 0, 0]);
+
+	world.set_block(near, lamp);
+	world.set_block(far, lamp);
+	settle(&world, &mut lighting);
+	assert_eq!(world.get_light([3, 0, 0]).get(Channel::Red), 11);
+	let around_far: Vec<_> = (-15..16).map(|dx| world.get_light([far[0] + dx, 0, 1])).collect();
+
+	world.set_block(near, AIR);
+	settle(&world, &mut lighting);
+	for x in -15..16 {
+		for y in -15..16 {
+			for z in -15..16 {
+				assert_eq!(world.get_light([x, y, z]).block(), [0; 3], "at {:?}", [x, y, z]);
+			}
+		}
+	}
+	let after: Vec<_> = (-15..16).map(|dx| world.get_light([far[0] + dx, 0, 1])).collect();
+	assert_eq!(after, around_far);
+	// The sky was never the lamp's to take
+	assert_eq!(sky(&world, [0, 0, 0]), MAX_LIGHT);
+}
+
+#[test]
+fn relighting_stops_at_the_relight_radius() {
+	let world = World::from_preset(0, &WorldPreset::Void);
+	let mut lighting = Lighting::new(&world, Rc::new(block_properties()));
+
+	world.set_block([0, 0, 10], GROUND);
+	settle(&world, &mut lighting);
+	assert_eq!(sky(&world, [0, 0, 10 - RELIGHT_RADIUS]), MAX_LIGHT - 1);
+	// Left as it was, rather than the shadow running on forever
+	assert_eq!(sky(&world, [0, 0, 10 - RELIGHT_RADIUS - 1]), MAX_LIGHT);
+}
+
+#[test]
+fn relighting_past_the_step_limit_carries_over_to_later_ticks() {
+	let world = World::from_preset(0, &WorldPreset::Void);
+	let mut lighting = Lighting::new(&world, Rc::new(block_properties()));
+
+	// Every column under the roof is cleared the full relight radius down, which is far more than
+	// a tick's worth of steps
+	world.fill_region([0, 0, 10], [19, 19, 10], GROUND);
+	while world.pending_updates() > 0 {
+		world.update_blocks();
+	}
+	lighting.tick(&world);
+	assert!(lighting.pending() > 0);
+
+	assert!(settle(&world, &mut lighting) > 1);
+	// Ten blocks in from the nearest edge
+	assert_eq!(sky(&world, [10, 10, 9]), MAX_LIGHT - 10);
+	assert_eq!(sky(&world, [20, 10, 9]), MAX_LIGHT);
+}
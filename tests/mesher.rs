@@ -0,0 +1,436 @@
+#[macro_use]
+extern crate proptest;
+extern crate space3;
+
+use std::collections::HashSet;
+
+use proptest::prelude::*;
+
+use space3::block::{self, BlockRenderData, ObscuresFaces, InvalidRenderData, Chunk, Neighbours, NormalDirection, CHUNK_SIZE};
+use space3::gl_util::{Vertex, PackedVertex};
+use space3::light::Light;
+use space3::biome::{Biome, Tint};
+use space3::orientation::Orientation;
+
+// A unit face: its normal, the coordinate of its plane along the normal axis, and its
+// position along the two remaining axes in ascending axis order.
+type Face = ([i32; 3], i32, i32, i32);
+
+fn render_data() -> Vec<BlockRenderData> {
+	vec![
+		BlockRenderData { obscures: ObscuresFaces::NONE, color: [0.0, 0.0, 0.0], should_render: false, liquid: false, tint: None, trim: None },
+		BlockRenderData { obscures: ObscuresFaces::ALL,  color: [0.3, 0.4, 0.2], should_render: true,  liquid: false, tint: None, trim: None },
+		BlockRenderData { obscures: ObscuresFaces::ALL,  color: [0.6, 0.2, 0.1], should_render: true,  liquid: false, tint: None, trim: None },
+	]
+}
+
+fn normal_axis(normal: [i32; 3]) -> (usize, usize, usize) {
+	match normal {
+		[_, 0, 0] => (0, 1, 2),
+		[0, _, 0] => (1, 0, 2),
+		_         => (2, 0, 1),
+	}
+}
+
+fn naive_faces(blocks: &[[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE], data: &[BlockRenderData]) -> HashSet<Face> {
+	let normals = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+	let mut faces = HashSet::new();
+
+	for x in 0..CHUNK_SIZE {
+		for y in 0..CHUNK_SIZE {
+			for z in 0..CHUNK_SIZE {
+				if !data[blocks[x][y][z]].should_render {
+					continue;
+				}
+
+				for normal in normals.iter() {
+					let pos = [x as i32, y as i32, z as i32];
+					let neighbour = [pos[0] + normal[0], pos[1] + normal[1], pos[2] + normal[2]];
+					let in_chunk = neighbour.iter().all(|&c| c >= 0 && c < CHUNK_SIZE as i32);
+					// Every solid block in these tests obscures all of its faces
+					if in_chunk && data[blocks[neighbour[0] as usize][neighbour[1] as usize][neighbour[2] as usize]].should_render {
+						continue;
+					}
+
+					let (n, a, b) = normal_axis(*normal);
+					let plane = pos[n] + if normal[n] > 0 { 1 } else { 0 };
+					faces.insert((*normal, plane, pos[a], pos[b]));
+				}
+			}
+		}
+	}
+	faces
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+	[a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+	[a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn check_mesh(blocks: &[[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> Result<(), TestCaseError> {
+	let data = render_data();
+	let chunk = Chunk::new(*blocks);
+	let (vertices, indices): (Vec<Vertex>, Vec<u16>) = chunk.build_mesh_data(&data, Neighbours::none());
+
+	prop_assert_eq!(vertices.len() % 4, 0);
+	prop_assert_eq!(indices.len(), vertices.len() / 4 * 6);
+
+	let mut covered = HashSet::new();
+	for (quad, (corners, quad_indices)) in vertices.chunks(4).zip(indices.chunks(6)).enumerate() {
+		let base = quad * 4;
+		for &index in quad_indices {
+			prop_assert!((index as usize) < vertices.len(), "index {} out of range", index);
+			prop_assert!(index as usize >= base && (index as usize) < base + 4, "quad {} references a foreign vertex", quad);
+		}
+
+		let normal = [corners[0].normal[0] as i32, corners[0].normal[1] as i32, corners[0].normal[2] as i32];
+		for corner in corners {
+			prop_assert_eq!(corner.normal, corners[0].normal);
+			prop_assert_eq!(corner.color, corners[0].color);
+		}
+
+		// Front faces must wind counter-clockwise when seen from outside the block
+		for triangle in quad_indices.chunks(3) {
+			let (p0, p1, p2) = (vertices[triangle[0] as usize].position, vertices[triangle[1] as usize].position, vertices[triangle[2] as usize].position);
+			let facing = cross(sub(p1, p0), sub(p2, p0));
+			let dot = facing[0] * normal[0] as f32 + facing[1] * normal[1] as f32 + facing[2] * normal[2] as f32;
+			prop_assert!(dot > 0.0, "quad {} with normal {:?} is wound backwards", quad, normal);
+		}
+
+		let (n, a, b) = normal_axis(normal);
+		let min = |axis: usize| corners.iter().map(|c| c.position[axis] as i32).min().unwrap();
+		let max = |axis: usize| corners.iter().map(|c| c.position[axis] as i32).max().unwrap();
+		prop_assert_eq!(min(n), max(n));
+
+		for u in min(a)..max(a) {
+			for v in min(b)..max(b) {
+				prop_assert!(covered.insert((normal, min(n), u, v)), "quads overlap at {:?}", (normal, min(n), u, v));
+			}
+		}
+	}
+
+	let expected = naive_faces(blocks, &data);
+	prop_assert_eq!(covered.len(), expected.len(), "quad area doesn't match the number of visible faces");
+	prop_assert!(covered == expected, "mesh doesn't cover exactly the visible faces");
+	Ok(())
+}
+
+fn chunk_strategy() -> BoxedStrategy<Box<[[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>> {
+	let size = CHUNK_SIZE;
+	let cuboids = prop::collection::vec((0..size, 0..size, 0..size, 1..9usize, 1..9usize, 1..9usize, 0..3usize), 0..16);
+	let speckles = prop::collection::vec((0..size, 0..size, 0..size, 0..3usize), 0..64);
+
+	(cuboids, speckles).prop_map(move |(cuboids, speckles)| {
+		let mut blocks = Box::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+		for (x, y, z, w, h, d, block) in cuboids {
+			for i in x..(x + w).min(size) {
+				for j in y..(y + h).min(size) {
+					for k in z..(z + d).min(size) {
+						blocks[i][j][k] = block;
+					}
+				}
+			}
+		}
+		for (x, y, z, block) in speckles {
+			blocks[x][y][z] = block;
+		}
+		blocks
+	}).boxed()
+}
+
+proptest! {
+	#![proptest_config(ProptestConfig::with_cases(64))]
+
+	#[test]
+	fn greedy_mesh_matches_naive_faces(blocks in chunk_strategy()) {
+		check_mesh(&blocks)?;
+	}
+}
+
+#[test]
+fn empty_chunk_has_no_faces() {
+	let chunk = Chunk::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+	let (vertices, indices) = chunk.build_mesh_data::<Vertex>(&render_data(), Neighbours::none());
+	assert!(vertices.is_empty());
+	assert!(indices.is_empty());
+}
+
+#[test]
+fn full_chunk_is_six_quads() {
+	let chunk = Chunk::new([[[1; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+	let (vertices, indices) = chunk.build_mesh_data::<Vertex>(&render_data(), Neighbours::none());
+	assert_eq!(vertices.len(), 6 * 4);
+	assert_eq!(indices.len(), 6 * 6);
+}
+
+#[test]
+fn light_packs_both_channels() {
+	let light = Light::new(12, [3, 7, 0]);
+	assert_eq!((light.sky(), light.block()), (12, [3, 7, 0]));
+	assert_eq!(Light::from_packed(light.packed()), light);
+	assert_eq!(Light::new(40, [40; 3]), Light::new(15, [15; 3]));
+	assert_eq!(Light::new(0, [15, 0, 0]).color(), [1.0, 0.0, 0.0]);
+}
+
+// Solid along the bottom of the chunk, or the top
+fn floor(z: usize) -> [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE] {
+	let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+	for x in 0..CHUNK_SIZE {
+		for y in 0..CHUNK_SIZE {
+			blocks[x][y][z] = 1;
+		}
+	}
+	blocks
+}
+
+// All channels together, as the mesher compares corners
+#[cfg(feature = "lighting")]
+fn brightness(light: u16) -> u32 {
+	let light = Light::from_packed(light);
+	light.sky() as u32 + light.block().iter().map(|&level| level as u32).sum::<u32>()
+}
+
+#[cfg(feature = "lighting")]
+#[test]
+fn faces_with_different_corner_light_are_not_merged() {
+	let mut chunk = Chunk::new(floor(0));
+	let (lit, _) = chunk.build_mesh_data::<PackedVertex>(&render_data(), Neighbours::none());
+
+	chunk.set_light(5, 5, 1, Light::new(3, [0, 0, 15]));
+	let (shaded, _) = chunk.build_mesh_data::<PackedVertex>(&render_data(), Neighbours::none());
+	assert!(shaded.len() > lit.len());
+
+	// Corners touching the shaded cell average it with three fully lit ones
+	let darkest = shaded.iter().map(|v| Light::from_packed(v.light)).min_by_key(|light| light.sky()).unwrap();
+	assert_eq!(darkest.sky(), 12);
+	assert_eq!(darkest.block(), [0, 0, 4]);
+}
+
+#[cfg(feature = "lighting")]
+#[test]
+fn quads_are_split_along_their_brighter_diagonal() {
+	let mut chunk = Chunk::new(floor(0));
+	chunk.set_light(5, 5, 1, Light::new(0, [0; 3]));
+	chunk.set_light(9, 3, 1, Light::new(15, [15, 15, 0]));
+	let (vertices, indices) = chunk.build_mesh_data::<PackedVertex>(&render_data(), Neighbours::none());
+
+	let mut uneven = 0;
+	for (quad, triangles) in indices.chunks(6).enumerate() {
+		// The diagonal is the two corners both triangles have
+		let (first, second) = (&triangles[..3], &triangles[3..]);
+		let shared: Vec<u16> = first.iter().cloned().filter(|index| second.contains(index)).collect();
+		assert_eq!(shared.len(), 2, "quad {} isn't two triangles sharing an edge", quad);
+		let others: Vec<u16> = (0..4).map(|corner| (quad * 4 + corner) as u16).filter(|index| !shared.contains(index)).collect();
+
+		let sum = |corners: &[u16]| corners.iter().map(|&index| brightness(vertices[index as usize].light)).sum::<u32>();
+		assert!(sum(&shared) >= sum(&others), "quad {} is split along its darker diagonal", quad);
+		if sum(&shared) > sum(&others) {
+			uneven += 1;
+		}
+	}
+	// Both ways round are needed to tell the rule from either fixed split
+	assert!(uneven > 0);
+}
+
+// Merged quads leave T-junctions, where a corner of one quad sits partway along the edge of
+// another. Unless the edge is lit the same all along, light there won't meet up.
+#[cfg(feature = "lighting")]
+#[test]
+fn t_junctions_are_lit_like_the_edges_they_sit_on() {
+	let mut chunk = Chunk::new(floor(0));
+	for &(x, y, light) in [(5, 5, Light::new(3, [0, 0, 15])), (6, 5, Light::new(8, [0; 3])), (20, 11, Light::new(0, [0; 3]))].iter() {
+		chunk.set_light(x, y, 1, light);
+	}
+	let (vertices, _) = chunk.build_mesh_data::<PackedVertex>(&render_data(), Neighbours::none());
+	let quads: Vec<&[PackedVertex]> = vertices.chunks(4).collect();
+
+	let mut junctions = 0;
+	for quad in &quads {
+		for edge in [(0, 1), (1, 3), (3, 2), (2, 0)].iter() {
+			let (from, to) = (quad[edge.0], quad[edge.1]);
+			let (a, b) = (from.packed_position, to.packed_position);
+			for vertex in quads.iter().flat_map(|other| other.iter()).filter(|v| v.packed_position[3] == a[3]) {
+				let p = vertex.packed_position;
+				// Strictly between the ends of an axis-aligned edge
+				let inside = (0..3).all(|axis| {
+					let (low, high) = (a[axis].min(b[axis]), a[axis].max(b[axis]));
+					if low == high { p[axis] == low } else { p[axis] > low && p[axis] < high }
+				});
+				if inside {
+					junctions += 1;
+					assert_eq!(from.light, to.light, "edge {:?} to {:?} is lit unevenly", a, b);
+					assert_eq!(vertex.light, from.light, "junction at {:?} doesn't match its edge", p);
+				}
+			}
+		}
+	}
+	assert!(junctions > 0);
+}
+
+#[cfg(feature = "lighting")]
+#[test]
+fn corners_on_chunk_edges_are_lit_alike_from_both_sides() {
+	// Two chunks side by side, their top faces lit from the chunks above. Cells by their shared
+	// edge are above one chunk and diagonal to the other.
+	let (left, right) = (Chunk::new(floor(CHUNK_SIZE - 1)), Chunk::new(floor(CHUNK_SIZE - 1)));
+	let (mut left_above, mut right_above) = (Chunk::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]), Chunk::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]));
+	left_above.set_light(CHUNK_SIZE - 1, 5, 0, Light::new(0, [0; 3]));
+	right_above.set_light(0, 9, 0, Light::new(4, [15, 0, 0]));
+
+	let mut left_neighbours = Neighbours::none();
+	left_neighbours.set([0, 0, 1], Some(&left_above));
+	left_neighbours.set([1, 0, 0], Some(&right));
+	left_neighbours.set([1, 0, 1], Some(&right_above));
+	let mut right_neighbours = Neighbours::none();
+	right_neighbours.set([0, 0, 1], Some(&right_above));
+	right_neighbours.set([-1, 0, 0], Some(&left));
+	right_neighbours.set([-1, 0, 1], Some(&left_above));
+
+	// Top faces along the shared edge, by y
+	let up = 1;
+	let edge = |chunk: &Chunk, neighbours: Neighbours, x: usize| -> Vec<(u8, u16)> {
+		let (vertices, _) = chunk.build_mesh_data::<PackedVertex>(&render_data(), neighbours);
+		vertices.iter()
+			.filter(|v| v.packed_position[3] == up && v.packed_position[0] as usize == x)
+			.map(|v| (v.packed_position[1], v.light))
+			.collect()
+	};
+	let left_edge = edge(&left, left_neighbours, CHUNK_SIZE);
+	let right_edge = edge(&right, right_neighbours, 0);
+
+	let mut shared = 0;
+	for &(y, light) in &left_edge {
+		for &(other_y, other_light) in &right_edge {
+			if y == other_y {
+				shared += 1;
+				assert_eq!(Light::from_packed(light), Light::from_packed(other_light), "corners at y {} differ", y);
+			}
+		}
+	}
+	assert!(shared > 0);
+	assert!(left_edge.iter().any(|&(_, light)| Light::from_packed(light) != Light::SKY));
+}
+
+#[test]
+fn packed_vertices_match_standard() {
+	let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+	for x in 3..20 {
+		for y in 5..CHUNK_SIZE {
+			for z in 0..(x + y) % 7 + 1 {
+				blocks[x][y][z] = 1 + (x / 8) % 2;
+			}
+		}
+	}
+	let chunk = Chunk::new(blocks);
+
+	let (standard, standard_indices) = chunk.build_mesh_data::<Vertex>(&render_data(), Neighbours::none());
+	let (packed, packed_indices) = chunk.build_mesh_data::<PackedVertex>(&render_data(), Neighbours::none());
+	assert_eq!(standard_indices, packed_indices);
+	assert_eq!(standard.len(), packed.len());
+
+	let normals = [[0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0], [-1.0, 0.0, 0.0]];
+	for (s, p) in standard.iter().zip(packed.iter()) {
+		let position = [p.packed_position[0] as f32, p.packed_position[1] as f32, p.packed_position[2] as f32];
+		assert_eq!(s.position, position);
+		assert_eq!(s.normal, normals[p.packed_position[3] as usize]);
+		assert_eq!(s.color, render_data()[p.color_index as usize].color);
+	}
+}
+
+#[test]
+fn tinted_faces_take_their_biome_color() {
+	let mut data = render_data();
+	data[1] = BlockRenderData::builder().color([0.3, 0.4, 0.2]).tint(Tint::Grass, ObscuresFaces::UP).build().unwrap();
+	// Desert on one half of the chunk, plains on the other
+	let mut biomes = [[Biome::Plains; CHUNK_SIZE]; CHUNK_SIZE];
+	for row in biomes[..CHUNK_SIZE / 2].iter_mut() {
+		*row = [Biome::Desert; CHUNK_SIZE];
+	}
+	let mut chunk = Chunk::new(floor(0));
+	chunk.set_biomes(biomes);
+	let (vertices, _) = chunk.build_mesh_data::<Vertex>(&data, Neighbours::none());
+
+	let tops: Vec<&Vertex> = vertices.iter().filter(|v| v.normal == [0.0, 0.0, 1.0]).collect();
+	let desert = Biome::Desert.tint_color(Tint::Grass);
+	for vertex in &tops {
+		let x = vertex.position[0];
+		let expected = if x < (CHUNK_SIZE / 2) as f32 {
+			[0.3 * desert[0], 0.4 * desert[1], 0.2 * desert[2]]
+		} else if x > (CHUNK_SIZE / 2) as f32 {
+			[0.3, 0.4, 0.2]
+		} else {
+			// Quads either side of the biome border share corners there
+			continue;
+		};
+		for channel in 0..3 {
+			assert!((vertex.color[channel] - expected[channel]).abs() < 1.0e-5, "{:?} at {:?}", vertex.color, vertex.position);
+		}
+	}
+	// Two quads, not merged across the border
+	assert_eq!(tops.len(), 8);
+
+	// Only the top is tinted
+	assert!(vertices.iter().filter(|v| v.normal != [0.0, 0.0, 1.0]).all(|v| v.color == [0.3, 0.4, 0.2]));
+
+	// Packed vertices find the same colors in the palette
+	let (packed, _) = chunk.build_mesh_data::<PackedVertex>(&data, Neighbours::none());
+	let palette = block::palette(&data);
+	for (standard, packed) in vertices.iter().zip(packed.iter()) {
+		assert_eq!(palette[packed.color_index as usize], standard.color);
+	}
+}
+
+#[test]
+fn turned_blocks_show_their_trim_where_they_face() {
+	let mut data = render_data();
+	// Trimmed front and back, and hiding only what's behind its back
+	data[1] = BlockRenderData::builder()
+		.color([0.4, 0.3, 0.2])
+		.trim([0.7, 0.6, 0.3], ObscuresFaces::FRONT | ObscuresFaces::BACK)
+		.obscures(ObscuresFaces::BACK)
+		.build().unwrap();
+	let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+	blocks[5][5][5] = 1;
+	blocks[5][5][4] = 2;
+	let mut chunk = Chunk::new(blocks);
+	// Stood on end, its back is down and hides the top of the block below; unturned, its back
+	// would face a side
+	chunk.set_state(5, 5, 5, Orientation::facing(NormalDirection::Up).state());
+	let (vertices, _) = chunk.build_mesh_data::<Vertex>(&data, Neighbours::none());
+
+	let log: Vec<&Vertex> = vertices.iter().filter(|v| v.color != [0.6, 0.2, 0.1]).collect();
+	assert_eq!(log.len(), 20);
+	for vertex in log {
+		let expected = if vertex.normal == [0.0, 0.0, 1.0] { [0.7, 0.6, 0.3] } else { [0.4, 0.3, 0.2] };
+		assert_eq!(vertex.color, expected, "at {:?} facing {:?}", vertex.position, vertex.normal);
+	}
+	assert!(!vertices.iter().any(|v| v.color == [0.6, 0.2, 0.1] && v.normal == [0.0, 0.0, 1.0]));
+
+	// Every turn reads back from its state, and takes the model's front where it faces
+	for dir in [NormalDirection::Up, NormalDirection::Down, NormalDirection::Left, NormalDirection::Right, NormalDirection::Front, NormalDirection::Back].iter() {
+		let turned = Orientation::from_state(Orientation::facing(*dir).state());
+		assert_eq!(turned.front(), *dir);
+		assert_eq!(turned.to_world(&NormalDirection::Front), *dir);
+		assert_eq!(turned.to_model(dir), NormalDirection::Front);
+	}
+}
+
+#[test]
+fn render_data_builder_defaults_and_validation() {
+	let opaque = BlockRenderData::builder().color([0.3, 0.4, 0.2]).build().unwrap();
+	assert_eq!(opaque.obscures, ObscuresFaces::ALL);
+	let water = BlockRenderData::builder().liquid(true).build().unwrap();
+	assert_eq!(water.obscures, ObscuresFaces::NONE);
+	let air = BlockRenderData::builder().should_render(false).build().unwrap();
+	assert_eq!(air.obscures, ObscuresFaces::NONE);
+
+	assert_eq!(
+		BlockRenderData::builder().should_render(false).obscures(ObscuresFaces::UP).build().err(),
+		Some(InvalidRenderData::HiddenObscures(ObscuresFaces::UP)));
+	assert_eq!(
+		BlockRenderData::builder().color([1.5, 0.0, 0.0]).build().err(),
+		Some(InvalidRenderData::ColorOutOfRange([1.5, 0.0, 0.0])));
+}
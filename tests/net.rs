@@ -0,0 +1,202 @@
+extern crate space3;
+
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+use space3::block::{World, CHUNK_SIZE};
+use space3::game_rules::GameRules;
+use space3::worldgen::{WorldPreset, FlatGenerator};
+use space3::net::{NetServer, NetClient, NetEvent, Connection, ToServer, ToClient, compress_chunk, decompress_chunk};
+
+const BLOCK_TYPES: usize = 4;
+// Generated terrain should compress to well under this per chunk; a chunk over it is a
+// bandwidth regression
+const CHUNK_BUDGET: usize = 2048;
+
+// Ticks the server until `done` yields something, as the client end runs on its own thread
+fn serve_until<T, F>(server: &mut NetServer, world: &World, mut done: F) -> (T, Vec<NetEvent>) where F: FnMut() -> Option<T> {
+	let mut events = Vec::new();
+	for _ in 0..500 {
+		world.update_blocks();
+		events.extend(server.tick(world));
+		if let Some(result) = done() {
+			return (result, events);
+		}
+		thread::sleep(Duration::from_millis(10));
+	}
+	panic!("timed out");
+}
+
+// Polls the client until `done` holds for its copy of the world
+fn sync_until<F>(client: &mut NetClient, world: &World, done: F) -> Vec<NetEvent> where F: Fn(&NetClient, &World) -> bool {
+	let mut events = Vec::new();
+	for _ in 0..500 {
+		events.extend(client.tick(world).unwrap());
+		if done(client, world) {
+			return events;
+		}
+		thread::sleep(Duration::from_millis(10));
+	}
+	panic!("timed out");
+}
+
+#[test]
+fn chunks_and_changes_reach_clients() {
+	let world = World::new();
+	let mut server = NetServer::bind("127.0.0.1:0", &world, BLOCK_TYPES).unwrap();
+	let addr = server.local_addr().unwrap();
+	world.set_block([1, 2, 3], 3);
+
+	let (results, received) = channel();
+	thread::spawn(move || {
+		let mut client = NetClient::connect(addr, "tester").unwrap();
+		let copy = World::new();
+		client.send_position([0.5, 0.5, 0.5]).unwrap();
+		// First with the chunk, then as a change to it
+		sync_until(&mut client, &copy, |_, copy| copy.get_block([1, 2, 3]) == 3);
+		results.send(client.spawn()).unwrap();
+		sync_until(&mut client, &copy, |_, copy| copy.get_block([1, 2, 3]) == 1);
+		results.send(client.spawn()).unwrap();
+	});
+
+	let (spawn, events) = serve_until(&mut server, &world, || received.try_recv().ok());
+	assert_eq!(spawn, world.spawn());
+	assert_eq!(events, vec![NetEvent::Joined("tester".to_string())]);
+
+	world.set_block([1, 2, 3], 1);
+	serve_until(&mut server, &world, || received.try_recv().ok());
+}
+
+#[test]
+fn refused_edits_are_rolled_back() {
+	let world = World::new();
+	let mut server = NetServer::bind("127.0.0.1:0", &world, BLOCK_TYPES).unwrap();
+	let addr = server.local_addr().unwrap();
+	let far = [100, 0, 0];
+	let original = world.get_block(far);
+	let near_original = world.get_block([1, 2, 3]);
+
+	let (results, received) = channel();
+	thread::spawn(move || {
+		let mut client = NetClient::connect(addr, "tester").unwrap();
+		let copy = World::new();
+		client.send_position([0.5, 0.5, 0.5]).unwrap();
+		client.edit(&copy, [1, 2, 3], 3).unwrap();
+		let refused = client.edit(&copy, far, 3).unwrap();
+		// Shown straight away, before the server has replied
+		assert_eq!(copy.get_block(far), 3);
+
+		let events = sync_until(&mut client, &copy, |client, _| client.pending_edits() == 0);
+		assert_eq!(events, vec![NetEvent::EditRefused { seq: refused, pos: far }]);
+		results.send((copy.get_block([1, 2, 3]), copy.get_block(far))).unwrap();
+	});
+
+	let ((near_block, far_block), events) = serve_until(&mut server, &world, || received.try_recv().ok());
+	assert!(events.contains(&NetEvent::Edited { pos: [1, 2, 3], old: near_original, new: 3 }));
+	assert_eq!(near_block, 3);
+	assert_eq!(world.get_block([1, 2, 3]), 3);
+	assert_eq!(far_block, original);
+	assert_eq!(world.get_block(far), original);
+}
+
+#[test]
+fn players_are_put_on_the_ground_where_flying_is_not_allowed() {
+	let mut world = World::from_preset(12, &WorldPreset::Superflat(FlatGenerator::default_layers()));
+	let rules = GameRules { fly: false, .. world.rules() };
+	world.set_rules(rules).unwrap();
+	let mut server = NetServer::bind("127.0.0.1:0", &world, BLOCK_TYPES).unwrap();
+	let addr = server.local_addr().unwrap();
+	let ground = world.spawn()[2];
+	// In reach of where the client says it is, but not of the ground below
+	let high = [0, 0, ground + 40];
+
+	let (results, received) = channel();
+	thread::spawn(move || {
+		let mut client = NetClient::connect(addr, "tester").unwrap();
+		let copy = World::new();
+		client.send_position([0.5, 0.5, high[2] as f32 + 2.5]).unwrap();
+		let refused = client.edit(&copy, high, 3).unwrap();
+		let events = sync_until(&mut client, &copy, |client, _| client.pending_edits() == 0);
+		results.send((client.fly_allowed(), events == vec![NetEvent::EditRefused { seq: refused, pos: high }])).unwrap();
+	});
+
+	let ((fly, refused), _) = serve_until(&mut server, &world, || received.try_recv().ok());
+	assert!(!fly);
+	assert!(refused);
+	assert_eq!(world.get_block(high), 0);
+}
+
+#[test]
+fn mismatched_versions_are_refused() {
+	let world = World::new();
+	let mut server = NetServer::bind("127.0.0.1:0", &world, BLOCK_TYPES).unwrap();
+	let addr = server.local_addr().unwrap();
+
+	let (results, received) = channel();
+	thread::spawn(move || {
+		let mut connection = Connection::new(::std::net::TcpStream::connect(addr).unwrap()).unwrap();
+		connection.send(&ToServer::Hello { version: 0, name: "old".to_string() }).unwrap();
+		loop {
+			match connection.receive::<ToClient>() {
+				Ok(Some(message)) => return results.send(message).unwrap(),
+				Ok(None) => thread::sleep(Duration::from_millis(10)),
+				Err(err) => panic!("{:?}", err),
+			}
+		}
+	});
+
+	let (message, events) = serve_until(&mut server, &world, || received.try_recv().ok());
+	match message {
+		ToClient::Rejected { .. } => (),
+		other => panic!("expected a rejection, got {:?}", other),
+	}
+	assert_eq!(server.players(), 0);
+	match events.as_slice() {
+		[NetEvent::Left { name: None, .. }] => (),
+		other => panic!("unexpected events {:?}", other),
+	}
+}
+
+#[test]
+fn non_finite_positions_drop_the_client() {
+	let world = World::new();
+	let mut server = NetServer::bind("127.0.0.1:0", &world, BLOCK_TYPES).unwrap();
+	let addr = server.local_addr().unwrap();
+
+	thread::spawn(move || {
+		let mut client = NetClient::connect(addr, "tester").unwrap();
+		client.send_position([::std::f32::NAN, 0.0, 0.0]).unwrap();
+		// Kept open so it's the server that hangs up
+		thread::sleep(Duration::from_secs(5));
+	});
+
+	for _ in 0..500 {
+		let events = server.tick(&world);
+		if let Some(event) = events.into_iter().find(|event| match *event { NetEvent::Left { .. } => true, _ => false }) {
+			assert_eq!(server.players(), 0);
+			match event {
+				NetEvent::Left { name: Some(ref name), .. } if name == "tester" => return,
+				other => panic!("unexpected event {:?}", other),
+			}
+		}
+		thread::sleep(Duration::from_millis(10));
+	}
+	panic!("timed out");
+}
+
+#[test]
+fn terrain_chunks_compress_within_budget() {
+	let world = World::new();
+	for pos in &[[0, 0, -3], [0, 0, -1], [0, 0, 0], [0, 0, 1], [3, -2, 0], [-5, 7, -1]] {
+		let mut blocks = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+		world.generate_blocks(pos[0], pos[1], pos[2], &mut blocks);
+
+		let data = compress_chunk(&blocks).unwrap();
+		assert!(data.len() <= CHUNK_BUDGET, "chunk {:?} took {} bytes", pos, data.len());
+
+		let mut decoded = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+		decompress_chunk(&data, &mut decoded).unwrap();
+		assert!(decoded[..] == blocks[..], "chunk {:?} changed in transit", pos);
+	}
+}
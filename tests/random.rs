@@ -0,0 +1,36 @@
+extern crate rand;
+extern crate space3;
+
+use rand::RngCore;
+
+use space3::random::{WorldRandom, Stream, StreamRng};
+
+const STREAMS: [Stream; 3] = [Stream::Mobs, Stream::ItemDrops, Stream::Snowfall];
+
+fn draw(mut rng: StreamRng) -> Vec<u64> {
+	(0..16).map(|_| rng.next_u64()).collect()
+}
+
+#[test]
+fn a_seed_reproduces_its_streams() {
+	for &stream in &STREAMS {
+		assert_eq!(draw(WorldRandom::new(12).stream(stream)), draw(WorldRandom::new(12).stream(stream)));
+		assert_ne!(draw(WorldRandom::new(12).stream(stream)), draw(WorldRandom::new(13).stream(stream)));
+	}
+	// Nor do streams follow one another
+	for (i, &a) in STREAMS.iter().enumerate() {
+		for &b in &STREAMS[i + 1..] {
+			assert_ne!(draw(WorldRandom::new(12).stream(a)), draw(WorldRandom::new(12).stream(b)));
+		}
+	}
+}
+
+#[test]
+fn split_streams_are_reproducible_and_independent() {
+	let mut rng = WorldRandom::new(12).stream(Stream::Mobs);
+	let mut again = WorldRandom::new(12).stream(Stream::Mobs);
+	let (split, split_again) = (rng.split(), again.split());
+	assert_eq!(split, split_again);
+	assert_ne!(draw(split), draw(rng.clone()));
+	assert_eq!(draw(rng), draw(again));
+}
@@ -0,0 +1,81 @@
+extern crate space3;
+
+use space3::block::{BlockRenderData, ObscuresFaces, Chunk, CHUNK_SIZE, NEIGHBOUR_OFFSETS};
+use space3::visibility::{FaceConnectivity, visible_chunks};
+
+const AIR: usize = 0;
+const STONE: usize = 1;
+// Faces as numbered in NEIGHBOUR_OFFSETS
+const EAST: usize = 0;
+const WEST: usize = 1;
+const UP: usize = 4;
+
+fn render_data() -> Vec<BlockRenderData> {
+	vec![
+		BlockRenderData { obscures: ObscuresFaces::NONE, color: [0.0, 0.0, 0.0], should_render: false, liquid: false, tint: None, trim: None },
+		BlockRenderData { obscures: ObscuresFaces::ALL,  color: [0.5, 0.5, 0.5], should_render: true,  liquid: false, tint: None, trim: None },
+	]
+}
+
+fn filled(block: usize) -> [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE] {
+	[[[block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]
+}
+
+// Solid, but for a tunnel in from the west face that turns up to the top face halfway across
+fn l_tunnel() -> FaceConnectivity {
+	let mut blocks = filled(STONE);
+	let mid = CHUNK_SIZE / 2;
+	for x in 0..mid + 1 {
+		blocks[x][mid][mid] = AIR;
+	}
+	for z in mid..CHUNK_SIZE {
+		blocks[mid][mid][z] = AIR;
+	}
+	FaceConnectivity::of(&Chunk::new(blocks), &render_data())
+}
+
+fn visible(size: [usize; 3], chunks: &[([usize; 3], FaceConnectivity)]) -> Vec<bool> {
+	let connectivity = |pos: [usize; 3]| chunks.iter()
+		.find(|&&(at, _)| at == pos)
+		.map(|&(_, connectivity)| connectivity)
+		.unwrap_or(FaceConnectivity::ALL);
+	visible_chunks(size, [0, 0, 0], connectivity, |_| true)
+}
+
+#[test]
+fn opposite_faces_are_paired() {
+	// Walking through a chunk pairs each face with face ^ 1 as its opposite
+	for face in 0..6 {
+		let (a, b) = (NEIGHBOUR_OFFSETS[face], NEIGHBOUR_OFFSETS[face ^ 1]);
+		assert_eq!([a[0] + b[0], a[1] + b[1], a[2] + b[2]], [0, 0, 0]);
+	}
+}
+
+#[test]
+fn empty_chunks_connect_every_face_and_solid_ones_none() {
+	assert_eq!(FaceConnectivity::of(&Chunk::new(filled(AIR)), &render_data()), FaceConnectivity::ALL);
+	assert_eq!(FaceConnectivity::of(&Chunk::new(filled(STONE)), &render_data()), FaceConnectivity::NONE);
+}
+
+#[test]
+fn solid_walls_hide_chunks_behind_them() {
+	let wall = FaceConnectivity::of(&Chunk::new(filled(STONE)), &render_data());
+	let visible = visible([3, 1, 1], &[([1, 0, 0], wall)]);
+	// The wall itself is seen, but not past it
+	assert_eq!(visible, vec![true, true, false]);
+}
+
+#[test]
+fn tunnels_that_turn_keep_their_far_end_visible() {
+	let tunnel = l_tunnel();
+	assert!(tunnel.connected(WEST, UP));
+	assert!(tunnel.connected(UP, WEST));
+	assert!(!tunnel.connected(WEST, EAST));
+
+	// Entered from the west, the tunnel comes out above itself and nowhere else
+	let visible = visible([3, 1, 2], &[([0, 0, 1], FaceConnectivity::NONE), ([1, 0, 0], tunnel)]);
+	let index = |pos: [usize; 3]| pos[0] * 2 + pos[2];
+	assert!(visible[index([1, 0, 0])]);
+	assert!(visible[index([1, 0, 1])]);
+	assert!(!visible[index([2, 0, 0])]);
+}
@@ -0,0 +1,278 @@
+extern crate space3;
+
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use space3::block::{World, CHUNK_SIZE};
+use space3::generation_queue::chunk_priority;
+use space3::biome::Biome;
+use space3::save;
+use space3::worldgen::{WorldPreset, FlatGenerator, TerrainSettings, WarpSettings, NoiseSettings, NoiseAlgorithm, Spline};
+
+// Regenerate with `UPDATE_GOLDEN=1 cargo test --test worldgen` after an intentional change to
+// generator output, and commit the result along with it.
+const GOLDEN_FILE: &'static str = "tests/golden/worldgen.txt";
+const SEED: u32 = 12;
+
+const CHUNKS: &'static [[i64; 3]] = &[
+	[40, 40, -1], [0, 0, 0], [0, 0, 1],
+	[3, -2, 0], [-5, 7, -1], [11, 4, 2],
+	[-20, -20, -3], [64, -64, 0], [0, 0, 600], [0, 0, -600],
+];
+
+fn presets() -> Vec<(&'static str, WorldPreset)> {
+	let shaped = TerrainSettings {
+		noise: NoiseSettings {
+			algorithm: NoiseAlgorithm::Ridged,
+			.. NoiseSettings::default()
+		},
+		shape: Spline::new(vec![(-64.0, 2.0), (0.0, 0.0), (16.0, -0.1), (96.0, -4.0)]),
+		warp: Some(WarpSettings::default()),
+		.. TerrainSettings::default()
+	};
+	let simplex = TerrainSettings {
+		noise: NoiseSettings {
+			algorithm: NoiseAlgorithm::OpenSimplex,
+			octaves: 4,
+			.. NoiseSettings::default()
+		},
+		sea_level: None,
+		.. TerrainSettings::default()
+	};
+
+	vec![
+		("normal", WorldPreset::Normal(TerrainSettings::default())),
+		("shaped", WorldPreset::Normal(shaped)),
+		("simplex", WorldPreset::Normal(simplex)),
+		("superflat", WorldPreset::Superflat(FlatGenerator::default_layers())),
+		("void", WorldPreset::Void),
+	]
+}
+
+// FNV-1a over the block ids in x, y, z order. Unlike std's hashers it is stable across
+// compiler versions.
+fn hash_blocks(blocks: &[[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for &block in blocks.iter().flat_map(|plane| plane.iter()).flat_map(|row| row.iter()) {
+		for byte in (block as u32).to_le_bytes().iter() {
+			hash ^= *byte as u64;
+			hash = hash.wrapping_mul(0x100000001b3);
+		}
+	}
+	hash
+}
+
+fn golden_path() -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_FILE)
+}
+
+fn generate_all() -> BTreeMap<String, u64> {
+	let mut hashes = BTreeMap::new();
+	let mut blocks = Box::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+	for (name, preset) in presets() {
+		let generator = preset.generator(SEED);
+		for pos in CHUNKS {
+			generator.generate(pos[0], pos[1], pos[2], &mut blocks);
+			hashes.insert(format!("{} {} {} {}", name, pos[0], pos[1], pos[2]), hash_blocks(&blocks));
+		}
+	}
+	hashes
+}
+
+fn read_golden() -> BTreeMap<String, u64> {
+	let mut contents = String::new();
+	File::open(golden_path()).and_then(|mut file| file.read_to_string(&mut contents))
+		.expect("Missing golden data; run with UPDATE_GOLDEN=1 to create it");
+
+	contents.lines()
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(|line| {
+			let split = line.rfind(' ').expect("Malformed golden line");
+			let hash = u64::from_str_radix(&line[split + 1..], 16).expect("Malformed golden hash");
+			(line[..split].to_string(), hash)
+		})
+		.collect()
+}
+
+fn write_golden(hashes: &BTreeMap<String, u64>) {
+	let mut file = File::create(golden_path()).unwrap();
+	writeln!(file, "# preset x y z hash, generated by tests/worldgen.rs").unwrap();
+	for (key, hash) in hashes {
+		writeln!(file, "{} {:016x}", key, hash).unwrap();
+	}
+}
+
+#[test]
+fn generated_chunks_match_golden_data() {
+	let hashes = generate_all();
+	if env::var_os("UPDATE_GOLDEN").is_some() {
+		write_golden(&hashes);
+		return;
+	}
+
+	let golden = read_golden();
+	let mismatched: Vec<&String> = hashes.iter()
+		.filter(|&(key, hash)| golden.get(key) != Some(hash))
+		.map(|(key, _)| key)
+		.collect();
+	assert!(mismatched.is_empty(), "Generated chunks differ from golden data: {:?}", mismatched);
+	assert_eq!(hashes.len(), golden.len(), "Golden data has entries no longer generated");
+}
+
+#[test]
+fn generation_is_repeatable() {
+	assert_eq!(generate_all(), generate_all());
+}
+
+#[test]
+fn terrain_biomes_vary_across_the_world_and_not_between_runs() {
+	let generator = WorldPreset::Normal(TerrainSettings::default()).generator(SEED);
+	let again = WorldPreset::Normal(TerrainSettings::default()).generator(SEED);
+	let mut seen = HashSet::new();
+	for i in -40..40 {
+		for j in -40..40 {
+			let (x, y) = (i * 64, j * 64);
+			assert_eq!(generator.biome(x, y), again.biome(x, y));
+			seen.insert(generator.biome(x, y));
+		}
+	}
+	assert!(seen.len() >= 3, "only found {:?}", seen);
+
+	// Flat worlds are all one biome
+	let flat = WorldPreset::Superflat(FlatGenerator::default_layers()).generator(SEED);
+	assert_eq!(flat.biome(1000, -1000), Biome::Plains);
+}
+
+#[test]
+fn splines_with_points_that_are_not_finite_are_refused() {
+	for text in &["NaN:0,1:1", "0:0,1:NaN", "inf:0,1:1", "0:0,NaN:1,2:2"] {
+		assert_eq!(text.parse::<Spline>(), Err(()), "{}", text);
+	}
+	assert_eq!("1:1,0:0".parse::<Spline>(), Ok(Spline::new(vec![(0.0, 0.0), (1.0, 1.0)])));
+	assert_eq!("0:0,0:1".parse::<Spline>(), Err(()));
+}
+
+#[test]
+fn queued_chunks_generate_most_wanted_first() {
+	let world = World::from_preset(SEED, &WorldPreset::Superflat(FlatGenerator::default_layers()));
+	let requester = world.generation_requester();
+	let (near, far) = ([0, 0, 0], [3, 0, 0]);
+	world.request_chunks(requester, &[(chunk_priority(far, [8.0; 3], None), far), (chunk_priority(near, [8.0; 3], None), near)]);
+	assert_eq!(world.generate_queued(1), 1);
+	assert!(world.loaded_chunk(near).is_some());
+	assert!(world.loaded_chunk(far).is_none());
+
+	// Chunks behind the eye wait for those in front
+	let (ahead, behind) = ([5, 0, 0], [-5, 0, 0]);
+	let look = Some([1.0, 0.0, 0.0]);
+	world.request_chunks(requester, &[(chunk_priority(behind, [8.0; 3], look), behind), (chunk_priority(ahead, [8.0; 3], look), ahead)]);
+	assert_eq!(world.generate_queued(1), 1);
+	assert!(world.loaded_chunk(ahead).is_some());
+	assert!(world.loaded_chunk(behind).is_none());
+}
+
+#[test]
+fn stale_and_cancelled_requests_are_dropped_instead_of_generated() {
+	let world = World::from_preset(SEED, &WorldPreset::Superflat(FlatGenerator::default_layers()));
+	let turned = world.generation_requester();
+	let left = world.generation_requester();
+
+	// Turning around asks for other chunks, and what was asked for before goes stale
+	world.request_chunks(turned, &[(0.0, [4, 0, 0]), (1.0, [5, 0, 0])]);
+	world.request_chunks(turned, &[(2.0, [-4, 0, 0])]);
+	world.request_chunks(left, &[(0.0, [0, 4, 0])]);
+	world.cancel_chunks(left);
+	// Cancelled requesters can't ask again
+	world.request_chunks(left, &[(0.0, [0, 5, 0])]);
+
+	assert_eq!(world.generate_queued(10), 1);
+	assert!(world.loaded_chunk([-4, 0, 0]).is_some());
+	for &pos in &[[4, 0, 0], [5, 0, 0], [0, 4, 0], [0, 5, 0]] {
+		assert!(world.loaded_chunk(pos).is_none(), "{:?} was generated", pos);
+	}
+	assert_eq!(world.dropped_chunks(), 3);
+	assert_eq!(world.queued_chunks(), 0);
+}
+
+#[test]
+fn asking_again_for_the_same_chunks_queues_nothing_more() {
+	let world = World::from_preset(SEED, &WorldPreset::Superflat(FlatGenerator::default_layers()));
+	let requester = world.generation_requester();
+	let wanted: Vec<(f32, [i64; 3])> = (0..8).map(|i| (i as f32, [i + 4, 0, 0])).collect();
+	for _ in 0..100 {
+		world.request_chunks(requester, &wanted);
+	}
+	assert_eq!(world.queued_chunks(), 8);
+
+	// Moving changes every priority, and the jobs left behind are swept before they pile up
+	for tick in 0..100 {
+		let moved: Vec<(f32, [i64; 3])> = wanted.iter().map(|&(priority, pos)| (priority + tick as f32, pos)).collect();
+		world.request_chunks(requester, &moved);
+		assert!(world.queued_chunks() <= 16, "{} queued by tick {}", world.queued_chunks(), tick);
+	}
+	assert_eq!(world.generate_queued(100), 8);
+}
+
+#[test]
+fn workers_generate_what_the_world_would_have() {
+	let preset = WorldPreset::Normal(TerrainSettings::default());
+	let world = World::from_preset(SEED, &preset);
+	world.start_generation_workers(2);
+	let requester = world.generation_requester();
+	let wanted: Vec<(f32, [i64; 3])> = (0..6).map(|i| (i as f32, [i + 4, 3, -2])).collect();
+	world.request_chunks(requester, &wanted);
+
+	let mut made = 0;
+	for _ in 0..500 {
+		made += world.generate_queued(8);
+		if made == wanted.len() {
+			break;
+		}
+		thread::sleep(Duration::from_millis(10));
+	}
+	assert_eq!(made, wanted.len());
+
+	let inline = World::from_preset(SEED, &preset);
+	for &(_, pos) in &wanted {
+		let generated = world.loaded_chunk(pos).expect("not generated");
+		assert!(generated.borrow().blocks == inline.get_chunk(pos[0], pos[1], pos[2]).borrow().blocks, "{:?} differs", pos);
+	}
+}
+
+#[test]
+fn unsaved_worlds_look_for_a_spawn_only_when_asked() {
+	let world = World::from_preset(SEED, &WorldPreset::Superflat(FlatGenerator::default_layers()));
+	assert_eq!(world.chunk_counts().0, 0);
+	let spawn = world.spawn();
+	assert!(world.chunk_counts().0 > 0);
+	assert_eq!(world.spawn(), spawn);
+
+	// Nothing to stand on, so nothing to look through
+	let void = World::from_preset(SEED, &WorldPreset::Void);
+	assert_eq!(void.spawn(), [0, 0, 0]);
+	assert_eq!(void.chunk_counts().0, 0);
+}
+
+#[test]
+fn corrupt_chunks_are_moved_aside_and_regenerated() {
+	let dir = env::temp_dir().join(format!("space3-corrupt-chunk-{}", std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	let preset = WorldPreset::Superflat(FlatGenerator::default_layers());
+	let world = World::open_or_create_preset(&dir, SEED, &preset).unwrap();
+	let path = save::chunk_path(&dir, [40, 40, -1]);
+	fs::create_dir_all(path.parent().unwrap()).unwrap();
+	File::create(&path).unwrap().write_all(&[0xff; 7]).unwrap();
+
+	let chunk = world.get_chunk(40, 40, -1);
+	assert!(chunk.borrow().blocks == World::from_preset(SEED, &preset).get_chunk(40, 40, -1).borrow().blocks);
+	assert!(!path.exists());
+	assert!(path.with_extension("chunk.corrupt").is_file());
+	drop(chunk);
+	drop(world);
+	fs::remove_dir_all(&dir).unwrap();
+}